@@ -1,6 +1,7 @@
 use cast::{Cast, Typed};
 use env;
 use error::Error;
+use field::Field;
 use fixed::Fixed;
 use layer::Layer;
 use metadata::Metadata;
@@ -25,6 +26,7 @@ impl AttrBuilder {
             class: self.class,
             range: self.range,
             value: self.value,
+            owned: false,
         }
     }
 
@@ -53,6 +55,7 @@ pub struct Attr {
     class: Fixed<AttrClass>,
     range: Range<usize>,
     value: Option<Fixed<Variant>>,
+    owned: bool,
 }
 
 impl fmt::Debug for Attr {
@@ -101,10 +104,36 @@ impl Attr {
     pub fn try_get(&self, layer: &Layer) -> Result<Variant> {
         self.class.try_get(self, layer)
     }
+
+    /// Returns true if self was moved into a Layer and is released with it.
+    pub fn is_owned(&self) -> bool {
+        self.owned
+    }
+}
+
+impl Clone for Attr {
+    /// Returns a copy of self with its own value, which is not owned by any layer.
+    fn clone(&self) -> Attr {
+        Attr {
+            class: self.class.clone(),
+            range: self.range.clone(),
+            value: self.value.as_ref().map(|value| Fixed::new((**value).clone())),
+            owned: false,
+        }
+    }
+}
+
+impl Drop for Attr {
+    fn drop(&mut self) {
+        if let Some(value) = self.value.take() {
+            unsafe { drop(Box::from_raw(value.as_ptr() as *mut Variant)) };
+        }
+    }
 }
 
 impl Into<Fixed<Attr>> for Attr {
-    fn into(self) -> Fixed<Attr> {
+    fn into(mut self) -> Fixed<Attr> {
+        self.owned = true;
         Fixed::new(self)
     }
 }
@@ -291,6 +320,12 @@ impl AttrClass {
     }
 }
 
+impl<'a> From<&'a AttrClass> for Field {
+    fn from(class: &'a AttrClass) -> Self {
+        Field::new(&class.id().to_string(), &class.typ().to_string())
+    }
+}
+
 impl Into<Fixed<AttrClass>> for &'static AttrClass {
     fn into(self) -> Fixed<AttrClass> {
         Fixed::from_static(self)
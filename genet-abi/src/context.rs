@@ -6,18 +6,36 @@ use std::{slice, str};
 #[repr(C)]
 pub struct Context {
     class: Fixed<ContextClass>,
+    frame_index: u32,
     config: FnvHashMap<String, String>,
 }
 
+impl Default for Context {
+    fn default() -> Context {
+        Context::new(FnvHashMap::default())
+    }
+}
+
 impl Context {
     /// Creates a new Context.
     pub fn new(config: FnvHashMap<String, String>) -> Context {
         Self {
             class: CONTEXT_CLASS.clone(),
+            frame_index: 0,
             config,
         }
     }
 
+    /// Returns the index of the frame being decoded.
+    pub fn frame_index(&self) -> u32 {
+        self.frame_index
+    }
+
+    /// Sets the index of the frame being decoded.
+    pub fn set_frame_index(&mut self, index: u32) {
+        self.frame_index = index;
+    }
+
     /// Returns a config value in the current profile.
     pub fn get_config(&self, key: &str) -> &str {
         let mut len = key.len() as u64;
@@ -1,6 +1,7 @@
 use bincode;
 use context::Context;
 use error::Error;
+use field::Field;
 use fixed::MutFixed;
 use layer::{Layer, LayerStack, Parent};
 use result::Result;
@@ -29,6 +30,23 @@ pub struct Metadata {
     pub name: String,
     pub description: String,
     pub exec_type: ExecType,
+    pub fields: Vec<Field>,
+
+    /// Ids of the layers or payloads consumed by this decoder, such as `tcp` or `@data:tcp`.
+    ///
+    /// The decoder runs only on layers which have one of these ids or payloads.
+    /// If empty, the decoder runs on every layer.
+    pub consumes: Vec<String>,
+
+    /// Decoders with a higher priority run first.
+    pub priority: i32,
+
+    /// Ids of the layers, such as `udp`, whose untyped payloads this decoder claims
+    /// by inspecting their bytes.
+    ///
+    /// A heuristic decoder runs on those layers only when no other decoder
+    /// has decoded them.
+    pub heuristics: Vec<String>,
 }
 
 impl Default for Metadata {
@@ -38,6 +56,10 @@ impl Default for Metadata {
             name: String::new(),
             description: String::new(),
             exec_type: ExecType::ParallelSync,
+            fields: Vec::new(),
+            consumes: Vec::new(),
+            priority: 0,
+            heuristics: Vec::new(),
         }
     }
 }
@@ -0,0 +1,23 @@
+/// A layer or attribute token provided by a package.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Field {
+    id: String,
+    typ: String,
+}
+
+impl Field {
+    pub fn new(id: &str, typ: &str) -> Self {
+        Field {
+            id: id.into(),
+            typ: typ.into(),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn typ(&self) -> &str {
+        &self.typ
+    }
+}
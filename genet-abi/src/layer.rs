@@ -1,11 +1,12 @@
 use attr::Attr;
+use field::Field;
 use fixed::{Fixed, MutFixed};
 use metadata::Metadata;
 use slice::ByteSlice;
 use std::{
     fmt,
     marker::PhantomData,
-    ops::{Deref, DerefMut},
+    ops::{Deref, DerefMut, Range},
     slice,
 };
 use token::Token;
@@ -22,6 +23,11 @@ impl<'a> LayerStack<'a> {
         }
     }
 
+    /// Creates a LayerStack from layers ordered from the bottom.
+    pub fn from_slice(layers: &'a [&'a Layer]) -> LayerStack<'a> {
+        unsafe { Self::new(layers.as_ptr() as *const *const Layer, layers.len()) }
+    }
+
     /// Returns the top of the LayerStack.
     pub fn top(&self) -> Option<&Layer> {
         self.layers().last()
@@ -115,6 +121,16 @@ impl<'a> Parent<'a> {
         self.deref_mut().add_payload(payload);
     }
 
+    /// Returns the slice of annotations.
+    pub fn annotations(&self) -> &[Annotation] {
+        self.deref().annotations()
+    }
+
+    /// Adds an annotation to the Layer.
+    pub fn add_annotation(&mut self, annotation: Annotation) {
+        self.deref_mut().add_annotation(annotation);
+    }
+
     pub fn add_child<T: Into<MutFixed<Layer>>>(&mut self, layer: T) {
         (self.add_child)(self, layer.into().as_mut_ptr());
     }
@@ -159,6 +175,7 @@ pub struct Layer {
     data: ByteSlice,
     attrs: Vec<Fixed<Attr>>,
     payloads: Vec<Payload>,
+    annotations: Vec<Annotation>,
 }
 
 unsafe impl Send for Layer {}
@@ -171,6 +188,7 @@ impl Layer {
             data: data.into(),
             attrs: Vec::new(),
             payloads: Vec::new(),
+            annotations: Vec::new(),
         }
     }
 
@@ -226,6 +244,17 @@ impl Layer {
         let func = self.class.add_payload;
         (func)(self, payload);
     }
+
+    /// Returns the slice of annotations.
+    pub fn annotations(&self) -> &[Annotation] {
+        self.class.annotations(self)
+    }
+
+    /// Adds an annotation to the Layer.
+    pub fn add_annotation(&mut self, annotation: Annotation) {
+        let func = self.class.add_annotation;
+        (func)(self, annotation);
+    }
 }
 
 impl fmt::Debug for Layer {
@@ -234,6 +263,16 @@ impl fmt::Debug for Layer {
     }
 }
 
+impl Drop for Layer {
+    fn drop(&mut self) {
+        for attr in &self.attrs {
+            if attr.is_owned() {
+                unsafe { drop(Box::from_raw(attr.as_ptr() as *mut Attr)) };
+            }
+        }
+    }
+}
+
 impl Into<MutFixed<Layer>> for Layer {
     fn into(self) -> MutFixed<Layer> {
         MutFixed::new(self)
@@ -286,6 +325,93 @@ impl Payload {
     }
 }
 
+/// Severity of an annotation.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Chat,
+    Note,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    /// Returns all severities in ascending order.
+    pub fn all() -> &'static [Severity] {
+        &[
+            Severity::Chat,
+            Severity::Note,
+            Severity::Warning,
+            Severity::Error,
+        ]
+    }
+
+    /// Returns the name of self, such as `warning`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Chat => "chat",
+            Severity::Note => "note",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+/// An annotation object.
+///
+/// Annotations flag notable conditions of a layer,
+/// such as retransmissions or bad checksums.
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct Annotation {
+    severity: Severity,
+    group: Token,
+    message: Token,
+    start: u64,
+    end: u64,
+}
+
+impl Annotation {
+    /// Creates a new annotation.
+    ///
+    /// `group` classifies the condition, such as `checksum`, and `message` describes it,
+    /// such as `tcp.zeroWindow`. `range` is the byte range in the layer data.
+    pub fn new<T: Into<Token>, U: Into<Token>>(
+        severity: Severity,
+        group: T,
+        message: U,
+        range: Range<usize>,
+    ) -> Annotation {
+        Self {
+            severity,
+            group: group.into(),
+            message: message.into(),
+            start: range.start as u64,
+            end: range.end as u64,
+        }
+    }
+
+    /// Returns the severity of self.
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// Returns the group of self.
+    pub fn group(&self) -> Token {
+        self.group
+    }
+
+    /// Returns the message of self.
+    pub fn message(&self) -> Token {
+        self.message
+    }
+
+    /// Returns the byte range of self.
+    pub fn range(&self) -> Range<usize> {
+        self.start as usize..self.end as usize
+    }
+}
+
 /// A builder object for LayerClass.
 pub struct LayerClassBuilder {
     id: Token,
@@ -337,6 +463,9 @@ impl LayerClassBuilder {
             payloads_len: abi_payloads_len,
             payloads_data: abi_payloads_data,
             add_payload: abi_add_payload,
+            annotations_len: abi_annotations_len,
+            annotations_data: abi_annotations_data,
+            add_annotation: abi_add_annotation,
             id: self.id,
             meta: self.meta,
             aliases: self.aliases,
@@ -366,6 +495,9 @@ pub struct LayerClass {
     payloads_len: extern "C" fn(*const Layer) -> u64,
     payloads_data: extern "C" fn(*const Layer) -> *const Payload,
     add_payload: extern "C" fn(*mut Layer, Payload),
+    annotations_len: extern "C" fn(*const Layer) -> u64,
+    annotations_data: extern "C" fn(*const Layer) -> *const Annotation,
+    add_annotation: extern "C" fn(*mut Layer, Annotation),
     id: Token,
     meta: Metadata,
     aliases: Vec<Alias>,
@@ -383,6 +515,30 @@ impl LayerClass {
         }
     }
 
+    /// Returns the tokens of the layer, its headers and aliases.
+    pub fn fields(&self) -> Vec<Field> {
+        let headers = self
+            .headers()
+            .iter()
+            .map(|attr| Field::new(&attr.id().to_string(), &attr.typ().to_string()))
+            .collect::<Vec<_>>();
+        let aliases = self
+            .aliases()
+            .map(|alias| {
+                let target = alias.target.to_string();
+                let typ = headers
+                    .iter()
+                    .find(|f| f.id() == target)
+                    .map_or("", |f| f.typ());
+                Field::new(&alias.id.to_string(), typ)
+            })
+            .collect::<Vec<_>>();
+        let mut fields = vec![Field::new(&self.id().to_string(), "")];
+        fields.extend(aliases);
+        fields.extend(headers);
+        fields
+    }
+
     fn id(&self) -> Token {
         (self.get_id)(self)
     }
@@ -417,6 +573,12 @@ impl LayerClass {
         let len = (self.payloads_len)(layer) as usize;
         unsafe { slice::from_raw_parts(data, len) }
     }
+
+    fn annotations(&self, layer: &Layer) -> &[Annotation] {
+        let data = (self.annotations_data)(layer);
+        let len = (self.annotations_len)(layer) as usize;
+        unsafe { slice::from_raw_parts(data, len) }
+    }
 }
 
 impl Into<Fixed<LayerClass>> for &'static LayerClass {
@@ -479,12 +641,25 @@ extern "C" fn abi_add_payload(layer: *mut Layer, payload: Payload) {
     payloads.push(payload);
 }
 
+extern "C" fn abi_annotations_len(layer: *const Layer) -> u64 {
+    unsafe { (*layer).annotations.len() as u64 }
+}
+
+extern "C" fn abi_annotations_data(layer: *const Layer) -> *const Annotation {
+    unsafe { (*layer).annotations.as_ptr() }
+}
+
+extern "C" fn abi_add_annotation(layer: *mut Layer, annotation: Annotation) {
+    let annotations = unsafe { &mut (*layer).annotations };
+    annotations.push(annotation);
+}
+
 #[cfg(test)]
 mod tests {
     use attr::{Attr, AttrClass};
     use cast::Cast;
     use fixed::Fixed;
-    use layer::{Layer, LayerClass, Payload};
+    use layer::{Annotation, Layer, LayerClass, Payload, Severity};
     use slice::ByteSlice;
     use std::io::Result;
     use token::Token;
@@ -528,6 +703,33 @@ mod tests {
         assert!(iter.next().is_none());
     }
 
+    #[test]
+    fn annotations() {
+        let class = Fixed::new(LayerClass::builder(Token::null()).build());
+        let mut layer = Layer::new(class, ByteSlice::new());
+        assert!(layer.annotations().is_empty());
+
+        let count = 100;
+        for i in 0..count {
+            layer.add_annotation(Annotation::new(
+                Severity::Warning,
+                Token::from(i),
+                Token::from(i + 1),
+                i as usize..i as usize + 2,
+            ));
+        }
+
+        let mut iter = layer.annotations().iter();
+        for i in 0..count {
+            let annotation = iter.next().unwrap();
+            assert_eq!(annotation.severity(), Severity::Warning);
+            assert_eq!(annotation.group(), Token::from(i));
+            assert_eq!(annotation.message(), Token::from(i + 1));
+            assert_eq!(annotation.range(), i as usize..i as usize + 2);
+        }
+        assert!(iter.next().is_none());
+    }
+
     #[test]
     fn attrs() {
         let class = Fixed::new(LayerClass::builder(Token::null()).build());
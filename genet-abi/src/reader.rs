@@ -1,6 +1,7 @@
 use bincode;
 use context::Context;
 use error::Error;
+use field::Field;
 use file::FileType;
 use fixed::MutFixed;
 use layer::Layer;
@@ -16,6 +17,7 @@ pub struct Metadata {
     pub name: String,
     pub description: String,
     pub filters: Vec<FileType>,
+    pub fields: Vec<Field>,
 }
 
 impl Default for Metadata {
@@ -25,6 +27,7 @@ impl Default for Metadata {
             name: String::new(),
             description: String::new(),
             filters: Vec::new(),
+            fields: Vec::new(),
         }
     }
 }
@@ -121,13 +124,35 @@ extern "C" fn abi_metadata(reader: *const ReaderBox) -> SafeVec<u8> {
 /// Reader worker trait.
 pub trait Worker: Send {
     fn read(&mut self) -> Result<Vec<Layer>>;
+
+    /// Returns the positions of the frames returned by the last read().
+    ///
+    /// A worker supporting random access returns one position per frame,
+    /// which can be passed to read_at() to read the frame again.
+    /// The data of such frames must be allocated as a `Vec<u8>` or `Box<[u8]>`
+    /// because the kernel releases it when the frame is evicted from the cache.
+    fn positions(&mut self) -> Vec<u64> {
+        Vec::new()
+    }
+
+    /// Reads a single frame at the position.
+    fn read_at(&mut self, _position: u64) -> Result<Layer> {
+        Err(Box::new(Error::new("random access is not supported")))
+    }
 }
 
 type ReaderFunc = extern "C" fn(*mut Box<Worker>, *mut SafeVec<MutFixed<Layer>>, *mut Error) -> u8;
 
+type ReaderPositionsFunc = extern "C" fn(*mut Box<Worker>) -> SafeVec<u64>;
+
+type ReaderAtFunc =
+    extern "C" fn(*mut Box<Worker>, u64, *mut SafeVec<MutFixed<Layer>>, *mut Error) -> u8;
+
 pub struct WorkerBox {
     worker: *mut Box<Worker>,
     read: ReaderFunc,
+    positions: ReaderPositionsFunc,
+    read_at: ReaderAtFunc,
     drop: extern "C" fn(*mut Box<Worker>),
 }
 
@@ -138,6 +163,8 @@ impl WorkerBox {
         Self {
             worker: Box::into_raw(Box::new(worker)),
             read: abi_reader_worker_read,
+            positions: abi_reader_worker_positions,
+            read_at: abi_reader_worker_read_at,
             drop: abi_reader_worker_drop,
         }
     }
@@ -151,6 +178,22 @@ impl WorkerBox {
             Ok(v.into_iter().collect())
         }
     }
+
+    pub fn positions(&mut self) -> Vec<u64> {
+        (self.positions)(self.worker).into_iter().collect()
+    }
+
+    pub fn read_at(&mut self, position: u64) -> Result<MutFixed<Layer>> {
+        let mut v = SafeVec::new();
+        let mut e = Error::new("");
+        if (self.read_at)(self.worker, position, &mut v, &mut e) == 0 {
+            Err(Box::new(e))
+        } else {
+            v.into_iter()
+                .next()
+                .ok_or_else(|| Box::new(Error::new("no frame")) as Box<std::error::Error>)
+        }
+    }
 }
 
 impl fmt::Debug for WorkerBox {
@@ -190,3 +233,29 @@ extern "C" fn abi_reader_worker_read(
         }
     }
 }
+
+extern "C" fn abi_reader_worker_positions(worker: *mut Box<Worker>) -> SafeVec<u64> {
+    let worker = unsafe { &mut *worker };
+    worker.positions().into()
+}
+
+extern "C" fn abi_reader_worker_read_at(
+    worker: *mut Box<Worker>,
+    position: u64,
+    out: *mut SafeVec<MutFixed<Layer>>,
+    err: *mut Error,
+) -> u8 {
+    let worker = unsafe { &mut *worker };
+    match worker.read_at(position) {
+        Ok(layer) => {
+            let mut safe = SafeVec::with_capacity(1);
+            safe.push(MutFixed::new(layer));
+            unsafe { *out = safe };
+            1
+        }
+        Err(e) => {
+            unsafe { *err = Error::new(e.description()) };
+            0
+        }
+    }
+}
@@ -0,0 +1,88 @@
+#[macro_use]
+extern crate criterion;
+extern crate genet_abi;
+extern crate genet_filter;
+
+use criterion::Criterion;
+use genet_abi::{
+    attr::{Attr, AttrClass},
+    fixed::MutFixed,
+    layer::{Layer, LayerClass},
+};
+use genet_filter::{context::Context, parser::parse, Filter};
+
+const FILTER: &str =
+    "ipv4.src == @192.168.0.1 && tcp.dst in {80, 443, 8000..8080} && ipv4.ttl > 16 * 2";
+
+const FUNCTION_FILTER: &str = "len(tcp.payload) > 16 && count(tcp.options.nop) < 2";
+
+fn attr<T: 'static + Into<genet_abi::variant::Variant> + Send + Sync + Clone>(
+    id: &str,
+    value: T,
+) -> Attr {
+    let class: &'static AttrClass = Box::leak(Box::new(AttrClass::builder(id).value(value).build()));
+    Attr::builder(class).build()
+}
+
+fn layers() -> Vec<MutFixed<Layer>> {
+    let eth: &'static LayerClass = Box::leak(Box::new(
+        LayerClass::builder("eth")
+            .header(attr("eth.src", vec![0u8; 6].into_boxed_slice()))
+            .header(attr("eth.dst", vec![0u8; 6].into_boxed_slice()))
+            .header(attr("eth.type", 0x0800u64))
+            .build(),
+    ));
+    let ipv4: &'static LayerClass = Box::leak(Box::new(
+        LayerClass::builder("ipv4")
+            .header(attr("ipv4.ttl", 64u64))
+            .header(attr("ipv4.protocol", 6u64))
+            .header(attr("ipv4.src", vec![192u8, 168, 0, 1].into_boxed_slice()))
+            .header(attr("ipv4.dst", vec![192u8, 168, 0, 2].into_boxed_slice()))
+            .build(),
+    ));
+    let tcp: &'static LayerClass = Box::leak(Box::new(
+        LayerClass::builder("tcp")
+            .header(attr("tcp.src", 52000u64))
+            .header(attr("tcp.dst", 8080u64))
+            .header(attr("tcp.options.nop", true))
+            .header(attr("tcp.payload", vec![0u8; 32].into_boxed_slice()))
+            .build(),
+    ));
+    vec![
+        MutFixed::new(Layer::new(eth, vec![0u8; 64])),
+        MutFixed::new(Layer::new(ipv4, vec![0u8; 64])),
+        MutFixed::new(Layer::new(tcp, vec![0u8; 64])),
+    ]
+}
+
+fn tree_walk(c: &mut Criterion) {
+    let expr = parse(FILTER).unwrap();
+    let layers = layers();
+    c.bench_function("tree walk", move |b| {
+        b.iter(|| expr.eval(&Context::new(&layers)))
+    });
+}
+
+fn bytecode(c: &mut Criterion) {
+    let filter = Filter::compile(FILTER).unwrap();
+    let layers = layers();
+    c.bench_function("bytecode", move |b| {
+        b.iter(|| filter.test(&Context::new(&layers)))
+    });
+}
+
+fn functions(c: &mut Criterion) {
+    let expr = parse(FUNCTION_FILTER).unwrap();
+    let filter = Filter::compile(FUNCTION_FILTER).unwrap();
+    let tree_layers = layers();
+    let layers = layers();
+    c.bench_function("tree walk len()", move |b| {
+        b.iter(|| expr.eval(&Context::new(&tree_layers)))
+    });
+    c.bench_function("bytecode len()", move |b| {
+        b.iter(|| filter.test(&Context::new(&layers)))
+    });
+}
+
+criterion_group!(benches, tree_walk, bytecode, functions);
+criterion_main!(benches);
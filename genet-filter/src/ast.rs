@@ -1,20 +1,68 @@
 use context::Context;
+use function::{Function, Param};
 use genet_abi::{token::Token, variant::Variant};
+use macros::Subnet;
+use regex::{bytes::Regex, Error};
 use variant::VariantExt;
 
+/// A compiled regular expression.
+///
+/// Patterns are compared by their source strings.
+#[derive(Clone, Debug)]
+pub struct Pattern(Regex);
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Result<Pattern, Error> {
+        Regex::new(pattern).map(Pattern)
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub fn is_match(&self, text: &[u8]) -> bool {
+        self.0.is_match(text)
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Pattern) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
 #[derive(PartialEq, Clone, Debug)]
 pub enum Expr {
     Literal(Variant),
     Token(Token),
     Macro(String),
+    Subnet(Subnet),
     CmpEq(Box<Expr>, Box<Expr>),
     CmpNotEq(Box<Expr>, Box<Expr>),
     CmpLt(Box<Expr>, Box<Expr>),
     CmpGt(Box<Expr>, Box<Expr>),
     CmpLte(Box<Expr>, Box<Expr>),
     CmpGte(Box<Expr>, Box<Expr>),
+    CmpContains(Box<Expr>, Box<Expr>),
+    CmpMatches(Box<Expr>, Pattern),
+    CmpIn(Box<Expr>, Box<Expr>),
+    Set(Vec<Expr>),
+    Range(Box<Expr>, Box<Expr>),
+    Call(&'static Function, Vec<Expr>),
+    Index(Box<Expr>, Box<Expr>),
+    Slice(Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>),
     LogicalAnd(Box<Expr>, Box<Expr>),
     LogicalOr(Box<Expr>, Box<Expr>),
+    ArithAdd(Box<Expr>, Box<Expr>),
+    ArithSub(Box<Expr>, Box<Expr>),
+    ArithMul(Box<Expr>, Box<Expr>),
+    ArithDiv(Box<Expr>, Box<Expr>),
+    ArithRem(Box<Expr>, Box<Expr>),
+    BitAnd(Box<Expr>, Box<Expr>),
+    BitOr(Box<Expr>, Box<Expr>),
+    BitXor(Box<Expr>, Box<Expr>),
+    BitShl(Box<Expr>, Box<Expr>),
+    BitShr(Box<Expr>, Box<Expr>),
     LogicalNegation(Box<Expr>),
     UnaryPlus(Box<Expr>),
     UnaryNegation(Box<Expr>),
@@ -24,40 +72,76 @@ impl Expr {
     pub fn eval(&self, ctx: &Context) -> Variant {
         match self {
             Expr::Literal(v) => v.clone(),
-            Expr::CmpEq(l, r) => Variant::Bool(l.eval(ctx).op_eq(&r.eval(ctx))),
-            Expr::CmpNotEq(l, r) => Variant::Bool(!l.eval(ctx).op_eq(&r.eval(ctx))),
+            Expr::CmpEq(l, r) => Variant::Bool(l.eval_eq(r, ctx)),
+            Expr::CmpNotEq(l, r) => Variant::Bool(!l.eval_eq(r, ctx)),
             Expr::CmpLt(l, r) => Variant::Bool(l.eval(ctx).op_lt(&r.eval(ctx))),
             Expr::CmpGt(l, r) => Variant::Bool(l.eval(ctx).op_gt(&r.eval(ctx))),
             Expr::CmpLte(l, r) => Variant::Bool(l.eval(ctx).op_lte(&r.eval(ctx))),
             Expr::CmpGte(l, r) => Variant::Bool(l.eval(ctx).op_gte(&r.eval(ctx))),
+            Expr::CmpContains(l, r) => Variant::Bool(l.eval(ctx).op_contains(&r.eval(ctx))),
+            Expr::CmpMatches(l, p) => {
+                Variant::Bool(l.eval(ctx).as_bytes().map_or(false, |b| p.is_match(b)))
+            }
+            Expr::CmpIn(l, r) => {
+                let value = l.eval(ctx);
+                let found = match r.as_ref() {
+                    Expr::Set(items) => items.iter().any(|item| match item {
+                        Expr::Range(start, end) => {
+                            value.op_gte(&start.eval(ctx)) && value.op_lte(&end.eval(ctx))
+                        }
+                        Expr::Subnet(net) => net.contains(&value),
+                        _ => value.op_eq(&item.eval(ctx)),
+                    }),
+                    _ => false,
+                };
+                Variant::Bool(found)
+            }
+            Expr::Call(f, args) => {
+                let args = f
+                    .params()
+                    .iter()
+                    .zip(args.iter())
+                    .map(|(param, arg)| match (param, arg) {
+                        (Param::Field, Expr::Token(t)) => Variant::UInt64(ctx.count(*t) as u64),
+                        _ => arg.eval(ctx),
+                    })
+                    .collect::<Vec<_>>();
+                f.eval(&args)
+            }
+            Expr::Index(v, i) => v.eval(ctx).op_index(&i.eval(ctx)),
+            Expr::Slice(v, start, end) => v.eval(ctx).op_slice(
+                start.as_ref().map(|e| e.eval(ctx)).as_ref(),
+                end.as_ref().map(|e| e.eval(ctx)).as_ref(),
+            ),
             Expr::LogicalAnd(l, r) => {
                 Variant::Bool(l.eval(ctx).is_truthy() && r.eval(ctx).is_truthy())
             }
             Expr::LogicalOr(l, r) => {
                 Variant::Bool(l.eval(ctx).is_truthy() || r.eval(ctx).is_truthy())
             }
+            Expr::ArithAdd(l, r) => l.eval(ctx).op_add(&r.eval(ctx)),
+            Expr::ArithSub(l, r) => l.eval(ctx).op_sub(&r.eval(ctx)),
+            Expr::ArithMul(l, r) => l.eval(ctx).op_mul(&r.eval(ctx)),
+            Expr::ArithDiv(l, r) => l.eval(ctx).op_div(&r.eval(ctx)),
+            Expr::ArithRem(l, r) => l.eval(ctx).op_rem(&r.eval(ctx)),
+            Expr::BitAnd(l, r) => l.eval(ctx).op_bit_and(&r.eval(ctx)),
+            Expr::BitOr(l, r) => l.eval(ctx).op_bit_or(&r.eval(ctx)),
+            Expr::BitXor(l, r) => l.eval(ctx).op_bit_xor(&r.eval(ctx)),
+            Expr::BitShl(l, r) => l.eval(ctx).op_shl(&r.eval(ctx)),
+            Expr::BitShr(l, r) => l.eval(ctx).op_shr(&r.eval(ctx)),
             Expr::LogicalNegation(v) => Variant::Bool(!v.eval(ctx).is_truthy()),
             Expr::UnaryPlus(v) => v.eval(ctx).op_unary_plus(),
             Expr::UnaryNegation(v) => v.eval(ctx).op_unary_negation(),
-            Expr::Token(t) => {
-                for layer in ctx.layers().iter().rev() {
-                    if layer.id() == *t {
-                        return Variant::Bool(true);
-                    }
-                    if let Some(attr) = layer
-                        .headers()
-                        .iter()
-                        .chain(layer.attrs().iter())
-                        .find(|a| a.id() == *t)
-                    {
-                        if let Ok(val) = attr.try_get(layer) {
-                            return val;
-                        }
-                    }
-                }
-                Variant::Nil
-            }
-            Expr::Macro(_) => Variant::Nil,
+            Expr::Token(t) => ctx.load(*t),
+            Expr::Macro(_) | Expr::Subnet(_) | Expr::Set(_) | Expr::Range(_, _) => Variant::Nil,
+        }
+    }
+
+    /// Compares with the other expression, or tests subnet membership.
+    fn eval_eq(&self, other: &Expr, ctx: &Context) -> bool {
+        match (self, other) {
+            (Expr::Subnet(net), expr) | (expr, Expr::Subnet(net)) => net.contains(&expr.eval(ctx)),
+            (lhs, rhs) => lhs.eval(ctx).op_eq(&rhs.eval(ctx)),
         }
     }
 }
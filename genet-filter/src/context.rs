@@ -1,4 +1,8 @@
-use genet_abi::{fixed::MutFixed, layer::Layer};
+use genet_abi::{fixed::MutFixed, layer::Layer, token::Token, variant::Variant};
+
+lazy_static! {
+    static ref SEVERITY_TOKEN: Token = Token::from("_.annotation.severity");
+}
 
 pub struct Context<'a> {
     layers: &'a [MutFixed<Layer>],
@@ -12,4 +16,63 @@ impl<'a> Context<'a> {
     pub fn layers(&self) -> &'a [MutFixed<Layer>] {
         self.layers
     }
+
+    /// Returns the value of the innermost layer or attribute with the token.
+    ///
+    /// Aliases such as `_.src` are resolved by each layer.
+    /// The group or message of an annotation evaluates to `true`, and `_.annotation.severity`
+    /// to the name of the highest severity of the annotations in the frame.
+    pub fn load(&self, token: Token) -> Variant {
+        for layer in self.layers.iter().rev() {
+            if layer.id() == token {
+                return Variant::Bool(true);
+            }
+            if let Some(attr) = layer.attr(token) {
+                if let Ok(val) = attr.try_get(layer) {
+                    return val;
+                }
+            }
+            if layer
+                .annotations()
+                .iter()
+                .any(|a| a.group() == token || a.message() == token)
+            {
+                return Variant::Bool(true);
+            }
+        }
+        if token == *SEVERITY_TOKEN {
+            return self
+                .layers
+                .iter()
+                .flat_map(|layer| layer.annotations().iter())
+                .map(|a| a.severity())
+                .max()
+                .map_or(Variant::Nil, |severity| {
+                    Variant::String(severity.as_str().to_string().into_boxed_str())
+                });
+        }
+        Variant::Nil
+    }
+
+    /// Returns the number of layers, attributes and annotations with the token.
+    pub fn count(&self, token: Token) -> usize {
+        self.layers
+            .iter()
+            .map(|layer| {
+                let id = layer.attr(token).map_or(token, |attr| attr.id());
+                let attrs = layer
+                    .headers()
+                    .iter()
+                    .chain(layer.attrs().iter())
+                    .filter(|a| a.id() == id)
+                    .count();
+                let annotations = layer
+                    .annotations()
+                    .iter()
+                    .filter(|a| a.group() == token || a.message() == token)
+                    .count();
+                attrs + annotations + if layer.id() == token { 1 } else { 0 }
+            })
+            .sum()
+    }
 }
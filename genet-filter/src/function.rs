@@ -0,0 +1,149 @@
+use ast::Expr;
+use genet_abi::variant::Variant;
+use std::fmt;
+use variant::VariantExt;
+
+/// A parameter of a built-in function.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Param {
+    /// Any expression.
+    Value,
+
+    /// Any expression, which may be omitted.
+    Optional,
+
+    /// A field name such as `tcp.options.nop`, passed as the number of the fields in the frame.
+    Field,
+}
+
+/// A built-in function.
+pub struct Function {
+    name: &'static str,
+    params: &'static [Param],
+    eval: fn(&[Variant]) -> Variant,
+}
+
+impl Function {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn params(&self) -> &'static [Param] {
+        self.params
+    }
+
+    /// Checks the arguments at compile time.
+    pub fn check(&self, args: &[Expr]) -> Result<(), String> {
+        let min = self.params.iter().filter(|p| **p != Param::Optional).count();
+        let max = self.params.len();
+        if args.len() < min || args.len() > max {
+            let expected = if min == max {
+                format!("{}", max)
+            } else {
+                format!("{} to {}", min, max)
+            };
+            return Err(format!(
+                "{}() takes {} argument(s) but {} were given",
+                self.name,
+                expected,
+                args.len()
+            ));
+        }
+        let fields = self
+            .params
+            .iter()
+            .zip(args.iter())
+            .all(|(param, arg)| match (param, arg) {
+                (Param::Field, Expr::Token(_)) => true,
+                (Param::Field, _) => false,
+                _ => true,
+            });
+        if !fields {
+            return Err(format!("{}() requires a field name", self.name));
+        }
+        Ok(())
+    }
+
+    /// Calls the function with the evaluated arguments.
+    pub fn eval(&self, args: &[Variant]) -> Variant {
+        (self.eval)(args)
+    }
+}
+
+impl PartialEq for Function {
+    fn eq(&self, other: &Function) -> bool {
+        self.name == other.name
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Function {}", self.name)
+    }
+}
+
+static FUNCTIONS: &[Function] = &[
+    Function {
+        name: "len",
+        params: &[Param::Value],
+        eval: len,
+    },
+    Function {
+        name: "lower",
+        params: &[Param::Value],
+        eval: lower,
+    },
+    Function {
+        name: "upper",
+        params: &[Param::Value],
+        eval: upper,
+    },
+    Function {
+        name: "slice",
+        params: &[Param::Value, Param::Value, Param::Optional],
+        eval: slice,
+    },
+    Function {
+        name: "count",
+        params: &[Param::Field],
+        eval: count,
+    },
+];
+
+/// Returns the built-in function with the given name.
+pub fn lookup(name: &str) -> Option<&'static Function> {
+    FUNCTIONS.iter().find(|f| f.name == name)
+}
+
+/// Returns all built-in functions.
+pub fn functions() -> &'static [Function] {
+    FUNCTIONS
+}
+
+fn len(args: &[Variant]) -> Variant {
+    args[0]
+        .len()
+        .map_or(Variant::Nil, |len| Variant::UInt64(len as u64))
+}
+
+fn lower(args: &[Variant]) -> Variant {
+    match &args[0] {
+        Variant::String(s) => Variant::String(s.to_lowercase().into_boxed_str()),
+        _ => Variant::Nil,
+    }
+}
+
+fn upper(args: &[Variant]) -> Variant {
+    match &args[0] {
+        Variant::String(s) => Variant::String(s.to_uppercase().into_boxed_str()),
+        _ => Variant::Nil,
+    }
+}
+
+fn slice(args: &[Variant]) -> Variant {
+    args[0].op_slice(Some(&args[1]), args.get(2))
+}
+
+fn count(args: &[Variant]) -> Variant {
+    args[0].clone()
+}
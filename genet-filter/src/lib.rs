@@ -3,6 +3,7 @@ extern crate hwaddr;
 extern crate num_bigint;
 extern crate num_traits;
 extern crate pest;
+extern crate regex;
 extern crate serde;
 extern crate serde_json;
 
@@ -12,35 +13,88 @@ extern crate pest_derive;
 #[macro_use]
 extern crate arrayref;
 
+#[macro_use]
+extern crate lazy_static;
+
 use ast::Expr;
 use context::Context;
-use parser::parse;
+use genet_abi::variant::Variant;
+use macros::MacroTable;
+use parser::parse_with_macros;
 use result::Result;
+use schema::{Diagnostic, Schema};
 use std::fmt;
 use variant::VariantExt;
+use vm::Program;
 
 pub mod ast;
 pub mod context;
+pub mod function;
+pub mod macros;
 pub mod parser;
 pub mod result;
+pub mod schema;
 pub mod unparser;
 pub mod variant;
+pub mod vm;
 
 #[derive(Clone, Debug)]
 pub struct Filter {
     expr: Expr,
+    program: Program,
 }
 
 impl Filter {
     pub fn compile(filter: &str) -> Result<Filter> {
-        match parse(filter) {
-            Ok(expr) => Ok(Filter { expr }),
+        Filter::compile_with_macros(filter, &MacroTable::new())
+    }
+
+    pub fn compile_with_macros(filter: &str, macros: &MacroTable) -> Result<Filter> {
+        match parse_with_macros(filter, macros) {
+            Ok(expr) => {
+                let program = Program::compile(&expr);
+                Ok(Filter { expr, program })
+            }
             Err(err) => Err(Box::new(Error(format!("{}", err)))),
         }
     }
 
     pub fn test(&self, ctx: &Context) -> bool {
-        self.expr.eval(ctx).is_truthy()
+        self.program.eval(ctx).is_truthy()
+    }
+
+    pub fn eval(&self, ctx: &Context) -> Variant {
+        self.program.eval(ctx)
+    }
+
+    /// Returns a filter matching the frames both the filter and the other one match.
+    pub fn and(&self, other: &Filter) -> Filter {
+        let expr = Expr::LogicalAnd(Box::new(self.expr.clone()), Box::new(other.expr.clone()));
+        let program = Program::compile(&expr);
+        Filter { expr, program }
+    }
+
+    /// Returns true if the filter is a conjunction including all the conditions of
+    /// the other filter, so that it matches only the frames the other one matches.
+    pub fn refines(&self, other: &Filter) -> bool {
+        let terms = conjuncts(&self.expr);
+        conjuncts(&other.expr).iter().all(|t| terms.contains(t))
+    }
+
+    /// Checks the filter against the known fields.
+    pub fn validate(&self, schema: &Schema) -> Vec<Diagnostic> {
+        schema::validate(&self.expr, schema)
+    }
+}
+
+fn conjuncts(expr: &Expr) -> Vec<&Expr> {
+    match expr {
+        Expr::LogicalAnd(lhs, rhs) => {
+            let mut terms = conjuncts(lhs);
+            terms.append(&mut conjuncts(rhs));
+            terms
+        }
+        expr => vec![expr],
     }
 }
 
@@ -58,3 +112,35 @@ impl fmt::Display for Error {
         write!(f, "{}", self.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use context::Context;
+    use Filter;
+
+    #[test]
+    fn refines() {
+        let parent = Filter::compile("tcp.dst == 80").unwrap();
+        let child = Filter::compile("tcp.dst == 80 && tcp.src == 1000").unwrap();
+        assert!(child.refines(&parent));
+        assert!(!parent.refines(&child));
+        assert!(parent.refines(&parent));
+
+        let child = Filter::compile("tcp.dst == 80 || tcp.src == 1000").unwrap();
+        assert!(!child.refines(&parent));
+    }
+
+    #[test]
+    fn and() {
+        let parent = Filter::compile("tcp.dst == 80").unwrap();
+        let child = Filter::compile("tcp.src == 1000").unwrap();
+        let filter = parent.and(&child);
+        assert!(filter.refines(&parent));
+        assert!(filter.refines(&child));
+        assert!(!filter.test(&Context::new(&[])));
+        assert!(Filter::compile("true")
+            .unwrap()
+            .and(&Filter::compile("1 < 2").unwrap())
+            .test(&Context::new(&[])));
+    }
+}
@@ -0,0 +1,200 @@
+use ast::Expr;
+use genet_abi::variant::Variant;
+use hwaddr::HwAddr;
+use parser::parse_with_macros;
+use result::Result;
+use std::{
+    collections::HashMap,
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+/// Well-known ports available as macros by default.
+const PORTS: &[(&str, u64)] = &[
+    ("ftp", 21),
+    ("ssh", 22),
+    ("telnet", 23),
+    ("smtp", 25),
+    ("dns", 53),
+    ("http", 80),
+    ("pop3", 110),
+    ("ntp", 123),
+    ("imap", 143),
+    ("https", 443),
+];
+
+/// A table of named macros such as `@http`.
+#[derive(Clone, Debug)]
+pub struct MacroTable {
+    macros: HashMap<String, Expr>,
+}
+
+impl Default for MacroTable {
+    fn default() -> MacroTable {
+        MacroTable::new()
+    }
+}
+
+impl MacroTable {
+    /// Creates a new table with the well-known port macros.
+    pub fn new() -> MacroTable {
+        let macros = PORTS
+            .iter()
+            .map(|(name, port)| (name.to_string(), Expr::Literal(Variant::UInt64(*port))))
+            .collect();
+        MacroTable { macros }
+    }
+
+    /// Defines a macro, overriding the existing one with the same name.
+    pub fn insert(&mut self, name: &str, expr: Expr) {
+        self.macros.insert(name.to_string(), expr);
+    }
+
+    /// Compiles and defines a macro.
+    ///
+    /// The expression may refer to the macros already defined.
+    pub fn define(&mut self, name: &str, expr: &str) -> Result<()> {
+        match parse_with_macros(expr, self) {
+            Ok(expr) => {
+                self.insert(name, expr);
+                Ok(())
+            }
+            Err(err) => Err(Box::new(::Error(format!("{}", err)))),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Expr> {
+        self.macros.get(name)
+    }
+}
+
+/// An IPv4 or IPv6 subnet such as `@10.0.0.0/8`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Subnet {
+    addr: Box<[u8]>,
+    prefix: usize,
+}
+
+impl Subnet {
+    pub fn parse(exp: &str) -> Option<Subnet> {
+        let mut parts = exp.splitn(2, '/');
+        let addr = parts.next()?.parse::<IpAddr>().ok()?;
+        let prefix = parts.next()?.parse::<usize>().ok()?;
+        let addr = match addr {
+            IpAddr::V4(addr) => addr.octets().to_vec(),
+            IpAddr::V6(addr) => addr.octets().to_vec(),
+        };
+        if prefix > addr.len() * 8 {
+            return None;
+        }
+        Some(Subnet {
+            addr: addr.into_boxed_slice(),
+            prefix,
+        })
+    }
+
+    /// Returns true if the value is an address of the same family in the subnet.
+    pub fn contains(&self, value: &Variant) -> bool {
+        let addr: &[u8] = match value {
+            Variant::Buffer(b) => b,
+            Variant::Slice(b) => b,
+            _ => return false,
+        };
+        if addr.len() != self.addr.len() {
+            return false;
+        }
+        let bytes = self.prefix / 8;
+        let bits = self.prefix % 8;
+        if addr[..bytes] != self.addr[..bytes] {
+            return false;
+        }
+        if bits > 0 {
+            let mask = 0xffu8 << (8 - bits);
+            return addr[bytes] & mask == self.addr[bytes] & mask;
+        }
+        true
+    }
+}
+
+impl fmt::Display for Subnet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.addr.len() == 4 {
+            write!(f, "{}/{}", Ipv4Addr::from(*array_ref![self.addr, 0, 4]), self.prefix)
+        } else {
+            write!(f, "{}/{}", Ipv6Addr::from(*array_ref![self.addr, 0, 16]), self.prefix)
+        }
+    }
+}
+
+/// Expands a macro without the leading `@`.
+pub fn expand(exp: &str, table: &MacroTable) -> ::std::result::Result<Expr, String> {
+    if let Ok(addr) = exp.parse::<Ipv4Addr>() {
+        return Ok(Expr::Literal(Variant::Buffer(
+            addr.octets().to_vec().into_boxed_slice(),
+        )));
+    }
+    if let Ok(addr) = exp.parse::<Ipv6Addr>() {
+        return Ok(Expr::Literal(Variant::Buffer(
+            addr.octets().to_vec().into_boxed_slice(),
+        )));
+    }
+    if let Ok(addr) = exp.parse::<HwAddr>() {
+        return Ok(Expr::Literal(Variant::Buffer(
+            addr.octets().to_vec().into_boxed_slice(),
+        )));
+    }
+    if let Some(subnet) = Subnet::parse(exp) {
+        return Ok(Expr::Subnet(subnet));
+    }
+    table
+        .get(exp)
+        .cloned()
+        .ok_or_else(|| format!("unknown macro: @{}", exp))
+}
+
+#[cfg(test)]
+mod tests {
+    use ast::Expr;
+    use genet_abi::variant::Variant;
+    use macros::{MacroTable, Subnet};
+
+    #[test]
+    fn define() {
+        let mut table = MacroTable::new();
+        assert!(table.define("alt", "@http + 8000").is_ok());
+        assert_eq!(
+            table.get("alt"),
+            Some(&Expr::ArithAdd(
+                Box::new(Expr::Literal(Variant::UInt64(80))),
+                Box::new(Expr::Literal(Variant::UInt64(8000)))
+            ))
+        );
+        assert!(table.define("broken", "80 +").is_err());
+        assert!(table.get("broken").is_none());
+    }
+
+    #[test]
+    fn subnet() {
+        let net = Subnet::parse("192.168.0.0/16").unwrap();
+        assert!(net.contains(&Variant::Buffer(vec![192, 168, 10, 1].into_boxed_slice())));
+        assert!(!net.contains(&Variant::Buffer(vec![192, 169, 0, 1].into_boxed_slice())));
+        assert!(!net.contains(&Variant::UInt64(0)));
+        assert_eq!(net.to_string(), "192.168.0.0/16");
+
+        let net = Subnet::parse("10.0.0.0/9").unwrap();
+        assert!(net.contains(&Variant::Buffer(vec![10, 127, 0, 1].into_boxed_slice())));
+        assert!(!net.contains(&Variant::Buffer(vec![10, 128, 0, 1].into_boxed_slice())));
+
+        let net = Subnet::parse("fe80::/10").unwrap();
+        let mut addr = vec![0u8; 16];
+        addr[0] = 0xfe;
+        addr[1] = 0xbf;
+        assert!(net.contains(&Variant::Buffer(addr.clone().into_boxed_slice())));
+        addr[1] = 0xc0;
+        assert!(!net.contains(&Variant::Buffer(addr.into_boxed_slice())));
+        assert!(!net.contains(&Variant::Buffer(vec![254, 128, 0, 1].into_boxed_slice())));
+
+        assert!(Subnet::parse("10.0.0.0/33").is_none());
+        assert!(Subnet::parse("10.0.0.0").is_none());
+    }
+}
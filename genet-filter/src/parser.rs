@@ -1,16 +1,17 @@
-use ast::Expr;
+use ast::{Expr, Pattern};
+use function;
+use macros::{self, MacroTable};
 use genet_abi::{token::Token, variant::Variant};
-use hwaddr::HwAddr;
 use num_bigint::BigInt;
 use num_traits::Num;
 use pest::{
-    error::Error,
+    error::{Error, ErrorVariant},
     iterators::Pair,
     prec_climber::{Assoc, Operator, PrecClimber},
-    Parser,
+    Parser, Span,
 };
 use serde_json;
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str;
 use variant::VariantExt;
 
 #[derive(Parser)]
@@ -18,45 +19,51 @@ use variant::VariantExt;
 pub struct FilterParser;
 
 pub fn parse(filter: &str) -> Result<Expr, Error<Rule>> {
-    let result = FilterParser::parse(Rule::filter, filter);
-    match result {
-        Ok(mut expr) => Ok(consume_expr(
-            expr.next().unwrap().into_inner().next().unwrap(),
-        )),
-        Err(e) => Err(e),
-    }
+    parse_with_macros(filter, &MacroTable::new())
 }
 
-fn parse_macro(exp: String) -> Expr {
-    if let Ok(addr) = exp.parse::<Ipv4Addr>() {
-        return Expr::Literal(Variant::Buffer(addr.octets().to_vec().into_boxed_slice()));
-    }
-    if let Ok(addr) = exp.parse::<Ipv6Addr>() {
-        return Expr::Literal(Variant::Buffer(addr.octets().to_vec().into_boxed_slice()));
-    }
-    if let Ok(addr) = exp.parse::<HwAddr>() {
-        return Expr::Literal(Variant::Buffer(addr.octets().to_vec().into_boxed_slice()));
-    }
-    Expr::Macro(exp)
+pub fn parse_with_macros(filter: &str, table: &MacroTable) -> Result<Expr, Error<Rule>> {
+    let mut expr = FilterParser::parse(Rule::filter, filter)?;
+    consume_expr(expr.next().unwrap().into_inner().next().unwrap(), table)
+}
+
+fn custom_error(span: Span, message: String) -> Error<Rule> {
+    Error::new_from_span(ErrorVariant::CustomError { message }, span)
 }
 
-fn consume_expr(pair: Pair<Rule>) -> Expr {
+fn consume_expr(pair: Pair<Rule>, table: &MacroTable) -> Result<Expr, Error<Rule>> {
     let cmp = Operator::new(Rule::op_lt, Assoc::Left)
         | Operator::new(Rule::op_lte, Assoc::Left)
         | Operator::new(Rule::op_gt, Assoc::Left)
-        | Operator::new(Rule::op_gte, Assoc::Left);
+        | Operator::new(Rule::op_gte, Assoc::Left)
+        | Operator::new(Rule::op_contains, Assoc::Left)
+        | Operator::new(Rule::op_matches, Assoc::Left)
+        | Operator::new(Rule::op_in, Assoc::Left);
+    let mul = Operator::new(Rule::op_mul, Assoc::Left)
+        | Operator::new(Rule::op_div, Assoc::Left)
+        | Operator::new(Rule::op_rem, Assoc::Left);
     let climber = PrecClimber::new(vec![
-        cmp,
-        Operator::new(Rule::op_eq, Assoc::Left) | Operator::new(Rule::op_ne, Assoc::Left),
-        Operator::new(Rule::op_logical_and, Assoc::Left),
         Operator::new(Rule::op_logical_or, Assoc::Left),
+        Operator::new(Rule::op_logical_and, Assoc::Left),
+        Operator::new(Rule::op_eq, Assoc::Left) | Operator::new(Rule::op_ne, Assoc::Left),
+        cmp,
+        Operator::new(Rule::op_bit_or, Assoc::Left),
+        Operator::new(Rule::op_bit_xor, Assoc::Left),
+        Operator::new(Rule::op_bit_and, Assoc::Left),
+        Operator::new(Rule::op_shl, Assoc::Left) | Operator::new(Rule::op_shr, Assoc::Left),
+        Operator::new(Rule::op_add, Assoc::Left) | Operator::new(Rule::op_sub, Assoc::Left),
+        mul,
     ]);
     let primary = |pair: Pair<Rule>| match pair.as_rule() {
-        Rule::primary => consume_primary(pair),
-        _ => Expr::Literal(Variant::Nil),
+        Rule::primary => consume_primary(pair, table),
+        _ => Ok(Expr::Literal(Variant::Nil)),
     };
-    let infix = |lhs: Expr, op: Pair<Rule>, rhs: Expr| -> Expr {
-        match op.as_rule() {
+    let infix = |lhs: Result<Expr, Error<Rule>>,
+                 op: Pair<Rule>,
+                 rhs: Result<Expr, Error<Rule>>|
+     -> Result<Expr, Error<Rule>> {
+        let (lhs, rhs) = (lhs?, rhs?);
+        Ok(match op.as_rule() {
             Rule::op_lt => Expr::CmpLt(Box::new(lhs), Box::new(rhs)),
             Rule::op_lte => Expr::CmpLte(Box::new(lhs), Box::new(rhs)),
             Rule::op_gt => Expr::CmpGt(Box::new(lhs), Box::new(rhs)),
@@ -65,48 +72,172 @@ fn consume_expr(pair: Pair<Rule>) -> Expr {
             Rule::op_ne => Expr::CmpNotEq(Box::new(lhs), Box::new(rhs)),
             Rule::op_logical_and => Expr::LogicalAnd(Box::new(lhs), Box::new(rhs)),
             Rule::op_logical_or => Expr::LogicalOr(Box::new(lhs), Box::new(rhs)),
+            Rule::op_add => Expr::ArithAdd(Box::new(lhs), Box::new(rhs)),
+            Rule::op_sub => Expr::ArithSub(Box::new(lhs), Box::new(rhs)),
+            Rule::op_mul => Expr::ArithMul(Box::new(lhs), Box::new(rhs)),
+            Rule::op_div => Expr::ArithDiv(Box::new(lhs), Box::new(rhs)),
+            Rule::op_rem => Expr::ArithRem(Box::new(lhs), Box::new(rhs)),
+            Rule::op_bit_and => Expr::BitAnd(Box::new(lhs), Box::new(rhs)),
+            Rule::op_bit_or => Expr::BitOr(Box::new(lhs), Box::new(rhs)),
+            Rule::op_bit_xor => Expr::BitXor(Box::new(lhs), Box::new(rhs)),
+            Rule::op_shl => Expr::BitShl(Box::new(lhs), Box::new(rhs)),
+            Rule::op_shr => Expr::BitShr(Box::new(lhs), Box::new(rhs)),
+            Rule::op_contains => Expr::CmpContains(Box::new(lhs), Box::new(rhs)),
+            Rule::op_matches => match rhs {
+                Expr::Literal(Variant::String(pattern)) => match Pattern::new(&pattern) {
+                    Ok(pattern) => Expr::CmpMatches(Box::new(lhs), pattern),
+                    Err(err) => return Err(custom_error(op.as_span(), format!("{}", err))),
+                },
+                _ => {
+                    return Err(custom_error(
+                        op.as_span(),
+                        "matches requires a string literal".to_string(),
+                    ))
+                }
+            },
+            Rule::op_in => match rhs {
+                Expr::Set(_) => Expr::CmpIn(Box::new(lhs), Box::new(rhs)),
+                _ => return Err(custom_error(op.as_span(), "in requires a set".to_string())),
+            },
             _ => Expr::Literal(Variant::Nil),
-        }
+        })
     };
     climber.climb(pair.into_inner(), primary, infix)
 }
 
-fn consume_primary(pair: Pair<Rule>) -> Expr {
+fn consume_primary(pair: Pair<Rule>, table: &MacroTable) -> Result<Expr, Error<Rule>> {
+    let mut unary = Vec::new();
     let mut result = None;
-    for item in pair.into_inner().rev() {
-        result = Some(match item.as_rule() {
-            Rule::expression => consume_expr(item),
-            Rule::op_unary_plus => Expr::UnaryPlus(Box::new(result.take().unwrap())),
-            Rule::op_unary_negation => Expr::UnaryNegation(Box::new(result.take().unwrap())),
-            Rule::op_logical_negation => Expr::LogicalNegation(Box::new(result.take().unwrap())),
-            Rule::bin_integer => {
-                let v = BigInt::from_str_radix(&item.as_str()[2..], 2).unwrap();
-                Expr::Literal(Variant::BigInt(v.to_signed_bytes_be().into_boxed_slice()).shrink())
+    for item in pair.into_inner() {
+        match item.as_rule() {
+            Rule::op_unary_plus | Rule::op_unary_negation | Rule::op_logical_negation => {
+                unary.push(item.as_rule())
             }
-            Rule::oct_integer => {
-                let v = BigInt::from_str_radix(&item.as_str()[2..], 8).unwrap();
-                Expr::Literal(Variant::BigInt(v.to_signed_bytes_be().into_boxed_slice()).shrink())
+            Rule::index => {
+                let index = consume_expr(item.into_inner().next().unwrap(), table)?;
+                let operand = result.take().unwrap();
+                result = Some(Expr::Index(Box::new(operand), Box::new(index)));
             }
-            Rule::hex_integer => {
-                let v = BigInt::from_str_radix(&item.as_str()[2..], 16).unwrap();
-                Expr::Literal(Variant::BigInt(v.to_signed_bytes_be().into_boxed_slice()).shrink())
+            Rule::slice => {
+                let mut start = None;
+                let mut end = None;
+                for bound in item.into_inner() {
+                    let expr = consume_expr(bound.clone().into_inner().next().unwrap(), table)?;
+                    if bound.as_rule() == Rule::slice_start {
+                        start = Some(Box::new(expr));
+                    } else {
+                        end = Some(Box::new(expr));
+                    }
+                }
+                let operand = result.take().unwrap();
+                result = Some(Expr::Slice(Box::new(operand), start, end));
             }
-            Rule::dec_integer => {
-                let v = BigInt::from_str_radix(item.as_str(), 10).unwrap();
-                Expr::Literal(Variant::BigInt(v.to_signed_bytes_be().into_boxed_slice()).shrink())
+            _ => result = Some(consume_operand(item, table)?),
+        }
+    }
+    let mut result = result.unwrap();
+    for op in unary.into_iter().rev() {
+        result = match op {
+            Rule::op_unary_plus => Expr::UnaryPlus(Box::new(result)),
+            Rule::op_unary_negation => Expr::UnaryNegation(Box::new(result)),
+            _ => Expr::LogicalNegation(Box::new(result)),
+        };
+    }
+    Ok(result)
+}
+
+fn consume_call(pair: Pair<Rule>, table: &MacroTable) -> Result<Expr, Error<Rule>> {
+    let span = pair.as_span();
+    let mut inner = pair.into_inner();
+    let name = inner.next().unwrap().as_str();
+    let args = inner
+        .map(|arg| consume_expr(arg, table))
+        .collect::<Result<Vec<_>, _>>()?;
+    match function::lookup(name) {
+        Some(func) => match func.check(&args) {
+            Ok(()) => Ok(Expr::Call(func, args)),
+            Err(err) => Err(custom_error(span, err)),
+        },
+        None => Err(custom_error(span, format!("unknown function: {}", name))),
+    }
+}
+
+fn consume_operand(item: Pair<Rule>, table: &MacroTable) -> Result<Expr, Error<Rule>> {
+    Ok(match item.as_rule() {
+        Rule::expression => consume_expr(item, table)?,
+        Rule::set => Expr::Set(
+            item.into_inner()
+                .map(|item| match item.as_rule() {
+                    Rule::range => {
+                        let mut bounds = item.into_inner();
+                        let start = consume_primary(bounds.next().unwrap(), table)?;
+                        let end = consume_primary(bounds.next().unwrap(), table)?;
+                        Ok(Expr::Range(Box::new(start), Box::new(end)))
+                    }
+                    _ => consume_expr(item, table),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        Rule::call => consume_call(item, table)?,
+        Rule::bin_integer => {
+            let v = BigInt::from_str_radix(&item.as_str()[2..], 2).unwrap();
+            Expr::Literal(Variant::BigInt(v.to_signed_bytes_be().into_boxed_slice()).shrink())
+        }
+        Rule::oct_integer => {
+            let v = BigInt::from_str_radix(&item.as_str()[2..], 8).unwrap();
+            Expr::Literal(Variant::BigInt(v.to_signed_bytes_be().into_boxed_slice()).shrink())
+        }
+        Rule::hex_integer => {
+            let v = BigInt::from_str_radix(&item.as_str()[2..], 16).unwrap();
+            Expr::Literal(Variant::BigInt(v.to_signed_bytes_be().into_boxed_slice()).shrink())
+        }
+        Rule::dec_integer => {
+            let v = BigInt::from_str_radix(item.as_str(), 10).unwrap();
+            Expr::Literal(Variant::BigInt(v.to_signed_bytes_be().into_boxed_slice()).shrink())
+        }
+        Rule::string => Expr::Literal(Variant::String(
+            serde_json::from_str(item.as_str()).unwrap(),
+        )),
+        Rule::bytes => {
+            let s = item.as_str();
+            Expr::Literal(Variant::Buffer(unescape_bytes(&s[2..s.len() - 1])))
+        }
+        Rule::macro_exp => match macros::expand(&item.as_str()[1..], table) {
+            Ok(expr) => expr,
+            Err(err) => return Err(custom_error(item.as_span(), err)),
+        },
+        Rule::float => Expr::Literal(Variant::Float64(item.as_str().parse().unwrap())),
+        Rule::nil => Expr::Literal(Variant::Nil),
+        Rule::boolean => Expr::Literal(Variant::Bool(item.as_str() == "true")),
+        Rule::member => Expr::Token(Token::from(item.as_str())),
+        _ => Expr::Literal(Variant::Nil),
+    })
+}
+
+/// Decodes the body of a `b"..."` literal already validated by the grammar.
+fn unescape_bytes(s: &str) -> Box<[u8]> {
+    let mut data = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(c) = iter.next() {
+        if c != b'\\' {
+            data.push(c);
+            continue;
+        }
+        match iter.next() {
+            Some(b'0') => data.push(0),
+            Some(b'n') => data.push(b'\n'),
+            Some(b'r') => data.push(b'\r'),
+            Some(b't') => data.push(b'\t'),
+            Some(b'x') => {
+                let hex = [iter.next().unwrap(), iter.next().unwrap()];
+                let hex = str::from_utf8(&hex).unwrap();
+                data.push(u8::from_str_radix(hex, 16).unwrap());
             }
-            Rule::string => Expr::Literal(Variant::String(
-                serde_json::from_str(item.as_str()).unwrap(),
-            )),
-            Rule::macro_exp => parse_macro(item.as_str()[1..].to_string()),
-            Rule::float => Expr::Literal(Variant::Float64(item.as_str().parse().unwrap())),
-            Rule::nil => Expr::Literal(Variant::Nil),
-            Rule::boolean => Expr::Literal(Variant::Bool(item.as_str() == "true")),
-            Rule::member => Expr::Token(Token::from(item.as_str())),
-            _ => Expr::Literal(Variant::Nil),
-        });
+            Some(c) => data.push(c),
+            None => {}
+        }
     }
-    result.unwrap()
+    data.into_boxed_slice()
 }
 
 #[cfg(test)]
@@ -132,6 +263,15 @@ mod tests {
         assert_eq!(parse("0b110110"), Ok(Literal(Variant::UInt64(54))));
         assert_eq!(parse("0o776503"), Ok(Literal(Variant::UInt64(261_443))));
         assert_eq!(parse("0xff5678"), Ok(Literal(Variant::UInt64(16_733_816))));
+        assert_eq!(
+            parse(r#"b"GET\t\x00\xff\"""#),
+            Ok(Literal(Variant::Buffer(
+                b"GET\t\x00\xff\"".to_vec().into_boxed_slice()
+            )))
+        );
+        assert_eq!(parse(r#"b"""#), Ok(Literal(Variant::Buffer(Box::new([])))));
+        assert!(parse(r#"b"\x0""#).is_err());
+        assert!(parse(r#"b"\u00ff""#).is_err());
         assert_eq!(
             parse(r#" "aaaa" "#),
             Ok(Literal(Variant::String(
@@ -272,6 +412,164 @@ mod tests {
         );
     }
 
+    #[test]
+    fn arith() {
+        assert_eq!(
+            parse("a - b * 4 > 100"),
+            Ok(CmpGt(
+                Box::new(ArithSub(
+                    Box::new(Token(Token::from("a"))),
+                    Box::new(ArithMul(
+                        Box::new(Token(Token::from("b"))),
+                        Box::new(Literal(Variant::UInt64(4)))
+                    ))
+                )),
+                Box::new(Literal(Variant::UInt64(100)))
+            ))
+        );
+
+        assert_eq!(
+            parse("a % 2 == 0 || b"),
+            Ok(LogicalOr(
+                Box::new(CmpEq(
+                    Box::new(ArithRem(
+                        Box::new(Token(Token::from("a"))),
+                        Box::new(Literal(Variant::UInt64(2)))
+                    )),
+                    Box::new(Literal(Variant::UInt64(0)))
+                )),
+                Box::new(Token(Token::from("b")))
+            ))
+        );
+    }
+
+    #[test]
+    fn bitwise() {
+        assert_eq!(
+            parse("a & 0x02 | b << 1"),
+            Ok(BitOr(
+                Box::new(BitAnd(
+                    Box::new(Token(Token::from("a"))),
+                    Box::new(Literal(Variant::UInt64(2)))
+                )),
+                Box::new(BitShl(
+                    Box::new(Token(Token::from("b"))),
+                    Box::new(Literal(Variant::UInt64(1)))
+                ))
+            ))
+        );
+
+        assert_eq!(
+            parse("a && b & c"),
+            Ok(LogicalAnd(
+                Box::new(Token(Token::from("a"))),
+                Box::new(BitAnd(
+                    Box::new(Token(Token::from("b"))),
+                    Box::new(Token(Token::from("c")))
+                ))
+            ))
+        );
+    }
+
+    #[test]
+    fn membership() {
+        assert_eq!(
+            parse(r#"a contains "b""#),
+            Ok(CmpContains(
+                Box::new(Token(Token::from("a"))),
+                Box::new(Literal(Variant::String("b".to_string().into_boxed_str())))
+            ))
+        );
+
+        assert_eq!(
+            parse(r#"a matches "^b+$""#),
+            Ok(CmpMatches(
+                Box::new(Token(Token::from("a"))),
+                Pattern::new("^b+$").unwrap()
+            ))
+        );
+
+        assert_eq!(
+            parse("a in {80, 1024..65535} && b"),
+            Ok(LogicalAnd(
+                Box::new(CmpIn(
+                    Box::new(Token(Token::from("a"))),
+                    Box::new(Set(vec![
+                        Literal(Variant::UInt64(80)),
+                        Range(
+                            Box::new(Literal(Variant::UInt64(1024))),
+                            Box::new(Literal(Variant::UInt64(65535)))
+                        ),
+                    ]))
+                )),
+                Box::new(Token(Token::from("b")))
+            ))
+        );
+
+        assert_eq!(parse("index"), Ok(Token(Token::from("index"))));
+        assert_eq!(parse("contains.x"), Ok(Token(Token::from("contains.x"))));
+    }
+
+    #[test]
+    fn call() {
+        assert_eq!(
+            parse("len(a) > 0"),
+            Ok(CmpGt(
+                Box::new(Call(
+                    function::lookup("len").unwrap(),
+                    vec![Token(Token::from("a"))]
+                )),
+                Box::new(Literal(Variant::UInt64(0)))
+            ))
+        );
+
+        assert_eq!(
+            parse("count(a.b)"),
+            Ok(Call(
+                function::lookup("count").unwrap(),
+                vec![Token(Token::from("a.b"))]
+            ))
+        );
+
+        assert_eq!(
+            parse("-a[1][0:2]"),
+            Ok(UnaryNegation(Box::new(Slice(
+                Box::new(Index(
+                    Box::new(Token(Token::from("a"))),
+                    Box::new(Literal(Variant::UInt64(1)))
+                )),
+                Some(Box::new(Literal(Variant::UInt64(0)))),
+                Some(Box::new(Literal(Variant::UInt64(2))))
+            ))))
+        );
+
+        assert_eq!(
+            parse("a[:]"),
+            Ok(Slice(Box::new(Token(Token::from("a"))), None, None))
+        );
+    }
+
+    #[test]
+    fn macros() {
+        assert_eq!(
+            parse("@1.2.3.4"),
+            Ok(Literal(Variant::Buffer(vec![1, 2, 3, 4].into_boxed_slice())))
+        );
+        assert_eq!(
+            parse("@10.0.0.0/8"),
+            Ok(Subnet(macros::Subnet::parse("10.0.0.0/8").unwrap()))
+        );
+        assert_eq!(parse("@https"), Ok(Literal(Variant::UInt64(443))));
+
+        let mut table = MacroTable::new();
+        table.insert("web", parse("{80, 443, 8080}").unwrap());
+        assert_eq!(
+            parse_with_macros("a in @web", &table),
+            parse("a in {80, 443, 8080}")
+        );
+        assert!(parse("a in @web").is_err());
+    }
+
     #[test]
     fn error() {
         assert!(parse("| 12.5").is_err());
@@ -283,6 +581,20 @@ mod tests {
         assert!(parse(">= 12.5").is_err());
         assert!(parse("< 12.5").is_err());
         assert!(parse("> 12.5").is_err());
+        assert!(parse("* 12.5").is_err());
+        assert!(parse("12.5 <<").is_err());
+        assert!(parse("a matches b").is_err());
+        assert!(parse(r#"a matches "(""#).is_err());
+        assert!(parse("a in 80").is_err());
+        assert!(parse("a in {80,}").is_err());
+        assert!(parse("unknown(a)").is_err());
+        assert!(parse("len()").is_err());
+        assert!(parse("len(a, b)").is_err());
+        assert!(parse("slice(a)").is_err());
+        assert!(parse("count(1)").is_err());
+        assert!(parse("a[]").is_err());
+        assert!(parse("@unknown").is_err());
+        assert!(parse("@10.0.0.0/40").is_err());
         assert!(parse(r#" """ "#).is_err());
         assert!(parse(r#" "\x" "#).is_err());
         assert!(parse(r#" "\\"" "#).is_err());
@@ -0,0 +1,262 @@
+use ast::Expr;
+use genet_abi::variant::Variant;
+use std::{collections::HashMap, fmt};
+
+/// Types whose values are compared as raw addresses.
+const ADDRESS_TYPES: &[&str] = &["@ipv4:addr", "@ipv6:addr", "@eth:mac"];
+
+/// A set of known fields and their types.
+#[derive(Clone, Debug, Default)]
+pub struct Schema {
+    fields: HashMap<String, String>,
+}
+
+impl Schema {
+    pub fn new() -> Schema {
+        Schema::default()
+    }
+
+    /// Adds a field. An empty type means the field is untyped.
+    pub fn insert(&mut self, id: &str, typ: &str) {
+        let entry = self.fields.entry(id.to_string()).or_insert_with(String::new);
+        if entry.is_empty() {
+            *entry = typ.to_string();
+        }
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.fields.contains_key(id)
+    }
+
+    /// Returns the type of the field.
+    pub fn typ(&self, id: &str) -> Option<&str> {
+        self.fields.get(id).map(|t| t.as_str())
+    }
+
+    /// Returns the known field closest to the given name, if any.
+    pub fn suggest(&self, id: &str) -> Option<&str> {
+        let max = (id.chars().count() / 4).max(1);
+        self.fields
+            .keys()
+            .map(|name| (distance(id, name), name.as_str()))
+            .filter(|(dist, _)| *dist <= max)
+            .min()
+            .map(|(_, name)| name)
+    }
+}
+
+/// A problem found by the static analysis of a filter.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Diagnostic {
+    /// The field is not provided by any package.
+    UnknownField {
+        name: String,
+        suggestion: Option<String>,
+    },
+
+    /// The field is compared with a value of an incompatible type.
+    TypeMismatch {
+        name: String,
+        typ: String,
+        found: &'static str,
+    },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Diagnostic::UnknownField {
+                name,
+                suggestion: Some(suggestion),
+            } => write!(f, "unknown field: {} (did you mean {}?)", name, suggestion),
+            Diagnostic::UnknownField { name, .. } => write!(f, "unknown field: {}", name),
+            Diagnostic::TypeMismatch { name, typ, found } => {
+                write!(f, "{} ({}) cannot be compared with {}", name, typ, found)
+            }
+        }
+    }
+}
+
+/// Checks the fields and comparisons in the expression against the schema.
+pub fn validate(expr: &Expr, schema: &Schema) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    visit(expr, schema, &mut diagnostics);
+    diagnostics
+}
+
+fn visit(expr: &Expr, schema: &Schema, diagnostics: &mut Vec<Diagnostic>) {
+    match expr {
+        Expr::Literal(_) | Expr::Macro(_) | Expr::Subnet(_) => {}
+        Expr::Token(t) => {
+            let name = t.to_string();
+            if !schema.contains(&name) {
+                let suggestion = schema.suggest(&name).map(|s| s.to_string());
+                diagnostics.push(Diagnostic::UnknownField { name, suggestion });
+            }
+        }
+        Expr::CmpEq(l, r)
+        | Expr::CmpNotEq(l, r)
+        | Expr::CmpLt(l, r)
+        | Expr::CmpGt(l, r)
+        | Expr::CmpLte(l, r)
+        | Expr::CmpGte(l, r) => {
+            compare(l, r, schema, diagnostics);
+            compare(r, l, schema, diagnostics);
+            visit(l, schema, diagnostics);
+            visit(r, schema, diagnostics);
+        }
+        Expr::CmpIn(l, r) => {
+            if let Expr::Set(items) = r.as_ref() {
+                for item in items {
+                    compare(l, item, schema, diagnostics);
+                }
+            }
+            visit(l, schema, diagnostics);
+            visit(r, schema, diagnostics);
+        }
+        Expr::CmpMatches(v, _)
+        | Expr::LogicalNegation(v)
+        | Expr::UnaryPlus(v)
+        | Expr::UnaryNegation(v) => visit(v, schema, diagnostics),
+        Expr::Set(items) | Expr::Call(_, items) => {
+            for item in items {
+                visit(item, schema, diagnostics);
+            }
+        }
+        Expr::Slice(v, start, end) => {
+            visit(v, schema, diagnostics);
+            for e in start.iter().chain(end.iter()) {
+                visit(e, schema, diagnostics);
+            }
+        }
+        Expr::CmpContains(l, r)
+        | Expr::Range(l, r)
+        | Expr::Index(l, r)
+        | Expr::LogicalAnd(l, r)
+        | Expr::LogicalOr(l, r)
+        | Expr::ArithAdd(l, r)
+        | Expr::ArithSub(l, r)
+        | Expr::ArithMul(l, r)
+        | Expr::ArithDiv(l, r)
+        | Expr::ArithRem(l, r)
+        | Expr::BitAnd(l, r)
+        | Expr::BitOr(l, r)
+        | Expr::BitXor(l, r)
+        | Expr::BitShl(l, r)
+        | Expr::BitShr(l, r) => {
+            visit(l, schema, diagnostics);
+            visit(r, schema, diagnostics);
+        }
+    }
+}
+
+/// Reports an address field compared with a literal other than bytes.
+fn compare(field: &Expr, value: &Expr, schema: &Schema, diagnostics: &mut Vec<Diagnostic>) {
+    let (name, typ) = match field {
+        Expr::Token(t) => {
+            let name = t.to_string();
+            match schema.typ(&name) {
+                Some(typ) if ADDRESS_TYPES.contains(&typ) => {
+                    let typ = typ.to_string();
+                    (name, typ)
+                }
+                _ => return,
+            }
+        }
+        _ => return,
+    };
+    let found = match value {
+        Expr::Literal(Variant::String(_)) => "a string",
+        Expr::Literal(Variant::Bool(_)) => "a boolean",
+        Expr::Literal(Variant::Float64(_)) => "a number",
+        Expr::Literal(Variant::Int64(_))
+        | Expr::Literal(Variant::UInt64(_))
+        | Expr::Literal(Variant::BigInt(_)) => "an integer",
+        _ => return,
+    };
+    diagnostics.push(Diagnostic::TypeMismatch { name, typ, found });
+}
+
+/// Returns the Levenshtein distance between two strings.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            let next = (row[j + 1] + 1).min(row[j] + 1).min(prev + cost);
+            prev = row[j + 1];
+            row[j + 1] = next;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse;
+    use schema::{distance, validate, Diagnostic, Schema};
+
+    fn schema() -> Schema {
+        let mut schema = Schema::new();
+        schema.insert("ipv4", "");
+        schema.insert("ipv4.src", "@ipv4:addr");
+        schema.insert("ipv4.dst", "@ipv4:addr");
+        schema.insert("ipv4.ttl", "");
+        schema.insert("tcp.srcPort", "");
+        schema
+    }
+
+    #[test]
+    fn unknown() {
+        let schema = schema();
+        assert_eq!(
+            validate(&parse("ipv4.srcc == @127.0.0.1").unwrap(), &schema),
+            vec![Diagnostic::UnknownField {
+                name: "ipv4.srcc".into(),
+                suggestion: Some("ipv4.src".into()),
+            }]
+        );
+        assert_eq!(
+            validate(&parse("len(udp.payload) > 0").unwrap(), &schema),
+            vec![Diagnostic::UnknownField {
+                name: "udp.payload".into(),
+                suggestion: None,
+            }]
+        );
+        assert!(validate(&parse("ipv4 && tcp.srcPort in {80, 443}").unwrap(), &schema).is_empty());
+    }
+
+    #[test]
+    fn mismatch() {
+        let schema = schema();
+        let diagnostics = validate(&parse("\"127.0.0.1\" == ipv4.src").unwrap(), &schema);
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic::TypeMismatch {
+                name: "ipv4.src".into(),
+                typ: "@ipv4:addr".into(),
+                found: "a string",
+            }]
+        );
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "ipv4.src (@ipv4:addr) cannot be compared with a string"
+        );
+        assert_eq!(
+            validate(&parse("ipv4.dst in {@10.0.0.1, 5}").unwrap(), &schema).len(),
+            1
+        );
+        assert!(validate(&parse("ipv4.src == @10.0.0.0/8").unwrap(), &schema).is_empty());
+        assert!(validate(&parse("ipv4.ttl == \"64\"").unwrap(), &schema).is_empty());
+    }
+
+    #[test]
+    fn levenshtein() {
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("ipv4.src", "ipv4.src"), 0);
+    }
+}
@@ -1,6 +1,7 @@
 use ast::Expr;
 use genet_abi::{token::Token, variant::Variant};
 use hwaddr::HwAddr;
+use serde_json;
 use std::net::{Ipv4Addr, Ipv6Addr};
 use variant::VariantExt;
 
@@ -27,33 +28,204 @@ pub fn unparse_attr(typ: Token, var: &Variant) -> Expr {
     Expr::Literal(var.clone())
 }
 
+const PREC_OR: u8 = 1;
+const PREC_AND: u8 = 2;
+const PREC_EQ: u8 = 3;
+const PREC_CMP: u8 = 4;
+const PREC_BIT_OR: u8 = 5;
+const PREC_BIT_XOR: u8 = 6;
+const PREC_BIT_AND: u8 = 7;
+const PREC_SHIFT: u8 = 8;
+const PREC_ADD: u8 = 9;
+const PREC_MUL: u8 = 10;
+const PREC_UNARY: u8 = 11;
+const PREC_PRIMARY: u8 = 12;
+
 pub fn unparse(expr: &Expr) -> String {
+    unparse_prec(expr).0
+}
+
+/// Returns the string with the precedence of its outermost operator.
+fn unparse_prec(expr: &Expr) -> (String, u8) {
     match expr {
-        Expr::Literal(var) => var.to_string(),
-        Expr::Token(t) => t.to_string(),
-        Expr::Macro(expr) => format!("@{}", expr),
+        Expr::Literal(var) => (var.to_string(), PREC_PRIMARY),
+        Expr::Token(t) => (t.to_string(), PREC_PRIMARY),
+        Expr::Macro(expr) => (format!("@{}", expr), PREC_PRIMARY),
+        Expr::Subnet(net) => (format!("@{}", net), PREC_PRIMARY),
         Expr::CmpEq(lhs, rhs) => match (lhs.as_ref(), rhs.as_ref()) {
-            (lhs, &Expr::Literal(Variant::Bool(true))) => unparse(lhs),
-            (lhs, &Expr::Literal(Variant::Bool(false))) => format!("!{}", unparse(lhs)),
-            (&Expr::Literal(Variant::Bool(true)), rhs) => unparse(rhs),
-            (&Expr::Literal(Variant::Bool(false)), rhs) => format!("!{}", unparse(rhs)),
-            (lhs, rhs) => format!("{} == {}", unparse(lhs), unparse(rhs)),
+            (lhs, &Expr::Literal(Variant::Bool(true))) => unparse_prec(lhs),
+            (lhs, &Expr::Literal(Variant::Bool(false))) => unary("!", lhs),
+            (&Expr::Literal(Variant::Bool(true)), rhs) => unparse_prec(rhs),
+            (&Expr::Literal(Variant::Bool(false)), rhs) => unary("!", rhs),
+            (lhs, rhs) => binary(lhs, "==", rhs, PREC_EQ),
         },
         Expr::CmpNotEq(lhs, rhs) => match (lhs.as_ref(), rhs.as_ref()) {
-            (lhs, &Expr::Literal(Variant::Bool(false))) => unparse(lhs),
-            (lhs, &Expr::Literal(Variant::Bool(true))) => format!("!{}", unparse(lhs)),
-            (&Expr::Literal(Variant::Bool(false)), rhs) => unparse(rhs),
-            (&Expr::Literal(Variant::Bool(true)), rhs) => format!("!{}", unparse(rhs)),
-            (lhs, rhs) => format!("{} != {}", unparse(lhs), unparse(rhs)),
+            (lhs, &Expr::Literal(Variant::Bool(false))) => unparse_prec(lhs),
+            (lhs, &Expr::Literal(Variant::Bool(true))) => unary("!", lhs),
+            (&Expr::Literal(Variant::Bool(false)), rhs) => unparse_prec(rhs),
+            (&Expr::Literal(Variant::Bool(true)), rhs) => unary("!", rhs),
+            (lhs, rhs) => binary(lhs, "!=", rhs, PREC_EQ),
         },
-        Expr::CmpLt(lhs, rhs) => format!("{} < {}", unparse(lhs), unparse(rhs)),
-        Expr::CmpGt(lhs, rhs) => format!("{} > {}", unparse(lhs), unparse(rhs)),
-        Expr::CmpLte(lhs, rhs) => format!("{} <= {}", unparse(lhs), unparse(rhs)),
-        Expr::CmpGte(lhs, rhs) => format!("{} >= {}", unparse(lhs), unparse(rhs)),
-        Expr::LogicalAnd(lhs, rhs) => format!("{} && {}", unparse(lhs), unparse(rhs)),
-        Expr::LogicalOr(lhs, rhs) => format!("{} || {}", unparse(lhs), unparse(rhs)),
-        Expr::LogicalNegation(expr) => format!("!{}", unparse(expr)),
-        Expr::UnaryPlus(expr) => format!("+{}", unparse(expr)),
-        Expr::UnaryNegation(expr) => format!("-{}", unparse(expr)),
+        Expr::CmpLt(lhs, rhs) => binary(lhs, "<", rhs, PREC_CMP),
+        Expr::CmpGt(lhs, rhs) => binary(lhs, ">", rhs, PREC_CMP),
+        Expr::CmpLte(lhs, rhs) => binary(lhs, "<=", rhs, PREC_CMP),
+        Expr::CmpGte(lhs, rhs) => binary(lhs, ">=", rhs, PREC_CMP),
+        Expr::CmpContains(lhs, rhs) => binary(lhs, "contains", rhs, PREC_CMP),
+        Expr::CmpMatches(lhs, pattern) => (
+            format!(
+                "{} matches {}",
+                operand(lhs, PREC_CMP),
+                serde_json::to_string(pattern.as_str()).unwrap()
+            ),
+            PREC_CMP,
+        ),
+        Expr::CmpIn(lhs, rhs) => binary(lhs, "in", rhs, PREC_CMP),
+        Expr::Set(items) => (
+            format!(
+                "{{{}}}",
+                items.iter().map(unparse).collect::<Vec<_>>().join(", ")
+            ),
+            PREC_PRIMARY,
+        ),
+        Expr::Range(start, end) => (
+            format!(
+                "{}..{}",
+                operand(start, PREC_UNARY),
+                operand(end, PREC_UNARY)
+            ),
+            PREC_PRIMARY,
+        ),
+        Expr::LogicalAnd(lhs, rhs) => binary(lhs, "&&", rhs, PREC_AND),
+        Expr::LogicalOr(lhs, rhs) => binary(lhs, "||", rhs, PREC_OR),
+        Expr::ArithAdd(lhs, rhs) => binary(lhs, "+", rhs, PREC_ADD),
+        Expr::ArithSub(lhs, rhs) => binary(lhs, "-", rhs, PREC_ADD),
+        Expr::ArithMul(lhs, rhs) => binary(lhs, "*", rhs, PREC_MUL),
+        Expr::ArithDiv(lhs, rhs) => binary(lhs, "/", rhs, PREC_MUL),
+        Expr::ArithRem(lhs, rhs) => binary(lhs, "%", rhs, PREC_MUL),
+        Expr::BitAnd(lhs, rhs) => binary(lhs, "&", rhs, PREC_BIT_AND),
+        Expr::BitOr(lhs, rhs) => binary(lhs, "|", rhs, PREC_BIT_OR),
+        Expr::BitXor(lhs, rhs) => binary(lhs, "^", rhs, PREC_BIT_XOR),
+        Expr::BitShl(lhs, rhs) => binary(lhs, "<<", rhs, PREC_SHIFT),
+        Expr::BitShr(lhs, rhs) => binary(lhs, ">>", rhs, PREC_SHIFT),
+        Expr::Call(func, args) => (
+            format!(
+                "{}({})",
+                func.name(),
+                args.iter().map(unparse).collect::<Vec<_>>().join(", ")
+            ),
+            PREC_PRIMARY,
+        ),
+        Expr::Index(expr, index) => (
+            format!("{}[{}]", operand(expr, PREC_PRIMARY), unparse(index)),
+            PREC_PRIMARY,
+        ),
+        Expr::Slice(expr, start, end) => (
+            format!(
+                "{}[{}:{}]",
+                operand(expr, PREC_PRIMARY),
+                start.as_ref().map(|e| unparse(e)).unwrap_or_default(),
+                end.as_ref().map(|e| unparse(e)).unwrap_or_default()
+            ),
+            PREC_PRIMARY,
+        ),
+        Expr::LogicalNegation(expr) => unary("!", expr),
+        Expr::UnaryPlus(expr) => unary("+", expr),
+        Expr::UnaryNegation(expr) => unary("-", expr),
+    }
+}
+
+fn unary(op: &str, expr: &Expr) -> (String, u8) {
+    (format!("{}{}", op, operand(expr, PREC_UNARY)), PREC_UNARY)
+}
+
+fn binary(lhs: &Expr, op: &str, rhs: &Expr, prec: u8) -> (String, u8) {
+    // All binary operators are left-associative.
+    (
+        format!("{} {} {}", operand(lhs, prec), op, operand(rhs, prec + 1)),
+        prec,
+    )
+}
+
+fn operand(expr: &Expr, prec: u8) -> String {
+    let (s, p) = unparse_prec(expr);
+    if p < prec {
+        format!("({})", s)
+    } else {
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parser::parse;
+    use unparser::unparse;
+
+    fn round_trip(filter: &str) -> String {
+        unparse(&parse(filter).unwrap())
+    }
+
+    #[test]
+    fn arith() {
+        assert_eq!(round_trip("a + b * c"), "a + b * c");
+        assert_eq!(round_trip("(a + b) * c"), "(a + b) * c");
+        assert_eq!(round_trip("a - (b - c)"), "a - (b - c)");
+        assert_eq!(round_trip("(a - b) - c"), "a - b - c");
+        assert_eq!(round_trip("a % 2 == 0"), "a % 2 == 0");
+        assert_eq!(round_trip("-(a + b)"), "-(a + b)");
+    }
+
+    #[test]
+    fn bitwise() {
+        assert_eq!(round_trip("a & 0x02 != 0"), "a & 2 != 0");
+        assert_eq!(round_trip("(a | b) & c"), "(a | b) & c");
+        assert_eq!(round_trip("a ^ b << 2"), "a ^ b << 2");
+        assert_eq!(round_trip("(a ^ b) >> 2"), "(a ^ b) >> 2");
+    }
+
+    #[test]
+    fn membership() {
+        assert_eq!(
+            round_trip(r#"a contains b"\x01\x02""#),
+            r#"a contains b"\x01\x02""#
+        );
+        assert_eq!(
+            round_trip(r#"a contains b"GET /\r\n\"""#),
+            r#"a contains b"GET /\r\n\"""#
+        );
+        assert_eq!(
+            round_trip(r#"a matches "^www\\.""#),
+            r#"a matches "^www\\.""#
+        );
+        assert_eq!(
+            round_trip("a in {80,443, 1024 .. 65535}"),
+            "a in {80, 443, 1024..65535}"
+        );
+        assert_eq!(round_trip("a + 1 in {b * 2}"), "a + 1 in {b * 2}");
+    }
+
+    #[test]
+    fn call() {
+        assert_eq!(round_trip("len( a )>0"), "len(a) > 0");
+        assert_eq!(round_trip("slice(a, 1,2)"), "slice(a, 1, 2)");
+        assert_eq!(round_trip("a[0:2] == b[ 1 ]"), "a[0:2] == b[1]");
+        assert_eq!(round_trip("a[:-1]"), "a[:-1]");
+        assert_eq!(round_trip("(-a)[1:]"), "(-a)[1:]");
+        assert_eq!(round_trip("-a[1:]"), "-a[1:]");
+    }
+
+    #[test]
+    fn macros() {
+        assert_eq!(round_trip("a == @10.0.0.0/8"), "a == @10.0.0.0/8");
+        assert_eq!(round_trip("a in {@fe80::/10}"), "a in {@fe80::/10}");
+        assert_eq!(round_trip("a == @http"), "a == 80");
+    }
+
+    #[test]
+    fn logical() {
+        assert_eq!(round_trip("a || b && c"), "a || b && c");
+        assert_eq!(round_trip("(a || b) && c"), "(a || b) && c");
+        assert_eq!(round_trip("a < 1 || b > 2"), "a < 1 || b > 2");
+        assert_eq!(round_trip("!(a && b)"), "!(a && b)");
+        assert_eq!(round_trip("(a && b) == false"), "!(a && b)");
     }
 }
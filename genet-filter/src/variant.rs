@@ -1,6 +1,6 @@
-use genet_abi::variant::Variant;
+use genet_abi::{slice::TryGet, variant::Variant};
 use num_bigint::{BigInt, Sign};
-use num_traits::ToPrimitive;
+use num_traits::{ToPrimitive, Zero};
 use serde_json;
 use std::cmp::Ordering;
 
@@ -15,6 +15,21 @@ pub trait VariantExt {
     fn op_lte(&self, other: &Variant) -> bool;
     fn op_gte(&self, other: &Variant) -> bool;
     fn op_eq(&self, other: &Variant) -> bool;
+    fn op_contains(&self, other: &Variant) -> bool;
+    fn op_add(&self, other: &Variant) -> Variant;
+    fn op_sub(&self, other: &Variant) -> Variant;
+    fn op_mul(&self, other: &Variant) -> Variant;
+    fn op_div(&self, other: &Variant) -> Variant;
+    fn op_rem(&self, other: &Variant) -> Variant;
+    fn op_bit_and(&self, other: &Variant) -> Variant;
+    fn op_bit_or(&self, other: &Variant) -> Variant;
+    fn op_bit_xor(&self, other: &Variant) -> Variant;
+    fn op_shl(&self, other: &Variant) -> Variant;
+    fn op_shr(&self, other: &Variant) -> Variant;
+    fn op_index(&self, index: &Variant) -> Variant;
+    fn op_slice(&self, start: Option<&Variant>, end: Option<&Variant>) -> Variant;
+    fn len(&self) -> Option<usize>;
+    fn as_bytes(&self) -> Option<&[u8]>;
     fn to_string(&self) -> String;
 }
 
@@ -49,8 +64,8 @@ impl VariantExt for Variant {
 
     fn op_unary_negation(&self) -> Variant {
         match self {
-            Variant::Int64(v) => Variant::Int64(-v),
-            Variant::UInt64(v) => Variant::Int64(-(*v as i64)),
+            Variant::Int64(v) => from_bigint(-BigInt::from(*v)),
+            Variant::UInt64(v) => from_bigint(-BigInt::from(*v)),
             Variant::Float64(v) => Variant::Float64(-v),
             Variant::BigInt(v) => Variant::BigInt(
                 (-BigInt::from_signed_bytes_be(&v))
@@ -164,6 +179,135 @@ impl VariantExt for Variant {
         }
     }
 
+    fn op_contains(&self, other: &Variant) -> bool {
+        match (self.as_bytes(), other.as_bytes()) {
+            (Some(a), Some(b)) => b.is_empty() || a.windows(b.len()).any(|w| w == b),
+            _ => false,
+        }
+    }
+
+    fn op_add(&self, other: &Variant) -> Variant {
+        if let (Variant::String(a), Variant::String(b)) = (self, other) {
+            return Variant::String((a.to_string() + b).into_boxed_str());
+        }
+        if let (Some(a), Some(b)) = (self.as_bytes(), other.as_bytes()) {
+            return Variant::Buffer([a, b].concat().into_boxed_slice());
+        }
+        arith(self, other, |a, b| Some(a + b), |a, b| a + b)
+    }
+
+    fn op_sub(&self, other: &Variant) -> Variant {
+        arith(self, other, |a, b| Some(a - b), |a, b| a - b)
+    }
+
+    fn op_mul(&self, other: &Variant) -> Variant {
+        arith(self, other, |a, b| Some(a * b), |a, b| a * b)
+    }
+
+    fn op_div(&self, other: &Variant) -> Variant {
+        arith(
+            self,
+            other,
+            |a, b| if b.is_zero() { None } else { Some(a / b) },
+            |a, b| a / b,
+        )
+    }
+
+    fn op_rem(&self, other: &Variant) -> Variant {
+        arith(
+            self,
+            other,
+            |a, b| if b.is_zero() { None } else { Some(a % b) },
+            |a, b| a % b,
+        )
+    }
+
+    fn op_bit_and(&self, other: &Variant) -> Variant {
+        bitwise(self, other, |a, b| Some(a & b))
+    }
+
+    fn op_bit_or(&self, other: &Variant) -> Variant {
+        bitwise(self, other, |a, b| Some(a | b))
+    }
+
+    fn op_bit_xor(&self, other: &Variant) -> Variant {
+        bitwise(self, other, |a, b| Some(a ^ b))
+    }
+
+    fn op_shl(&self, other: &Variant) -> Variant {
+        bitwise(self, other, |a, b| shift_amount(&b).map(|n| a << n))
+    }
+
+    fn op_shr(&self, other: &Variant) -> Variant {
+        bitwise(self, other, |a, b| shift_amount(&b).map(|n| a >> n))
+    }
+
+    fn op_index(&self, index: &Variant) -> Variant {
+        let index = match self.len().and_then(|len| position(index, len)) {
+            Some(index) => index,
+            None => return Variant::Nil,
+        };
+        match self {
+            Variant::String(s) => s
+                .chars()
+                .nth(index)
+                .map(|c| Variant::String(c.to_string().into_boxed_str()))
+                .unwrap_or(Variant::Nil),
+            Variant::Buffer(b) => b.get(index).map_or(Variant::Nil, |v| Variant::UInt64(*v as u64)),
+            Variant::Slice(b) => b.get(index).map_or(Variant::Nil, |v| Variant::UInt64(*v as u64)),
+            _ => Variant::Nil,
+        }
+    }
+
+    fn op_slice(&self, start: Option<&Variant>, end: Option<&Variant>) -> Variant {
+        let len = match self.len() {
+            Some(len) => len,
+            None => return Variant::Nil,
+        };
+        let start = match start {
+            Some(v) => position(v, len),
+            None => Some(0),
+        };
+        let end = match end {
+            Some(v) => position(v, len),
+            None => Some(len),
+        };
+        let range = match (start, end) {
+            (Some(start), Some(end)) if start <= end && end <= len => start..end,
+            _ => return Variant::Nil,
+        };
+        match self {
+            Variant::String(s) => Variant::String(
+                s.chars()
+                    .skip(range.start)
+                    .take(range.end - range.start)
+                    .collect::<String>()
+                    .into_boxed_str(),
+            ),
+            Variant::Buffer(b) => Variant::Buffer(b[range].to_vec().into_boxed_slice()),
+            Variant::Slice(b) => b.try_get(range).map_or(Variant::Nil, Variant::Slice),
+            _ => Variant::Nil,
+        }
+    }
+
+    fn len(&self) -> Option<usize> {
+        match self {
+            Variant::String(s) => Some(s.chars().count()),
+            Variant::Buffer(b) => Some(b.len()),
+            Variant::Slice(b) => Some(b.len()),
+            _ => None,
+        }
+    }
+
+    fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Variant::String(s) => Some(s.as_bytes()),
+            Variant::Buffer(b) => Some(b),
+            Variant::Slice(b) => Some(b),
+            _ => None,
+        }
+    }
+
     fn to_string(&self) -> String {
         match self {
             Variant::Nil => "nil".to_string(),
@@ -172,12 +316,205 @@ impl VariantExt for Variant {
             Variant::UInt64(v) => format!("{}", v),
             Variant::Float64(v) => format!("{}", v),
             Variant::String(s) => serde_json::to_string(&s).unwrap(),
-            Variant::BigInt(b) | Variant::Buffer(b) => {
+            Variant::BigInt(b) => {
                 "0x".to_string() + &BigInt::from_bytes_be(Sign::Plus, &b).to_str_radix(16)
             }
-            Variant::Slice(b) => {
-                "0x".to_string() + &BigInt::from_bytes_be(Sign::Plus, &b).to_str_radix(16)
+            Variant::Buffer(b) => escape_bytes(&b),
+            Variant::Slice(b) => escape_bytes(&b),
+        }
+    }
+}
+
+const MAX_SHIFT: usize = 4096;
+
+/// Formats bytes as a `b"..."` literal.
+fn escape_bytes(data: &[u8]) -> String {
+    let mut s = "b\"".to_string();
+    for &b in data {
+        match b {
+            b'"' => s += "\\\"",
+            b'\\' => s += "\\\\",
+            b'\n' => s += "\\n",
+            b'\r' => s += "\\r",
+            b'\t' => s += "\\t",
+            b' '..=b'~' => s.push(b as char),
+            _ => s += &format!("\\x{:02x}", b),
+        }
+    }
+    s + "\""
+}
+
+/// Resolves an index, counting from the end if negative.
+fn position(v: &Variant, len: usize) -> Option<usize> {
+    match v {
+        Variant::UInt64(n) => Some(*n as usize),
+        Variant::Int64(n) if *n >= 0 => Some(*n as usize),
+        Variant::Int64(n) => n
+            .checked_abs()
+            .and_then(|n| len.checked_sub(n as usize)),
+        _ => None,
+    }
+}
+
+fn to_bigint(v: &Variant) -> Option<BigInt> {
+    match v {
+        Variant::Int64(v) => Some(BigInt::from(*v)),
+        Variant::UInt64(v) => Some(BigInt::from(*v)),
+        Variant::BigInt(v) => Some(BigInt::from_signed_bytes_be(&v)),
+        Variant::Buffer(v) => Some(BigInt::from_bytes_be(Sign::Plus, &v)),
+        Variant::Slice(v) => Some(BigInt::from_bytes_be(Sign::Plus, &v)),
+        _ => None,
+    }
+}
+
+fn to_float(v: &Variant) -> Option<f64> {
+    match v {
+        Variant::Float64(v) => Some(*v),
+        _ => to_bigint(v).and_then(|v| v.to_f64()),
+    }
+}
+
+fn from_bigint(v: BigInt) -> Variant {
+    Variant::BigInt(v.to_signed_bytes_be().into_boxed_slice()).shrink()
+}
+
+fn shift_amount(v: &BigInt) -> Option<usize> {
+    v.to_usize().filter(|n| *n <= MAX_SHIFT)
+}
+
+/// Applies an arithmetic operator.
+///
+/// Integers are promoted to BigInt so the result never overflows,
+/// and Buffer and Slice are treated as unsigned big-endian integers
+/// when combined with numbers.
+/// Two byte strings can only be concatenated with `+`, so the result is Nil.
+/// The result is Float64 if either side is Float64.
+fn arith<I, F>(lhs: &Variant, rhs: &Variant, int: I, float: F) -> Variant
+where
+    I: Fn(BigInt, BigInt) -> Option<BigInt>,
+    F: Fn(f64, f64) -> f64,
+{
+    if lhs.as_bytes().is_some() && rhs.as_bytes().is_some() {
+        return Variant::Nil;
+    }
+    match (lhs, rhs) {
+        (Variant::Float64(_), _) | (_, Variant::Float64(_)) => {
+            match (to_float(lhs), to_float(rhs)) {
+                (Some(a), Some(b)) => Variant::Float64(float(a, b)),
+                _ => Variant::Nil,
             }
         }
+        _ => bitwise(lhs, rhs, int),
+    }
+}
+
+fn bitwise<I>(lhs: &Variant, rhs: &Variant, int: I) -> Variant
+where
+    I: Fn(BigInt, BigInt) -> Option<BigInt>,
+{
+    match (to_bigint(lhs), to_bigint(rhs)) {
+        (Some(a), Some(b)) => int(a, b).map(from_bigint).unwrap_or(Variant::Nil),
+        _ => Variant::Nil,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use genet_abi::variant::Variant;
+    use variant::VariantExt;
+
+    #[test]
+    fn arith() {
+        let a = Variant::UInt64(10);
+        let b = Variant::Int64(-4);
+        assert_eq!(a.op_add(&b), Variant::UInt64(6));
+        assert_eq!(b.op_sub(&a), Variant::Int64(-14));
+        assert_eq!(a.op_mul(&b), Variant::Int64(-40));
+        assert_eq!(a.op_div(&b), Variant::Int64(-2));
+        assert_eq!(a.op_rem(&b), Variant::UInt64(2));
+        assert_eq!(a.op_div(&Variant::UInt64(0)), Variant::Nil);
+        assert_eq!(a.op_add(&Variant::Float64(0.5)), Variant::Float64(10.5));
+        assert_eq!(a.op_add(&Variant::Nil), Variant::Nil);
+        assert_eq!(
+            Variant::UInt64(u64::max_value()).op_add(&Variant::UInt64(1)),
+            Variant::BigInt(vec![1, 0, 0, 0, 0, 0, 0, 0, 0].into_boxed_slice())
+        );
+        assert_eq!(
+            Variant::Buffer(vec![1, 0].into_boxed_slice()).op_sub(&Variant::UInt64(1)),
+            Variant::UInt64(255)
+        );
+        assert_eq!(
+            Variant::Buffer(vec![1, 0].into_boxed_slice())
+                .op_sub(&Variant::Buffer(vec![1].into_boxed_slice())),
+            Variant::Nil
+        );
+        assert_eq!(
+            Variant::Int64(i64::min_value()).op_unary_negation(),
+            Variant::UInt64(1 << 63)
+        );
+    }
+
+    #[test]
+    fn bitwise() {
+        let a = Variant::UInt64(0b1100);
+        let b = Variant::UInt64(0b1010);
+        assert_eq!(a.op_bit_and(&b), Variant::UInt64(0b1000));
+        assert_eq!(a.op_bit_or(&b), Variant::UInt64(0b1110));
+        assert_eq!(a.op_bit_xor(&b), Variant::UInt64(0b0110));
+        assert_eq!(a.op_shl(&Variant::UInt64(2)), Variant::UInt64(0b110000));
+        assert_eq!(a.op_shr(&Variant::UInt64(2)), Variant::UInt64(0b11));
+        assert_eq!(a.op_shl(&Variant::Int64(-1)), Variant::Nil);
+        assert_eq!(a.op_bit_and(&Variant::Float64(1.0)), Variant::Nil);
+    }
+
+    #[test]
+    fn contains() {
+        let s = Variant::String("example.com".to_string().into_boxed_str());
+        let b = Variant::Buffer(vec![1, 2, 3, 4].into_boxed_slice());
+        assert!(s.op_contains(&Variant::String("ple.c".to_string().into_boxed_str())));
+        assert!(!s.op_contains(&Variant::String("org".to_string().into_boxed_str())));
+        assert!(b.op_contains(&Variant::Buffer(vec![2, 3].into_boxed_slice())));
+        assert!(!b.op_contains(&Variant::Buffer(vec![3, 2].into_boxed_slice())));
+        assert!(!b.op_contains(&Variant::UInt64(2)));
+    }
+
+    #[test]
+    fn index() {
+        let s = Variant::String("abc".to_string().into_boxed_str());
+        let b = Variant::Buffer(vec![1, 2, 3, 4].into_boxed_slice());
+        assert_eq!(
+            s.op_index(&Variant::UInt64(1)),
+            Variant::String("b".to_string().into_boxed_str())
+        );
+        assert_eq!(b.op_index(&Variant::Int64(-1)), Variant::UInt64(4));
+        assert_eq!(b.op_index(&Variant::UInt64(4)), Variant::Nil);
+        assert_eq!(b.op_index(&Variant::Int64(i64::min_value())), Variant::Nil);
+        assert_eq!(
+            b.op_slice(Some(&Variant::UInt64(1)), Some(&Variant::UInt64(3))),
+            Variant::Buffer(vec![2, 3].into_boxed_slice())
+        );
+        assert_eq!(
+            b.op_slice(None, Some(&Variant::Int64(-2))),
+            Variant::Buffer(vec![1, 2].into_boxed_slice())
+        );
+        assert_eq!(
+            s.op_slice(Some(&Variant::UInt64(1)), None),
+            Variant::String("bc".to_string().into_boxed_str())
+        );
+        assert_eq!(b.op_slice(Some(&Variant::UInt64(3)), Some(&Variant::UInt64(5))), Variant::Nil);
+    }
+
+    #[test]
+    fn concat() {
+        assert_eq!(
+            Variant::String("ab".to_string().into_boxed_str())
+                .op_add(&Variant::String("cd".to_string().into_boxed_str())),
+            Variant::String("abcd".to_string().into_boxed_str())
+        );
+        assert_eq!(
+            Variant::Buffer(vec![1, 2].into_boxed_slice())
+                .op_add(&Variant::Buffer(vec![3].into_boxed_slice())),
+            Variant::Buffer(vec![1, 2, 3].into_boxed_slice())
+        );
     }
 }
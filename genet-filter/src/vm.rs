@@ -0,0 +1,606 @@
+use ast::{Expr, Pattern};
+use context::Context;
+use function::{Function, Param};
+use genet_abi::{token::Token, variant::Variant};
+use macros::Subnet;
+use variant::VariantExt;
+
+/// A member of a set in an `in` expression.
+#[derive(Debug, Clone)]
+enum Item {
+    Value,
+    Range,
+    Subnet(Subnet),
+}
+
+/// A VM instruction.
+#[derive(Debug, Clone)]
+enum Op {
+    Push(Variant),
+    Load(usize),
+    Eq,
+    NotEq,
+    Lt,
+    Gt,
+    Lte,
+    Gte,
+    Contains,
+    Matches(Pattern),
+    InSubnet(Subnet),
+    In(Box<[Item]>),
+    /// Pushes the number of the fields with the token in the slot.
+    Count(usize),
+
+    /// Pops the arguments and pushes the result of the function.
+    Call(&'static Function, usize),
+    Index,
+    Slice(bool, bool),
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+    Not,
+    Plus,
+    Neg,
+    Truthy,
+
+    /// Pops a value and jumps with `false` pushed if it is falsy.
+    AndThen(usize),
+
+    /// Pops a value and jumps with `true` pushed if it is truthy.
+    OrElse(usize),
+}
+
+/// A filter expression compiled into a flat instruction sequence.
+#[derive(Debug, Clone)]
+pub struct Program {
+    ops: Vec<Op>,
+    tokens: Vec<Token>,
+}
+
+impl Program {
+    /// Folds the constant subexpressions and compiles the expression.
+    pub fn compile(expr: &Expr) -> Program {
+        let mut program = Program {
+            ops: Vec::new(),
+            tokens: Vec::new(),
+        };
+        program.emit(&fold(expr.clone()));
+        program
+    }
+
+    pub fn eval(&self, ctx: &Context) -> Variant {
+        let mut stack: Vec<Variant> = Vec::with_capacity(self.ops.len());
+        let mut slots: Vec<Option<Variant>> = vec![None; self.tokens.len()];
+        let mut ip = 0;
+        while ip < self.ops.len() {
+            match &self.ops[ip] {
+                Op::Push(v) => stack.push(v.clone()),
+                Op::Load(slot) => {
+                    if slots[*slot].is_none() {
+                        slots[*slot] = Some(ctx.load(self.tokens[*slot]));
+                    }
+                    stack.push(slots[*slot].clone().unwrap());
+                }
+                Op::Eq => binary(&mut stack, |l, r| Variant::Bool(l.op_eq(r))),
+                Op::NotEq => binary(&mut stack, |l, r| Variant::Bool(!l.op_eq(r))),
+                Op::Lt => binary(&mut stack, |l, r| Variant::Bool(l.op_lt(r))),
+                Op::Gt => binary(&mut stack, |l, r| Variant::Bool(l.op_gt(r))),
+                Op::Lte => binary(&mut stack, |l, r| Variant::Bool(l.op_lte(r))),
+                Op::Gte => binary(&mut stack, |l, r| Variant::Bool(l.op_gte(r))),
+                Op::Contains => binary(&mut stack, |l, r| Variant::Bool(l.op_contains(r))),
+                Op::Matches(p) => unary(&mut stack, |v| {
+                    Variant::Bool(v.as_bytes().map_or(false, |b| p.is_match(b)))
+                }),
+                Op::InSubnet(net) => unary(&mut stack, |v| Variant::Bool(net.contains(v))),
+                Op::In(items) => {
+                    let len = items
+                        .iter()
+                        .map(|item| match item {
+                            Item::Value => 1,
+                            Item::Range => 2,
+                            Item::Subnet(_) => 0,
+                        })
+                        .sum::<usize>();
+                    let values = stack.split_off(stack.len() - len);
+                    let value = stack.pop().unwrap();
+                    let mut values = values.iter();
+                    let mut found = false;
+                    for item in items.iter() {
+                        found = found || match item {
+                            Item::Value => value.op_eq(values.next().unwrap()),
+                            Item::Range => {
+                                let start = values.next().unwrap();
+                                let end = values.next().unwrap();
+                                value.op_gte(start) && value.op_lte(end)
+                            }
+                            Item::Subnet(net) => net.contains(&value),
+                        };
+                    }
+                    stack.push(Variant::Bool(found));
+                }
+                Op::Count(slot) => {
+                    let count = ctx.count(self.tokens[*slot]);
+                    stack.push(Variant::UInt64(count as u64));
+                }
+                Op::Call(f, argc) => {
+                    let start = stack.len() - argc;
+                    let value = f.eval(&stack[start..]);
+                    stack.truncate(start);
+                    stack.push(value);
+                }
+                Op::Index => binary(&mut stack, |v, i| v.op_index(i)),
+                Op::Slice(has_start, has_end) => {
+                    let end = if *has_end { stack.pop() } else { None };
+                    let start = if *has_start { stack.pop() } else { None };
+                    let value = stack.pop().unwrap();
+                    stack.push(value.op_slice(start.as_ref(), end.as_ref()));
+                }
+                Op::Add => binary(&mut stack, |l, r| l.op_add(r)),
+                Op::Sub => binary(&mut stack, |l, r| l.op_sub(r)),
+                Op::Mul => binary(&mut stack, |l, r| l.op_mul(r)),
+                Op::Div => binary(&mut stack, |l, r| l.op_div(r)),
+                Op::Rem => binary(&mut stack, |l, r| l.op_rem(r)),
+                Op::BitAnd => binary(&mut stack, |l, r| l.op_bit_and(r)),
+                Op::BitOr => binary(&mut stack, |l, r| l.op_bit_or(r)),
+                Op::BitXor => binary(&mut stack, |l, r| l.op_bit_xor(r)),
+                Op::Shl => binary(&mut stack, |l, r| l.op_shl(r)),
+                Op::Shr => binary(&mut stack, |l, r| l.op_shr(r)),
+                Op::Not => unary(&mut stack, |v| Variant::Bool(!v.is_truthy())),
+                Op::Plus => unary(&mut stack, |v| v.op_unary_plus()),
+                Op::Neg => unary(&mut stack, |v| v.op_unary_negation()),
+                Op::Truthy => unary(&mut stack, |v| Variant::Bool(v.is_truthy())),
+                Op::AndThen(target) => {
+                    if !stack.pop().unwrap().is_truthy() {
+                        stack.push(Variant::Bool(false));
+                        ip = *target;
+                        continue;
+                    }
+                }
+                Op::OrElse(target) => {
+                    if stack.pop().unwrap().is_truthy() {
+                        stack.push(Variant::Bool(true));
+                        ip = *target;
+                        continue;
+                    }
+                }
+            }
+            ip += 1;
+        }
+        stack.pop().unwrap_or(Variant::Nil)
+    }
+
+    fn slot(&mut self, token: Token) -> usize {
+        if let Some(slot) = self.tokens.iter().position(|t| *t == token) {
+            slot
+        } else {
+            self.tokens.push(token);
+            self.tokens.len() - 1
+        }
+    }
+
+    fn emit_binary(&mut self, lhs: &Expr, rhs: &Expr, op: Op) {
+        self.emit(lhs);
+        self.emit(rhs);
+        self.ops.push(op);
+    }
+
+    fn emit(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal(v) => self.ops.push(Op::Push(v.clone())),
+            Expr::Token(t) => {
+                let slot = self.slot(*t);
+                self.ops.push(Op::Load(slot));
+            }
+            Expr::CmpEq(l, r) | Expr::CmpNotEq(l, r) => {
+                let op = match (l.as_ref(), r.as_ref()) {
+                    (Expr::Subnet(net), expr) | (expr, Expr::Subnet(net)) => {
+                        self.emit(expr);
+                        Op::InSubnet(net.clone())
+                    }
+                    (lhs, rhs) => {
+                        self.emit(lhs);
+                        self.emit(rhs);
+                        Op::Eq
+                    }
+                };
+                let negate = if let Expr::CmpNotEq(_, _) = expr {
+                    true
+                } else {
+                    false
+                };
+                match (op, negate) {
+                    (Op::Eq, true) => self.ops.push(Op::NotEq),
+                    (op, true) => {
+                        self.ops.push(op);
+                        self.ops.push(Op::Not);
+                    }
+                    (op, false) => self.ops.push(op),
+                }
+            }
+            Expr::CmpLt(l, r) => self.emit_binary(l, r, Op::Lt),
+            Expr::CmpGt(l, r) => self.emit_binary(l, r, Op::Gt),
+            Expr::CmpLte(l, r) => self.emit_binary(l, r, Op::Lte),
+            Expr::CmpGte(l, r) => self.emit_binary(l, r, Op::Gte),
+            Expr::CmpContains(l, r) => self.emit_binary(l, r, Op::Contains),
+            Expr::CmpMatches(v, p) => {
+                self.emit(v);
+                self.ops.push(Op::Matches(p.clone()));
+            }
+            Expr::CmpIn(l, r) => {
+                self.emit(l);
+                let items = match r.as_ref() {
+                    Expr::Set(items) => items
+                        .iter()
+                        .map(|item| match item {
+                            Expr::Range(start, end) => {
+                                self.emit(start);
+                                self.emit(end);
+                                Item::Range
+                            }
+                            Expr::Subnet(net) => Item::Subnet(net.clone()),
+                            _ => {
+                                self.emit(item);
+                                Item::Value
+                            }
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                self.ops.push(Op::In(items.into_boxed_slice()));
+            }
+            Expr::Call(f, args) => {
+                for (param, arg) in f.params().iter().zip(args.iter()) {
+                    match (param, arg) {
+                        (Param::Field, Expr::Token(t)) => {
+                            let slot = self.slot(*t);
+                            self.ops.push(Op::Count(slot));
+                        }
+                        _ => self.emit(arg),
+                    }
+                }
+                self.ops.push(Op::Call(f, args.len()));
+            }
+            Expr::Index(v, i) => self.emit_binary(v, i, Op::Index),
+            Expr::Slice(v, start, end) => {
+                self.emit(v);
+                if let Some(start) = start {
+                    self.emit(start);
+                }
+                if let Some(end) = end {
+                    self.emit(end);
+                }
+                self.ops.push(Op::Slice(start.is_some(), end.is_some()));
+            }
+            Expr::LogicalAnd(l, r) | Expr::LogicalOr(l, r) => {
+                self.emit(l);
+                let jump = self.ops.len();
+                self.ops.push(Op::Truthy);
+                self.emit(r);
+                self.ops.push(Op::Truthy);
+                let target = self.ops.len();
+                self.ops[jump] = if let Expr::LogicalAnd(_, _) = expr {
+                    Op::AndThen(target)
+                } else {
+                    Op::OrElse(target)
+                };
+            }
+            Expr::ArithAdd(l, r) => self.emit_binary(l, r, Op::Add),
+            Expr::ArithSub(l, r) => self.emit_binary(l, r, Op::Sub),
+            Expr::ArithMul(l, r) => self.emit_binary(l, r, Op::Mul),
+            Expr::ArithDiv(l, r) => self.emit_binary(l, r, Op::Div),
+            Expr::ArithRem(l, r) => self.emit_binary(l, r, Op::Rem),
+            Expr::BitAnd(l, r) => self.emit_binary(l, r, Op::BitAnd),
+            Expr::BitOr(l, r) => self.emit_binary(l, r, Op::BitOr),
+            Expr::BitXor(l, r) => self.emit_binary(l, r, Op::BitXor),
+            Expr::BitShl(l, r) => self.emit_binary(l, r, Op::Shl),
+            Expr::BitShr(l, r) => self.emit_binary(l, r, Op::Shr),
+            Expr::LogicalNegation(v) => {
+                self.emit(v);
+                self.ops.push(Op::Not);
+            }
+            Expr::UnaryPlus(v) => {
+                self.emit(v);
+                self.ops.push(Op::Plus);
+            }
+            Expr::UnaryNegation(v) => {
+                self.emit(v);
+                self.ops.push(Op::Neg);
+            }
+            Expr::Macro(_) | Expr::Subnet(_) | Expr::Set(_) | Expr::Range(_, _) => {
+                self.ops.push(Op::Push(Variant::Nil))
+            }
+        }
+    }
+}
+
+fn unary<F: FnOnce(&Variant) -> Variant>(stack: &mut Vec<Variant>, f: F) {
+    let value = stack.pop().unwrap();
+    stack.push(f(&value));
+}
+
+fn binary<F: FnOnce(&Variant, &Variant) -> Variant>(stack: &mut Vec<Variant>, f: F) {
+    let rhs = stack.pop().unwrap();
+    let lhs = stack.pop().unwrap();
+    stack.push(f(&lhs, &rhs));
+}
+
+fn is_literal(expr: &Expr) -> bool {
+    if let Expr::Literal(_) = expr {
+        true
+    } else {
+        false
+    }
+}
+
+/// Returns true if the value of the expression does not depend on frames.
+fn is_constant(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal(_)
+        | Expr::Token(_)
+        | Expr::Macro(_)
+        | Expr::Subnet(_)
+        | Expr::Set(_)
+        | Expr::Range(_, _) => false,
+        Expr::CmpEq(l, r) | Expr::CmpNotEq(l, r) => is_literal(l) && is_literal(r),
+        Expr::CmpIn(l, r) => {
+            is_literal(l) && match r.as_ref() {
+                Expr::Set(items) => items.iter().all(|item| match item {
+                    Expr::Range(start, end) => is_literal(start) && is_literal(end),
+                    Expr::Subnet(_) => true,
+                    item => is_literal(item),
+                }),
+                _ => false,
+            }
+        }
+        Expr::Call(f, args) => {
+            !f.params().contains(&Param::Field) && args.iter().all(is_literal)
+        }
+        Expr::Slice(v, start, end) => {
+            is_literal(v)
+                && start.as_ref().map_or(true, |e| is_literal(e))
+                && end.as_ref().map_or(true, |e| is_literal(e))
+        }
+        Expr::CmpMatches(v, _)
+        | Expr::LogicalNegation(v)
+        | Expr::UnaryPlus(v)
+        | Expr::UnaryNegation(v) => is_literal(v),
+        Expr::CmpLt(l, r)
+        | Expr::CmpGt(l, r)
+        | Expr::CmpLte(l, r)
+        | Expr::CmpGte(l, r)
+        | Expr::CmpContains(l, r)
+        | Expr::Index(l, r)
+        | Expr::LogicalAnd(l, r)
+        | Expr::LogicalOr(l, r)
+        | Expr::ArithAdd(l, r)
+        | Expr::ArithSub(l, r)
+        | Expr::ArithMul(l, r)
+        | Expr::ArithDiv(l, r)
+        | Expr::ArithRem(l, r)
+        | Expr::BitAnd(l, r)
+        | Expr::BitOr(l, r)
+        | Expr::BitXor(l, r)
+        | Expr::BitShl(l, r)
+        | Expr::BitShr(l, r) => is_literal(l) && is_literal(r),
+    }
+}
+
+/// Replaces the constant subexpressions with literals.
+pub fn fold(expr: Expr) -> Expr {
+    fn f(expr: Box<Expr>) -> Box<Expr> {
+        Box::new(fold(*expr))
+    }
+    let expr = match expr {
+        Expr::CmpEq(l, r) => Expr::CmpEq(f(l), f(r)),
+        Expr::CmpNotEq(l, r) => Expr::CmpNotEq(f(l), f(r)),
+        Expr::CmpLt(l, r) => Expr::CmpLt(f(l), f(r)),
+        Expr::CmpGt(l, r) => Expr::CmpGt(f(l), f(r)),
+        Expr::CmpLte(l, r) => Expr::CmpLte(f(l), f(r)),
+        Expr::CmpGte(l, r) => Expr::CmpGte(f(l), f(r)),
+        Expr::CmpContains(l, r) => Expr::CmpContains(f(l), f(r)),
+        Expr::CmpMatches(v, p) => Expr::CmpMatches(f(v), p),
+        Expr::CmpIn(l, r) => Expr::CmpIn(f(l), f(r)),
+        Expr::Set(items) => Expr::Set(items.into_iter().map(fold).collect()),
+        Expr::Range(l, r) => Expr::Range(f(l), f(r)),
+        Expr::Call(func, args) => Expr::Call(func, args.into_iter().map(fold).collect()),
+        Expr::Index(v, i) => Expr::Index(f(v), f(i)),
+        Expr::Slice(v, start, end) => Expr::Slice(f(v), start.map(f), end.map(f)),
+        Expr::LogicalAnd(l, r) => match fold(*l) {
+            Expr::Literal(ref v) if !v.is_truthy() => Expr::Literal(Variant::Bool(false)),
+            l => Expr::LogicalAnd(Box::new(l), f(r)),
+        },
+        Expr::LogicalOr(l, r) => match fold(*l) {
+            Expr::Literal(ref v) if v.is_truthy() => Expr::Literal(Variant::Bool(true)),
+            l => Expr::LogicalOr(Box::new(l), f(r)),
+        },
+        Expr::ArithAdd(l, r) => Expr::ArithAdd(f(l), f(r)),
+        Expr::ArithSub(l, r) => Expr::ArithSub(f(l), f(r)),
+        Expr::ArithMul(l, r) => Expr::ArithMul(f(l), f(r)),
+        Expr::ArithDiv(l, r) => Expr::ArithDiv(f(l), f(r)),
+        Expr::ArithRem(l, r) => Expr::ArithRem(f(l), f(r)),
+        Expr::BitAnd(l, r) => Expr::BitAnd(f(l), f(r)),
+        Expr::BitOr(l, r) => Expr::BitOr(f(l), f(r)),
+        Expr::BitXor(l, r) => Expr::BitXor(f(l), f(r)),
+        Expr::BitShl(l, r) => Expr::BitShl(f(l), f(r)),
+        Expr::BitShr(l, r) => Expr::BitShr(f(l), f(r)),
+        Expr::LogicalNegation(v) => Expr::LogicalNegation(f(v)),
+        Expr::UnaryPlus(v) => Expr::UnaryPlus(f(v)),
+        Expr::UnaryNegation(v) => Expr::UnaryNegation(f(v)),
+        expr => expr,
+    };
+    if is_constant(&expr) {
+        Expr::Literal(expr.eval(&Context::new(&[])))
+    } else {
+        expr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ast::Expr;
+    use context::Context;
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        fixed::{Fixed, MutFixed},
+        layer::{Annotation, Layer, LayerClass, Severity},
+        slice::ByteSlice,
+        token::Token,
+        variant::Variant,
+    };
+    use parser::parse;
+    use vm::{fold, Program};
+
+    fn eval(filter: &str) -> Variant {
+        Program::compile(&parse(filter).unwrap()).eval(&Context::new(&[]))
+    }
+
+    #[test]
+    fn fold_constants() {
+        assert_eq!(
+            fold(parse("1 + 2 * 3").unwrap()),
+            Expr::Literal(Variant::UInt64(7))
+        );
+        assert_eq!(
+            fold(parse("a > 1 + 2").unwrap()),
+            Expr::CmpGt(
+                Box::new(Expr::Token(Token::from("a"))),
+                Box::new(Expr::Literal(Variant::UInt64(3)))
+            )
+        );
+        assert_eq!(
+            fold(parse("false && a").unwrap()),
+            Expr::Literal(Variant::Bool(false))
+        );
+        assert_eq!(
+            fold(parse("1 in {0..2, 5}").unwrap()),
+            Expr::Literal(Variant::Bool(true))
+        );
+        assert_eq!(
+            fold(parse("len(\"abc\") == 3").unwrap()),
+            Expr::Literal(Variant::Bool(true))
+        );
+        assert_eq!(Program::compile(&parse("-(3 - 5) == 2").unwrap()).ops.len(), 1);
+    }
+
+    #[test]
+    fn eval_ops() {
+        assert_eq!(eval("a"), Variant::Nil);
+        assert_eq!(eval("a == a"), Variant::Bool(true));
+        assert_eq!(eval("!a && a == a"), Variant::Bool(true));
+        assert_eq!(eval("a || !a"), Variant::Bool(true));
+        assert_eq!(eval("a || a"), Variant::Bool(false));
+        assert_eq!(eval("a in {a, 1}"), Variant::Bool(true));
+        assert_eq!(eval("a != @10.0.0.0/8"), Variant::Bool(true));
+        assert_eq!(eval("a[0]"), Variant::Nil);
+        assert_eq!(eval("count(a)"), Variant::UInt64(0));
+    }
+
+    #[test]
+    fn eval_annotations() {
+        let class = Fixed::new(LayerClass::builder("tcp").build());
+        let mut layer = Layer::new(class.clone(), ByteSlice::new());
+        let layers = vec![MutFixed::new(Layer::new(class, ByteSlice::new()))];
+        let ctx = Context::new(&layers);
+        let eval = |filter: &str| Program::compile(&parse(filter).unwrap()).eval(&ctx);
+        assert_eq!(eval("_.annotation.severity"), Variant::Nil);
+
+        layer.add_annotation(Annotation::new(
+            Severity::Note,
+            "sequence",
+            "tcp.retransmission",
+            0..20,
+        ));
+        layer.add_annotation(Annotation::new(
+            Severity::Warning,
+            "sequence",
+            "tcp.zeroWindow",
+            14..16,
+        ));
+        let layers = vec![MutFixed::new(layer)];
+        let ctx = Context::new(&layers);
+        let eval = |filter: &str| Program::compile(&parse(filter).unwrap()).eval(&ctx);
+        assert_eq!(eval("tcp.zeroWindow"), Variant::Bool(true));
+        assert_eq!(eval("sequence"), Variant::Bool(true));
+        assert_eq!(eval("tcp.windowFull"), Variant::Nil);
+        assert_eq!(
+            eval("_.annotation.severity == \"warning\""),
+            Variant::Bool(true)
+        );
+    }
+
+    #[test]
+    fn eval_functions() {
+        let class = Fixed::new(
+            LayerClass::builder("@error")
+                .alias("_.error", "@error.message")
+                .build(),
+        );
+        let message = Fixed::new(AttrClass::builder("@error.message").typ("@str").build());
+        let mut layer = Layer::new(class, ByteSlice::new());
+        layer.add_attr(
+            Attr::builder(message)
+                .value(Variant::String("truncated".to_string().into_boxed_str()))
+                .build(),
+        );
+        layer.add_annotation(Annotation::new(
+            Severity::Warning,
+            "sequence",
+            "tcp.zeroWindow",
+            0..0,
+        ));
+        let layers = vec![MutFixed::new(layer)];
+        let ctx = Context::new(&layers);
+        let eval = |filter: &str| Program::compile(&parse(filter).unwrap()).eval(&ctx);
+        assert_eq!(eval("len(_.error)"), Variant::UInt64(9));
+        assert_eq!(eval("upper(_.error) == \"TRUNCATED\""), Variant::Bool(true));
+        assert_eq!(eval("count(_.error)"), Variant::UInt64(1));
+        assert_eq!(eval("count(tcp.zeroWindow)"), Variant::UInt64(1));
+        assert_eq!(eval("count(sequence)"), Variant::UInt64(1));
+        assert_eq!(eval("count(tcp.windowFull)"), Variant::UInt64(0));
+        assert_eq!(eval("slice(_.error, 1, 3) == \"ru\""), Variant::Bool(true));
+
+        // Arguments are compiled into instructions instead of being evaluated as trees.
+        let program = Program::compile(&parse("count(_.error) + len(_.error)").unwrap());
+        assert_eq!(program.ops.len(), 5);
+    }
+
+    #[test]
+    fn eval_bytes() {
+        let class = Fixed::new(LayerClass::builder("http").build());
+        let body = Fixed::new(AttrClass::builder("http.body").typ("@slice").build());
+        let data = b"\x16\x03\x01\x00\x05GET /\r\n";
+        let mut layer = Layer::new(class, ByteSlice::from(&data[..]));
+        layer.add_attr(
+            Attr::builder(body)
+                .value(Variant::Slice(ByteSlice::from(&data[..])))
+                .build(),
+        );
+        let layers = vec![MutFixed::new(layer)];
+        let ctx = Context::new(&layers);
+        let eval = |filter: &str| Program::compile(&parse(filter).unwrap()).eval(&ctx);
+        assert_eq!(
+            eval(r#"http.body contains b"\x03\x01""#),
+            Variant::Bool(true)
+        );
+        assert_eq!(
+            eval(r#"http.body contains b"GET /\r\n""#),
+            Variant::Bool(true)
+        );
+        assert_eq!(
+            eval(r#"http.body contains b"\x01\x03""#),
+            Variant::Bool(false)
+        );
+        assert_eq!(eval("http.body contains 0x0301"), Variant::Bool(false));
+        assert_eq!(
+            eval(r#"http.body[0:2] == b"\x16\x03""#),
+            Variant::Bool(true)
+        );
+    }
+}
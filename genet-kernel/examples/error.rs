@@ -0,0 +1,40 @@
+extern crate genet_sdk;
+
+use genet_sdk::{decoder::*, error::Error, prelude::*};
+
+struct ErrorWorker {}
+
+impl Worker for ErrorWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() == token!("eth") {
+            Err(Error::new("malformed packet").into())
+        } else {
+            Ok(Status::Skip)
+        }
+    }
+}
+
+#[derive(Clone)]
+struct ErrorDecoder {}
+
+impl Decoder for ErrorDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(ErrorWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.test-error".into(),
+            exec_type: ExecType::ParallelSync,
+            consumes: vec![String::from("eth")],
+            ..Metadata::default()
+        }
+    }
+}
+
+genet_decoders!(ErrorDecoder {});
@@ -1,3 +1,4 @@
+use frame::Frame;
 use genet_abi::{self, attr::Attr, layer::Layer, variant::Variant};
 use genet_filter::{
     ast::Expr,
@@ -7,9 +8,9 @@ use genet_napi::napi::{
     CallbackInfo, Env, PropertyAttributes, PropertyDescriptor, Result, TypedArrayType, Value,
     ValueRef,
 };
-use std::rc::Rc;
+use std::{rc::Rc, sync::Arc};
 
-fn variant_to_js<'env>(
+pub fn variant_to_js<'env>(
     env: &'env Env,
     value: &genet_abi::result::Result<Variant>,
 ) -> Result<&'env Value> {
@@ -39,14 +40,23 @@ fn variant_to_js<'env>(
     }
 }
 
+/// An attr exposed to JavaScript.
+///
+/// The wrapper holds the frame to keep the attr alive
+/// even if the frame is evicted from the frame cache.
 pub struct AttrWrapper {
     attr: *const Attr,
     layer: *const Layer,
+    _frame: Arc<Frame>,
 }
 
 impl AttrWrapper {
-    pub fn new(attr: &Attr, layer: &Layer) -> AttrWrapper {
-        AttrWrapper { attr, layer }
+    pub fn new(attr: &Attr, layer: &Layer, frame: Arc<Frame>) -> AttrWrapper {
+        AttrWrapper {
+            attr,
+            layer,
+            _frame: frame,
+        }
     }
 
     fn attr(&self) -> &Attr {
@@ -1,11 +1,11 @@
-use binding::{attr::AttrWrapper, JsClass};
+use binding::{attr::AttrWrapper, layer::LayerWrapper, JsClass};
 use frame::Frame;
 use genet_abi::token::Token;
 use genet_napi::napi::{
     CallbackInfo, Env, PropertyAttributes, PropertyDescriptor, Result, Status, Value, ValueRef,
     ValueType,
 };
-use std::rc::Rc;
+use std::{rc::Rc, sync::Arc};
 
 pub fn wrapper(env: &Env) -> Rc<ValueRef> {
     fn ctor<'env>(env: &'env Env, _info: &CallbackInfo) -> Result<&'env Value> {
@@ -13,12 +13,12 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
     }
 
     fn frame_index<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
-        let frame = env.unwrap::<Frame>(info.this())?;
+        let frame = env.unwrap::<Arc<Frame>>(info.this())?;
         env.create_uint32(frame.index())
     }
 
     fn frame_tree_indices<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
-        let frame = env.unwrap::<Frame>(info.this())?;
+        let frame = env.unwrap::<Arc<Frame>>(info.this())?;
         let indices = frame.tree_indices();
         let array = env.create_array(indices.len())?;
         for (i, item) in indices.iter().enumerate() {
@@ -28,7 +28,7 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
     }
 
     fn frame_query<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
-        let frame = env.unwrap::<Frame>(info.this())?;
+        let frame = env.unwrap::<Arc<Frame>>(info.this())?;
         if let Some(id) = info.argv().get(0) {
             let id = match env.type_of(id)? {
                 ValueType::Number => Token::from(env.get_value_uint32(id)?),
@@ -39,13 +39,13 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
                 if layer.id() == id {
                     let layer_class = env.get_constructor(JsClass::Layer as usize).unwrap();
                     let instance = env.new_instance(&layer_class, &[])?;
-                    env.wrap_mut_fixed(instance, layer)?;
+                    env.wrap(instance, LayerWrapper::new(layer, frame.clone()))?;
                     return Ok(instance);
                 }
                 if let Some(attr) = layer.attr(id) {
                     let attr_class = env.get_constructor(JsClass::Attr as usize).unwrap();
                     let instance = env.new_instance(&attr_class, &[])?;
-                    env.wrap(instance, AttrWrapper::new(attr, layer))?;
+                    env.wrap(instance, AttrWrapper::new(attr, layer, frame.clone()))?;
                     return Ok(instance);
                 }
             }
@@ -56,13 +56,13 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
     }
 
     fn frame_layers<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
-        let frame = env.unwrap::<Frame>(info.this())?;
+        let frame = env.unwrap::<Arc<Frame>>(info.this())?;
         let layers = frame.layers();
         let layer_class = env.get_constructor(JsClass::Layer as usize).unwrap();
         let array = env.create_array(layers.len())?;
         for (i, item) in layers.iter().enumerate() {
             let instance = env.new_instance(&layer_class, &[])?;
-            env.wrap_mut_fixed(instance, item)?;
+            env.wrap(instance, LayerWrapper::new(item, frame.clone()))?;
             env.set_element(array, i as u32, instance)?;
         }
         Ok(array)
@@ -1,11 +1,31 @@
 use binding::{attr::AttrWrapper, JsClass};
+use frame::Frame;
 use genet_abi::{layer::Layer, token::Token};
 use genet_filter::{ast::Expr, unparser::unparse};
 use genet_napi::napi::{
     CallbackInfo, Env, PropertyAttributes, PropertyDescriptor, Result, Status, TypedArrayType,
     Value, ValueRef, ValueType,
 };
-use std::rc::Rc;
+use std::{rc::Rc, sync::Arc};
+
+/// A layer exposed to JavaScript.
+///
+/// The wrapper holds the frame to keep the layer alive
+/// even if the frame is evicted from the frame cache.
+pub struct LayerWrapper {
+    layer: *const Layer,
+    frame: Arc<Frame>,
+}
+
+impl LayerWrapper {
+    pub fn new(layer: &Layer, frame: Arc<Frame>) -> LayerWrapper {
+        LayerWrapper { layer, frame }
+    }
+
+    fn layer(&self) -> &Layer {
+        unsafe { &*self.layer }
+    }
+}
 
 pub fn wrapper(env: &Env) -> Rc<ValueRef> {
     fn ctor<'env>(env: &'env Env, _info: &CallbackInfo) -> Result<&'env Value> {
@@ -13,12 +33,13 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
     }
 
     fn layer_id<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
-        let layer = env.unwrap::<Layer>(info.this())?;
+        let layer = env.unwrap::<LayerWrapper>(info.this())?.layer();
         env.create_string(&layer.id().to_string())
     }
 
     fn layer_attr<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
-        let layer = env.unwrap::<Layer>(info.this())?;
+        let wrapper = env.unwrap::<LayerWrapper>(info.this())?;
+        let layer = wrapper.layer();
         if let Some(id) = info.argv().get(0) {
             let id = match env.type_of(id)? {
                 ValueType::Number => Token::from(env.get_value_uint32(id)?),
@@ -27,7 +48,7 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
             if let Some(attr) = layer.attr(id) {
                 let attr_class = env.get_constructor(JsClass::Attr as usize).unwrap();
                 let instance = env.new_instance(&attr_class, &[])?;
-                env.wrap(instance, AttrWrapper::new(attr, layer))?;
+                env.wrap(instance, AttrWrapper::new(attr, layer, wrapper.frame.clone()))?;
                 Ok(instance)
             } else {
                 env.get_null()
@@ -38,26 +59,27 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
     }
 
     fn layer_attrs<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
-        let layer = env.unwrap::<Layer>(info.this())?;
+        let wrapper = env.unwrap::<LayerWrapper>(info.this())?;
+        let layer = wrapper.layer();
         let headers = layer.headers();
         let attrs = layer.attrs();
         let attr_class = env.get_constructor(JsClass::Attr as usize).unwrap();
         let array = env.create_array(headers.len() + attrs.len())?;
         for (i, item) in headers.iter().enumerate() {
             let instance = env.new_instance(&attr_class, &[])?;
-            env.wrap(instance, AttrWrapper::new(item, layer))?;
+            env.wrap(instance, AttrWrapper::new(item, layer, wrapper.frame.clone()))?;
             env.set_element(array, i as u32, instance)?;
         }
         for (i, item) in attrs.iter().enumerate() {
             let instance = env.new_instance(&attr_class, &[])?;
-            env.wrap(instance, AttrWrapper::new(item, layer))?;
+            env.wrap(instance, AttrWrapper::new(item, layer, wrapper.frame.clone()))?;
             env.set_element(array, (headers.len() + i) as u32, instance)?;
         }
         Ok(array)
     }
 
     fn layer_payloads<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
-        let layer = env.unwrap::<Layer>(info.this())?;
+        let layer = env.unwrap::<LayerWrapper>(info.this())?.layer();
         let payloads = layer.payloads();
         let array = env.create_array(payloads.len())?;
         for (i, paylaod) in payloads.iter().enumerate() {
@@ -83,8 +105,39 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
         Ok(array)
     }
 
+    fn layer_annotations<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let layer = env.unwrap::<LayerWrapper>(info.this())?.layer();
+        let annotations = layer.annotations();
+        let array = env.create_array(annotations.len())?;
+        for (i, annotation) in annotations.iter().enumerate() {
+            let object = env.create_object()?;
+            env.set_named_property(
+                object,
+                "severity",
+                env.create_string(annotation.severity().as_str())?,
+            )?;
+            env.set_named_property(
+                object,
+                "group",
+                env.create_string(&annotation.group().to_string())?,
+            )?;
+            env.set_named_property(
+                object,
+                "message",
+                env.create_string(&annotation.message().to_string())?,
+            )?;
+            let range = annotation.range();
+            let range_array = env.create_array(2)?;
+            env.set_element(range_array, 0, env.create_uint32(range.start as u32)?)?;
+            env.set_element(range_array, 1, env.create_uint32(range.end as u32)?)?;
+            env.set_named_property(object, "range", range_array)?;
+            env.set_element(array, i as u32, object)?;
+        }
+        Ok(array)
+    }
+
     fn layer_data<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
-        let layer = env.unwrap::<Layer>(info.this())?;
+        let layer = env.unwrap::<LayerWrapper>(info.this())?.layer();
         env.create_typedarray(
             TypedArrayType::Uint8Array,
             layer.data().len(),
@@ -94,7 +147,7 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
     }
 
     fn layer_filter_expression<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
-        let layer = env.unwrap::<Layer>(info.this())?;
+        let layer = env.unwrap::<LayerWrapper>(info.this())?.layer();
         env.create_string(&unparse(&Expr::Token(layer.id())))
     }
 
@@ -130,6 +183,13 @@ pub fn wrapper(env: &Env) -> Rc<ValueRef> {
                     layer_payloads,
                     false,
                 ),
+                PropertyDescriptor::new_property(
+                    env,
+                    "annotations",
+                    PropertyAttributes::DEFAULT,
+                    layer_annotations,
+                    false,
+                ),
                 PropertyDescriptor::new_property(
                     env,
                     "data",
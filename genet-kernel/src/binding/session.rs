@@ -1,4 +1,4 @@
-use binding::JsClass;
+use binding::{attr::variant_to_js, JsClass};
 use genet_filter::Filter;
 use genet_napi::{
     napi::{
@@ -89,6 +89,20 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
         }
     }
 
+    fn profile_define_macro<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let profile = env.unwrap::<Profile>(info.this())?;
+        if let Some([name, expr]) = info.argv().get(0..2) {
+            if let Err(err) =
+                profile.define_macro(&env.get_value_string(name)?, &env.get_value_string(expr)?)
+            {
+                env.throw_error("define_macro", &err.to_string())?;
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
     fn profile_concurrency<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let profile = env.unwrap::<Profile>(info.this())?;
         if let Some(value) = info.argv().get(0) {
@@ -99,6 +113,16 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
         }
     }
 
+    fn profile_frame_cache<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let profile = env.unwrap::<Profile>(info.this())?;
+        if let Some(value) = info.argv().get(0) {
+            profile.set_frame_cache(env.get_value_uint32(value)? as usize);
+            env.get_null()
+        } else {
+            env.create_uint32(profile.frame_cache() as u32)
+        }
+    }
+
     fn session_frames<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let session = env.unwrap::<Session>(info.this())?;
         if let Some([start, end]) = info.argv().get(0..2) {
@@ -109,7 +133,7 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
             let array = env.create_array(frames.len())?;
             for (i, item) in frames.iter().enumerate() {
                 let instance = env.new_instance(&frame_class, &[])?;
-                env.wrap_ptr(instance, *item)?;
+                env.wrap(instance, item.clone())?;
                 env.set_element(array, i as u32, instance)?;
             }
             Ok(array)
@@ -118,6 +142,64 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
         }
     }
 
+    fn session_evaluate<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([start, end, exprs]) = info.argv().get(0..3) {
+            let start = env.get_value_uint32(start)?;
+            let end = env.get_value_uint32(end)?;
+            let mut filters = Vec::new();
+            for i in 0..env.get_array_length(exprs)? {
+                let expr = env.get_value_string(env.get_element(exprs, i)?)?;
+                match Filter::compile_with_macros(&expr, session.profile().macros()) {
+                    Ok(filter) => filters.push(filter),
+                    Err(err) => {
+                        env.throw_error("evaluate", &err.to_string())?;
+                        return env.get_null();
+                    }
+                }
+            }
+            let rows = session.evaluate(start as usize..end as usize, &filters);
+            let array = env.create_array(rows.len())?;
+            for (i, row) in rows.into_iter().enumerate() {
+                if let Some(row) = row {
+                    let values = env.create_array(row.len())?;
+                    for (j, value) in row.into_iter().enumerate() {
+                        env.set_element(values, j as u32, variant_to_js(env, &Ok(value))?)?;
+                    }
+                    env.set_element(array, i as u32, values)?;
+                } else {
+                    env.set_element(array, i as u32, env.get_null()?)?;
+                }
+            }
+            Ok(array)
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
+    fn session_validate_filter<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some(filter) = info.argv().get(0) {
+            let filter = env.get_value_string(filter)?;
+            match Filter::compile_with_macros(&filter, session.profile().macros()) {
+                Ok(filter) => {
+                    let diagnostics = filter.validate(&session.profile().schema());
+                    let array = env.create_array(diagnostics.len())?;
+                    for (i, item) in diagnostics.iter().enumerate() {
+                        env.set_element(array, i as u32, env.create_string(&item.to_string())?)?;
+                    }
+                    Ok(array)
+                }
+                Err(err) => {
+                    env.throw_error("validate_filter", &err.to_string())?;
+                    env.get_null()
+                }
+            }
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
     fn session_filtered_frames<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let session = env.unwrap::<Session>(info.this())?;
         if let Some([id, start, end]) = info.argv().get(0..3) {
@@ -144,7 +226,7 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
                 if filter.is_empty() {
                     None
                 } else {
-                    match Filter::compile(&filter) {
+                    match Filter::compile_with_macros(&filter, session.profile().macros()) {
                         Ok(filter) => Some(filter),
                         Err(err) => {
                             env.throw_error("load_library", &err.to_string())?;
@@ -159,6 +241,22 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
         }
     }
 
+    fn session_refine_filter<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
+        let session = env.unwrap::<Session>(info.this())?;
+        if let Some([id, parent, filter]) = info.argv().get(0..3) {
+            let id = env.get_value_uint32(id)?;
+            let parent = env.get_value_uint32(parent)?;
+            let filter = env.get_value_string(filter)?;
+            match Filter::compile_with_macros(&filter, session.profile().macros()) {
+                Ok(filter) => session.refine_filter(id, parent, filter),
+                Err(err) => env.throw_error("load_library", &err.to_string())?,
+            }
+            env.get_null()
+        } else {
+            Err(Status::InvalidArg)
+        }
+    }
+
     fn session_create_reader<'env>(env: &'env Env, info: &CallbackInfo) -> Result<&'env Value> {
         let session = env.unwrap::<Session>(info.this())?;
         if let Some([id, arg]) = info.argv().get(0..2) {
@@ -180,7 +278,7 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
                 if filter.is_empty() {
                     None
                 } else {
-                    match Filter::compile(&filter) {
+                    match Filter::compile_with_macros(&filter, session.profile().macros()) {
                         Ok(filter) => Some(filter),
                         Err(err) => {
                             env.throw_error("load_library", &err.to_string())?;
@@ -226,6 +324,18 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
                 PropertyAttributes::DEFAULT,
                 session_frames,
             ),
+            PropertyDescriptor::new_method(
+                env,
+                "evaluate",
+                PropertyAttributes::DEFAULT,
+                session_evaluate,
+            ),
+            PropertyDescriptor::new_method(
+                env,
+                "validateFilter",
+                PropertyAttributes::DEFAULT,
+                session_validate_filter,
+            ),
             PropertyDescriptor::new_method(
                 env,
                 "filteredFrames",
@@ -238,6 +348,12 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
                 PropertyAttributes::DEFAULT,
                 session_set_filter,
             ),
+            PropertyDescriptor::new_method(
+                env,
+                "refineFilter",
+                PropertyAttributes::DEFAULT,
+                session_refine_filter,
+            ),
             PropertyDescriptor::new_method(
                 env,
                 "createReader",
@@ -289,6 +405,12 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
                 PropertyAttributes::DEFAULT,
                 profile_load_library,
             ),
+            PropertyDescriptor::new_method(
+                env,
+                "defineMacro",
+                PropertyAttributes::DEFAULT,
+                profile_define_macro,
+            ),
             PropertyDescriptor::new_property(
                 env,
                 "concurrency",
@@ -296,6 +418,13 @@ pub fn init(env: &Env, exports: &Value) -> Result<()> {
                 profile_concurrency,
                 true,
             ),
+            PropertyDescriptor::new_property(
+                env,
+                "frameCache",
+                PropertyAttributes::DEFAULT,
+                profile_frame_cache,
+                true,
+            ),
         ],
     )?;
 
@@ -0,0 +1,91 @@
+use genet_abi::{
+    attr::Attr,
+    layer::{Annotation, Layer, Payload},
+    slice::{ByteSlice, TryGet},
+    token::Token,
+};
+use std::ops::Range;
+
+/// The numbers of attrs, payloads and annotations of a layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Counts {
+    attrs: usize,
+    payloads: usize,
+    annotations: usize,
+}
+
+impl Counts {
+    pub fn of(layer: &Layer) -> Counts {
+        Counts {
+            attrs: layer.attrs().len(),
+            payloads: layer.payloads().len(),
+            annotations: layer.annotations().len(),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum PayloadData {
+    /// A range of the root layer, which is read again with the frame.
+    Root(Range<usize>),
+    Slice(ByteSlice),
+}
+
+/// Results of SerialSync decoders on the layers from the ParallelSync pass.
+///
+/// The results depend on the preceding frames, so they are kept with the frame index
+/// and added again when the frame is read and decoded again.
+#[derive(Debug, Default)]
+pub struct Delta {
+    attrs: Vec<(usize, Attr)>,
+    payloads: Vec<(usize, PayloadData, Token, Token)>,
+    annotations: Vec<(usize, Annotation)>,
+}
+
+// Attr classes and payload data outlive the frames.
+unsafe impl Send for Delta {}
+unsafe impl Sync for Delta {}
+
+impl Delta {
+    pub fn is_empty(&self) -> bool {
+        self.attrs.is_empty() && self.payloads.is_empty() && self.annotations.is_empty()
+    }
+
+    /// Records the items added to the layer at `index` since `counts` was taken.
+    pub fn record(&mut self, index: usize, layer: &Layer, root: ByteSlice, counts: Counts) {
+        for attr in &layer.attrs()[counts.attrs..] {
+            self.attrs.push((index, (**attr).clone()));
+        }
+        for payload in &layer.payloads()[counts.payloads..] {
+            let data = payload.data();
+            let start = data.as_ptr() as usize;
+            let base = root.as_ptr() as usize;
+            let data = if start >= base && start + data.len() <= base + root.len() {
+                PayloadData::Root(start - base..start - base + data.len())
+            } else {
+                PayloadData::Slice(data)
+            };
+            self.payloads.push((index, data, payload.id(), payload.typ()));
+        }
+        for annotation in &layer.annotations()[counts.annotations..] {
+            self.annotations.push((index, annotation.clone()));
+        }
+    }
+
+    /// Adds the items recorded for the layer at `index` again.
+    pub fn apply(&self, index: usize, layer: &mut Layer, root: ByteSlice) {
+        for (_, attr) in self.attrs.iter().filter(|(i, _)| *i == index) {
+            layer.add_attr(attr.clone());
+        }
+        for (_, data, id, typ) in self.payloads.iter().filter(|(i, ..)| *i == index) {
+            let data = match data {
+                PayloadData::Root(range) => root.try_get(range.clone()).unwrap_or_default(),
+                PayloadData::Slice(data) => *data,
+            };
+            layer.add_payload(Payload::with_typ(data, *id, *typ));
+        }
+        for (_, annotation) in self.annotations.iter().filter(|(i, _)| *i == index) {
+            layer.add_annotation(annotation.clone());
+        }
+    }
+}
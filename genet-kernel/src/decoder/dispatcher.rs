@@ -1,37 +1,98 @@
+use decoder::{
+    delta::{Counts, Delta},
+    error,
+};
+use fnv::FnvHashMap;
 use frame::Frame;
 use genet_abi::{
     context::Context,
     decoder::{DecoderBox, ExecType, Metadata, WorkerBox},
     fixed::MutFixed,
     layer::{Layer, Parent},
+    token::Token,
 };
 use profile::Profile;
+use std::cmp::Reverse;
 
 pub struct Dispatcher {
+    typ: ExecType,
     runners: Vec<Runner>,
+    followers: Vec<Runner>,
+    runner_index: RunnerIndex,
+    follower_index: RunnerIndex,
 }
 
 impl Dispatcher {
     pub fn new(typ: &ExecType, profile: &Profile) -> Dispatcher {
-        let runners = profile
+        let mut runners: Vec<Runner> = profile
             .decoders()
             .map(|d| Runner::new(typ, profile.context(), *d))
             .collect();
-        Dispatcher { runners }
-    }
+        runners.sort_by_key(|r| Reverse(r.metadata.priority));
 
-    pub fn runners(&mut self) -> Vec<OnceRunner> {
-        self.runners
-            .iter_mut()
-            .map(|r| OnceRunner::new(r))
-            .collect()
+        // ParallelSync decoders also run on the payloads produced by SerialSync decoders,
+        // such as reassembled datagrams.
+        let mut followers: Vec<Runner> = if *typ == ExecType::SerialSync {
+            profile
+                .decoders()
+                .map(|d| Runner::new(&ExecType::ParallelSync, profile.context(), *d))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        followers.sort_by_key(|r| Reverse(r.metadata.priority));
+        Dispatcher {
+            typ: typ.clone(),
+            runner_index: RunnerIndex::new(&runners),
+            follower_index: RunnerIndex::new(&followers),
+            runners,
+            followers,
+        }
     }
 
     pub fn process_frame(&mut self, frame: &mut Frame) {
+        self.process(frame, None);
+    }
+
+    /// Adds the results of SerialSync decoders recorded in the delta to a frame
+    /// decoded by a ParallelSync dispatcher, and decodes the added payloads.
+    pub fn replay(&mut self, frame: &mut Frame, delta: &Delta) {
+        self.process(frame, Some(delta));
+    }
+
+    fn process(&mut self, frame: &mut Frame, replay: Option<&Delta>) {
+        for r in self.runners.iter_mut().chain(self.followers.iter_mut()) {
+            r.ctx.set_frame_index(frame.index());
+        }
+
+        // The delta takes the place of SerialSync decoders,
+        // and the ParallelSync decoders follow it.
+        let empty_index = RunnerIndex::new(&[]);
+        let (runner_list, runner_index, follower_list, follower_index) = if replay.is_some() {
+            (&mut [][..], &empty_index, &mut self.runners, &self.runner_index)
+        } else {
+            (
+                &mut self.runners[..],
+                &self.runner_index,
+                &mut self.followers,
+                &self.follower_index,
+            )
+        };
+
+        // Results of SerialSync decoders are recorded if they can be replayed,
+        // that is, they are added to the layers from the ParallelSync pass without new layers.
+        let record = self.typ == ExecType::SerialSync;
+        let mut delta = Delta::default();
+        let mut stateful = false;
+
         let mut indices = frame.fetch_tree_indices();
         let mut layers = frame.fetch_layers();
+        let base = layers.len();
+        let root = layers[0].data();
         let mut offset = 0;
-        let mut runners = self.runners();
+        let mut runners: Vec<OnceRunner> = runner_list.iter_mut().map(OnceRunner::new).collect();
+        let mut followers: Vec<OnceRunner> =
+            follower_list.iter_mut().map(OnceRunner::new).collect();
         loop {
             let len = layers.len() - offset;
             for index in offset..layers.len() {
@@ -40,29 +101,55 @@ impl Dispatcher {
                         continue;
                     }
                 }
-                let mut children = 0;
-                loop {
-                    let mut executed = 0;
-                    for mut r in &mut runners.iter_mut() {
-                        let mut layer =
-                            Parent::from_mut_ref(unsafe { &mut *layers[index].as_mut_ptr() });
-                        let done = r.execute(&layers, &mut layer);
-                        if done {
-                            executed += 1;
-                        }
-                        let mut results: Vec<MutFixed<Layer>> = layer
-                            .children()
-                            .iter()
-                            .map(|v| unsafe { MutFixed::from_ptr(*v) })
-                            .collect();
-                        children += results.len();
-                        layers.append(&mut results);
+                let payloads = layers[index].payloads().len();
+                let counts = Counts::of(&layers[index]);
+                let mut children =
+                    Self::execute(&mut runners, runner_index, &mut layers, index, None);
+                if let Some(delta) = replay {
+                    delta.apply(index, &mut layers[index], root);
+                }
+                if record {
+                    if children > 0 || (index >= base && Counts::of(&layers[index]) != counts) {
+                        stateful = true;
+                    } else {
+                        delta.record(index, &layers[index], root, counts);
                     }
-                    if executed == 0 {
-                        break;
+                }
+
+                // Followers run on new layers, and on the payloads added to the existing
+                // layers since the ParallelSync pass has already decoded the rest.
+                let follow = if index >= base {
+                    Some(None)
+                } else if layers[index].payloads().len() > payloads {
+                    Some(Some(payloads))
+                } else {
+                    None
+                };
+                if let Some(start) = follow {
+                    children += Self::execute(
+                        &mut followers,
+                        follower_index,
+                        &mut layers,
+                        index,
+                        start,
+                    );
+                }
+                if children == 0 {
+                    let counts = Counts::of(&layers[index]);
+                    children += Self::execute_heuristics(&mut runners, &mut layers, index, None);
+                    if record && (children > 0 || Counts::of(&layers[index]) != counts) {
+                        stateful = true;
                     }
                 }
-                indices.push(children as u8);
+                if let (0, Some(start)) = (children, follow) {
+                    children +=
+                        Self::execute_heuristics(&mut followers, &mut layers, index, start);
+                }
+                if index < indices.len() {
+                    indices[index] = children as u8;
+                } else {
+                    indices.push(children as u8);
+                }
             }
 
             offset += len;
@@ -71,9 +158,132 @@ impl Dispatcher {
             }
         }
 
+        if stateful {
+            frame.set_stateful();
+        } else if !delta.is_empty() {
+            frame.set_delta(delta);
+        }
         frame.set_layers(layers);
         frame.set_tree_indices(indices);
     }
+
+    /// Runs the decoders consuming the layer, or only the payloads from `start` if given.
+    fn execute(
+        runners: &mut [OnceRunner],
+        runner_index: &RunnerIndex,
+        layers: &mut Vec<MutFixed<Layer>>,
+        index: usize,
+        start: Option<usize>,
+    ) -> usize {
+        let mut children = 0;
+        loop {
+            let mut executed = 0;
+            // Payloads added by a decoder may be consumed by other decoders in the next round.
+            for i in runner_index.candidates(&layers[index], start) {
+                let r = &mut runners[i];
+                let mut layer = Parent::from_mut_ref(unsafe { &mut *layers[index].as_mut_ptr() });
+                let done = r.execute(layers, &mut layer);
+                if done {
+                    executed += 1;
+                }
+                children += Self::append_children(&layer, layers);
+            }
+            if executed == 0 {
+                break;
+            }
+        }
+        children
+    }
+
+    /// Tries heuristic decoders in priority order until one of them claims
+    /// the untyped payloads of the layer, or of the payloads from `start` if given.
+    fn execute_heuristics(
+        runners: &mut [OnceRunner],
+        layers: &mut Vec<MutFixed<Layer>>,
+        index: usize,
+        start: Option<usize>,
+    ) -> usize {
+        let id = layers[index].id();
+        if !layers[index].payloads()[start.unwrap_or(0)..]
+            .iter()
+            .any(|p| p.id() == Token::null())
+        {
+            return 0;
+        }
+        for r in runners
+            .iter_mut()
+            .filter(|r| r.runner.heuristics.contains(&id))
+        {
+            let mut layer = Parent::from_mut_ref(unsafe { &mut *layers[index].as_mut_ptr() });
+            r.execute(layers, &mut layer);
+            let children = Self::append_children(&layer, layers);
+            if children > 0 {
+                return children;
+            }
+        }
+        0
+    }
+
+    fn append_children(layer: &Parent, layers: &mut Vec<MutFixed<Layer>>) -> usize {
+        let mut results: Vec<MutFixed<Layer>> = layer
+            .children()
+            .iter()
+            .map(|v| unsafe { MutFixed::from_ptr(*v) })
+            .collect();
+        let len = results.len();
+        layers.append(&mut results);
+        len
+    }
+}
+
+/// Maps layer and payload ids to the runners consuming them.
+struct RunnerIndex {
+    map: FnvHashMap<Token, Vec<usize>>,
+    fallback: Vec<usize>,
+}
+
+impl RunnerIndex {
+    fn new(runners: &[Runner]) -> RunnerIndex {
+        let mut map = FnvHashMap::<Token, Vec<usize>>::default();
+        let mut fallback = Vec::new();
+        for (i, r) in runners.iter().enumerate() {
+            if r.worker.is_none() || !r.heuristics.is_empty() {
+                continue;
+            }
+            if r.metadata.consumes.is_empty() {
+                fallback.push(i);
+            }
+            for id in &r.metadata.consumes {
+                map.entry(Token::from(id.as_str()))
+                    .or_insert_with(Vec::new)
+                    .push(i);
+            }
+        }
+        RunnerIndex { map, fallback }
+    }
+
+    /// Returns the runners to be invoked on the layer in priority order.
+    ///
+    /// If `start` is given, only the runners consuming the payloads from `start` are returned.
+    fn candidates(&self, layer: &Layer, start: Option<usize>) -> Vec<usize> {
+        let (mut list, layer_id) = match start {
+            Some(_) => (Vec::new(), None),
+            None => (self.fallback.clone(), Some(layer.id())),
+        };
+        let ids = layer_id.into_iter().chain(
+            layer.payloads()[start.unwrap_or(0)..]
+                .iter()
+                .map(|p| p.id()),
+        );
+        for id in ids {
+            if let Some(runners) = self.map.get(&id) {
+                list.extend(runners);
+            }
+        }
+        list.sort();
+        list.dedup();
+        list
+    }
 }
 
 struct Runner {
@@ -81,16 +291,24 @@ struct Runner {
     typ: ExecType,
     decoder: DecoderBox,
     metadata: Metadata,
+    heuristics: Vec<Token>,
     worker: Option<WorkerBox>,
 }
 
 impl Runner {
     fn new(typ: &ExecType, ctx: Context, decoder: DecoderBox) -> Runner {
+        let metadata = decoder.metadata();
+        let heuristics = metadata
+            .heuristics
+            .iter()
+            .map(|id| Token::from(id.as_str()))
+            .collect();
         let mut runner = Runner {
             ctx,
             typ: typ.clone(),
             decoder,
-            metadata: decoder.metadata(),
+            metadata,
+            heuristics,
             worker: None,
         };
         runner.reset();
@@ -101,7 +319,22 @@ impl Runner {
         if let Some(worker) = &mut self.worker {
             match worker.decode(&mut self.ctx, layers, layer) {
                 Ok(done) => done,
-                Err(_) => true,
+                Err(err) => {
+                    let data = layer.data();
+                    let offset = layers.first().and_then(|root| {
+                        let root = root.data();
+                        let start = data.as_ptr() as usize;
+                        let base = root.as_ptr() as usize;
+                        if start >= base && start + data.len() <= base + root.len() {
+                            Some(start - base)
+                        } else {
+                            None
+                        }
+                    });
+                    let id = &self.metadata.id;
+                    layer.add_child(error::layer(data, id, &err.to_string(), offset));
+                    true
+                }
             }
         } else {
             true
@@ -142,3 +375,311 @@ impl<'a> OnceRunner<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use decoder::dispatcher::Dispatcher;
+    use frame::Frame;
+    use genet_abi::{
+        context::Context,
+        decoder::{Decoder, DecoderBox, ExecType, Metadata, Status, Worker},
+        fixed::{Fixed, MutFixed},
+        layer::{Layer, LayerClass, LayerStack, Parent, Payload},
+        result::Result,
+        slice::ByteSlice,
+        token::Token,
+    };
+    use profile::Profile;
+
+    type Action = fn(&mut Parent) -> Result<Status>;
+
+    #[derive(Clone)]
+    struct StubDecoder {
+        exec_type: ExecType,
+        consumes: Vec<&'static str>,
+        priority: i32,
+        heuristics: Vec<&'static str>,
+        action: Action,
+    }
+
+    impl StubDecoder {
+        fn new(exec_type: ExecType, consumes: &[&'static str], action: Action) -> StubDecoder {
+            StubDecoder {
+                exec_type,
+                consumes: consumes.to_vec(),
+                priority: 0,
+                heuristics: Vec::new(),
+                action,
+            }
+        }
+
+        fn priority(mut self, priority: i32) -> StubDecoder {
+            self.priority = priority;
+            self
+        }
+
+        fn heuristics(mut self, heuristics: &[&'static str]) -> StubDecoder {
+            self.heuristics = heuristics.to_vec();
+            self
+        }
+    }
+
+    struct StubWorker {
+        action: Action,
+    }
+
+    impl Worker for StubWorker {
+        fn decode(
+            &mut self,
+            _ctx: &mut Context,
+            _stack: &LayerStack,
+            parent: &mut Parent,
+        ) -> Result<Status> {
+            (self.action)(parent)
+        }
+    }
+
+    impl Decoder for StubDecoder {
+        fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+            Box::new(StubWorker {
+                action: self.action,
+            })
+        }
+
+        fn metadata(&self) -> Metadata {
+            Metadata {
+                exec_type: self.exec_type.clone(),
+                consumes: self.consumes.iter().map(|id| id.to_string()).collect(),
+                priority: self.priority,
+                heuristics: self.heuristics.iter().map(|id| id.to_string()).collect(),
+                ..Metadata::default()
+            }
+        }
+    }
+
+    fn layer(id: &str, payloads: &[&str]) -> Layer {
+        let mut layer = Layer::new(
+            Fixed::new(LayerClass::builder(id).build()),
+            ByteSlice::new(),
+        );
+        for id in payloads {
+            layer.add_payload(Payload::new(ByteSlice::new(), *id));
+        }
+        layer
+    }
+
+    /// Decodes a frame with the ParallelSync and SerialSync passes and returns
+    /// the ids of the layers with the ids of their payloads.
+    fn decode(decoders: Vec<StubDecoder>, root: Layer) -> Vec<(String, Vec<String>)> {
+        let mut profile = Profile::new();
+        for decoder in decoders {
+            profile.add_decoder(DecoderBox::new(decoder));
+        }
+        let mut frame = Frame::new(0, MutFixed::new(root));
+        Dispatcher::new(&ExecType::ParallelSync, &profile).process_frame(&mut frame);
+        Dispatcher::new(&ExecType::SerialSync, &profile).process_frame(&mut frame);
+        frame
+            .layers()
+            .iter()
+            .map(|layer| {
+                let payloads = layer
+                    .payloads()
+                    .iter()
+                    .map(|p| p.id().to_string())
+                    .collect();
+                (layer.id().to_string(), payloads)
+            })
+            .collect()
+    }
+
+    fn child(consumes: &[&'static str], action: Action) -> StubDecoder {
+        StubDecoder::new(ExecType::ParallelSync, consumes, action)
+    }
+
+    /// Returns a heuristic decoder for the layers with the id `root`.
+    fn heuristic(priority: i32, action: Action) -> StubDecoder {
+        child(&[], action).heuristics(&["root"]).priority(priority)
+    }
+
+    fn dump(layers: &[(&str, &[&str])]) -> Vec<(String, Vec<String>)> {
+        layers
+            .iter()
+            .map(|(id, payloads)| {
+                let payloads = payloads.iter().map(|p| p.to_string()).collect();
+                (id.to_string(), payloads)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn followers() {
+        let decoders = vec![
+            StubDecoder::new(ExecType::ParallelSync, &["@data:a"], |p| {
+                p.add_child(layer("a", &["@data:b"]));
+                Ok(Status::Done)
+            }),
+            StubDecoder::new(ExecType::ParallelSync, &["@data:b"], |p| {
+                p.add_payload(Payload::new(ByteSlice::new(), "@data:c"));
+                Ok(Status::Done)
+            }),
+            StubDecoder::new(ExecType::SerialSync, &["a"], |p| {
+                p.add_payload(Payload::new(ByteSlice::new(), "@stream:a"));
+                Ok(Status::Done)
+            }),
+            StubDecoder::new(ExecType::ParallelSync, &["@stream:a"], |p| {
+                p.add_child(layer("stream", &[]));
+                Ok(Status::Done)
+            }),
+        ];
+        assert_eq!(
+            decode(decoders, layer("root", &["@data:a"])),
+            dump(&[
+                ("root", &["@data:a"]),
+                ("a", &["@data:b", "@data:c", "@stream:a"]),
+                ("stream", &[]),
+            ])
+        );
+    }
+
+    #[test]
+    fn consumes() {
+        let decoders = vec![
+            child(&["@data:a"], |p| {
+                p.add_child(layer("a", &["@data:b"]));
+                Ok(Status::Done)
+            }),
+            // Consumes the layer id.
+            child(&["a"], |p| {
+                p.add_child(layer("by-layer", &[]));
+                Ok(Status::Done)
+            }),
+            child(&["@data:b"], |p| {
+                p.add_child(layer("b", &[]));
+                Ok(Status::Done)
+            }),
+            child(&["@data:unknown"], |p| {
+                p.add_child(layer("unknown", &[]));
+                Ok(Status::Done)
+            }),
+        ];
+        assert_eq!(
+            decode(decoders, layer("root", &["@data:a"])),
+            dump(&[
+                ("root", &["@data:a"]),
+                ("a", &["@data:b"]),
+                ("by-layer", &[]),
+                ("b", &[]),
+            ])
+        );
+    }
+
+    #[test]
+    fn fallback() {
+        // Decoders without consumes are tried on every layer until they finish.
+        let decoders = vec![
+            child(&[], |p| {
+                if p.id() != Token::from("a") {
+                    return Ok(Status::Skip);
+                }
+                p.add_child(layer("any", &[]));
+                Ok(Status::Done)
+            }),
+            child(&["@data:a"], |p| {
+                p.add_child(layer("a", &[]));
+                Ok(Status::Done)
+            }),
+        ];
+        assert_eq!(
+            decode(decoders, layer("root", &["@data:a"])),
+            dump(&[("root", &["@data:a"]), ("a", &[]), ("any", &[])])
+        );
+    }
+
+    #[test]
+    fn priority() {
+        let low = child(&["@data:a"], |p| {
+            p.add_child(layer("low", &[]));
+            Ok(Status::Done)
+        });
+        let high = child(&["@data:a"], |p| {
+            p.add_child(layer("high", &[]));
+            Ok(Status::Done)
+        });
+        let default = child(&["@data:a"], |p| {
+            p.add_child(layer("default", &[]));
+            Ok(Status::Done)
+        });
+        let decoders = vec![low.priority(-1), high.priority(10), default];
+        assert_eq!(
+            decode(decoders, layer("root", &["@data:a"])),
+            dump(&[
+                ("root", &["@data:a"]),
+                ("high", &[]),
+                ("default", &[]),
+                ("low", &[]),
+            ])
+        );
+    }
+
+    #[test]
+    fn heuristics() {
+        // The first heuristic decoder in priority order claiming the payload wins.
+        let decoders = || {
+            vec![
+                heuristic(0, |p| {
+                    p.add_child(layer("low", &[]));
+                    Ok(Status::Done)
+                }),
+                heuristic(20, |_| Ok(Status::Done)),
+                heuristic(10, |p| {
+                    p.add_child(layer("high", &[]));
+                    Ok(Status::Done)
+                }),
+            ]
+        };
+        assert_eq!(
+            decode(decoders(), layer("root", &[""])),
+            dump(&[("root", &[""]), ("high", &[])])
+        );
+
+        // Heuristic decoders do not run on layers without untyped payloads.
+        assert_eq!(
+            decode(decoders(), layer("root", &["@data:unknown"])),
+            dump(&[("root", &["@data:unknown"])])
+        );
+
+        // Heuristic decoders run on the ids listed in heuristics only.
+        assert_eq!(
+            decode(decoders(), layer("other", &[""])),
+            dump(&[("other", &[""])])
+        );
+    }
+
+    #[test]
+    fn heuristics_after_explicit() {
+        let heuristic = heuristic(0, |p| {
+            p.add_child(layer("heuristic", &[]));
+            Ok(Status::Done)
+        });
+
+        // Heuristic decoders run only when no explicit decoder matched.
+        let decoders = vec![
+            heuristic.clone(),
+            child(&["@data:a"], |p| {
+                p.add_child(layer("a", &[]));
+                Ok(Status::Done)
+            }),
+        ];
+        assert_eq!(
+            decode(decoders, layer("root", &["@data:a", ""])),
+            dump(&[("root", &["@data:a", ""]), ("a", &[])])
+        );
+
+        // An explicit decoder declining the layer leaves it to the heuristics.
+        let decoders = vec![heuristic, child(&["root"], |_| Ok(Status::Done))];
+        assert_eq!(
+            decode(decoders, layer("root", &[""])),
+            dump(&[("root", &[""]), ("heuristic", &[])])
+        );
+    }
+}
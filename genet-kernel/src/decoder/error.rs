@@ -0,0 +1,52 @@
+use genet_abi::{
+    attr::{Attr, AttrClass},
+    field::Field,
+    layer::{Layer, LayerClass},
+    slice::ByteSlice,
+    token::Token,
+};
+
+lazy_static! {
+    static ref ERROR_CLASS: LayerClass = LayerClass::builder("@error")
+        .alias("_.error", "@error.message")
+        .alias("_.error.decoder", "@error.decoder")
+        .alias("_.error.offset", "@error.offset")
+        .build();
+    static ref DECODER_ATTR: AttrClass = AttrClass::builder("@error.decoder").build();
+    static ref MESSAGE_ATTR: AttrClass = AttrClass::builder("@error.message").build();
+    static ref OFFSET_ATTR: AttrClass = AttrClass::builder("@error.offset").build();
+}
+
+/// Returns the token of error layers.
+pub fn token() -> Token {
+    Token::from("@error")
+}
+
+/// Returns the fields of error layers.
+pub fn fields() -> Vec<Field> {
+    [
+        "@error",
+        "@error.decoder",
+        "@error.message",
+        "@error.offset",
+        "_.error",
+        "_.error.decoder",
+        "_.error.offset",
+    ]
+        .iter()
+        .map(|id| Field::new(id, ""))
+        .collect()
+}
+
+/// Creates an error layer covering the data which the decoder failed to decode.
+///
+/// `offset` is the position of the data in the root layer, if it is a part of it.
+pub fn layer(data: ByteSlice, decoder: &str, message: &str, offset: Option<usize>) -> Layer {
+    let mut layer = Layer::new(&ERROR_CLASS, data);
+    layer.add_attr(Attr::builder(&DECODER_ATTR).value(Box::<str>::from(decoder)).build());
+    layer.add_attr(Attr::builder(&MESSAGE_ATTR).value(Box::<str>::from(message)).build());
+    if let Some(offset) = offset {
+        layer.add_attr(Attr::builder(&OFFSET_ATTR).value(offset as u64).build());
+    }
+    layer
+}
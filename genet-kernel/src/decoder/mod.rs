@@ -1,3 +1,5 @@
-mod dispatcher;
+pub mod delta;
+pub mod dispatcher;
+pub mod error;
 pub mod parallel;
 pub mod serial;
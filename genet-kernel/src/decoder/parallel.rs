@@ -35,15 +35,11 @@ impl Pool {
         thread::spawn(move || {
             let mut disp = Dispatcher::new(&ExecType::ParallelSync, &profile);
             loop {
-                if let Some(frames) = recv.recv() {
-                    if let Some(mut frames) = frames {
-                        for mut f in &mut frames {
-                            disp.process_frame(f);
-                        }
-                        callback.done(frames);
-                    } else {
-                        return;
+                if let Ok(Some(mut frames)) = recv.recv() {
+                    for mut f in &mut frames {
+                        disp.process_frame(f);
                     }
+                    callback.done(frames);
                 } else {
                     return;
                 }
@@ -52,14 +48,14 @@ impl Pool {
     }
 
     pub fn process(&mut self, frames: Vec<Frame>) {
-        self.sender.send(Some(frames));
+        let _ = self.sender.send(Some(frames));
     }
 }
 
 impl Drop for Pool {
     fn drop(&mut self) {
         for _ in 0..self.handles.len() {
-            self.sender.send(None);
+            let _ = self.sender.send(None);
         }
         while let Some(h) = self.handles.pop() {
             h.join().unwrap();
@@ -0,0 +1,94 @@
+use crossbeam_channel;
+use genet_filter::{context::Context, Filter};
+use profile::Profile;
+use std::{
+    ops::Range,
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
+use store::{load_frames, load_frames_at, FrameLoader, FrameStore};
+
+pub trait Callback: Sync + Send + Clone {
+    fn done(&self, task: Task, indices: Vec<u32>);
+}
+
+/// A range of frames to be filtered.
+#[derive(Clone, Debug)]
+pub struct Task {
+    pub id: u32,
+    pub serial: u64,
+    pub filter: Arc<Filter>,
+    pub range: Range<usize>,
+
+    /// Frames in the range to be tested. All frames are tested if None.
+    pub indices: Option<Vec<u32>>,
+}
+
+pub struct Pool {
+    sender: crossbeam_channel::Sender<Option<Task>>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl Pool {
+    pub fn new<C: 'static + Callback>(
+        profile: &Profile,
+        frames: &FrameStore,
+        loader: &FrameLoader,
+        callback: &C,
+    ) -> Pool {
+        let (send, recv) = crossbeam_channel::unbounded::<Option<Task>>();
+        let mut handles = Vec::new();
+        for _ in 0..profile.concurrency() {
+            handles.push(Self::spawn(
+                frames.clone(),
+                loader.clone(),
+                callback.clone(),
+                recv.clone(),
+            ));
+        }
+        Pool {
+            sender: send,
+            handles,
+        }
+    }
+
+    fn spawn<C: 'static + Callback>(
+        frames: FrameStore,
+        loader: FrameLoader,
+        callback: C,
+        recv: crossbeam_channel::Receiver<Option<Task>>,
+    ) -> JoinHandle<()> {
+        thread::spawn(move || loop {
+            if let Ok(Some(task)) = recv.recv() {
+                let loaded = if let Some(indices) = &task.indices {
+                    load_frames_at(&frames, &loader, indices)
+                } else {
+                    load_frames(&frames, &loader, task.range.clone())
+                };
+                let indices = loaded
+                    .iter()
+                    .filter(|frame| task.filter.test(&Context::new(frame.layers())))
+                    .map(|frame| frame.index())
+                    .collect();
+                callback.done(task, indices);
+            } else {
+                return;
+            }
+        })
+    }
+
+    pub fn process(&mut self, task: Task) {
+        let _ = self.sender.send(Some(task));
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        for _ in 0..self.handles.len() {
+            let _ = self.sender.send(None);
+        }
+        while let Some(h) = self.handles.pop() {
+            h.join().unwrap();
+        }
+    }
+}
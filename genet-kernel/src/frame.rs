@@ -1,10 +1,26 @@
-use genet_abi::{attr::Attr, fixed::MutFixed, layer::Layer, token::Token};
+use decoder::delta::Delta;
+use genet_abi::{
+    attr::Attr,
+    fixed::MutFixed,
+    layer::{Annotation, Layer},
+    token::Token,
+};
 use std::{fmt, mem};
 
+/// Location of a frame in the input it was read from.
+#[derive(Debug, Clone, Copy)]
+pub struct Source {
+    pub input: u32,
+    pub position: u64,
+}
+
 pub struct Frame {
     index: u32,
     layers: Vec<MutFixed<Layer>>,
     tree_indices: Vec<u8>,
+    source: Option<Source>,
+    stateful: bool,
+    delta: Option<Delta>,
 }
 
 impl fmt::Debug for Frame {
@@ -14,6 +30,24 @@ impl fmt::Debug for Frame {
 }
 
 unsafe impl Send for Frame {}
+unsafe impl Sync for Frame {}
+
+impl Drop for Frame {
+    fn drop(&mut self) {
+        for (i, layer) in self.layers.iter().enumerate() {
+            unsafe {
+                // Frames read with random access own the root layer data.
+                if i == 0 && self.source.is_some() {
+                    let data = layer.data();
+                    if !data.is_empty() {
+                        drop(Box::from_raw(&*data as *const [u8] as *mut [u8]));
+                    }
+                }
+                drop(Box::from_raw(layer.as_mut_ptr()));
+            }
+        }
+    }
+}
 
 impl Frame {
     pub fn new(index: u32, root: MutFixed<Layer>) -> Frame {
@@ -21,6 +55,9 @@ impl Frame {
             index,
             layers: vec![root],
             tree_indices: Vec::new(),
+            source: None,
+            stateful: false,
+            delta: None,
         }
     }
 
@@ -41,6 +78,13 @@ impl Frame {
         None
     }
 
+    /// Returns the annotations of all layers in the frame.
+    pub fn annotations(&self) -> impl Iterator<Item = &Annotation> {
+        self.layers
+            .iter()
+            .flat_map(|layer| layer.annotations().iter())
+    }
+
     pub fn fetch_layers(&mut self) -> Vec<MutFixed<Layer>> {
         let mut v = Vec::new();
         mem::swap(&mut self.layers, &mut v);
@@ -64,4 +108,34 @@ impl Frame {
     pub fn set_tree_indices(&mut self, tree_indices: Vec<u8>) {
         self.tree_indices = tree_indices;
     }
+
+    pub fn source(&self) -> Option<Source> {
+        self.source
+    }
+
+    pub fn set_source(&mut self, source: Source) {
+        self.source = Some(source);
+    }
+
+    /// Returns true if SerialSync decoders produced results on the frame
+    /// which cannot be recorded in a Delta, such as new layers.
+    ///
+    /// Such results depend on the preceding frames,
+    /// so the frame cannot be decoded again on its own.
+    pub fn is_stateful(&self) -> bool {
+        self.stateful
+    }
+
+    pub fn set_stateful(&mut self) {
+        self.stateful = true;
+    }
+
+    /// Takes the results of SerialSync decoders recorded on the frame.
+    pub fn take_delta(&mut self) -> Option<Delta> {
+        self.delta.take()
+    }
+
+    pub fn set_delta(&mut self, delta: Delta) {
+        self.delta = Some(delta);
+    }
 }
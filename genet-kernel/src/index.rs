@@ -0,0 +1,503 @@
+use decoder::{delta::Delta, dispatcher::Dispatcher};
+use fnv::FnvHashMap;
+use frame::{Frame, Source};
+use genet_abi::decoder::ExecType;
+use io::Input;
+use parking_lot::{Mutex, RwLock};
+use profile::Profile;
+use std::{collections::BTreeMap, fmt, sync::Arc};
+
+/// A stored frame.
+#[derive(Clone)]
+///
+/// Frames from inputs supporting random access are stored as compact indices
+/// if the frame cache is enabled.
+pub enum Entry {
+    Frame(Arc<Frame>),
+    Index(FrameIndex),
+}
+
+impl fmt::Debug for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Entry::Frame(frame) => write!(f, "Entry {:?}", frame),
+            Entry::Index(index) => write!(f, "Entry {:?}", index),
+        }
+    }
+}
+
+/// A compact index of a frame which can be read again from its input.
+#[derive(Debug, Clone)]
+pub struct FrameIndex {
+    pub source: Source,
+    pub delta: Option<Arc<Delta>>,
+}
+
+type SharedInput = Arc<Mutex<Box<Input>>>;
+
+/// Reads and decodes indexed frames again on demand.
+///
+/// Decoded frames are kept in an LRU cache up to Profile::frame_cache().
+/// The results of SerialSync decoders are kept with the index as a Delta
+/// and added again after decoding with ParallelSync decoders, which gives the same layers.
+/// Frames with new layers from SerialSync decoders are not indexed.
+///
+/// The loader is shared by the filter workers, so each part and each input is locked
+/// separately and frames are decoded in parallel with a pool of dispatchers.
+pub struct Loader {
+    profile: Profile,
+    inputs: RwLock<FnvHashMap<u32, SharedInput>>,
+    dispatchers: Mutex<Vec<Dispatcher>>,
+    cache: Mutex<Lru>,
+}
+
+// Decoder workers are only accessed by one thread at a time through the dispatcher pool.
+unsafe impl Send for Loader {}
+unsafe impl Sync for Loader {}
+
+impl fmt::Debug for Loader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Loader")
+    }
+}
+
+impl Loader {
+    pub fn new(profile: Profile) -> Loader {
+        Loader {
+            cache: Mutex::new(Lru::new(profile.frame_cache())),
+            profile,
+            inputs: RwLock::new(FnvHashMap::default()),
+            dispatchers: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.profile.frame_cache() > 0
+    }
+
+    pub fn add_input(&self, id: u32, input: Box<Input>) {
+        self.inputs.write().insert(id, Arc::new(Mutex::new(input)));
+    }
+
+    /// Converts a newly decoded frame into a stored entry.
+    pub fn store(&self, mut frame: Frame) -> Entry {
+        let delta = frame.take_delta();
+        if let Some(source) = frame.source() {
+            if self.is_enabled()
+                && !frame.is_stateful()
+                && self.inputs.read().contains_key(&source.input)
+            {
+                let index = FrameIndex {
+                    source,
+                    delta: delta.map(Arc::new),
+                };
+                self.cache.lock().insert(frame.index(), Arc::new(frame));
+                return Entry::Index(index);
+            }
+        }
+        Entry::Frame(Arc::new(frame))
+    }
+
+    /// Returns the decoded frame of the entry.
+    pub fn load(&self, index: u32, entry: &Entry) -> Option<Arc<Frame>> {
+        match entry {
+            Entry::Frame(frame) => Some(frame.clone()),
+            Entry::Index(frame_index) => {
+                if let Some(frame) = self.cache.lock().get(index) {
+                    return Some(frame);
+                }
+                let frame = Arc::new(self.decode(index, frame_index)?);
+                self.cache.lock().insert(index, frame.clone());
+                Some(frame)
+            }
+        }
+    }
+
+    fn decode(&self, index: u32, frame_index: &FrameIndex) -> Option<Frame> {
+        let source = frame_index.source;
+        let input = self.inputs.read().get(&source.input)?.clone();
+        let root = input.lock().read_at(source.position).ok()?;
+        let mut frame = Frame::new(index, root);
+        frame.set_source(source);
+
+        let dispatcher = self.dispatchers.lock().pop();
+        let mut dispatcher = dispatcher
+            .unwrap_or_else(|| Dispatcher::new(&ExecType::ParallelSync, &self.profile));
+        dispatcher.process_frame(&mut frame);
+        if let Some(delta) = &frame_index.delta {
+            dispatcher.replay(&mut frame, delta);
+        }
+        self.dispatchers.lock().push(dispatcher);
+        Some(frame)
+    }
+}
+
+struct Lru {
+    capacity: usize,
+    tick: u64,
+    frames: FnvHashMap<u32, (u64, Arc<Frame>)>,
+    order: BTreeMap<u64, u32>,
+}
+
+impl Lru {
+    fn new(capacity: usize) -> Lru {
+        Lru {
+            capacity,
+            tick: 0,
+            frames: FnvHashMap::default(),
+            order: BTreeMap::new(),
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    fn get(&mut self, index: u32) -> Option<Arc<Frame>> {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some((used, frame)) = self.frames.get_mut(&index) {
+            self.order.remove(used);
+            self.order.insert(tick, index);
+            *used = tick;
+            Some(frame.clone())
+        } else {
+            None
+        }
+    }
+
+    fn insert(&mut self, index: u32, frame: Arc<Frame>) {
+        self.tick += 1;
+        if let Some((used, _)) = self.frames.insert(index, (self.tick, frame)) {
+            self.order.remove(&used);
+        }
+        self.order.insert(self.tick, index);
+        while self.frames.len() > self.capacity {
+            let oldest = *self.order.keys().next().unwrap();
+            if let Some(index) = self.order.remove(&oldest) {
+                self.frames.remove(&index);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use decoder::dispatcher::Dispatcher;
+    use frame::{Frame, Source};
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        context::Context,
+        decoder::{Decoder, DecoderBox, ExecType, Metadata, Status, Worker},
+        fixed::{Fixed, MutFixed},
+        layer::{Annotation, Layer, LayerClass, LayerStack, Parent, Payload, Severity},
+        result::Result,
+        slice::ByteSlice,
+        token::Token,
+    };
+    use index::{Entry, Loader, Lru};
+    use io::Input;
+    use profile::Profile;
+    use std::sync::Arc;
+
+    lazy_static! {
+        static ref ROOT_CLASS: LayerClass = LayerClass::builder("[link-1]").build();
+        static ref CHILD_CLASS: LayerClass = LayerClass::builder("child").build();
+        static ref POSITION_ATTR: AttrClass = AttrClass::builder("[link-1].position").build();
+        static ref LENGTH_ATTR: AttrClass = AttrClass::builder("child.length").build();
+        static ref STREAM_CLASS: LayerClass = LayerClass::builder("stream").build();
+        static ref SERIAL_ATTR: AttrClass = AttrClass::builder("child.serial").build();
+    }
+
+    fn frame(index: u32) -> Arc<Frame> {
+        let class = Fixed::new(LayerClass::builder(Token::null()).build());
+        let layer = MutFixed::new(Layer::new(class, ByteSlice::new()));
+        Arc::new(Frame::new(index, layer))
+    }
+
+    #[derive(Debug)]
+    struct TestInput {}
+
+    impl Input for TestInput {
+        fn read(&mut self) -> Result<Vec<MutFixed<Layer>>> {
+            Ok(Vec::new())
+        }
+
+        fn positions(&mut self) -> Vec<u64> {
+            Vec::new()
+        }
+
+        fn read_at(&mut self, position: u64) -> Result<MutFixed<Layer>> {
+            let data = vec![position as u8; position as usize + 1];
+            let mut layer = Layer::new(&*ROOT_CLASS, ByteSlice::from(data));
+            layer.add_attr(Attr::builder(&*POSITION_ATTR).value(position).build());
+            Ok(MutFixed::new(layer))
+        }
+    }
+
+    struct TestWorker {}
+
+    impl Worker for TestWorker {
+        fn decode(
+            &mut self,
+            _ctx: &mut Context,
+            _stack: &LayerStack,
+            parent: &mut Parent,
+        ) -> Result<Status> {
+            let len = parent.data().len() as u64;
+            let mut layer = Layer::new(&*CHILD_CLASS, parent.data());
+            layer.add_attr(Attr::builder(&*LENGTH_ATTR).value(len).build());
+            parent.add_child(layer);
+            Ok(Status::Done)
+        }
+    }
+
+    #[derive(Clone)]
+    struct TestDecoder {}
+
+    impl Decoder for TestDecoder {
+        fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+            Box::new(TestWorker {})
+        }
+
+        fn metadata(&self) -> Metadata {
+            Metadata {
+                exec_type: ExecType::ParallelSync,
+                consumes: vec!["[link-1]".into()],
+                ..Metadata::default()
+            }
+        }
+    }
+
+    /// Numbers the child layers, and adds a stream payload and the child data as payloads.
+    /// A child layer is added too if `child` is set.
+    struct SerialWorker {
+        serial: u64,
+        child: bool,
+    }
+
+    impl Worker for SerialWorker {
+        fn decode(
+            &mut self,
+            _ctx: &mut Context,
+            _stack: &LayerStack,
+            parent: &mut Parent,
+        ) -> Result<Status> {
+            self.serial += 1;
+            parent.add_attr(Attr::builder(&*SERIAL_ATTR).value(self.serial).build());
+            let stream = vec![self.serial as u8; self.serial as usize];
+            parent.add_payload(Payload::new(stream, "@stream:child"));
+            let data = parent.data();
+            parent.add_payload(Payload::new(data, "@data:child"));
+            parent.add_annotation(Annotation::new(Severity::Note, "serial", "child.serial", 0..1));
+            if self.child {
+                parent.add_child(Layer::new(&*STREAM_CLASS, ByteSlice::new()));
+            }
+            Ok(Status::Done)
+        }
+    }
+
+    #[derive(Clone)]
+    struct SerialDecoder {
+        child: bool,
+    }
+
+    impl Decoder for SerialDecoder {
+        fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+            Box::new(SerialWorker {
+                serial: 0,
+                child: self.child,
+            })
+        }
+
+        fn metadata(&self) -> Metadata {
+            Metadata {
+                exec_type: ExecType::SerialSync,
+                consumes: vec!["child".into()],
+                ..Metadata::default()
+            }
+        }
+    }
+
+    /// Decodes the stream payloads into layers.
+    struct StreamWorker {}
+
+    impl Worker for StreamWorker {
+        fn decode(
+            &mut self,
+            _ctx: &mut Context,
+            _stack: &LayerStack,
+            parent: &mut Parent,
+        ) -> Result<Status> {
+            let stream = parent
+                .payloads()
+                .iter()
+                .find(|p| p.id() == Token::from("@stream:child"))
+                .map(|p| p.data())
+                .unwrap();
+            parent.add_child(Layer::new(&*STREAM_CLASS, stream));
+            Ok(Status::Done)
+        }
+    }
+
+    #[derive(Clone)]
+    struct StreamDecoder {}
+
+    impl Decoder for StreamDecoder {
+        fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+            Box::new(StreamWorker {})
+        }
+
+        fn metadata(&self) -> Metadata {
+            Metadata {
+                exec_type: ExecType::ParallelSync,
+                consumes: vec!["@stream:child".into()],
+                ..Metadata::default()
+            }
+        }
+    }
+
+    fn loader(frame_cache: usize) -> Loader {
+        let mut profile = Profile::new();
+        profile.set_frame_cache(frame_cache);
+        profile.add_decoder(DecoderBox::new(TestDecoder {}));
+        let loader = Loader::new(profile);
+        loader.add_input(0, Box::new(TestInput {}));
+        loader
+    }
+
+    fn read_frame(loader: &Loader, index: u32, position: u64) -> Frame {
+        let root = TestInput {}.read_at(position).unwrap();
+        let mut frame = Frame::new(index, root);
+        frame.set_source(Source { input: 0, position });
+        Dispatcher::new(&ExecType::ParallelSync, &loader.profile).process_frame(&mut frame);
+        frame
+    }
+
+    fn dump(frame: &Frame) -> Vec<(Token, Vec<u8>, Vec<String>)> {
+        frame
+            .layers()
+            .iter()
+            .map(|layer| {
+                let attrs = layer
+                    .attrs()
+                    .iter()
+                    .map(|attr| format!("{:?} {:?}", attr.id(), attr.try_get(layer).unwrap()));
+                let payloads = layer
+                    .payloads()
+                    .iter()
+                    .map(|payload| format!("{:?} {:?}", payload.id(), payload.data()));
+                let annotations = layer.annotations().iter().map(|a| format!("{:?}", a));
+                let items = attrs.chain(payloads).chain(annotations).collect();
+                (layer.id(), layer.data().to_vec(), items)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lru() {
+        let mut lru = Lru::new(2);
+        lru.insert(0, frame(0));
+        lru.insert(1, frame(1));
+        assert_eq!(lru.get(0).map(|f| f.index()), Some(0));
+        lru.insert(2, frame(2));
+        assert_eq!(lru.len(), 2);
+        assert!(lru.get(1).is_none());
+        assert_eq!(lru.get(0).map(|f| f.index()), Some(0));
+        assert_eq!(lru.get(2).map(|f| f.index()), Some(2));
+    }
+
+    #[test]
+    fn disabled() {
+        let mut lru = Lru::new(0);
+        lru.insert(0, frame(0));
+        assert_eq!(lru.len(), 0);
+        assert!(lru.get(0).is_none());
+    }
+
+    #[test]
+    fn reload() {
+        let loader = loader(1);
+        let first = read_frame(&loader, 0, 3);
+        let expected = dump(&first);
+        assert_eq!(expected.len(), 2);
+
+        let entries = vec![
+            loader.store(first),
+            loader.store(read_frame(&loader, 1, 5)),
+        ];
+        assert!(entries.iter().all(|e| match e {
+            Entry::Index(_) => true,
+            _ => false,
+        }));
+        assert_eq!(loader.cache.lock().len(), 1);
+
+        let frame = loader.load(0, &entries[0]).unwrap();
+        assert_eq!(frame.index(), 0);
+        assert_eq!(dump(&frame), expected);
+    }
+
+    #[test]
+    fn stateful() {
+        let loader = loader(1);
+        let mut frame = read_frame(&loader, 0, 3);
+        frame.set_stateful();
+        match loader.store(frame) {
+            Entry::Frame(frame) => assert_eq!(frame.index(), 0),
+            Entry::Index(_) => panic!("stateful frames must not be evicted"),
+        }
+    }
+
+    #[test]
+    fn serial() {
+        let serial_loader = |child| {
+            let mut profile = Profile::new();
+            profile.set_frame_cache(1);
+            profile.add_decoder(DecoderBox::new(TestDecoder {}));
+            profile.add_decoder(DecoderBox::new(SerialDecoder { child }));
+            profile.add_decoder(DecoderBox::new(StreamDecoder {}));
+            let loader = Loader::new(profile);
+            loader.add_input(0, Box::new(TestInput {}));
+            loader
+        };
+
+        // The results of SerialSync decoders are kept with the index,
+        // and the payloads are decoded again by ParallelSync decoders.
+        let loader = serial_loader(false);
+        let mut dispatcher = Dispatcher::new(&ExecType::SerialSync, &loader.profile);
+        let mut expected = Vec::new();
+        let mut entries = Vec::new();
+        for (index, position) in [3, 5, 7].iter().enumerate() {
+            let mut frame = read_frame(&loader, index as u32, *position);
+            dispatcher.process_frame(&mut frame);
+            assert!(!frame.is_stateful());
+            expected.push(dump(&frame));
+            entries.push(loader.store(frame));
+        }
+        assert!(entries.iter().all(|e| match e {
+            Entry::Index(index) => index.delta.is_some(),
+            _ => false,
+        }));
+        assert_eq!(expected[1].len(), 3);
+        assert_eq!(expected[1][2], (Token::from("stream"), vec![2, 2], Vec::new()));
+
+        for (index, entry) in entries.iter().enumerate() {
+            let frame = loader.load(index as u32, entry).unwrap();
+            assert_eq!(dump(&frame), expected[index]);
+        }
+
+        // Layers added by SerialSync decoders cannot be replayed.
+        let loader = serial_loader(true);
+        let mut frame = read_frame(&loader, 0, 3);
+        Dispatcher::new(&ExecType::SerialSync, &loader.profile).process_frame(&mut frame);
+        match loader.store(frame) {
+            Entry::Frame(frame) => assert_eq!(frame.index(), 0),
+            Entry::Index(_) => panic!("stateful frames must not be evicted"),
+        }
+    }
+}
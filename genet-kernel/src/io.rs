@@ -9,4 +9,6 @@ pub trait Output: Send + Debug {
 
 pub trait Input: Send + Debug {
     fn read(&mut self) -> Result<Vec<MutFixed<Layer>>>;
+    fn positions(&mut self) -> Vec<u64>;
+    fn read_at(&mut self, position: u64) -> Result<MutFixed<Layer>>;
 }
@@ -10,6 +10,8 @@ extern crate parking_lot;
 extern crate serde;
 extern crate serde_json;
 
+#[macro_use]
+extern crate lazy_static;
 #[macro_use]
 extern crate serde_derive;
 
@@ -19,7 +21,9 @@ pub mod session;
 
 mod array_vec;
 mod decoder;
+mod filter;
 mod frame;
+mod index;
 mod io;
 mod result;
 mod store;
@@ -1,3 +1,4 @@
+use decoder::error;
 use fnv::FnvHashMap;
 use genet_abi::{
     context::Context,
@@ -8,6 +9,7 @@ use genet_abi::{
     token::Token,
     writer::WriterBox,
 };
+use genet_filter::{macros::MacroTable, result::Result as FilterResult, schema::Schema};
 use libloading::Library;
 use num_cpus;
 use std::{fmt, io, mem};
@@ -15,10 +17,13 @@ use std::{fmt, io, mem};
 #[derive(Serialize, Clone, Default)]
 pub struct Profile {
     concurrency: u32,
+    frame_cache: usize,
     decoders: Vec<DecoderBox>,
     readers: Vec<ReaderBox>,
     writers: Vec<WriterBox>,
     config: FnvHashMap<String, String>,
+    #[serde(skip)]
+    macros: MacroTable,
 }
 
 impl fmt::Debug for Profile {
@@ -31,10 +36,12 @@ impl Profile {
     pub fn new() -> Profile {
         Profile {
             concurrency: 4,
+            frame_cache: 0,
             decoders: Vec::new(),
             readers: Vec::new(),
             writers: Vec::new(),
             config: FnvHashMap::default(),
+            macros: MacroTable::new(),
         }
     }
 
@@ -50,6 +57,20 @@ impl Profile {
         self.concurrency
     }
 
+    /// Sets the number of decoded frames kept in memory.
+    ///
+    /// If the value is non-zero, frames from readers supporting random access
+    /// are stored as compact indices and decoded again on demand,
+    /// except for frames with results of SerialSync decoders.
+    /// Otherwise, all decoded frames are kept in memory.
+    pub fn set_frame_cache(&mut self, frame_cache: usize) {
+        self.frame_cache = frame_cache;
+    }
+
+    pub fn frame_cache(&self) -> usize {
+        self.frame_cache
+    }
+
     pub fn get_config(&self, key: &str) -> Option<String> {
         self.config.get(key).map(|s| s.to_string())
     }
@@ -60,10 +81,36 @@ impl Profile {
             .or_insert_with(|| String::from(value));
     }
 
+    /// Defines a filter macro such as `@http`.
+    pub fn define_macro(&mut self, name: &str, expr: &str) -> FilterResult<()> {
+        self.macros.define(name, expr)
+    }
+
+    pub fn macros(&self) -> &MacroTable {
+        &self.macros
+    }
+
+    /// Returns the fields provided by the loaded decoders and readers.
+    pub fn schema(&self) -> Schema {
+        let mut schema = Schema::new();
+        let decoders = self.decoders.iter().map(|d| d.metadata().fields);
+        let readers = self.readers.iter().map(|r| r.metadata().fields);
+        for field in decoders.chain(readers).flatten().chain(error::fields()) {
+            schema.insert(field.id(), field.typ());
+        }
+        schema.insert("_.annotation.severity", "");
+        schema
+    }
+
     pub fn decoders(&self) -> impl Iterator<Item = &DecoderBox> {
         self.decoders.iter()
     }
 
+    #[cfg(test)]
+    pub fn add_decoder(&mut self, decoder: DecoderBox) {
+        self.decoders.push(decoder);
+    }
+
     pub fn readers(&self) -> impl Iterator<Item = &ReaderBox> {
         self.readers.iter()
     }
@@ -1,10 +1,17 @@
 use frame::Frame;
-use genet_abi::{self, fixed::MutFixed, layer::Layer, reader, writer};
-use genet_filter::Filter;
+use genet_abi::{
+    self,
+    fixed::MutFixed,
+    layer::{Layer, Severity},
+    reader,
+    variant::Variant,
+    writer,
+};
+use genet_filter::{context::Context, Filter};
 use io::{Input, Output};
 use profile::Profile;
 use serde::ser::{Serialize, SerializeMap, Serializer};
-use std::{fmt, ops::Range};
+use std::{fmt, ops::Range, sync::Arc};
 use store::{self, Store};
 
 pub struct Session {
@@ -29,10 +36,33 @@ impl Session {
         }
     }
 
-    pub fn frames(&self, range: Range<usize>) -> Vec<*const Frame> {
+    pub fn frames(&self, range: Range<usize>) -> Vec<Arc<Frame>> {
         self.store.frames(range)
     }
 
+    /// Evaluates the expressions against the frames in the range.
+    ///
+    /// Returns a row of values for each frame, in the order of the expressions,
+    /// or None for a frame which could not be read again from its input.
+    /// Slices are copied so that the values outlive the frames.
+    pub fn evaluate(&self, frames: Range<usize>, exprs: &[Filter]) -> Vec<Option<Vec<Variant>>> {
+        self.store
+            .frame_slots(frames)
+            .iter()
+            .map(|frame| {
+                let ctx = Context::new(frame.as_ref()?.layers());
+                let row = exprs
+                    .iter()
+                    .map(|expr| match expr.eval(&ctx) {
+                        Variant::Slice(s) => Variant::Buffer(s.as_ref().to_vec().into_boxed_slice()),
+                        value => value,
+                    })
+                    .collect();
+                Some(row)
+            })
+            .collect()
+    }
+
     pub fn filtered_frames(&self, id: u32, range: Range<usize>) -> Vec<u32> {
         self.store.filtered_frames(id, range)
     }
@@ -41,6 +71,10 @@ impl Session {
         self.store.set_filter(id, filter);
     }
 
+    pub fn refine_filter(&mut self, id: u32, parent: u32, filter: Filter) {
+        self.store.refine_filter(id, parent, filter);
+    }
+
     pub fn create_reader(&mut self, id: &str, arg: &str) -> u32 {
         if let Some(reader) = self
             .profile
@@ -51,6 +85,12 @@ impl Session {
             let ctx = self.profile.context();
             match reader.new_worker(&ctx, arg) {
                 Ok(input) => {
+                    if self.profile.frame_cache() > 0 {
+                        if let Ok(random) = reader.new_worker(&ctx, arg) {
+                            self.store
+                                .set_random_access_input(self.io_cnt, WorkerInput::new(random));
+                        }
+                    }
                     self.store.set_input(self.io_cnt, WorkerInput::new(input));
                     return self.io_cnt;
                 }
@@ -136,6 +176,15 @@ impl store::Callback for StoreCallback {
         self.callback.on_event(Event::FilteredFrames(id, frames));
     }
 
+    fn on_decode_errors_updated(&self, errors: u32) {
+        self.callback.on_event(Event::DecodeErrors(errors));
+    }
+
+    fn on_annotations_updated(&self, severity: Severity, annotations: u32) {
+        self.callback
+            .on_event(Event::Annotations(severity, annotations));
+    }
+
     fn on_output_done(&self, id: u32, error: Option<Box<::std::error::Error + Send>>) {
         self.callback.on_event(Event::Output(id, error));
     }
@@ -154,6 +203,8 @@ pub enum Event {
     Frames(u32),
     AsyncFrames(u32),
     FilteredFrames(u32, u32),
+    DecodeErrors(u32),
+    Annotations(Severity, u32),
     Input(u32, Option<Box<::std::error::Error + Send>>),
     Output(u32, Option<Box<::std::error::Error + Send>>),
     Error(Box<::std::error::Error + Send>),
@@ -207,6 +258,19 @@ impl Serialize for Event {
                 s.serialize_entry("length", &len)?;
                 s.end()
             }
+            Event::DecodeErrors(len) => {
+                let mut s = serializer.serialize_map(Some(2))?;
+                s.serialize_entry("type", "decode_errors")?;
+                s.serialize_entry("length", &len)?;
+                s.end()
+            }
+            Event::Annotations(severity, len) => {
+                let mut s = serializer.serialize_map(Some(3))?;
+                s.serialize_entry("type", "annotations")?;
+                s.serialize_entry("severity", severity.as_str())?;
+                s.serialize_entry("length", &len)?;
+                s.end()
+            }
             Event::Input(id, err) => {
                 let mut s = serializer.serialize_map(Some(3))?;
                 s.serialize_entry("type", "input")?;
@@ -270,4 +334,12 @@ impl Input for WorkerInput {
     fn read(&mut self) -> genet_abi::result::Result<Vec<MutFixed<Layer>>> {
         self.worker.read()
     }
+
+    fn positions(&mut self) -> Vec<u64> {
+        self.worker.positions()
+    }
+
+    fn read_at(&mut self, position: u64) -> genet_abi::result::Result<MutFixed<Layer>> {
+        self.worker.read_at(position)
+    }
 }
@@ -1,15 +1,21 @@
 use array_vec::ArrayVec;
 use crossbeam_channel;
-use decoder::{parallel, serial};
+use decoder::{error, parallel, serial};
+use filter::{self, Task};
 use fnv::FnvHashMap;
-use frame::Frame;
-use genet_abi::{fixed::MutFixed, layer::Layer};
+use frame::{Frame, Source};
+use genet_abi::{
+    fixed::MutFixed,
+    layer::{Layer, Severity},
+};
 use genet_filter::{self, Filter};
+use index::{Entry, Loader};
 use io::{Input, Output};
 use parking_lot::RwLock;
 use profile::Profile;
 use result::Result;
 use std::{
+    collections::BTreeMap,
     fmt,
     ops::Range,
     panic::{self, AssertUnwindSafe},
@@ -24,6 +30,8 @@ pub trait Callback: Send {
     fn on_frames_updated(&self, _frames: u32) {}
     fn on_async_frames_updated(&self, _frames: u32) {}
     fn on_filtered_frames_updated(&self, _id: u32, _frames: u32) {}
+    fn on_decode_errors_updated(&self, _errors: u32) {}
+    fn on_annotations_updated(&self, _severity: Severity, _annotations: u32) {}
     fn on_output_done(&self, _id: u32, _error: Option<Box<::std::error::Error + Send>>) {}
     fn on_input_done(&self, _id: u32, _error: Option<Box<::std::error::Error + Send>>) {}
     fn on_error(&self, _error: Box<::std::error::Error + Send>) {}
@@ -31,10 +39,12 @@ pub trait Callback: Send {
 
 #[derive(Debug)]
 enum Command {
-    PushFrames(Option<u32>, Result<Vec<MutFixed<Layer>>>),
+    PushFrames(Option<u32>, Result<Vec<MutFixed<Layer>>>, Vec<u64>),
     PushSerialFrames(Vec<Frame>),
     StoreFrames(Vec<Frame>),
     SetFilter(u32, Option<Filter>),
+    RefineFilter(u32, u32, Filter),
+    PushFilteredFrames(Task, Vec<u32>),
     PushOutput(u32, Box<Output>, Option<Filter>),
     Close,
 }
@@ -54,14 +64,59 @@ impl fmt::Display for Error {
     }
 }
 
-type FrameStore = Arc<RwLock<ArrayVec<Frame>>>;
+pub type FrameStore = Arc<RwLock<ArrayVec<Entry>>>;
+pub type FrameLoader = Arc<Loader>;
 type FilteredFrameStore = Arc<RwLock<FnvHashMap<u32, Vec<u32>>>>;
 
+/// Returns the frames in the range, with None for the frames
+/// which could not be read again from their inputs.
+///
+/// The entries are copied out first so that the store is not locked
+/// while the frames are read and decoded again.
+pub fn load_frame_slots(
+    frames: &FrameStore,
+    loader: &Loader,
+    range: Range<usize>,
+) -> Vec<Option<Arc<Frame>>> {
+    let entries = {
+        let frames = frames.read();
+        (range.start..range.end.min(frames.len()))
+            .filter_map(|index| frames.get(index).map(|entry| (index as u32, entry.clone())))
+            .collect::<Vec<_>>()
+    };
+    entries
+        .iter()
+        .map(|(index, entry)| loader.load(*index, entry))
+        .collect()
+}
+
+pub fn load_frames(frames: &FrameStore, loader: &Loader, range: Range<usize>) -> Vec<Arc<Frame>> {
+    load_frame_slots(frames, loader, range)
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+pub fn load_frames_at(frames: &FrameStore, loader: &Loader, indices: &[u32]) -> Vec<Arc<Frame>> {
+    let entries = {
+        let frames = frames.read();
+        indices
+            .iter()
+            .filter_map(|index| frames.get(*index as usize).map(|entry| (*index, entry.clone())))
+            .collect::<Vec<_>>()
+    };
+    entries
+        .iter()
+        .filter_map(|(index, entry)| loader.load(*index, entry))
+        .collect()
+}
+
 #[derive(Debug)]
 pub struct Store {
     sender: crossbeam_channel::Sender<Command>,
     ev: EventLoop,
     frames: FrameStore,
+    loader: FrameLoader,
     filtered: FilteredFrameStore,
     inputs: FnvHashMap<u32, InputContext>,
     inputs_trash: Vec<InputContext>,
@@ -70,26 +125,34 @@ pub struct Store {
 impl Store {
     pub fn new<C: 'static + Callback + Clone>(profile: Profile, callback: C) -> Store {
         let frames = Arc::new(RwLock::new(ArrayVec::new()));
+        let loader = Arc::new(Loader::new(profile.clone()));
         let filtered = Arc::new(RwLock::new(FnvHashMap::default()));
-        let (ev, send) = EventLoop::new(profile, callback, frames.clone(), filtered.clone());
+        let (ev, send) = EventLoop::new(
+            profile,
+            callback,
+            frames.clone(),
+            loader.clone(),
+            filtered.clone(),
+        );
         Store {
             sender: send,
             ev,
             frames,
+            loader,
             filtered,
             inputs: FnvHashMap::default(),
             inputs_trash: Vec::new(),
         }
     }
 
-    pub fn frames(&self, range: Range<usize>) -> Vec<*const Frame> {
-        self.frames
-            .read()
-            .iter()
-            .skip(range.start)
-            .take(range.end.saturating_sub(range.start))
-            .map(|f| f as *const Frame)
-            .collect::<Vec<_>>()
+    pub fn frames(&self, range: Range<usize>) -> Vec<Arc<Frame>> {
+        load_frames(&self.frames, &self.loader, range)
+    }
+
+    /// Returns the frames in the range, with None for the frames
+    /// which could not be read again from their inputs.
+    pub fn frame_slots(&self, range: Range<usize>) -> Vec<Option<Arc<Frame>>> {
+        load_frame_slots(&self.frames, &self.loader, range)
     }
 
     pub fn filtered_frames(&self, id: u32, range: Range<usize>) -> Vec<u32> {
@@ -111,11 +174,17 @@ impl Store {
     }
 
     pub fn set_filter(&mut self, id: u32, filter: Option<Filter>) {
-        self.sender.send(Command::SetFilter(id, filter));
+        let _ = self.sender.send(Command::SetFilter(id, filter));
+    }
+
+    /// Sets a filter which is evaluated only over the frames matched by the parent filter.
+    pub fn refine_filter(&mut self, id: u32, parent: u32, filter: Filter) {
+        let _ = self.sender.send(Command::RefineFilter(id, parent, filter));
     }
 
     pub fn push_output<O: 'static + Output>(&mut self, id: u32, output: O, filter: Option<Filter>) {
-        self.sender
+        let _ = self
+            .sender
             .send(Command::PushOutput(id, Box::new(output), filter));
     }
 
@@ -128,12 +197,18 @@ impl Store {
                 match input.read() {
                     Ok(layers) => {
                         if !layers.is_empty() {
-                            sender.send(Command::PushFrames(Some(id), Ok(layers)));
+                            let positions = input.positions();
+                            let _ =
+                                sender.send(Command::PushFrames(Some(id), Ok(layers), positions));
                         }
                     }
                     Err(err) => {
                         let err = Error(err.description().to_string());
-                        sender.send(Command::PushFrames(Some(id), Err(Box::new(err))));
+                        let _ = sender.send(Command::PushFrames(
+                            Some(id),
+                            Err(Box::new(err)),
+                            Vec::new(),
+                        ));
                         break;
                     }
                 }
@@ -148,6 +223,11 @@ impl Store {
         );
     }
 
+    /// Sets an input used to read indexed frames again.
+    pub fn set_random_access_input<I: 'static + Input>(&mut self, id: u32, input: I) {
+        self.loader.add_input(id, Box::new(input));
+    }
+
     pub fn unset_input(&mut self, id: u32) {
         if let Some(mut input) = self.inputs.remove(&id) {
             input.holder = None;
@@ -176,7 +256,7 @@ struct ParallelCallback {
 
 impl parallel::Callback for ParallelCallback {
     fn done(&self, result: Vec<Frame>) {
-        self.sender.send(Command::PushSerialFrames(result));
+        let _ = self.sender.send(Command::PushSerialFrames(result));
     }
 }
 
@@ -187,13 +267,44 @@ struct SerialCallback {
 
 impl serial::Callback for SerialCallback {
     fn done(&self, result: Vec<Frame>) {
-        self.sender.send(Command::StoreFrames(result));
+        let _ = self.sender.send(Command::StoreFrames(result));
+    }
+}
+
+#[derive(Clone)]
+struct FilterCallback {
+    sender: crossbeam_channel::Sender<Command>,
+}
+
+impl filter::Callback for FilterCallback {
+    fn done(&self, task: Task, indices: Vec<u32>) {
+        let _ = self.sender.send(Command::PushFilteredFrames(task, indices));
     }
 }
 
 struct FilterContext {
-    filter: Filter,
+    filter: Arc<Filter>,
+
+    /// The filter tested against the frames after `candidates_end`, including
+    /// the conditions of an explicit parent filter.
+    tail: Arc<Filter>,
+    serial: u64,
+
+    /// The end of the frames sent to the filter pool.
     offset: usize,
+
+    /// The end of the frames merged into the filtered frame store.
+    merged: usize,
+
+    /// Results waiting for the preceding ranges, keyed by the start of the range.
+    pending: BTreeMap<usize, (usize, Vec<u32>)>,
+
+    /// The frames which can match, known up to `candidates_end`.
+    candidates: Vec<u32>,
+    candidates_end: usize,
+
+    /// False if the results are narrowed by an explicit parent filter.
+    exact: bool,
 }
 
 struct EventLoop {
@@ -206,6 +317,7 @@ impl EventLoop {
         profile: Profile,
         callback: C,
         frames: FrameStore,
+        loader: FrameLoader,
         filtered: FilteredFrameStore,
     ) -> (EventLoop, crossbeam_channel::Sender<Command>) {
         let (send, recv) = crossbeam_channel::unbounded();
@@ -226,43 +338,104 @@ impl EventLoop {
                         sender: sender.clone(),
                     },
                 );
+                let mut fpool = filter::Pool::new(
+                    &profile,
+                    &frames,
+                    &loader,
+                    &FilterCallback {
+                        sender: sender.clone(),
+                    },
+                );
+                let mut serial = 0;
                 let mut cnt = 0;
+                let mut errors = 0;
+                let mut annotations = [0; 4];
                 callback.on_frames_updated(0);
                 callback.on_async_frames_updated(0);
                 loop {
-                    if let Some(cmd) = recv.recv() {
+                    if let Ok(cmd) = recv.recv() {
                         match cmd {
-                            Command::PushFrames(id, result) => {
-                                Self::process_input(id, result, &mut cnt, &mut ppool, &callback)
-                            }
+                            Command::PushFrames(id, result, positions) => Self::process_input(
+                                id,
+                                result,
+                                &positions,
+                                &mut cnt,
+                                &mut ppool,
+                                &callback,
+                            ),
                             Command::PushSerialFrames(vec) => {
                                 spool.process(vec);
                             }
-                            Command::StoreFrames(mut vec) => {
+                            Command::StoreFrames(vec) => {
+                                let prev = errors;
+                                let prev_annotations = annotations;
                                 let len = {
                                     let mut frames = frames.write();
                                     for f in vec {
-                                        frames.push(f);
+                                        errors += f
+                                            .layers()
+                                            .iter()
+                                            .filter(|layer| layer.id() == error::token())
+                                            .count();
+                                        for annotation in f.annotations() {
+                                            annotations[annotation.severity() as usize] += 1;
+                                        }
+                                        frames.push(loader.store(f));
                                     }
                                     frames.len()
                                 };
                                 callback.on_frames_updated(len as u32);
                                 callback.on_async_frames_updated(len as u32);
+                                if errors > prev {
+                                    callback.on_decode_errors_updated(errors as u32);
+                                }
+                                for severity in Severity::all() {
+                                    let count = annotations[*severity as usize];
+                                    if count > prev_annotations[*severity as usize] {
+                                        callback.on_annotations_updated(*severity, count as u32);
+                                    }
+                                }
                             }
-                            Command::SetFilter(id, filter) => Self::process_push_filter(
-                                id,
-                                filter,
-                                &filtered,
-                                &mut filter_map,
-                                &callback,
-                            ),
-                            Command::PushOutput(id, output, filter) => {
-                                Self::process_output(id, output, &filter, &frames, &callback)
+                            Command::SetFilter(id, filter) => {
+                                serial += 1;
+                                Self::process_push_filter(
+                                    id,
+                                    serial,
+                                    filter,
+                                    None,
+                                    &filtered,
+                                    &mut filter_map,
+                                    &callback,
+                                )
+                            }
+                            Command::RefineFilter(id, parent, filter) => {
+                                serial += 1;
+                                Self::process_push_filter(
+                                    id,
+                                    serial,
+                                    Some(filter),
+                                    Some(parent),
+                                    &filtered,
+                                    &mut filter_map,
+                                    &callback,
+                                )
+                            }
+                            Command::PushFilteredFrames(task, indices) => {
+                                Self::process_filtered_frames(
+                                    task,
+                                    indices,
+                                    &filtered,
+                                    &mut filter_map,
+                                    &callback,
+                                )
                             }
+                            Command::PushOutput(id, output, filter) => Self::process_output(
+                                id, output, &filter, &frames, &loader, &callback,
+                            ),
                             Command::Close => return,
                         }
                     }
-                    Self::process_filters(&frames, &filtered, &mut filter_map, &callback);
+                    Self::process_filters(&frames, &mut filter_map, &mut fpool);
                 }
             }));
             if let Err(err) = result {
@@ -289,6 +462,7 @@ impl EventLoop {
     fn process_input(
         id: Option<u32>,
         result: Result<Vec<MutFixed<Layer>>>,
+        positions: &[u64],
         cnt: &mut u32,
         pool: &mut parallel::Pool,
         callback: &Callback,
@@ -300,12 +474,20 @@ impl EventLoop {
                         callback.on_input_done(id, None);
                     }
                 } else {
+                    let indexed = positions.len() == layers.len();
                     let frames = layers
                         .into_iter()
                         .enumerate()
                         .map(|(i, root)| {
                             let index = *cnt + i as u32;
-                            Frame::new(index, root)
+                            let mut frame = Frame::new(index, root);
+                            if let (Some(input), true) = (id, indexed) {
+                                frame.set_source(Source {
+                                    input,
+                                    position: positions[i],
+                                });
+                            }
+                            frame
                         })
                         .collect::<Vec<_>>();
                     *cnt += frames.len() as u32;
@@ -325,18 +507,19 @@ impl EventLoop {
         output: Box<Output>,
         filter: &Option<Filter>,
         frames: &FrameStore,
+        loader: &FrameLoader,
         callback: &Callback,
     ) {
-        let frames = frames.read();
+        let end = frames.read().len();
         let mut offset = 0;
         {
             let mut output = output;
-            while offset < frames.len() {
-                let len = OUTPUT_BLOCK_SIZE.min(frames.len() - offset);
-                let frames = frames
+            while offset < end {
+                let len = OUTPUT_BLOCK_SIZE.min(end - offset);
+                let loaded = load_frames(frames, loader, offset..offset + len);
+                let frames = loaded
                     .iter()
-                    .skip(offset)
-                    .take(len)
+                    .map(|frame| frame.as_ref())
                     .filter(|frame| {
                         let ctx = genet_filter::context::Context::new(frame.layers());
                         filter.as_ref().map_or(true, |f| f.test(&ctx))
@@ -360,17 +543,43 @@ impl EventLoop {
 
     fn process_push_filter(
         id: u32,
+        serial: u64,
         filter: Option<Filter>,
+        parent: Option<u32>,
         filtered: &FilteredFrameStore,
         filter_map: &mut FnvHashMap<u32, FilterContext>,
         callback: &Callback,
     ) {
         if let Some(filter) = filter {
+            let filter = Arc::new(filter);
+            let (candidates, candidates_end, tail) = if let Some(parent) = parent {
+                if let Some(pctx) = filter_map.get(&parent) {
+                    let frames = filtered.read().get(&parent).cloned().unwrap_or_default();
+                    (frames, pctx.merged, Arc::new(pctx.tail.and(&filter)))
+                } else {
+                    filter_map.remove(&id);
+                    filtered.write().remove(&id);
+                    let err = Error(format!("unknown parent filter: {}", parent));
+                    callback.on_error(Box::new(err));
+                    return;
+                }
+            } else {
+                let (candidates, candidates_end) =
+                    Self::find_candidates(&filter, filtered, filter_map);
+                (candidates, candidates_end, filter.clone())
+            };
             filter_map.insert(
                 id,
                 FilterContext {
+                    filter,
+                    tail,
+                    serial,
                     offset: 0,
-                    filter: filter.clone(),
+                    merged: 0,
+                    pending: BTreeMap::new(),
+                    candidates,
+                    candidates_end,
+                    exact: parent.is_none(),
                 },
             );
             callback.on_filtered_frames_updated(id, 0);
@@ -380,52 +589,103 @@ impl EventLoop {
         filtered.write().remove(&id);
     }
 
+    /// Returns the results of the existing filter with the fewest results that
+    /// the new filter refines.
+    ///
+    /// Frames after the returned offset have not been tested by the parent yet.
+    fn find_candidates(
+        filter: &Filter,
+        filtered: &FilteredFrameStore,
+        filter_map: &FnvHashMap<u32, FilterContext>,
+    ) -> (Vec<u32>, usize) {
+        let filtered = filtered.read();
+        let parent = filter_map
+            .iter()
+            .filter(|(_, fctx)| fctx.exact && filter.refines(&fctx.filter))
+            .min_by_key(|(pid, _)| filtered.get(pid).map_or(0, |v| v.len()));
+        match parent {
+            Some((pid, fctx)) => {
+                let frames = filtered.get(pid).map_or_else(Vec::new, |v| v.clone());
+                (frames, fctx.merged)
+            }
+            None => (Vec::new(), 0),
+        }
+    }
+
     fn process_filters(
         frames: &FrameStore,
-        filtered: &FilteredFrameStore,
         filter_map: &mut FnvHashMap<u32, FilterContext>,
-        callback: &Callback,
+        pool: &mut filter::Pool,
     ) {
+        let len = frames.read().len();
         for (id, fctx) in filter_map.iter_mut() {
-            loop {
-                let (mut indices, end) = {
-                    let frames = frames.read();
-                    let mut indices = frames
-                        .iter()
-                        .skip(fctx.offset)
-                        .take(MAX_FILTER_SIZE)
-                        .filter_map(|frame| {
-                            let ctx = genet_filter::context::Context::new(frame.layers());
-                            if fctx.filter.test(&ctx) {
-                                Some(frame.index())
-                            } else {
-                                None
-                            }
-                        })
-                        .collect::<Vec<_>>();
-                    fctx.offset = frames.len().min(fctx.offset + MAX_FILTER_SIZE);
-                    (indices, fctx.offset >= frames.len())
+            while fctx.offset < fctx.candidates_end {
+                let size = fctx.candidates.len().min(MAX_FILTER_SIZE);
+                let indices = fctx.candidates.drain(..size).collect::<Vec<_>>();
+                let end = match (fctx.candidates.first(), indices.last()) {
+                    (Some(_), Some(last)) => *last as usize + 1,
+                    _ => fctx.candidates_end,
                 };
-                if !indices.is_empty() {
-                    let len = {
-                        let mut filtered = filtered.write();
-                        let mut frames = filtered.entry(*id).or_insert_with(Vec::new);
-                        frames.append(&mut indices);
-                        frames.len()
-                    };
-                    callback.on_filtered_frames_updated(*id, len as u32);
-                }
-                if end {
-                    break;
-                }
+                pool.process(Task {
+                    id: *id,
+                    serial: fctx.serial,
+                    filter: fctx.filter.clone(),
+                    range: fctx.offset..end,
+                    indices: Some(indices),
+                });
+                fctx.offset = end;
+            }
+            while fctx.offset < len {
+                let end = len.min(fctx.offset + MAX_FILTER_SIZE);
+                pool.process(Task {
+                    id: *id,
+                    serial: fctx.serial,
+                    filter: fctx.tail.clone(),
+                    range: fctx.offset..end,
+                    indices: None,
+                });
+                fctx.offset = end;
+            }
+        }
+    }
+
+    fn process_filtered_frames(
+        task: Task,
+        indices: Vec<u32>,
+        filtered: &FilteredFrameStore,
+        filter_map: &mut FnvHashMap<u32, FilterContext>,
+        callback: &Callback,
+    ) {
+        let fctx = match filter_map.get_mut(&task.id) {
+            Some(fctx) => fctx,
+            None => return,
+        };
+        if fctx.serial != task.serial {
+            return;
+        }
+        fctx.pending
+            .insert(task.range.start, (task.range.end, indices));
+
+        let mut updated = false;
+        let len = {
+            let mut filtered = filtered.write();
+            let frames = filtered.entry(task.id).or_insert_with(Vec::new);
+            while let Some((end, mut indices)) = fctx.pending.remove(&fctx.merged) {
+                updated = updated || !indices.is_empty();
+                frames.append(&mut indices);
+                fctx.merged = end;
             }
+            frames.len()
+        };
+        if updated {
+            callback.on_filtered_frames_updated(task.id, len as u32);
         }
     }
 }
 
 impl Drop for EventLoop {
     fn drop(&mut self) {
-        self.sender.send(Command::Close);
+        let _ = self.sender.send(Command::Close);
         self.handle.take().unwrap().join().expect("failed to join");
     }
 }
@@ -438,14 +698,155 @@ impl fmt::Debug for EventLoop {
 
 #[cfg(test)]
 mod tests {
+    use filter::Task;
+    use fnv::FnvHashMap;
+    use genet_abi::{
+        attr::{Attr, AttrClass},
+        fixed::MutFixed,
+        layer::{Layer, LayerClass},
+        result::Result,
+        slice::ByteSlice,
+    };
     use genet_filter::Filter;
+    use io::Input;
+    use crossbeam_channel;
+    use parking_lot::{Mutex, RwLock};
     use profile::Profile;
-    use store::{Callback, Store};
+    use std::{
+        collections::BTreeMap,
+        sync::Arc,
+        thread,
+        time::{Duration, Instant},
+    };
+    use store::{Callback, Error, EventLoop, FilterContext, Store, MAX_FILTER_SIZE};
+
+    lazy_static! {
+        static ref LINK_CLASS: LayerClass = LayerClass::builder("link").build();
+        static ref VALUE_ATTR: AttrClass = AttrClass::builder("link.value").build();
+    }
 
     #[derive(Clone)]
     struct TestCallback {}
     impl Callback for TestCallback {}
 
+    /// Reads the number of frames received from the channel, with `link.value`
+    /// set to the frame index.
+    #[derive(Debug)]
+    struct TestInput {
+        next: u64,
+        recv: crossbeam_channel::Receiver<u64>,
+    }
+
+    impl TestInput {
+        fn new() -> (TestInput, crossbeam_channel::Sender<u64>) {
+            let (send, recv) = crossbeam_channel::unbounded();
+            (TestInput { next: 0, recv }, send)
+        }
+    }
+
+    impl Input for TestInput {
+        fn read(&mut self) -> Result<Vec<MutFixed<Layer>>> {
+            let len = self.recv.recv_timeout(Duration::from_millis(10)).unwrap_or(0);
+            let layers = (self.next..self.next + len)
+                .map(|i| {
+                    let mut layer = Layer::new(&*LINK_CLASS, ByteSlice::new());
+                    layer.add_attr(Attr::builder(&*VALUE_ATTR).value(i).build());
+                    MutFixed::new(layer)
+                })
+                .collect();
+            self.next += len;
+            Ok(layers)
+        }
+
+        fn positions(&mut self) -> Vec<u64> {
+            Vec::new()
+        }
+
+        fn read_at(&mut self, _position: u64) -> Result<MutFixed<Layer>> {
+            unreachable!()
+        }
+    }
+
+    /// Reads `len` frames once, and fails to read the second one again.
+    #[derive(Debug)]
+    struct RandomInput {
+        len: u64,
+        read: bool,
+    }
+
+    impl Input for RandomInput {
+        fn read(&mut self) -> Result<Vec<MutFixed<Layer>>> {
+            if self.read {
+                thread::sleep(Duration::from_millis(10));
+                return Ok(Vec::new());
+            }
+            let layers = (0..self.len).map(|i| self.read_at(i).unwrap()).collect();
+            self.read = true;
+            Ok(layers)
+        }
+
+        fn positions(&mut self) -> Vec<u64> {
+            (0..self.len).collect()
+        }
+
+        fn read_at(&mut self, position: u64) -> Result<MutFixed<Layer>> {
+            if self.read && position == 1 {
+                return Err(Error("unavailable".to_string()).into());
+            }
+            let mut layer = Layer::new(&*LINK_CLASS, ByteSlice::new());
+            layer.add_attr(Attr::builder(&*VALUE_ATTR).value(position).build());
+            Ok(MutFixed::new(layer))
+        }
+    }
+
+    #[derive(Clone)]
+    struct ErrorCallback {
+        errors: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Callback for ErrorCallback {
+        fn on_error(&self, error: Box<::std::error::Error + Send>) {
+            self.errors.lock().push(error.to_string());
+        }
+    }
+
+    /// Waits until the filter has merged `len` frames or a timeout elapses.
+    fn wait_filtered(store: &Store, id: u32, len: usize) -> Vec<u32> {
+        let start = Instant::now();
+        loop {
+            let frames = store.filtered_frames(id, 0..usize::MAX);
+            if frames.len() >= len || start.elapsed() > Duration::from_secs(30) {
+                return frames;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn filter_context(serial: u64) -> FilterContext {
+        let filter = Arc::new(Filter::compile("true").unwrap());
+        FilterContext {
+            filter: filter.clone(),
+            tail: filter,
+            serial,
+            offset: 0,
+            merged: 0,
+            pending: BTreeMap::new(),
+            candidates: Vec::new(),
+            candidates_end: 0,
+            exact: true,
+        }
+    }
+
+    fn task(serial: u64, start: usize, end: usize) -> Task {
+        Task {
+            id: 0,
+            serial,
+            filter: Arc::new(Filter::compile("true").unwrap()),
+            range: start..end,
+            indices: None,
+        }
+    }
+
     #[test]
     fn drop() {
         let profile = Profile::new();
@@ -460,4 +861,132 @@ mod tests {
         assert_eq!(store.frames(100..0).len(), 0);
         assert_eq!(store.filtered_frames(0, 100..0).len(), 0);
     }
+
+    #[test]
+    fn frame_slots() {
+        let mut profile = Profile::new();
+        profile.set_frame_cache(1);
+        let mut store = Store::new(profile, TestCallback {});
+        store.set_random_access_input(1, RandomInput { len: 3, read: true });
+        store.set_input(1, RandomInput { len: 3, read: false });
+
+        let start = Instant::now();
+        while store.frames(0..3).len() < 2 && start.elapsed() < Duration::from_secs(30) {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // A row is returned for each frame, even if it fails to be read again.
+        let slots = store.frame_slots(0..10);
+        assert_eq!(slots.len(), 3);
+        assert_eq!(slots[0].as_ref().map(|f| f.index()), Some(0));
+        assert!(slots[1].is_none());
+        assert_eq!(slots[2].as_ref().map(|f| f.index()), Some(2));
+    }
+
+    #[test]
+    fn merge_filtered_frames() {
+        let filtered = Arc::new(RwLock::new(FnvHashMap::default()));
+        let mut filter_map = FnvHashMap::default();
+        filter_map.insert(0, filter_context(1));
+        let merged = |filtered: &Arc<RwLock<FnvHashMap<u32, Vec<u32>>>>| {
+            filtered.read().get(&0).cloned().unwrap_or_default()
+        };
+
+        EventLoop::process_filtered_frames(
+            task(1, 200, 300),
+            vec![210, 299],
+            &filtered,
+            &mut filter_map,
+            &TestCallback {},
+        );
+        assert_eq!(merged(&filtered), Vec::<u32>::new());
+
+        EventLoop::process_filtered_frames(
+            task(1, 100, 200),
+            vec![150],
+            &filtered,
+            &mut filter_map,
+            &TestCallback {},
+        );
+        assert_eq!(merged(&filtered), Vec::<u32>::new());
+
+        EventLoop::process_filtered_frames(
+            task(0, 0, 100),
+            vec![7],
+            &filtered,
+            &mut filter_map,
+            &TestCallback {},
+        );
+        assert_eq!(merged(&filtered), Vec::<u32>::new());
+
+        EventLoop::process_filtered_frames(
+            task(1, 0, 100),
+            vec![1, 50],
+            &filtered,
+            &mut filter_map,
+            &TestCallback {},
+        );
+        assert_eq!(merged(&filtered), vec![1, 50, 150, 210, 299]);
+        assert_eq!(filter_map[&0].merged, 300);
+        assert!(filter_map[&0].pending.is_empty());
+    }
+
+    #[test]
+    fn filter_order() {
+        let len = MAX_FILTER_SIZE as u32 * 3 + 100;
+        let mut profile = Profile::new();
+        profile.set_concurrency(4);
+        let mut store = Store::new(profile, TestCallback {});
+        let (input, send) = TestInput::new();
+        store.set_input(1, input);
+        store.set_filter(0, Filter::compile("link.value % 3 == 0").ok());
+        send.send(u64::from(len)).unwrap();
+
+        let expected = (0..len).filter(|i| i % 3 == 0).collect::<Vec<_>>();
+        assert_eq!(wait_filtered(&store, 0, expected.len()), expected);
+    }
+
+    #[test]
+    fn refine_filter() {
+        let mut store = Store::new(Profile::new(), TestCallback {});
+        let (input, send) = TestInput::new();
+        store.set_input(1, input);
+        store.set_filter(0, Filter::compile("link.value % 2 == 1").ok());
+        send.send(100).unwrap();
+        assert_eq!(wait_filtered(&store, 0, 50).len(), 50);
+
+        // The frames already filtered by the parent are taken from its results.
+        store.refine_filter(1, 0, Filter::compile("link.value % 3 == 0").unwrap());
+        let expected = (0..100).filter(|i| i % 6 == 3).collect::<Vec<_>>();
+        assert_eq!(wait_filtered(&store, 1, expected.len()), expected);
+
+        // The new frames must match both the parent and the child.
+        send.send(100).unwrap();
+        let expected = (0..200).filter(|i| i % 6 == 3).collect::<Vec<_>>();
+        assert_eq!(wait_filtered(&store, 1, expected.len()), expected);
+
+        // A filter refined automatically matches its parent by itself.
+        store.set_filter(2, Filter::compile("link.value % 2 == 1 && link.value > 150").ok());
+        let expected = (151..200).filter(|i| i % 2 == 1).collect::<Vec<_>>();
+        assert_eq!(wait_filtered(&store, 2, expected.len()), expected);
+    }
+
+    #[test]
+    fn refine_unknown_parent() {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let mut store = Store::new(
+            Profile::new(),
+            ErrorCallback {
+                errors: errors.clone(),
+            },
+        );
+        let (input, send) = TestInput::new();
+        store.set_input(1, input);
+        send.send(100).unwrap();
+        store.refine_filter(1, 42, Filter::compile("link.value < 10").unwrap());
+        store.set_filter(2, Filter::compile("link.value < 10").ok());
+        assert_eq!(wait_filtered(&store, 2, 10).len(), 10);
+        assert_eq!(store.filtered_frames(1, 0..usize::MAX), Vec::<u32>::new());
+        assert_eq!(*errors.lock(), vec!["unknown parent filter: 42".to_string()]);
+    }
 }
@@ -1,13 +1,17 @@
 extern crate genet_abi;
+extern crate genet_filter;
 extern crate genet_kernel;
 extern crate libloading;
 
 extern crate genet_sdk;
 
+use genet_abi::variant::Variant;
+use genet_filter::Filter;
 use genet_kernel::{
     profile::Profile,
     session::{Callback, Event, Session},
 };
+use std::{sync::mpsc, time::Duration};
 
 #[derive(Clone)]
 struct SessionCallback {}
@@ -61,3 +65,64 @@ fn session() {
 
     assert_eq!(session.create_reader("app.genet.reader.test-input", ""), 1);
 }
+
+fn library(name: &str) -> String {
+    let libdir = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf()
+        .join("../examples");
+
+    let file = if cfg!(target_os = "macos") {
+        format!("lib{}.dylib", name)
+    } else if cfg!(target_os = "windows") {
+        format!("{}.dll", name)
+    } else {
+        format!("lib{}.so", name)
+    };
+    libdir.join(file).to_str().unwrap().to_string()
+}
+
+#[derive(Clone)]
+struct ErrorCallback {
+    sender: mpsc::Sender<u32>,
+}
+
+impl Callback for ErrorCallback {
+    fn on_event(&self, event: Event) {
+        if let Event::DecodeErrors(errors) = event {
+            let _ = self.sender.send(errors);
+        }
+    }
+}
+
+#[test]
+fn decode_error() {
+    let mut profile = Profile::new();
+    for name in &["eth", "reader", "error"] {
+        profile
+            .load_library(&library(name))
+            .expect("failed to load dylib");
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let mut session = Session::new(profile, ErrorCallback { sender });
+    assert_eq!(session.create_reader("app.genet.reader.test-input", ""), 1);
+
+    let errors = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert!(errors > 0);
+
+    let exprs: Vec<Filter> = ["_.error", "_.error.decoder", "_.error.offset"]
+        .iter()
+        .map(|expr| Filter::compile(expr).unwrap())
+        .collect();
+    let values = session.evaluate(0..1, &exprs);
+    let values = [values[0].clone().unwrap()];
+    assert_eq!(values[0][0], Variant::String("malformed packet".into()));
+    assert_eq!(
+        values[0][1],
+        Variant::String("app.genet.decoder.test-error".into())
+    );
+    assert_eq!(values[0][2], Variant::UInt64(0));
+}
@@ -542,6 +542,16 @@ impl Env {
         }
     }
 
+    pub fn get_element<'env>(&self, object: &Value, index: u32) -> Result<&'env Value> {
+        unsafe {
+            let mut result: *const Value = mem::uninitialized();
+            match napi_get_element(self, object, index, &mut result) {
+                Status::Ok => Ok(&*result),
+                s => Err(s),
+            }
+        }
+    }
+
     pub fn set_named_property(&self, object: &Value, utf8name: &str, value: &Value) -> Result<()> {
         unsafe {
             let name = CString::new(utf8name).unwrap();
@@ -1164,6 +1174,13 @@ extern "C" {
         value: *const Value,
     ) -> Status;
 
+    fn napi_get_element(
+        env: *const Env,
+        object: *const Value,
+        index: u32,
+        result: *mut *const Value,
+    ) -> Status;
+
     fn napi_set_named_property(
         env: *const Env,
         object: *const Value,
@@ -75,6 +75,12 @@ class Session extends EventEmitter {
         case 'async_frames':
           this._status.asyncFrames = event.length
           break
+        case 'decode_errors':
+          this._status.decodeErrors = event.length
+          break
+        case 'annotations':
+          this._status.annotations[event.severity] = event.length
+          break
         case 'filtered_frames':
           this._status.filters[Token.string(event.id)] =
             { frames: event.length }
@@ -92,6 +98,8 @@ class Session extends EventEmitter {
       filters: {},
       frames: 0,
       asyncFrames: 0,
+      decodeErrors: 0,
+      annotations: {},
       stream: false,
     }
   }
@@ -106,6 +114,14 @@ class Session extends EventEmitter {
       .map((frame) => new Frame(frame))
   }
 
+  evaluate (start, end, exprs) {
+    return this._sess.evaluate(start, end, exprs)
+  }
+
+  validateFilter (filter) {
+    return this._sess.validateFilter(filter)
+  }
+
   filteredFrames (id, start, end) {
     return this._sess.filteredFrames(Token.get(id), start, end)
   }
@@ -121,6 +137,10 @@ class Session extends EventEmitter {
     }
   }
 
+  refineFilter (id, parent, filter) {
+    this._sess.refineFilter(Token.get(id), Token.get(parent), filter)
+  }
+
   createReader (id, arg = {}) {
     const handle = this._sess.createReader(id, JSON.stringify(arg))
     if (handle === 0) {
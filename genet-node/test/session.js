@@ -95,6 +95,37 @@ describe('Session', () => {
             sess.close()
         })
     })
+  describe('#evaluate', () => {
+        it('should return an array', function() {
+            const profile = new Profile()
+            const sess = new Session(profile)
+            assert(Array.isArray(sess.evaluate(0, 0, ['link.length'])))
+            sess.close()
+        })
+        it('should throw for wrong arguments', () => {
+            const profile = new Profile()
+            const sess = new Session(profile)
+            assert.throws(() => sess.evaluate(), TypeError)
+            assert.throws(() => sess.evaluate(0, 0), TypeError)
+            assert.throws(() => sess.evaluate(0, 0, ['(']), Error)
+            sess.close()
+        })
+    })
+  describe('#validateFilter', () => {
+        it('should report unknown fields', function() {
+            const profile = new Profile()
+            const sess = new Session(profile)
+            assert.deepEqual(sess.validateFilter('ipv4.srcc'), ['unknown field: ipv4.srcc'])
+            sess.close()
+        })
+        it('should throw for wrong arguments', () => {
+            const profile = new Profile()
+            const sess = new Session(profile)
+            assert.throws(() => sess.validateFilter(), TypeError)
+            assert.throws(() => sess.validateFilter('('), Error)
+            sess.close()
+        })
+    })
   describe('#filteredFrames', () => {
         it('should return an array', function() {
             const profile = new Profile()
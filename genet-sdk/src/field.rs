@@ -0,0 +1,3 @@
+//! Field.
+
+pub use genet_abi::field::Field;
@@ -2,4 +2,22 @@
 //!
 //! Type Layer represents a layer of a protocol stack.
 
-pub use genet_abi::layer::{Layer, LayerClass, LayerClassBuilder, LayerStack, Parent, Payload};
+use error::Error;
+use result::Result;
+use token::Token;
+use variant::{Value, Variant};
+
+pub use genet_abi::layer::{
+    Annotation, Layer, LayerClass, LayerClassBuilder, LayerStack, Parent, Payload, Severity,
+};
+
+/// Returns the value of the attribute of the layer converted into `T`.
+pub fn attr_value<T>(layer: &Layer, id: Token) -> Result<T>
+where
+    Variant: Value<T>,
+{
+    let attr = layer
+        .attr(id)
+        .ok_or_else(|| Error::new("missing attribute"))?;
+    Ok(attr.try_get(layer)?.try_into()?)
+}
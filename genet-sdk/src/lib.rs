@@ -10,12 +10,14 @@ pub mod cast;
 pub mod context;
 pub mod decoder;
 pub mod error;
+pub mod field;
 pub mod file;
 pub mod fixed;
 pub mod helper;
 pub mod layer;
 pub mod prelude;
 pub mod reader;
+pub mod reassembly;
 pub mod result;
 pub mod slice;
 pub mod token;
@@ -3,9 +3,10 @@
 pub use attr::{Attr, AttrClass};
 pub use cast::Map;
 pub use context::Context;
+pub use field::Field;
 pub use file::FileType;
 pub use fixed::Fixed;
-pub use layer::{Layer, LayerClass, LayerStack, Parent, Payload};
+pub use layer::{Annotation, Layer, LayerClass, LayerStack, Parent, Payload, Severity};
 pub use result::Result;
 pub use slice::{ByteSlice, TryGet};
 pub use token::Token;
@@ -0,0 +1,314 @@
+//! Fragment reassembly.
+//!
+//! Type Reassembler buffers the fragments of datagrams, such as IPv4 and IPv6
+//! fragments, until the whole payload of each datagram is available.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+};
+
+/// A reassembled datagram.
+#[derive(Debug, PartialEq)]
+pub struct Reassembled {
+    /// The indices of the frames carrying the fragments.
+    pub frames: Vec<u32>,
+    pub payload: Vec<u8>,
+
+    /// True if overlapping fragments disagree.
+    pub overlap: bool,
+}
+
+/// Fragments of datagrams identified by keys of type `K`.
+///
+/// Incomplete datagrams are discarded after the timeout.
+#[derive(Debug)]
+pub struct Reassembler<K> {
+    timeout: u64,
+    map: HashMap<K, Fragments>,
+}
+
+impl<K: Eq + Hash + Clone> Reassembler<K> {
+    /// Creates a reassembler with the timeout in seconds.
+    pub fn new(timeout: u64) -> Reassembler<K> {
+        Reassembler {
+            timeout,
+            map: HashMap::new(),
+        }
+    }
+
+    /// Adds a fragment received at the timestamp in seconds, and returns the datagram
+    /// if it is complete.
+    pub fn put(
+        &mut self,
+        key: K,
+        timestamp: u64,
+        frame: u32,
+        offset: usize,
+        data: &[u8],
+        last: bool,
+    ) -> Option<Reassembled> {
+        let timeout = self.timeout;
+        self.map
+            .retain(|_, fragments| fragments.timestamp() + timeout >= timestamp);
+
+        let assembled = {
+            let fragments = self
+                .map
+                .entry(key.clone())
+                .or_insert_with(|| Fragments::new(timestamp));
+            fragments.put(frame, offset, data, last);
+            fragments.assemble()
+        };
+
+        assembled.map(|(payload, overlap)| Reassembled {
+            frames: self.map.remove(&key).unwrap().frames,
+            payload,
+            overlap,
+        })
+    }
+
+    /// Returns the number of incomplete datagrams.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns true if there are no incomplete datagrams.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+/// A buffer of fragments keyed by their offsets.
+///
+/// The data is copied because fragments outlive the frames carrying them.
+#[derive(Debug)]
+pub struct Fragments {
+    timestamp: u64,
+    frames: Vec<u32>,
+    data: BTreeMap<usize, Vec<u8>>,
+    len: Option<usize>,
+    overlap: bool,
+}
+
+impl Fragments {
+    /// Creates an empty buffer received at the timestamp.
+    pub fn new(timestamp: u64) -> Fragments {
+        Fragments {
+            timestamp,
+            frames: Vec::new(),
+            data: BTreeMap::new(),
+            len: None,
+            overlap: false,
+        }
+    }
+
+    /// Returns the timestamp of the first fragment.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Returns the indices of the frames carrying the fragments.
+    pub fn frames(&self) -> &[u32] {
+        &self.frames
+    }
+
+    /// Adds a fragment. The last fragment determines the length of the payload.
+    ///
+    /// For a fragment at the same offset as an existing one, only the bytes beyond
+    /// the existing one are added.
+    pub fn put(&mut self, frame: u32, offset: usize, data: &[u8], last: bool) {
+        self.frames.push(frame);
+        let slice = self.data.entry(offset).or_default();
+        let len = slice.len().min(data.len());
+        if slice[..len] != data[..len] {
+            self.overlap = true;
+        }
+        if data.len() > slice.len() {
+            let len = slice.len();
+            slice.extend_from_slice(&data[len..]);
+        }
+        if last && self.len.is_none() {
+            self.len = Some(offset + data.len());
+        }
+    }
+
+    /// Returns the payload and whether overlapping fragments disagree,
+    /// or None if the payload is incomplete.
+    ///
+    /// Overlapping bytes are taken from the fragment with the lowest offset.
+    pub fn assemble(&self) -> Option<(Vec<u8>, bool)> {
+        let len = self.len?;
+        let mut buf = Vec::with_capacity(len);
+        let mut overlap = self.overlap;
+        for (offset, data) in &self.data {
+            let offset = *offset;
+            let filled = buf.len();
+            if offset > filled {
+                return None;
+            }
+            let end = len.min(offset + data.len());
+            if end <= offset {
+                continue;
+            }
+            let covered = filled.min(end);
+            if covered > offset && buf[offset..covered] != data[..covered - offset] {
+                overlap = true;
+            }
+            if end > filled {
+                buf.extend_from_slice(&data[filled - offset..end - offset]);
+            }
+        }
+        if buf.len() < len {
+            None
+        } else {
+            Some((buf, overlap))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reassembly::{Fragments, Reassembled, Reassembler};
+
+    #[test]
+    fn in_order() {
+        let mut f = Fragments::new(10);
+        f.put(0, 0, b"abcd", false);
+        assert_eq!(f.assemble(), None);
+        f.put(1, 4, b"efgh", false);
+        assert_eq!(f.assemble(), None);
+        f.put(2, 8, b"ij", true);
+        assert_eq!(f.assemble(), Some((b"abcdefghij".to_vec(), false)));
+        assert_eq!(f.frames(), &[0, 1, 2]);
+        assert_eq!(f.timestamp(), 10);
+    }
+
+    #[test]
+    fn out_of_order() {
+        let mut f = Fragments::new(0);
+        f.put(0, 8, b"ij", true);
+        assert_eq!(f.assemble(), None);
+        f.put(1, 0, b"abcd", false);
+        assert_eq!(f.assemble(), None);
+        f.put(2, 4, b"efgh", false);
+        assert_eq!(f.assemble(), Some((b"abcdefghij".to_vec(), false)));
+    }
+
+    #[test]
+    fn overlap() {
+        let mut f = Fragments::new(0);
+        f.put(0, 0, b"abcdef", false);
+        f.put(1, 4, b"efgh", true);
+        assert_eq!(f.assemble(), Some((b"abcdefgh".to_vec(), false)));
+
+        let mut f = Fragments::new(0);
+        f.put(0, 0, b"abcdef", false);
+        f.put(1, 4, b"XYgh", true);
+        assert_eq!(f.assemble(), Some((b"abcdefgh".to_vec(), true)));
+
+        // Duplicates at the same offset keep the first fragment.
+        let mut f = Fragments::new(0);
+        f.put(0, 0, b"abcd", false);
+        f.put(1, 0, b"abcd", false);
+        f.put(2, 4, b"ef", true);
+        assert_eq!(f.assemble(), Some((b"abcdef".to_vec(), false)));
+    }
+
+    #[test]
+    fn conflicting_duplicate() {
+        let mut f = Fragments::new(0);
+        f.put(0, 0, b"abcd", false);
+        f.put(1, 0, b"WXYZ", false);
+        f.put(2, 4, b"ef", true);
+        assert_eq!(f.assemble(), Some((b"abcdef".to_vec(), true)));
+    }
+
+    #[test]
+    fn longer_duplicate() {
+        let mut f = Fragments::new(0);
+        f.put(0, 0, b"ab", false);
+        f.put(1, 0, b"abcd", false);
+        f.put(2, 4, b"ef", true);
+        assert_eq!(f.assemble(), Some((b"abcdef".to_vec(), false)));
+
+        // A shorter duplicate neither truncates the fragment nor conflicts.
+        let mut f = Fragments::new(0);
+        f.put(0, 0, b"abcd", false);
+        f.put(1, 0, b"ab", false);
+        f.put(2, 4, b"ef", true);
+        assert_eq!(f.assemble(), Some((b"abcdef".to_vec(), false)));
+
+        let mut f = Fragments::new(0);
+        f.put(0, 0, b"ab", false);
+        f.put(1, 0, b"aXcd", false);
+        f.put(2, 4, b"ef", true);
+        assert_eq!(f.assemble(), Some((b"abcdef".to_vec(), true)));
+    }
+
+    #[test]
+    fn gap() {
+        let mut f = Fragments::new(0);
+        f.put(0, 0, b"abcd", false);
+        f.put(1, 6, b"gh", true);
+        assert_eq!(f.assemble(), None);
+    }
+
+    #[test]
+    fn trailing_data() {
+        let mut f = Fragments::new(0);
+        f.put(0, 0, b"abcd", false);
+        f.put(1, 2, b"cd", true);
+        f.put(2, 4, b"efgh", false);
+        assert_eq!(f.assemble(), Some((b"abcd".to_vec(), false)));
+    }
+
+    #[test]
+    fn reassembler() {
+        let mut r = Reassembler::new(30);
+        assert_eq!(r.put(1, 100, 0, 0, b"abcd", false), None);
+        assert_eq!(r.put(2, 100, 1, 0, b"ABCD", false), None);
+        assert_eq!(r.len(), 2);
+        assert_eq!(
+            r.put(1, 110, 2, 4, b"ef", true),
+            Some(Reassembled {
+                frames: vec![0, 2],
+                payload: b"abcdef".to_vec(),
+                overlap: false,
+            })
+        );
+        assert_eq!(r.len(), 1);
+        assert_eq!(
+            r.put(2, 130, 3, 4, b"EF", true),
+            Some(Reassembled {
+                frames: vec![1, 3],
+                payload: b"ABCDEF".to_vec(),
+                overlap: false,
+            })
+        );
+        assert!(r.is_empty());
+    }
+
+    #[test]
+    fn timeout() {
+        let mut r = Reassembler::new(30);
+        assert_eq!(r.put(1, 100, 0, 0, b"abcd", false), None);
+
+        // The first fragment expired, so the datagram starts over.
+        assert_eq!(r.put(1, 131, 1, 4, b"ef", true), None);
+        assert_eq!(r.len(), 1);
+        assert_eq!(
+            r.put(1, 140, 2, 0, b"abcd", false),
+            Some(Reassembled {
+                frames: vec![1, 2],
+                payload: b"abcdef".to_vec(),
+                overlap: false,
+            })
+        );
+
+        // Other datagrams expire when a fragment arrives.
+        assert_eq!(r.put(2, 200, 3, 0, b"abcd", false), None);
+        assert_eq!(r.put(3, 231, 4, 0, b"abcd", false), None);
+        assert_eq!(r.len(), 1);
+    }
+}
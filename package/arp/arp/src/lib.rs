@@ -72,8 +72,18 @@ impl Decoder for ArpDecoder {
     }
 
     fn metadata(&self) -> Metadata {
+        let mut fields = ARP_CLASS.fields();
+        let hw = (0..=0xffff).filter_map(get_hw);
+        let proto = (0..=0xffff).filter_map(get_proto);
+        for (typ, src, dst) in hw.chain(proto) {
+            fields.extend(vec![Field::from(typ), Field::from(src), Field::from(dst)]);
+        }
+        fields.extend((0..=0xffff).filter_map(get_op).map(Field::from));
         Metadata {
+            id: "app.genet.decoder.arp".into(),
             exec_type: ExecType::ParallelSync,
+            consumes: vec![String::from("@data:arp")],
+            fields,
             ..Metadata::default()
         }
     }
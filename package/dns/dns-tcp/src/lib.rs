@@ -0,0 +1,160 @@
+extern crate genet_sdk;
+
+use genet_sdk::{decoder::*, layer::attr_value, prelude::*};
+use std::collections::HashMap;
+
+const PORT: u16 = 53;
+
+struct DnsTcpWorker {
+    map: HashMap<u64, Vec<u8>>,
+}
+
+impl DnsTcpWorker {
+    fn new() -> DnsTcpWorker {
+        DnsTcpWorker {
+            map: HashMap::new(),
+        }
+    }
+}
+
+impl Worker for DnsTcpWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() != token!("tcp") {
+            return Ok(Status::Skip);
+        }
+
+        let slices: Vec<ByteSlice> = parent
+            .payloads()
+            .iter()
+            .filter(|p| p.id() == token!("@stream:tcp"))
+            .map(|p| p.data())
+            .collect();
+
+        if slices.is_empty() {
+            return Ok(Status::Skip);
+        }
+
+        // Segment payloads are typed by profile configs such as tcp.port.5353=dns-tcp,
+        // and untyped ones fall back to the well-known port.
+        let typ = parent.payloads()[0].id();
+        let dns = if typ == token!("@data:dns-tcp") {
+            true
+        } else if typ == token!("") {
+            let src: u16 = attr_value(parent, token!("tcp.src"))?;
+            let dst: u16 = attr_value(parent, token!("tcp.dst"))?;
+            src == PORT || dst == PORT
+        } else {
+            false
+        };
+        if !dns {
+            return Ok(Status::Done);
+        }
+
+        let stream: u64 = attr_value(parent, token!("tcp.stream"))?;
+
+        // Messages are prefixed with a two byte length field and may span segments.
+        let buf = self.map.entry(stream).or_default();
+        for slice in slices {
+            buf.extend_from_slice(&slice);
+        }
+        while buf.len() >= 2 {
+            let len = (buf[0] as usize) << 8 | buf[1] as usize;
+            if buf.len() < 2 + len {
+                break;
+            }
+            let message: Vec<u8> = buf.drain(..2 + len).skip(2).collect();
+            parent.add_payload(Payload::new(message, "@data:dns"));
+        }
+
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct DnsTcpDecoder {}
+
+impl Decoder for DnsTcpDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(DnsTcpWorker::new())
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.dns-tcp".into(),
+            exec_type: ExecType::SerialSync,
+            consumes: vec![String::from("@stream:tcp")],
+            ..Metadata::default()
+        }
+    }
+}
+
+genet_decoders!(DnsTcpDecoder {});
+
+#[cfg(test)]
+mod tests {
+    use genet_sdk::{cast, decoder::*, prelude::*};
+    use DnsTcpWorker;
+
+    def_layer_class!(TCP_CLASS, "tcp",
+        header: attr!(&SRC_ATTR, range: 0..2),
+        header: attr!(&DST_ATTR, range: 2..4)
+    );
+
+    def_attr_class!(SRC_ATTR, "tcp.src", cast: cast::UInt16BE());
+
+    def_attr_class!(DST_ATTR, "tcp.dst", cast: cast::UInt16BE());
+
+    def_attr_class!(STREAM_ATTR, "tcp.stream");
+
+    /// Decodes a segment to the port with the segment payload type and the stream data,
+    /// and returns the DNS messages.
+    fn segment(worker: &mut DnsTcpWorker, port: u16, typ: &str, data: &[u8]) -> Vec<Vec<u8>> {
+        let mut tcp = Layer::new(&TCP_CLASS, vec![0x04, 0xd2, (port >> 8) as u8, port as u8]);
+        tcp.add_attr(attr!(&STREAM_ATTR, value: 0u64));
+        tcp.add_payload(Payload::new(data.to_vec(), typ));
+        tcp.add_payload(Payload::new(data.to_vec(), "@stream:tcp"));
+
+        let mut ctx = Context::default();
+        let mut parent = Parent::from_mut_ref(&mut tcp);
+        worker
+            .decode(&mut ctx, &LayerStack::from_slice(&[]), &mut parent)
+            .unwrap();
+        parent
+            .payloads()
+            .iter()
+            .filter(|p| p.id() == token!("@data:dns"))
+            .map(|p| p.data().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn messages() {
+        let mut w = DnsTcpWorker::new();
+        assert_eq!(
+            segment(&mut w, 53, "", b"\x00\x02ab\x00\x03cde"),
+            vec![b"ab".to_vec(), b"cde".to_vec()]
+        );
+
+        // A message spanning segments.
+        assert!(segment(&mut w, 53, "", b"\x00").is_empty());
+        assert!(segment(&mut w, 53, "", b"\x04fo").is_empty());
+        assert_eq!(segment(&mut w, 53, "", b"oo\x00"), vec![b"fooo".to_vec()]);
+        assert_eq!(segment(&mut w, 53, "", b"\x01x"), vec![b"x".to_vec()]);
+    }
+
+    #[test]
+    fn ports() {
+        let mut w = DnsTcpWorker::new();
+        assert!(segment(&mut w, 80, "", b"\x00\x02ab").is_empty());
+        assert!(segment(&mut w, 53, "@data:foo", b"\x00\x02ab").is_empty());
+        assert_eq!(
+            segment(&mut w, 5353, "@data:dns-tcp", b"\x00\x02ab"),
+            vec![b"ab".to_vec()]
+        );
+    }
+}
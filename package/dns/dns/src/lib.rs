@@ -0,0 +1,485 @@
+extern crate genet_sdk;
+
+use genet_sdk::{cast, decoder::*, error::Error, prelude::*};
+
+/// Maximum number of compression pointers to follow in a single name.
+const MAX_POINTERS: usize = 64;
+
+struct DnsWorker {}
+
+impl Worker for DnsWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let payloads: Vec<ByteSlice> = parent
+            .payloads()
+            .iter()
+            .filter(|p| p.id() == token!("@data:dns"))
+            .map(|p| p.data())
+            .collect();
+
+        if payloads.is_empty() {
+            return Ok(Status::Skip);
+        }
+
+        for data in payloads {
+            let layer = decode_message(data)?;
+            parent.add_child(layer);
+        }
+        Ok(Status::Done)
+    }
+}
+
+fn decode_message(data: ByteSlice) -> Result<Layer> {
+    let mut layer = Layer::new(&DNS_CLASS, data);
+    let opcode = OPCODE_ATTR_HEADER.try_get(&layer)?.try_into()?;
+    if let Some(attr) = get_opcode(opcode) {
+        layer.add_attr(attr!(attr, bit_range: 2 1..5));
+    }
+    let rcode = RCODE_ATTR_HEADER.try_get(&layer)?.try_into()?;
+    if let Some(attr) = get_rcode(rcode) {
+        layer.add_attr(attr!(attr, bit_range: 3 4..8));
+    }
+
+    let questions: usize = QDCOUNT_ATTR_HEADER.try_get(&layer)?.try_into()?;
+    let mut offset = 12;
+    for _ in 0..questions {
+        let (name, end) = read_name(&data, offset)?;
+        data.try_get(end..end + 4)?;
+        layer.add_attr(attr!(&QUESTION_ATTR, range: offset..end + 4));
+        layer.add_attr(attr!(&QNAME_ATTR, range: offset..end, value: name.into_boxed_str()));
+        layer.add_attr(attr!(&QTYPE_ATTR, range: end..end + 2));
+        layer.add_attr(attr!(&QCLASS_ATTR, range: end + 2..end + 4));
+        let qtype = u64::from(data.try_get(end)?) << 8 | u64::from(data.try_get(end + 1)?);
+        if let Some(attr) = get_qtype(qtype) {
+            layer.add_attr(attr!(attr, range: end..end + 2));
+        }
+        offset = end + 4;
+    }
+
+    let answers: usize = ANCOUNT_ATTR_HEADER.try_get(&layer)?.try_into()?;
+    let authorities: usize = NSCOUNT_ATTR_HEADER.try_get(&layer)?.try_into()?;
+    let additionals: usize = ARCOUNT_ATTR_HEADER.try_get(&layer)?.try_into()?;
+    for (count, section) in &[
+        (answers, answer()),
+        (authorities, authority()),
+        (additionals, additional()),
+    ] {
+        for _ in 0..*count {
+            offset = read_record(&mut layer, section, offset)?;
+        }
+    }
+
+    Ok(layer)
+}
+
+/// Reads a possibly compressed domain name at `offset`.
+///
+/// Returns the name and the end offset of its encoding in the message.
+fn read_name(data: &ByteSlice, offset: usize) -> Result<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut pos = offset;
+    let mut end = None;
+    let mut pointers = 0;
+    loop {
+        let len = data.try_get(pos)? as usize;
+        if len == 0 {
+            end.get_or_insert(pos + 1);
+            break;
+        } else if len & 0xc0 == 0xc0 {
+            let ptr = (len & 0x3f) << 8 | data.try_get(pos + 1)? as usize;
+            end.get_or_insert(pos + 2);
+            pointers += 1;
+            if pointers > MAX_POINTERS {
+                return Err(Error::new("too many compression pointers").into());
+            }
+            pos = ptr;
+        } else if len & 0xc0 != 0 {
+            return Err(Error::new("invalid label type").into());
+        } else {
+            let label = data.try_get(pos + 1..pos + 1 + len)?;
+            labels.push(String::from_utf8_lossy(&label).into_owned());
+            pos += 1 + len;
+        }
+    }
+    let name = if labels.is_empty() {
+        String::from(".")
+    } else {
+        labels.join(".")
+    };
+    Ok((name, end.unwrap_or(pos)))
+}
+
+/// Reads a resource record at `offset` and returns the offset of the next record.
+fn read_record(layer: &mut Layer, section: &Section, offset: usize) -> Result<usize> {
+    let data = layer.data();
+    let (name, pos) = read_name(&data, offset)?;
+    let rtype = (data.try_get(pos)? as u16) << 8 | data.try_get(pos + 1)? as u16;
+    let rdlen = (data.try_get(pos + 8)? as usize) << 8 | data.try_get(pos + 9)? as usize;
+    let rdata = pos + 10;
+    let end = rdata + rdlen;
+    data.try_get(rdata..end)?;
+
+    layer.add_attr(attr!(section.attr, range: offset..end));
+    layer.add_attr(attr!(section.name, range: offset..pos, value: name.into_boxed_str()));
+    layer.add_attr(attr!(section.typ, range: pos..pos + 2));
+    layer.add_attr(attr!(section.class, range: pos + 2..pos + 4));
+    layer.add_attr(attr!(section.ttl, range: pos + 4..pos + 8));
+    layer.add_attr(attr!(section.length, range: pos + 8..pos + 10));
+    layer.add_attr(attr!(section.data, range: rdata..end));
+
+    match rtype {
+        // A
+        1 if rdlen == 4 => layer.add_attr(attr!(section.a, range: rdata..end)),
+        // AAAA
+        28 if rdlen == 16 => layer.add_attr(attr!(section.aaaa, range: rdata..end)),
+        // NS, CNAME, PTR
+        2 | 5 | 12 => {
+            let (domain, _) = read_name(&data, rdata)?;
+            layer.add_attr(attr!(section.domain,
+                range: rdata..end,
+                value: domain.into_boxed_str()
+            ));
+        }
+        // MX
+        15 if rdlen > 2 => {
+            let (exchange, _) = read_name(&data, rdata + 2)?;
+            layer.add_attr(attr!(section.preference, range: rdata..rdata + 2));
+            layer.add_attr(attr!(section.exchange,
+                range: rdata + 2..end,
+                value: exchange.into_boxed_str()
+            ));
+        }
+        _ => {}
+    }
+    Ok(end)
+}
+
+/// Attribute classes of the resource records in a section.
+struct Section {
+    attr: &'static AttrClass,
+    name: &'static AttrClass,
+    typ: &'static AttrClass,
+    class: &'static AttrClass,
+    ttl: &'static AttrClass,
+    length: &'static AttrClass,
+    data: &'static AttrClass,
+    a: &'static AttrClass,
+    aaaa: &'static AttrClass,
+    domain: &'static AttrClass,
+    preference: &'static AttrClass,
+    exchange: &'static AttrClass,
+}
+
+impl Section {
+    fn fields(&self) -> Vec<Field> {
+        [
+            self.attr,
+            self.name,
+            self.typ,
+            self.class,
+            self.ttl,
+            self.length,
+            self.data,
+            self.a,
+            self.aaaa,
+            self.domain,
+            self.preference,
+            self.exchange,
+        ]
+            .iter()
+            .map(|attr| Field::from(*attr))
+            .collect()
+    }
+}
+
+fn answer() -> Section {
+    Section {
+        attr: attr_class_lazy!("dns.answer", typ: "@nested", value: true),
+        name: attr_class_lazy!("dns.answer.name"),
+        typ: attr_class_lazy!("dns.answer.type", cast: cast::UInt16BE()),
+        class: attr_class_lazy!("dns.answer.class", cast: cast::UInt16BE()),
+        ttl: attr_class_lazy!("dns.answer.ttl", cast: cast::UInt32BE()),
+        length: attr_class_lazy!("dns.answer.length", cast: cast::UInt16BE()),
+        data: attr_class_lazy!("dns.answer.data", cast: cast::ByteSlice()),
+        a: attr_class_lazy!("dns.answer.a", typ: "@ipv4:addr", cast: cast::ByteSlice()),
+        aaaa: attr_class_lazy!("dns.answer.aaaa", typ: "@ipv6:addr", cast: cast::ByteSlice()),
+        domain: attr_class_lazy!("dns.answer.domain"),
+        preference: attr_class_lazy!("dns.answer.preference", cast: cast::UInt16BE()),
+        exchange: attr_class_lazy!("dns.answer.exchange"),
+    }
+}
+
+fn authority() -> Section {
+    Section {
+        attr: attr_class_lazy!("dns.authority", typ: "@nested", value: true),
+        name: attr_class_lazy!("dns.authority.name"),
+        typ: attr_class_lazy!("dns.authority.type", cast: cast::UInt16BE()),
+        class: attr_class_lazy!("dns.authority.class", cast: cast::UInt16BE()),
+        ttl: attr_class_lazy!("dns.authority.ttl", cast: cast::UInt32BE()),
+        length: attr_class_lazy!("dns.authority.length", cast: cast::UInt16BE()),
+        data: attr_class_lazy!("dns.authority.data", cast: cast::ByteSlice()),
+        a: attr_class_lazy!("dns.authority.a", typ: "@ipv4:addr", cast: cast::ByteSlice()),
+        aaaa: attr_class_lazy!("dns.authority.aaaa", typ: "@ipv6:addr", cast: cast::ByteSlice()),
+        domain: attr_class_lazy!("dns.authority.domain"),
+        preference: attr_class_lazy!("dns.authority.preference", cast: cast::UInt16BE()),
+        exchange: attr_class_lazy!("dns.authority.exchange"),
+    }
+}
+
+fn additional() -> Section {
+    Section {
+        attr: attr_class_lazy!("dns.additional", typ: "@nested", value: true),
+        name: attr_class_lazy!("dns.additional.name"),
+        typ: attr_class_lazy!("dns.additional.type", cast: cast::UInt16BE()),
+        class: attr_class_lazy!("dns.additional.class", cast: cast::UInt16BE()),
+        ttl: attr_class_lazy!("dns.additional.ttl", cast: cast::UInt32BE()),
+        length: attr_class_lazy!("dns.additional.length", cast: cast::UInt16BE()),
+        data: attr_class_lazy!("dns.additional.data", cast: cast::ByteSlice()),
+        a: attr_class_lazy!("dns.additional.a", typ: "@ipv4:addr", cast: cast::ByteSlice()),
+        aaaa: attr_class_lazy!("dns.additional.aaaa", typ: "@ipv6:addr", cast: cast::ByteSlice()),
+        domain: attr_class_lazy!("dns.additional.domain"),
+        preference: attr_class_lazy!("dns.additional.preference", cast: cast::UInt16BE()),
+        exchange: attr_class_lazy!("dns.additional.exchange"),
+    }
+}
+
+#[derive(Clone)]
+struct DnsDecoder {}
+
+impl Decoder for DnsDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(DnsWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        let mut fields = DNS_CLASS.fields();
+        fields.extend(
+            [&*QUESTION_ATTR, &*QNAME_ATTR, &*QTYPE_ATTR, &*QCLASS_ATTR]
+                .iter()
+                .map(|attr| Field::from(*attr)),
+        );
+        fields.extend(answer().fields());
+        fields.extend(authority().fields());
+        fields.extend(additional().fields());
+        fields.extend((0..=0xf).filter_map(get_opcode).map(Field::from));
+        fields.extend((0..=0xf).filter_map(get_rcode).map(Field::from));
+        fields.extend((0..=0xffff).filter_map(get_qtype).map(Field::from));
+        Metadata {
+            id: "app.genet.decoder.dns".into(),
+            exec_type: ExecType::ParallelSync,
+            consumes: vec![String::from("@data:dns")],
+            fields,
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(DNS_CLASS, "dns",
+    header: attr!(&ID_ATTR, range: 0..2),
+    header: attr!(&FLAGS_ATTR, range: 2..4),
+    header: attr!(&FLAGS_QR_ATTR, bit_range: 2 0..1),
+    header: &OPCODE_ATTR_HEADER,
+    header: attr!(&FLAGS_AA_ATTR, bit_range: 2 5..6),
+    header: attr!(&FLAGS_TC_ATTR, bit_range: 2 6..7),
+    header: attr!(&FLAGS_RD_ATTR, bit_range: 2 7..8),
+    header: attr!(&FLAGS_RA_ATTR, bit_range: 3 0..1),
+    header: &RCODE_ATTR_HEADER,
+    header: &QDCOUNT_ATTR_HEADER,
+    header: &ANCOUNT_ATTR_HEADER,
+    header: &NSCOUNT_ATTR_HEADER,
+    header: &ARCOUNT_ATTR_HEADER
+);
+
+def_attr!(OPCODE_ATTR_HEADER, &OPCODE_ATTR, bit_range: 2 1..5);
+
+def_attr!(RCODE_ATTR_HEADER, &RCODE_ATTR, bit_range: 3 4..8);
+
+def_attr!(QDCOUNT_ATTR_HEADER, &QDCOUNT_ATTR, range: 4..6);
+
+def_attr!(ANCOUNT_ATTR_HEADER, &ANCOUNT_ATTR, range: 6..8);
+
+def_attr!(NSCOUNT_ATTR_HEADER, &NSCOUNT_ATTR, range: 8..10);
+
+def_attr!(ARCOUNT_ATTR_HEADER, &ARCOUNT_ATTR, range: 10..12);
+
+def_attr_class!(ID_ATTR, "dns.id", cast: cast::UInt16BE());
+
+def_attr_class!(FLAGS_ATTR, "dns.flags",
+    typ: "@flags",
+    cast: cast::UInt16BE()
+);
+
+def_attr_class!(FLAGS_QR_ATTR, "dns.flags.qr",
+    cast: cast::UInt8().map(|v| v & 0b1000_0000 != 0)
+);
+
+def_attr_class!(OPCODE_ATTR, "dns.opcode",
+    typ: "@enum",
+    cast: cast::UInt8().map(|v| (v >> 3) & 0b0000_1111)
+);
+
+def_attr_class!(FLAGS_AA_ATTR, "dns.flags.aa",
+    cast: cast::UInt8().map(|v| v & 0b0000_0100 != 0)
+);
+
+def_attr_class!(FLAGS_TC_ATTR, "dns.flags.tc",
+    cast: cast::UInt8().map(|v| v & 0b0000_0010 != 0)
+);
+
+def_attr_class!(FLAGS_RD_ATTR, "dns.flags.rd",
+    cast: cast::UInt8().map(|v| v & 0b0000_0001 != 0)
+);
+
+def_attr_class!(FLAGS_RA_ATTR, "dns.flags.ra",
+    cast: cast::UInt8().map(|v| v & 0b1000_0000 != 0)
+);
+
+def_attr_class!(RCODE_ATTR, "dns.rcode",
+    typ: "@enum",
+    cast: cast::UInt8().map(|v| v & 0b0000_1111)
+);
+
+def_attr_class!(QDCOUNT_ATTR, "dns.questions", cast: cast::UInt16BE());
+
+def_attr_class!(ANCOUNT_ATTR, "dns.answers", cast: cast::UInt16BE());
+
+def_attr_class!(NSCOUNT_ATTR, "dns.authorities", cast: cast::UInt16BE());
+
+def_attr_class!(ARCOUNT_ATTR, "dns.additionals", cast: cast::UInt16BE());
+
+def_attr_class!(QUESTION_ATTR, "dns.question",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(QNAME_ATTR, "dns.qname");
+
+def_attr_class!(QTYPE_ATTR, "dns.qtype",
+    typ: "@enum",
+    cast: cast::UInt16BE()
+);
+
+def_attr_class!(QCLASS_ATTR, "dns.qclass", cast: cast::UInt16BE());
+
+fn get_opcode(val: u64) -> Option<&'static AttrClass> {
+    match val {
+        0 => Some(attr_class_lazy!("dns.opcode.query", typ: "@novalue", value: true)),
+        1 => Some(attr_class_lazy!("dns.opcode.inverseQuery", typ: "@novalue", value: true)),
+        2 => Some(attr_class_lazy!("dns.opcode.status", typ: "@novalue", value: true)),
+        4 => Some(attr_class_lazy!("dns.opcode.notify", typ: "@novalue", value: true)),
+        5 => Some(attr_class_lazy!("dns.opcode.update", typ: "@novalue", value: true)),
+        _ => None,
+    }
+}
+
+fn get_rcode(val: u64) -> Option<&'static AttrClass> {
+    match val {
+        0 => Some(attr_class_lazy!("dns.rcode.noError", typ: "@novalue", value: true)),
+        1 => Some(attr_class_lazy!("dns.rcode.formatError", typ: "@novalue", value: true)),
+        2 => Some(attr_class_lazy!("dns.rcode.serverFailure", typ: "@novalue", value: true)),
+        3 => Some(attr_class_lazy!("dns.rcode.nameError", typ: "@novalue", value: true)),
+        4 => Some(attr_class_lazy!("dns.rcode.notImplemented", typ: "@novalue", value: true)),
+        5 => Some(attr_class_lazy!("dns.rcode.refused", typ: "@novalue", value: true)),
+        _ => None,
+    }
+}
+
+fn get_qtype(val: u64) -> Option<&'static AttrClass> {
+    match val {
+        1 => Some(attr_class_lazy!("dns.qtype.a", typ: "@novalue", value: true)),
+        2 => Some(attr_class_lazy!("dns.qtype.ns", typ: "@novalue", value: true)),
+        5 => Some(attr_class_lazy!("dns.qtype.cname", typ: "@novalue", value: true)),
+        6 => Some(attr_class_lazy!("dns.qtype.soa", typ: "@novalue", value: true)),
+        12 => Some(attr_class_lazy!("dns.qtype.ptr", typ: "@novalue", value: true)),
+        15 => Some(attr_class_lazy!("dns.qtype.mx", typ: "@novalue", value: true)),
+        16 => Some(attr_class_lazy!("dns.qtype.txt", typ: "@novalue", value: true)),
+        28 => Some(attr_class_lazy!("dns.qtype.aaaa", typ: "@novalue", value: true)),
+        33 => Some(attr_class_lazy!("dns.qtype.srv", typ: "@novalue", value: true)),
+        41 => Some(attr_class_lazy!("dns.qtype.opt", typ: "@novalue", value: true)),
+        255 => Some(attr_class_lazy!("dns.qtype.any", typ: "@novalue", value: true)),
+        _ => None,
+    }
+}
+
+genet_decoders!(DnsDecoder {});
+
+#[cfg(test)]
+mod tests {
+    use genet_sdk::{layer::attr_value, prelude::*};
+    use {decode_message, read_name};
+
+    #[test]
+    fn name() {
+        let data = ByteSlice::from(&b"\x03www\x07example\x03com\x00\x03ftp\xc0\x04"[..]);
+        assert_eq!(read_name(&data, 0).unwrap(), ("www.example.com".into(), 17));
+        assert_eq!(read_name(&data, 17).unwrap(), ("ftp.example.com".into(), 23));
+        assert_eq!(read_name(&data, 16).unwrap(), (".".into(), 17));
+    }
+
+    #[test]
+    fn compression_loop() {
+        // A pointer to itself.
+        let data = ByteSlice::from(&b"\xc0\x00"[..]);
+        assert!(read_name(&data, 0).is_err());
+
+        // Pointers to each other after a label.
+        let data = ByteSlice::from(&b"\x01a\xc0\x04\x01b\xc0\x00"[..]);
+        assert!(read_name(&data, 0).is_err());
+
+        // A pointer out of range.
+        let data = ByteSlice::from(&b"\x01a\xc0\x10"[..]);
+        assert!(read_name(&data, 0).is_err());
+
+        // A truncated label.
+        let data = ByteSlice::from(&b"\x05ab"[..]);
+        assert!(read_name(&data, 0).is_err());
+    }
+
+    #[test]
+    fn response() {
+        let data = ByteSlice::from(
+            &b"\x12\x34\x81\x80\x00\x01\x00\x02\x00\x00\x00\x00\
+               \x07example\x03com\x00\x00\x01\x00\x01\
+               \xc0\x0c\x00\x05\x00\x01\x00\x00\x0e\x10\x00\x06\x03www\xc0\x0c\
+               \xc0\x0c\x00\x01\x00\x01\x00\x00\x0e\x10\x00\x04\x5d\xb8\xd8\x22"[..],
+        );
+        let layer = decode_message(data).unwrap();
+        assert_eq!(attr_value::<u64>(&layer, token!("dns.id")).unwrap(), 0x1234);
+        assert_eq!(
+            attr_value::<String>(&layer, token!("dns.qname")).unwrap(),
+            "example.com"
+        );
+        assert_eq!(
+            attr_value::<String>(&layer, token!("dns.answer.name")).unwrap(),
+            "example.com"
+        );
+        assert_eq!(
+            attr_value::<String>(&layer, token!("dns.answer.domain")).unwrap(),
+            "www.example.com"
+        );
+        assert_eq!(
+            attr_value::<Vec<u8>>(&layer, token!("dns.answer.a")).unwrap(),
+            vec![93, 184, 216, 34]
+        );
+        let answers = layer
+            .attrs()
+            .iter()
+            .filter(|attr| attr.id() == token!("dns.answer"))
+            .count();
+        assert_eq!(answers, 2);
+    }
+
+    #[test]
+    fn truncated() {
+        let data = ByteSlice::from(
+            &b"\x12\x34\x81\x80\x00\x01\x00\x01\x00\x00\x00\x00\
+               \x07example\x03com\x00\x00\x01\x00\x01\
+               \xc0\x0c\x00\x01\x00\x01\x00\x00\x0e\x10\x00\x04\x5d\xb8"[..],
+        );
+        assert!(decode_message(data).is_err());
+    }
+}
@@ -42,8 +42,15 @@ impl Decoder for EthDecoder {
     }
 
     fn metadata(&self) -> Metadata {
+        let mut fields = ETH_CLASS.fields();
+        fields.push(Field::from(&*LEN_ATTR));
+        fields.push(Field::from(&*TYPE_ATTR));
+        fields.extend((0..=0xffff).filter_map(get_type).map(|(_, attr)| Field::from(attr)));
         Metadata {
+            id: "app.genet.decoder.eth".into(),
             exec_type: ExecType::ParallelSync,
+            consumes: vec![String::from("[link-1]")],
+            fields,
             ..Metadata::default()
         }
     }
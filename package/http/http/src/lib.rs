@@ -0,0 +1,741 @@
+extern crate genet_sdk;
+
+use genet_sdk::{decoder::*, error::Error, layer::attr_value, prelude::*};
+use std::{
+    collections::{HashMap, VecDeque},
+    ops::Range,
+    str,
+};
+
+/// Streams whose header does not end within this size are not treated as HTTP.
+const MAX_HEADER_SIZE: usize = 64 * 1024;
+
+/// Streams buffering an incomplete message larger than this are discarded.
+const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+enum StartLine {
+    Request {
+        method: Range<usize>,
+        uri: Range<usize>,
+        version: Range<usize>,
+    },
+    Response {
+        version: Range<usize>,
+        status: Range<usize>,
+        reason: Range<usize>,
+    },
+}
+
+enum Framing {
+    Length(usize),
+    Chunked,
+    Close,
+}
+
+struct Message {
+    data: Vec<u8>,
+    start: StartLine,
+    headers: Vec<(Range<usize>, Range<usize>)>,
+    body: Range<usize>,
+    chunked: Option<Vec<u8>>,
+}
+
+#[derive(Default)]
+struct Stream {
+    buf: Vec<u8>,
+    ignored: bool,
+    fin: bool,
+    closed: bool,
+}
+
+impl Stream {
+    /// Returns the next complete message in the buffer.
+    ///
+    /// `method` is the method of the oldest unanswered request on the connection,
+    /// which decides whether a response has a body.
+    fn next(&mut self, method: Option<&[u8]>) -> Option<Message> {
+        if self.ignored || self.closed {
+            self.buf.clear();
+            return None;
+        }
+
+        let line_end = if let Some(pos) = find(&self.buf, b"\r\n") {
+            pos
+        } else {
+            if self.buf.len() > MAX_HEADER_SIZE {
+                self.ignore();
+            }
+            return None;
+        };
+        let start = if let Some(start) = parse_start_line(&self.buf[..line_end]) {
+            start
+        } else {
+            self.ignore();
+            return None;
+        };
+        let head_len = if let Some(pos) = find(&self.buf, b"\r\n\r\n") {
+            pos + 4
+        } else {
+            if self.buf.len() > MAX_HEADER_SIZE {
+                self.ignore();
+            }
+            return None;
+        };
+
+        let mut headers = Vec::new();
+        let mut pos = line_end + 2;
+        while pos < head_len - 2 {
+            let end = pos + find(&self.buf[pos..], b"\r\n").unwrap();
+            if let Some(colon) = self.buf[pos..end].iter().position(|c| *c == b':') {
+                let value = trim(&self.buf, pos + colon + 1..end);
+                headers.push((pos..pos + colon, value));
+            }
+            pos = end + 2;
+        }
+
+        let framing = if let Some(framing) = self.framing(&start, &headers, method) {
+            framing
+        } else {
+            self.ignore();
+            return None;
+        };
+
+        let (end, body, chunked) = match framing {
+            Framing::Length(len) => match head_len.checked_add(len) {
+                Some(end) if end <= MAX_MESSAGE_SIZE => (end, head_len..end, None),
+                _ => {
+                    self.ignore();
+                    return None;
+                }
+            },
+            Framing::Chunked => match parse_chunked(&self.buf[head_len..]) {
+                Ok(Some((len, data_len, data))) => {
+                    (head_len + len, head_len..head_len + data_len, Some(data))
+                }
+                Ok(None) => (usize::MAX, 0..0, None),
+                Err(_) => {
+                    self.ignore();
+                    return None;
+                }
+            },
+            Framing::Close => {
+                // The body lasts until the connection is closed,
+                // so the rest of the stream belongs to this message.
+                if self.fin {
+                    self.closed = true;
+                    (self.buf.len(), head_len..self.buf.len(), None)
+                } else {
+                    (usize::MAX, 0..0, None)
+                }
+            }
+        };
+
+        if end > self.buf.len() {
+            if self.buf.len() > MAX_MESSAGE_SIZE {
+                self.ignore();
+            }
+            return None;
+        }
+
+        Some(Message {
+            data: self.buf.drain(..end).collect(),
+            start,
+            headers,
+            body,
+            chunked,
+        })
+    }
+
+    fn framing(
+        &self,
+        start: &StartLine,
+        headers: &[(Range<usize>, Range<usize>)],
+        method: Option<&[u8]>,
+    ) -> Option<Framing> {
+        let header = |name: &[u8]| {
+            headers
+                .iter()
+                .rev()
+                .find(|(n, _)| self.buf[n.clone()].eq_ignore_ascii_case(name))
+                .map(|(_, v)| &self.buf[v.clone()])
+        };
+
+        if let StartLine::Response { status, .. } = start {
+            let status = &self.buf[status.clone()];
+            if status[0] == b'1' || status == b"204" || status == b"304" || method == Some(b"HEAD")
+            {
+                return Some(Framing::Length(0));
+            }
+        }
+
+        if let Some(value) = header(b"transfer-encoding") {
+            if value.to_ascii_lowercase().ends_with(b"chunked") {
+                return Some(Framing::Chunked);
+            }
+        }
+
+        if let Some(value) = header(b"content-length") {
+            str::from_utf8(value)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .map(Framing::Length)
+        } else if let StartLine::Response { .. } = start {
+            Some(Framing::Close)
+        } else {
+            Some(Framing::Length(0))
+        }
+    }
+
+    fn ignore(&mut self) {
+        self.ignored = true;
+        self.buf.clear();
+    }
+}
+
+fn find(data: &[u8], pat: &[u8]) -> Option<usize> {
+    data.windows(pat.len()).position(|w| w == pat)
+}
+
+fn trim(data: &[u8], range: Range<usize>) -> Range<usize> {
+    let mut range = range;
+    while range.start < range.end && (data[range.start] == b' ' || data[range.start] == b'\t') {
+        range.start += 1;
+    }
+    while range.start < range.end && (data[range.end - 1] == b' ' || data[range.end - 1] == b'\t')
+    {
+        range.end -= 1;
+    }
+    range
+}
+
+fn parse_start_line(line: &[u8]) -> Option<StartLine> {
+    let first = line.iter().position(|c| *c == b' ')?;
+    if line.starts_with(b"HTTP/") {
+        let status = first + 1..first + 4;
+        let code = line.get(status.clone())?;
+        if !code.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        let reason = (status.end + 1).min(line.len())..line.len();
+        Some(StartLine::Response {
+            version: 0..first,
+            status,
+            reason,
+        })
+    } else {
+        let method = &line[..first];
+        if method.is_empty() || !method.iter().all(u8::is_ascii_uppercase) {
+            return None;
+        }
+        let second = first + 1 + line[first + 1..].iter().position(|c| *c == b' ')?;
+        if !line[second + 1..].starts_with(b"HTTP/") {
+            return None;
+        }
+        Some(StartLine::Request {
+            method: 0..first,
+            uri: first + 1..second,
+            version: second + 1..line.len(),
+        })
+    }
+}
+
+/// Parses a chunked body.
+///
+/// Returns the length of the encoded body, the length of the chunks
+/// excluding the last chunk and trailers, and the decoded data,
+/// or None if the body is incomplete.
+///
+/// Chunks extending beyond MAX_MESSAGE_SIZE are rejected.
+fn parse_chunked(data: &[u8]) -> std::result::Result<Option<(usize, usize, Vec<u8>)>, ()> {
+    let mut body = Vec::new();
+    let mut pos = 0;
+    loop {
+        let line_end = if let Some(end) = find(&data[pos..], b"\r\n") {
+            pos + end
+        } else {
+            return Ok(None);
+        };
+        let size = data[pos..line_end]
+            .split(|c| *c == b';')
+            .next()
+            .and_then(|size| str::from_utf8(size).ok())
+            .and_then(|size| usize::from_str_radix(size.trim(), 16).ok())
+            .ok_or(())?;
+        if size == 0 {
+            let chunks = pos;
+            let trailers = &data[line_end..];
+            return Ok(find(trailers, b"\r\n\r\n")
+                .map(|end| (line_end + end + 4, chunks, body)));
+        }
+        let start = line_end + 2;
+        let end = start
+            .checked_add(size)
+            .filter(|end| *end <= MAX_MESSAGE_SIZE)
+            .ok_or(())?;
+        if data.len() < end + 2 {
+            return Ok(None);
+        }
+        body.extend_from_slice(&data[start..end]);
+        pos = end + 2;
+    }
+}
+
+struct Request {
+    frame: u32,
+    method: Box<str>,
+    uri: Box<str>,
+}
+
+type Endpoint = (Vec<u8>, u16);
+
+struct HttpWorker {
+    streams: HashMap<u64, Stream>,
+    requests: HashMap<(Endpoint, Endpoint), VecDeque<Request>>,
+}
+
+impl HttpWorker {
+    fn new() -> HttpWorker {
+        HttpWorker {
+            streams: HashMap::new(),
+            requests: HashMap::new(),
+        }
+    }
+}
+
+impl Worker for HttpWorker {
+    fn decode(
+        &mut self,
+        ctx: &mut Context,
+        stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() != token!("tcp") {
+            return Ok(Status::Skip);
+        }
+
+        let slices: Vec<ByteSlice> = parent
+            .payloads()
+            .iter()
+            .filter(|p| p.id() == token!("@stream:tcp"))
+            .map(|p| p.data())
+            .collect();
+
+        // Wait for tcp-stream to reassemble the segment.
+        let id: u64 = if let Ok(id) = attr_value(parent, token!("tcp.stream")) {
+            id
+        } else {
+            return Ok(Status::Skip);
+        };
+
+        // FIN and RST end the bodies framed by the connection close.
+        let flags: u8 = attr_value(parent, token!("tcp.flags"))?;
+        let fin = flags & 0b0000_0101 != 0;
+        if slices.is_empty() && !(fin && self.streams.contains_key(&id)) {
+            return Ok(Status::Skip);
+        }
+
+        let stream = self.streams.entry(id).or_default();
+        if stream.ignored {
+            return Ok(Status::Done);
+        }
+        for slice in slices {
+            stream.buf.extend_from_slice(&slice);
+        }
+        stream.fin |= fin;
+
+        let requests = self.requests.entry(connection(stack, parent)?).or_default();
+
+        while let Some(message) = stream.next(requests.front().map(|req| req.method.as_bytes())) {
+            let layer = build_layer(message, requests, ctx.frame_index())?;
+            parent.add_child(layer);
+        }
+
+        Ok(Status::Done)
+    }
+}
+
+fn build_layer(message: Message, requests: &mut VecDeque<Request>, frame: u32) -> Result<Layer> {
+    let data = ByteSlice::from(message.data);
+    let mut layer = Layer::new(&HTTP_CLASS, data);
+
+    match message.start {
+        StartLine::Request {
+            method,
+            uri,
+            version,
+        } => {
+            layer.add_attr(text_attr(&METHOD_ATTR, data, method.clone()));
+            layer.add_attr(text_attr(&URI_ATTR, data, uri.clone()));
+            layer.add_attr(text_attr(&VERSION_ATTR, data, version));
+            requests.push_back(Request {
+                frame,
+                method: text(data, method),
+                uri: text(data, uri),
+            });
+        }
+        StartLine::Response {
+            version,
+            status,
+            reason,
+        } => {
+            let code: u64 = text(data, status.clone()).parse().unwrap_or(0);
+            layer.add_attr(text_attr(&VERSION_ATTR, data, version));
+            layer.add_attr(attr!(&STATUS_ATTR, range: status, value: code));
+            layer.add_attr(text_attr(&REASON_ATTR, data, reason));
+
+            // Informational responses precede the final response to the same request.
+            if code >= 200 {
+                if let Some(request) = requests.pop_front() {
+                    layer.add_attr(attr!(&REQUEST_FRAME_ATTR, value: u64::from(request.frame)));
+                    layer.add_attr(attr!(&REQUEST_METHOD_ATTR, value: request.method));
+                    layer.add_attr(attr!(&REQUEST_URI_ATTR, value: request.uri));
+                }
+            }
+        }
+    }
+
+    for (name, value) in message.headers {
+        layer.add_attr(attr!(&HEADER_ATTR, range: name.start..value.end));
+        layer.add_attr(text_attr(&HEADER_NAME_ATTR, data, name.clone()));
+        layer.add_attr(text_attr(&HEADER_VALUE_ATTR, data, value.clone()));
+        if let Some(attr) = get_header(&text(data, name).to_ascii_lowercase()) {
+            layer.add_attr(text_attr(attr, data, value));
+        }
+    }
+
+    let body = message.body;
+    if let Some(chunked) = message.chunked {
+        let len = chunked.len() as u64;
+        let value = chunked.clone().into_boxed_slice();
+        layer.add_attr(attr!(&BODY_ATTR, range: body.clone(), value: value));
+        layer.add_attr(attr!(&BODY_LENGTH_ATTR, range: body, value: len));
+        layer.add_payload(Payload::new(chunked, ""));
+    } else if !body.is_empty() {
+        let len = body.len() as u64;
+        let value = data.try_get(body.clone())?;
+        layer.add_attr(attr!(&BODY_ATTR, range: body.clone(), value: value));
+        layer.add_attr(attr!(&BODY_LENGTH_ATTR, range: body, value: len));
+        layer.add_payload(Payload::new(value, ""));
+    }
+
+    Ok(layer)
+}
+
+fn text(data: ByteSlice, range: Range<usize>) -> Box<str> {
+    String::from_utf8_lossy(&data[range])
+        .into_owned()
+        .into_boxed_str()
+}
+
+fn text_attr(class: &'static AttrClass, data: ByteSlice, range: Range<usize>) -> Attr {
+    attr!(class, range: range.clone(), value: text(data, range))
+}
+
+/// Returns the endpoints of the TCP connection in a direction-independent order.
+fn connection(stack: &LayerStack, parent: &Parent) -> Result<(Endpoint, Endpoint)> {
+    let ip = stack
+        .layer(token!("ipv4"))
+        .or_else(|| stack.layer(token!("ipv6")))
+        .ok_or_else(|| Error::new("missing network layer"))?;
+    let src: Endpoint = (
+        attr_value(ip, token!("_.src"))?,
+        attr_value(parent, token!("tcp.src"))?,
+    );
+    let dst: Endpoint = (
+        attr_value(ip, token!("_.dst"))?,
+        attr_value(parent, token!("tcp.dst"))?,
+    );
+    if src < dst {
+        Ok((src, dst))
+    } else {
+        Ok((dst, src))
+    }
+}
+
+#[derive(Clone)]
+struct HttpDecoder {}
+
+impl Decoder for HttpDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(HttpWorker::new())
+    }
+
+    fn metadata(&self) -> Metadata {
+        let mut fields = HTTP_CLASS.fields();
+        fields.extend(
+            [
+                &*METHOD_ATTR,
+                &*URI_ATTR,
+                &*VERSION_ATTR,
+                &*STATUS_ATTR,
+                &*REASON_ATTR,
+                &*HEADER_ATTR,
+                &*HEADER_NAME_ATTR,
+                &*HEADER_VALUE_ATTR,
+                &*BODY_ATTR,
+                &*BODY_LENGTH_ATTR,
+                &*REQUEST_FRAME_ATTR,
+                &*REQUEST_METHOD_ATTR,
+                &*REQUEST_URI_ATTR,
+            ]
+                .iter()
+                .map(|attr| Field::from(*attr)),
+        );
+        fields.extend(HEADERS.iter().filter_map(|name| get_header(name)).map(Field::from));
+        Metadata {
+            id: "app.genet.decoder.http".into(),
+            exec_type: ExecType::SerialSync,
+            // Segments without stream data are consumed to see FIN.
+            consumes: vec![String::from("tcp"), String::from("@stream:tcp")],
+            fields,
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(HTTP_CLASS, "http");
+
+def_attr_class!(METHOD_ATTR, "http.method");
+
+def_attr_class!(URI_ATTR, "http.uri");
+
+def_attr_class!(VERSION_ATTR, "http.version");
+
+def_attr_class!(STATUS_ATTR, "http.status");
+
+def_attr_class!(REASON_ATTR, "http.reason");
+
+def_attr_class!(HEADER_ATTR, "http.header",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(HEADER_NAME_ATTR, "http.header.name");
+
+def_attr_class!(HEADER_VALUE_ATTR, "http.header.value");
+
+def_attr_class!(BODY_ATTR, "http.body");
+
+def_attr_class!(BODY_LENGTH_ATTR, "http.body.length");
+
+def_attr_class!(REQUEST_FRAME_ATTR, "http.request.frame");
+
+def_attr_class!(REQUEST_METHOD_ATTR, "http.request.method");
+
+def_attr_class!(REQUEST_URI_ATTR, "http.request.uri");
+
+const HEADERS: &[&str] = &[
+    "host",
+    "user-agent",
+    "accept",
+    "accept-encoding",
+    "authorization",
+    "connection",
+    "content-encoding",
+    "content-length",
+    "content-type",
+    "cookie",
+    "location",
+    "referer",
+    "server",
+    "set-cookie",
+    "transfer-encoding",
+];
+
+fn get_header(name: &str) -> Option<&'static AttrClass> {
+    match name {
+        "host" => Some(attr_class_lazy!("http.header.host")),
+        "user-agent" => Some(attr_class_lazy!("http.header.userAgent")),
+        "accept" => Some(attr_class_lazy!("http.header.accept")),
+        "accept-encoding" => Some(attr_class_lazy!("http.header.acceptEncoding")),
+        "authorization" => Some(attr_class_lazy!("http.header.authorization")),
+        "connection" => Some(attr_class_lazy!("http.header.connection")),
+        "content-encoding" => Some(attr_class_lazy!("http.header.contentEncoding")),
+        "content-length" => Some(attr_class_lazy!("http.header.contentLength")),
+        "content-type" => Some(attr_class_lazy!("http.header.contentType")),
+        "cookie" => Some(attr_class_lazy!("http.header.cookie")),
+        "location" => Some(attr_class_lazy!("http.header.location")),
+        "referer" => Some(attr_class_lazy!("http.header.referer")),
+        "server" => Some(attr_class_lazy!("http.header.server")),
+        "set-cookie" => Some(attr_class_lazy!("http.header.setCookie")),
+        "transfer-encoding" => Some(attr_class_lazy!("http.header.transferEncoding")),
+        _ => None,
+    }
+}
+
+genet_decoders!(HttpDecoder {});
+
+#[cfg(test)]
+mod tests {
+    use genet_sdk::{cast, decoder::*, layer::attr_value, prelude::*};
+    use HttpWorker;
+
+    def_layer_class!(IPV4_CLASS, "ipv4",
+        alias: "_.src" "ipv4.src",
+        alias: "_.dst" "ipv4.dst",
+        header: attr!(&IPV4_SRC_ATTR, range: 12..16),
+        header: attr!(&IPV4_DST_ATTR, range: 16..20)
+    );
+
+    def_attr_class!(IPV4_SRC_ATTR, "ipv4.src", cast: cast::ByteSlice());
+
+    def_attr_class!(IPV4_DST_ATTR, "ipv4.dst", cast: cast::ByteSlice());
+
+    def_layer_class!(TCP_CLASS, "tcp",
+        header: attr!(&SRC_ATTR, range: 0..2),
+        header: attr!(&DST_ATTR, range: 2..4),
+        header: attr!(&FLAGS_ATTR, range: 13..14)
+    );
+
+    def_attr_class!(SRC_ATTR, "tcp.src", cast: cast::UInt16BE());
+
+    def_attr_class!(DST_ATTR, "tcp.dst", cast: cast::UInt16BE());
+
+    def_attr_class!(FLAGS_ATTR, "tcp.flags", cast: cast::UInt8());
+
+    def_attr_class!(STREAM_ATTR, "tcp.stream");
+
+    const ACK: u8 = 0b0001_0000;
+    const FIN: u8 = 0b0000_0001;
+
+    /// Decodes stream data from the client if `request` is true or from the server,
+    /// and returns the HTTP layers.
+    fn segment(
+        worker: &mut HttpWorker,
+        request: bool,
+        flags: u8,
+        data: &[u8],
+    ) -> Vec<&'static Layer> {
+        let (src, dst) = if request { (1, 2) } else { (2, 1) };
+        let mut ip = vec![0; 20];
+        ip[12..16].copy_from_slice(&[10, 0, 0, src]);
+        ip[16..20].copy_from_slice(&[10, 0, 0, dst]);
+        let ip = Layer::new(&IPV4_CLASS, ip);
+
+        let ports = if request {
+            [0x04, 0xd2, 0x00, 0x50]
+        } else {
+            [0x00, 0x50, 0x04, 0xd2]
+        };
+        let mut header = ports.to_vec();
+        header.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0x50, flags]);
+        let mut tcp = Layer::new(&TCP_CLASS, header);
+        tcp.add_attr(attr!(&STREAM_ATTR, value: if request { 0u64 } else { 1u64 }));
+        tcp.add_payload(Payload::new(data.to_vec(), ""));
+        if !data.is_empty() {
+            tcp.add_payload(Payload::new(data.to_vec(), "@stream:tcp"));
+        }
+
+        let mut ctx = Context::default();
+        let mut parent = Parent::from_mut_ref(&mut tcp);
+        worker
+            .decode(&mut ctx, &LayerStack::from_slice(&[&ip]), &mut parent)
+            .unwrap();
+        parent
+            .children()
+            .iter()
+            .map(|layer| unsafe { &**layer })
+            .collect()
+    }
+
+    fn text(layer: &Layer, id: Token) -> String {
+        attr_value(layer, id).unwrap()
+    }
+
+    fn body(layer: &Layer) -> Vec<u8> {
+        attr_value(layer, token!("http.body")).unwrap()
+    }
+
+    #[test]
+    fn pipelined() {
+        let mut w = HttpWorker::new();
+        let layers = segment(
+            &mut w,
+            true,
+            ACK,
+            b"GET /a HTTP/1.1\r\nHost: example.com\r\n\r\n\
+              HEAD /b HTTP/1.1\r\nHost: example.com\r\n\r\n\
+              POST /c HTTP/1.1\r\nContent-Length: 3\r\n\r\nabc",
+        );
+        assert_eq!(layers.len(), 3);
+        assert_eq!(text(layers[0], token!("http.uri")), "/a");
+        assert_eq!(text(layers[1], token!("http.method")), "HEAD");
+        assert_eq!(body(layers[2]), b"abc");
+
+        let layers = segment(
+            &mut w,
+            false,
+            ACK,
+            b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok\
+              HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n\
+              HTTP/1.1 100 Continue\r\n\r\n\
+              HTTP/1.1 201 Created\r\nContent-Length: 0\r\n\r\n",
+        );
+        assert_eq!(layers.len(), 4);
+        assert_eq!(text(layers[0], token!("http.request.uri")), "/a");
+        assert_eq!(body(layers[0]), b"ok");
+
+        // Responses to HEAD have no body regardless of Content-Length.
+        assert_eq!(text(layers[1], token!("http.request.uri")), "/b");
+        assert!(layers[1].attr(token!("http.body")).is_none());
+
+        // Informational responses do not answer the request.
+        assert!(layers[2].attr(token!("http.request.uri")).is_none());
+        assert_eq!(text(layers[3], token!("http.request.uri")), "/c");
+    }
+
+    #[test]
+    fn chunked() {
+        let mut w = HttpWorker::new();
+        segment(&mut w, true, ACK, b"GET / HTTP/1.1\r\n\r\n");
+        let head = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n";
+        assert!(segment(&mut w, false, ACK, head).is_empty());
+        assert!(segment(&mut w, false, ACK, b"5;ext=1\r\nhello\r\n6\r\n wo").is_empty());
+        assert!(segment(&mut w, false, ACK, b"rld\r\n0\r\n").is_empty());
+        let layers = segment(&mut w, false, ACK, b"Expires: 0\r\n\r\n");
+        assert_eq!(layers.len(), 1);
+        assert_eq!(body(layers[0]), b"hello world");
+        assert_eq!(
+            attr_value::<u64>(layers[0], token!("http.body.length")).unwrap(),
+            11
+        );
+        assert_eq!(text(layers[0], token!("http.request.uri")), "/");
+    }
+
+    #[test]
+    fn connection_close() {
+        let mut w = HttpWorker::new();
+        segment(&mut w, true, ACK, b"GET / HTTP/1.0\r\n\r\n");
+
+        // The body without Content-Length lasts until FIN.
+        assert!(segment(&mut w, false, ACK, b"HTTP/1.0 200 OK\r\n\r\nhello").is_empty());
+        assert!(segment(&mut w, false, ACK, b" world").is_empty());
+        let layers = segment(&mut w, false, FIN | ACK, b"");
+        assert_eq!(layers.len(), 1);
+        assert_eq!(body(layers[0]), b"hello world");
+
+        // Nothing follows the connection close.
+        assert!(segment(&mut w, false, ACK, b"HTTP/1.0 200 OK\r\n\r\n").is_empty());
+    }
+
+    #[test]
+    fn oversized() {
+        let mut w = HttpWorker::new();
+        let head = b"HTTP/1.1 200 OK\r\nContent-Length: 18446744073709551615\r\n\r\n";
+        assert!(segment(&mut w, false, ACK, head).is_empty());
+        assert!(w.streams[&1].ignored);
+
+        let mut w = HttpWorker::new();
+        let head = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n";
+        assert!(segment(&mut w, false, ACK, head).is_empty());
+        assert!(segment(&mut w, false, ACK, b"ffffffffffffffff\r\n").is_empty());
+        assert!(w.streams[&1].ignored);
+    }
+
+    #[test]
+    fn not_http() {
+        let mut w = HttpWorker::new();
+        assert!(segment(&mut w, true, ACK, b"\x16\x03\x01\x00\x05hello\r\n").is_empty());
+        assert!(w.streams[&0].ignored);
+    }
+}
@@ -0,0 +1,231 @@
+extern crate genet_sdk;
+
+use genet_sdk::{cast, decoder::*, prelude::*};
+
+struct IcmpWorker {}
+
+impl Worker for IcmpWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:icmp"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let mut layer = Layer::new(&ICMP_CLASS, data);
+        let typ = TYPE_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        if let Some(attr) = get_type(typ) {
+            layer.add_attr(attr!(attr, range: 0..1));
+        }
+
+        match typ {
+            // Echo Reply, Echo Request
+            0 | 8 => {
+                layer.add_attr(attr!(&ID_ATTR, range: 4..6));
+                layer.add_attr(attr!(&SEQ_ATTR, range: 6..8));
+                let payload = layer.data().try_get(8..)?;
+                layer.add_payload(Payload::new(payload, ""));
+            }
+            // Destination Unreachable, Source Quench, Redirect, Time Exceeded, Parameter Problem
+            3 | 4 | 5 | 11 | 12 => {
+                if typ == 5 {
+                    layer.add_attr(attr!(&GATEWAY_ATTR, range: 4..8));
+                } else if typ == 12 {
+                    layer.add_attr(attr!(&POINTER_ATTR, range: 4..5));
+                }
+                // The invoking datagram is usually truncated after 8 bytes of the payload,
+                // so it is not dispatched to the IPv4 decoder.
+                let payload = layer.data().try_get(8..)?;
+                layer.add_payload(Payload::with_typ(payload, "@data:icmp:datagram", "@data:ipv4"));
+            }
+            // Timestamp, Timestamp Reply
+            13 | 14 => {
+                layer.add_attr(attr!(&ID_ATTR, range: 4..6));
+                layer.add_attr(attr!(&SEQ_ATTR, range: 6..8));
+                layer.add_attr(attr!(&TS_ORIGINATE_ATTR, range: 8..12));
+                layer.add_attr(attr!(&TS_RECEIVE_ATTR, range: 12..16));
+                layer.add_attr(attr!(&TS_TRANSMIT_ATTR, range: 16..20));
+            }
+            _ => {}
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct IcmpDecoder {}
+
+impl Decoder for IcmpDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(IcmpWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        let mut fields = ICMP_CLASS.fields();
+        fields.extend(
+            [
+                &*ID_ATTR,
+                &*SEQ_ATTR,
+                &*GATEWAY_ATTR,
+                &*POINTER_ATTR,
+                &*TS_ORIGINATE_ATTR,
+                &*TS_RECEIVE_ATTR,
+                &*TS_TRANSMIT_ATTR,
+            ]
+                .iter()
+                .map(|attr| Field::from(*attr)),
+        );
+        fields.extend((0..=0xff).filter_map(get_type).map(Field::from));
+        Metadata {
+            id: "app.genet.decoder.icmp".into(),
+            exec_type: ExecType::ParallelSync,
+            consumes: vec![String::from("@data:icmp")],
+            fields,
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(ICMP_CLASS, "icmp",
+    header: &TYPE_ATTR_HEADER,
+    header: attr!(&CODE_ATTR, range: 1..2),
+    header: attr!(&CHECKSUM_ATTR, range: 2..4)
+);
+
+def_attr!(TYPE_ATTR_HEADER,  &TYPE_ATTR, range: 0..1);
+
+def_attr_class!(TYPE_ATTR, "icmp.type",
+    typ: "@enum",
+    cast: cast::UInt8()
+);
+
+def_attr_class!(CODE_ATTR, "icmp.code", cast: cast::UInt8());
+
+def_attr_class!(CHECKSUM_ATTR, "icmp.checksum", cast: cast::UInt16BE());
+
+def_attr_class!(ID_ATTR, "icmp.id", cast: cast::UInt16BE());
+
+def_attr_class!(SEQ_ATTR, "icmp.seq", cast: cast::UInt16BE());
+
+def_attr_class!(GATEWAY_ATTR, "icmp.gateway",
+    typ: "@ipv4:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(POINTER_ATTR, "icmp.pointer", cast: cast::UInt8());
+
+def_attr_class!(TS_ORIGINATE_ATTR, "icmp.timestamp.originate", cast: cast::UInt32BE());
+
+def_attr_class!(TS_RECEIVE_ATTR, "icmp.timestamp.receive", cast: cast::UInt32BE());
+
+def_attr_class!(TS_TRANSMIT_ATTR, "icmp.timestamp.transmit", cast: cast::UInt32BE());
+
+fn get_type(val: u64) -> Option<&'static AttrClass> {
+    match val {
+        0 => Some(attr_class_lazy!("icmp.type.echoReply", typ: "@novalue", value: true)),
+        3 => Some(attr_class_lazy!("icmp.type.destinationUnreachable", typ: "@novalue", value: true)),
+        4 => Some(attr_class_lazy!("icmp.type.sourceQuench", typ: "@novalue", value: true)),
+        5 => Some(attr_class_lazy!("icmp.type.redirect", typ: "@novalue", value: true)),
+        8 => Some(attr_class_lazy!("icmp.type.echoRequest", typ: "@novalue", value: true)),
+        9 => Some(attr_class_lazy!("icmp.type.routerAdvertisement", typ: "@novalue", value: true)),
+        10 => Some(attr_class_lazy!("icmp.type.routerSolicitation", typ: "@novalue", value: true)),
+        11 => Some(attr_class_lazy!("icmp.type.timeExceeded", typ: "@novalue", value: true)),
+        12 => Some(attr_class_lazy!("icmp.type.parameterProblem", typ: "@novalue", value: true)),
+        13 => Some(attr_class_lazy!("icmp.type.timestamp", typ: "@novalue", value: true)),
+        14 => Some(attr_class_lazy!("icmp.type.timestampReply", typ: "@novalue", value: true)),
+        _ => None,
+    }
+}
+
+genet_decoders!(IcmpDecoder {});
+
+#[cfg(test)]
+mod tests {
+    use genet_sdk::{decoder::*, layer::attr_value, prelude::*};
+    use IcmpWorker;
+
+    def_layer_class!(IPV4_CLASS, "ipv4");
+
+    fn decode(data: &'static [u8]) -> &'static Layer {
+        let mut ctx = Context::default();
+        let mut ipv4 = Layer::new(&IPV4_CLASS, data);
+        ipv4.add_payload(Payload::new(data, "@data:icmp"));
+        let mut parent = Parent::from_mut_ref(&mut ipv4);
+        let status = IcmpWorker {}.decode(&mut ctx, &LayerStack::from_slice(&[]), &mut parent);
+        match status {
+            Ok(Status::Done) => {}
+            _ => panic!("not decoded"),
+        }
+        assert_eq!(parent.children().len(), 1);
+        unsafe { &*parent.children()[0] }
+    }
+
+    #[test]
+    fn echo_request() {
+        let layer = decode(b"\x08\x00\xf7\xfe\x00\x01\x00\x01ping");
+        assert!(layer.attr(token!("icmp.type.echoRequest")).is_some());
+        assert_eq!(attr_value::<u64>(layer, token!("icmp.id")).unwrap(), 1);
+        assert_eq!(attr_value::<u64>(layer, token!("icmp.seq")).unwrap(), 1);
+        assert_eq!(layer.payloads().len(), 1);
+        assert_eq!(&layer.payloads()[0].data()[..], b"ping");
+    }
+
+    #[test]
+    fn destination_unreachable() {
+        let layer = decode(
+            b"\x03\x03\x00\x00\x00\x00\x00\x00\
+              \x45\x00\x00\x30\x00\x01\x00\x00\x40\x06\x00\x00\
+              \x0a\x00\x00\x02\x0a\x00\x00\x01\
+              \x04\xd2\x00\x50\x00\x00\x00\x01",
+        );
+        assert!(layer.attr(token!("icmp.type.destinationUnreachable")).is_some());
+        assert_eq!(attr_value::<u64>(layer, token!("icmp.code")).unwrap(), 3);
+
+        // The truncated datagram is not dispatched as @data:ipv4.
+        let payload = &layer.payloads()[0];
+        assert_eq!(payload.id(), token!("@data:icmp:datagram"));
+        assert_eq!(payload.typ(), token!("@data:ipv4"));
+        assert_eq!(payload.data().len(), 28);
+    }
+
+    #[test]
+    fn timestamp() {
+        let layer = decode(
+            b"\x0d\x00\x00\x00\x12\x34\x00\x02\
+              \x00\x00\x00\x64\x00\x00\x00\x00\x00\x00\x00\x00",
+        );
+        assert!(layer.attr(token!("icmp.type.timestamp")).is_some());
+        assert_eq!(attr_value::<u64>(layer, token!("icmp.id")).unwrap(), 0x1234);
+        assert_eq!(
+            attr_value::<u64>(layer, token!("icmp.timestamp.originate")).unwrap(),
+            100
+        );
+        assert!(layer.payloads().is_empty());
+    }
+
+    #[test]
+    fn truncated() {
+        let mut ctx = Context::default();
+        let mut ipv4 = Layer::new(&IPV4_CLASS, &b"\x08"[..]);
+        ipv4.add_payload(Payload::new(&b"\x08"[..], "@data:icmp"));
+        let mut parent = Parent::from_mut_ref(&mut ipv4);
+        assert!(
+            IcmpWorker {}
+                .decode(&mut ctx, &LayerStack::from_slice(&[]), &mut parent)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,405 @@
+extern crate genet_sdk;
+
+use genet_sdk::{cast, decoder::*, prelude::*};
+
+struct Icmpv6Worker {}
+
+impl Worker for Icmpv6Worker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:icmpv6"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let mut layer = Layer::new(&ICMPV6_CLASS, data);
+        let typ = TYPE_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        if let Some(attr) = get_type(typ) {
+            layer.add_attr(attr!(attr, range: 0..1));
+        }
+
+        let options = match typ {
+            // Destination Unreachable, Packet Too Big, Time Exceeded, Parameter Problem
+            1..=4 => {
+                if typ == 2 {
+                    layer.add_attr(attr!(&MTU_ATTR, range: 4..8));
+                } else if typ == 4 {
+                    layer.add_attr(attr!(&POINTER_ATTR, range: 4..8));
+                }
+                // The invoking packet is usually truncated to fit in the minimum MTU,
+                // so it is not dispatched to the IPv6 decoder.
+                let payload = layer.data().try_get(8..)?;
+                layer.add_payload(Payload::with_typ(payload, "@data:icmpv6:packet", "@data:ipv6"));
+                None
+            }
+            // Echo Request, Echo Reply
+            128 | 129 => {
+                layer.add_attr(attr!(&ID_ATTR, range: 4..6));
+                layer.add_attr(attr!(&SEQ_ATTR, range: 6..8));
+                let payload = layer.data().try_get(8..)?;
+                layer.add_payload(Payload::new(payload, ""));
+                None
+            }
+            // Router Solicitation
+            133 => Some(8),
+            // Router Advertisement
+            134 => {
+                layer.add_attr(attr!(&RA_HOP_LIMIT_ATTR, range: 4..5));
+                layer.add_attr(attr!(&RA_MANAGED_ATTR, range: 5..6));
+                layer.add_attr(attr!(&RA_OTHER_ATTR, range: 5..6));
+                layer.add_attr(attr!(&RA_LIFETIME_ATTR, range: 6..8));
+                layer.add_attr(attr!(&RA_REACHABLE_ATTR, range: 8..12));
+                layer.add_attr(attr!(&RA_RETRANS_ATTR, range: 12..16));
+                Some(16)
+            }
+            // Neighbor Solicitation
+            135 => {
+                layer.add_attr(attr!(&TARGET_ATTR, range: 8..24));
+                Some(24)
+            }
+            // Neighbor Advertisement
+            136 => {
+                layer.add_attr(attr!(&NA_ROUTER_ATTR, range: 4..5));
+                layer.add_attr(attr!(&NA_SOLICITED_ATTR, range: 4..5));
+                layer.add_attr(attr!(&NA_OVERRIDE_ATTR, range: 4..5));
+                layer.add_attr(attr!(&TARGET_ATTR, range: 8..24));
+                Some(24)
+            }
+            // Redirect
+            137 => {
+                layer.add_attr(attr!(&TARGET_ATTR, range: 8..24));
+                layer.add_attr(attr!(&DESTINATION_ATTR, range: 24..40));
+                Some(40)
+            }
+            _ => None,
+        };
+
+        if let Some(start) = options {
+            let mut offset = start;
+            while offset + 2 <= layer.data().len() {
+                let typ = layer.data().try_get(offset)?;
+                let len = layer.data().try_get(offset + 1)? as usize * 8;
+                if len == 0 || offset + len > layer.data().len() {
+                    break;
+                }
+                match typ {
+                    1 => layer.add_attr(attr!(&OPT_SRC_LLADDR_ATTR, range: offset..offset + len)),
+                    2 => layer.add_attr(attr!(&OPT_DST_LLADDR_ATTR, range: offset..offset + len)),
+                    3 => {
+                        layer.add_attr(attr!(&OPT_PREFIX_ATTR, range: offset..offset + len));
+                        layer.add_attr(attr!(&OPT_PREFIX_LENGTH_ATTR, range: offset + 2..offset + 3));
+                        layer.add_attr(attr!(&OPT_PREFIX_ONLINK_ATTR, range: offset + 3..offset + 4));
+                        layer.add_attr(attr!(&OPT_PREFIX_AUTO_ATTR, range: offset + 3..offset + 4));
+                        layer.add_attr(attr!(&OPT_PREFIX_VALID_ATTR, range: offset + 4..offset + 8));
+                        layer.add_attr(attr!(&OPT_PREFIX_PREFERRED_ATTR, range: offset + 8..offset + 12));
+                        layer.add_attr(attr!(&OPT_PREFIX_PREFIX_ATTR, range: offset + 16..offset + 32));
+                    }
+                    4 => layer.add_attr(attr!(&OPT_REDIRECTED_ATTR, range: offset..offset + len)),
+                    5 => layer.add_attr(attr!(&OPT_MTU_ATTR, range: offset..offset + len)),
+                    _ => {}
+                }
+                offset += len;
+            }
+            if offset > start {
+                layer.add_attr(attr!(&OPTIONS_ATTR, range: start..offset));
+            }
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct Icmpv6Decoder {}
+
+impl Decoder for Icmpv6Decoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(Icmpv6Worker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        let mut fields = ICMPV6_CLASS.fields();
+        fields.extend(
+            [
+                &*MTU_ATTR,
+                &*POINTER_ATTR,
+                &*ID_ATTR,
+                &*SEQ_ATTR,
+                &*RA_HOP_LIMIT_ATTR,
+                &*RA_MANAGED_ATTR,
+                &*RA_OTHER_ATTR,
+                &*RA_LIFETIME_ATTR,
+                &*RA_REACHABLE_ATTR,
+                &*RA_RETRANS_ATTR,
+                &*NA_ROUTER_ATTR,
+                &*NA_SOLICITED_ATTR,
+                &*NA_OVERRIDE_ATTR,
+                &*TARGET_ATTR,
+                &*DESTINATION_ATTR,
+                &*OPTIONS_ATTR,
+                &*OPT_SRC_LLADDR_ATTR,
+                &*OPT_DST_LLADDR_ATTR,
+                &*OPT_PREFIX_ATTR,
+                &*OPT_PREFIX_LENGTH_ATTR,
+                &*OPT_PREFIX_ONLINK_ATTR,
+                &*OPT_PREFIX_AUTO_ATTR,
+                &*OPT_PREFIX_VALID_ATTR,
+                &*OPT_PREFIX_PREFERRED_ATTR,
+                &*OPT_PREFIX_PREFIX_ATTR,
+                &*OPT_REDIRECTED_ATTR,
+                &*OPT_MTU_ATTR,
+            ]
+                .iter()
+                .map(|attr| Field::from(*attr)),
+        );
+        fields.extend((0..=0xff).filter_map(get_type).map(Field::from));
+        Metadata {
+            id: "app.genet.decoder.icmpv6".into(),
+            exec_type: ExecType::ParallelSync,
+            consumes: vec![String::from("@data:icmpv6")],
+            fields,
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(ICMPV6_CLASS, "icmpv6",
+    header: &TYPE_ATTR_HEADER,
+    header: attr!(&CODE_ATTR, range: 1..2),
+    header: attr!(&CHECKSUM_ATTR, range: 2..4)
+);
+
+def_attr!(TYPE_ATTR_HEADER,  &TYPE_ATTR, range: 0..1);
+
+def_attr_class!(TYPE_ATTR, "icmpv6.type",
+    typ: "@enum",
+    cast: cast::UInt8()
+);
+
+def_attr_class!(CODE_ATTR, "icmpv6.code", cast: cast::UInt8());
+
+def_attr_class!(CHECKSUM_ATTR, "icmpv6.checksum", cast: cast::UInt16BE());
+
+def_attr_class!(MTU_ATTR, "icmpv6.mtu", cast: cast::UInt32BE());
+
+def_attr_class!(POINTER_ATTR, "icmpv6.pointer", cast: cast::UInt32BE());
+
+def_attr_class!(ID_ATTR, "icmpv6.id", cast: cast::UInt16BE());
+
+def_attr_class!(SEQ_ATTR, "icmpv6.seq", cast: cast::UInt16BE());
+
+def_attr_class!(RA_HOP_LIMIT_ATTR, "icmpv6.curHopLimit", cast: cast::UInt8());
+
+def_attr_class!(RA_MANAGED_ATTR, "icmpv6.flags.managed",
+    cast: cast::UInt8().map(|v| v & 0b1000_0000 != 0)
+);
+
+def_attr_class!(RA_OTHER_ATTR, "icmpv6.flags.other",
+    cast: cast::UInt8().map(|v| v & 0b0100_0000 != 0)
+);
+
+def_attr_class!(RA_LIFETIME_ATTR, "icmpv6.routerLifetime", cast: cast::UInt16BE());
+
+def_attr_class!(RA_REACHABLE_ATTR, "icmpv6.reachableTime", cast: cast::UInt32BE());
+
+def_attr_class!(RA_RETRANS_ATTR, "icmpv6.retransTimer", cast: cast::UInt32BE());
+
+def_attr_class!(NA_ROUTER_ATTR, "icmpv6.flags.router",
+    cast: cast::UInt8().map(|v| v & 0b1000_0000 != 0)
+);
+
+def_attr_class!(NA_SOLICITED_ATTR, "icmpv6.flags.solicited",
+    cast: cast::UInt8().map(|v| v & 0b0100_0000 != 0)
+);
+
+def_attr_class!(NA_OVERRIDE_ATTR, "icmpv6.flags.override",
+    cast: cast::UInt8().map(|v| v & 0b0010_0000 != 0)
+);
+
+def_attr_class!(TARGET_ATTR, "icmpv6.target",
+    typ: "@ipv6:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(DESTINATION_ATTR, "icmpv6.destination",
+    typ: "@ipv6:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(OPTIONS_ATTR, "icmpv6.options",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(OPT_SRC_LLADDR_ATTR, "icmpv6.options.sourceLinkLayerAddress",
+    typ: "@eth:mac",
+    cast: cast::Ranged(cast::ByteSlice(), 2..8)
+);
+
+def_attr_class!(OPT_DST_LLADDR_ATTR, "icmpv6.options.targetLinkLayerAddress",
+    typ: "@eth:mac",
+    cast: cast::Ranged(cast::ByteSlice(), 2..8)
+);
+
+def_attr_class!(OPT_PREFIX_ATTR, "icmpv6.options.prefix",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(OPT_PREFIX_LENGTH_ATTR, "icmpv6.options.prefix.length", cast: cast::UInt8());
+
+def_attr_class!(OPT_PREFIX_ONLINK_ATTR, "icmpv6.options.prefix.onLink",
+    cast: cast::UInt8().map(|v| v & 0b1000_0000 != 0)
+);
+
+def_attr_class!(OPT_PREFIX_AUTO_ATTR, "icmpv6.options.prefix.autonomous",
+    cast: cast::UInt8().map(|v| v & 0b0100_0000 != 0)
+);
+
+def_attr_class!(OPT_PREFIX_VALID_ATTR, "icmpv6.options.prefix.validLifetime",
+    cast: cast::UInt32BE()
+);
+
+def_attr_class!(OPT_PREFIX_PREFERRED_ATTR, "icmpv6.options.prefix.preferredLifetime",
+    cast: cast::UInt32BE()
+);
+
+def_attr_class!(OPT_PREFIX_PREFIX_ATTR, "icmpv6.options.prefix.prefix",
+    typ: "@ipv6:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(OPT_REDIRECTED_ATTR, "icmpv6.options.redirectedHeader",
+    cast: cast::Ranged(cast::ByteSlice(), 8..)
+);
+
+def_attr_class!(OPT_MTU_ATTR, "icmpv6.options.mtu",
+    cast: cast::Ranged(cast::UInt32BE(), 4..8)
+);
+
+fn get_type(val: u64) -> Option<&'static AttrClass> {
+    match val {
+        1 => Some(attr_class_lazy!("icmpv6.type.destinationUnreachable", typ: "@novalue", value: true)),
+        2 => Some(attr_class_lazy!("icmpv6.type.packetTooBig", typ: "@novalue", value: true)),
+        3 => Some(attr_class_lazy!("icmpv6.type.timeExceeded", typ: "@novalue", value: true)),
+        4 => Some(attr_class_lazy!("icmpv6.type.parameterProblem", typ: "@novalue", value: true)),
+        128 => Some(attr_class_lazy!("icmpv6.type.echoRequest", typ: "@novalue", value: true)),
+        129 => Some(attr_class_lazy!("icmpv6.type.echoReply", typ: "@novalue", value: true)),
+        133 => Some(attr_class_lazy!("icmpv6.type.routerSolicitation", typ: "@novalue", value: true)),
+        134 => Some(attr_class_lazy!("icmpv6.type.routerAdvertisement", typ: "@novalue", value: true)),
+        135 => Some(attr_class_lazy!("icmpv6.type.neighborSolicitation", typ: "@novalue", value: true)),
+        136 => Some(attr_class_lazy!("icmpv6.type.neighborAdvertisement", typ: "@novalue", value: true)),
+        137 => Some(attr_class_lazy!("icmpv6.type.redirect", typ: "@novalue", value: true)),
+        _ => None,
+    }
+}
+
+genet_decoders!(Icmpv6Decoder {});
+
+#[cfg(test)]
+mod tests {
+    use genet_sdk::{decoder::*, layer::attr_value, prelude::*, variant::Variant};
+    use Icmpv6Worker;
+
+    def_layer_class!(IPV6_CLASS, "ipv6");
+
+    fn decode(data: &'static [u8]) -> &'static Layer {
+        let mut ctx = Context::default();
+        let mut ipv6 = Layer::new(&IPV6_CLASS, data);
+        ipv6.add_payload(Payload::new(data, "@data:icmpv6"));
+        let mut parent = Parent::from_mut_ref(&mut ipv6);
+        let status = Icmpv6Worker {}.decode(&mut ctx, &LayerStack::from_slice(&[]), &mut parent);
+        match status {
+            Ok(Status::Done) => {}
+            _ => panic!("not decoded"),
+        }
+        assert_eq!(parent.children().len(), 1);
+        unsafe { &*parent.children()[0] }
+    }
+
+    fn value(layer: &Layer, id: Token) -> Variant {
+        layer.attr(id).unwrap().try_get(layer).unwrap()
+    }
+
+    #[test]
+    fn packet_too_big() {
+        let layer = decode(
+            b"\x02\x00\x00\x00\x00\x00\x05\x00\
+              \x60\x00\x00\x00\x05\x00\x11\x40",
+        );
+        assert!(layer.attr(token!("icmpv6.type.packetTooBig")).is_some());
+        assert_eq!(attr_value::<u64>(layer, token!("icmpv6.mtu")).unwrap(), 1280);
+
+        // The truncated packet is not dispatched as @data:ipv6.
+        let payload = &layer.payloads()[0];
+        assert_eq!(payload.id(), token!("@data:icmpv6:packet"));
+        assert_eq!(payload.typ(), token!("@data:ipv6"));
+        assert_eq!(payload.data().len(), 8);
+    }
+
+    #[test]
+    fn neighbor_solicitation() {
+        let layer = decode(
+            b"\x87\x00\x00\x00\x00\x00\x00\x00\
+              \xfe\x80\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\
+              \x01\x01\x00\x11\x22\x33\x44\x55",
+        );
+        assert!(layer.attr(token!("icmpv6.type.neighborSolicitation")).is_some());
+        assert_eq!(
+            attr_value::<Vec<u8>>(layer, token!("icmpv6.options.sourceLinkLayerAddress"))
+                .unwrap(),
+            vec![0x00, 0x11, 0x22, 0x33, 0x44, 0x55]
+        );
+        assert_eq!(layer.attr(token!("icmpv6.options")).unwrap().range(), 24..32);
+    }
+
+    #[test]
+    fn router_advertisement() {
+        let layer = decode(
+            b"\x86\x00\x00\x00\x40\x80\x07\x08\x00\x00\x00\x00\x00\x00\x00\x00\
+              \x03\x04\x40\xc0\x00\x27\x8d\x00\x00\x09\x3a\x80\x00\x00\x00\x00\
+              \x20\x01\x0d\xb8\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00",
+        );
+        assert_eq!(attr_value::<u64>(layer, token!("icmpv6.curHopLimit")).unwrap(), 64);
+        assert_eq!(value(layer, token!("icmpv6.flags.managed")), Variant::Bool(true));
+        assert_eq!(value(layer, token!("icmpv6.flags.other")), Variant::Bool(false));
+        assert_eq!(
+            attr_value::<u64>(layer, token!("icmpv6.routerLifetime")).unwrap(),
+            1800
+        );
+        assert_eq!(
+            attr_value::<u64>(layer, token!("icmpv6.options.prefix.length")).unwrap(),
+            64
+        );
+        assert_eq!(
+            value(layer, token!("icmpv6.options.prefix.onLink")),
+            Variant::Bool(true)
+        );
+        assert_eq!(
+            attr_value::<u64>(layer, token!("icmpv6.options.prefix.validLifetime")).unwrap(),
+            2_592_000
+        );
+    }
+
+    #[test]
+    fn zero_length_option() {
+        let layer = decode(
+            b"\x85\x00\x00\x00\x00\x00\x00\x00\
+              \x01\x00\x00\x11\x22\x33\x44\x55",
+        );
+        assert!(layer.attr(token!("icmpv6.type.routerSolicitation")).is_some());
+        assert!(layer.attr(token!("icmpv6.options")).is_none());
+    }
+}
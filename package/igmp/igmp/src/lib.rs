@@ -0,0 +1,264 @@
+extern crate genet_sdk;
+
+use genet_sdk::{cast, decoder::*, prelude::*};
+
+struct IgmpWorker {}
+
+impl Worker for IgmpWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        let data;
+
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:igmp"))
+        {
+            data = payload.data();
+        } else {
+            return Ok(Status::Skip);
+        }
+
+        let mut layer = Layer::new(&IGMP_CLASS, data);
+        let typ = TYPE_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        if let Some(attr) = get_type(typ) {
+            layer.add_attr(attr!(attr, range: 0..1));
+        }
+
+        if typ == 0x22 {
+            // Version 3 Membership Report
+            layer.add_attr(attr!(&RECORDS_ATTR, range: 6..8));
+            let records: usize = RECORDS_ATTR_HEADER.try_get(&layer)?.try_into()?;
+            let mut offset = 8;
+            for _ in 0..records {
+                let aux_len = layer.data().try_get(offset + 1)? as usize * 4;
+                let sources = (layer.data().try_get(offset + 2)? as usize) << 8
+                    | layer.data().try_get(offset + 3)? as usize;
+                let len = 8 + sources * 4 + aux_len;
+                if offset + len > layer.data().len() {
+                    break;
+                }
+                layer.add_attr(attr!(&RECORD_ATTR, range: offset..offset + len));
+                layer.add_attr(attr!(&RECORD_TYPE_ATTR, range: offset..offset + 1));
+                layer.add_attr(attr!(&RECORD_GROUP_ATTR, range: offset + 4..offset + 8));
+                for i in 0..sources {
+                    let start = offset + 8 + i * 4;
+                    layer.add_attr(attr!(&RECORD_SOURCE_ATTR, range: start..start + 4));
+                }
+                offset += len;
+            }
+        } else {
+            layer.add_attr(attr!(&MAX_RESP_ATTR, range: 1..2));
+            layer.add_attr(attr!(&GROUP_ATTR, range: 4..8));
+
+            // Version 3 Membership Query
+            if typ == 0x11 && layer.data().len() >= 12 {
+                layer.add_attr(attr!(&QUERY_SUPPRESS_ATTR, range: 8..9));
+                layer.add_attr(attr!(&QUERY_QRV_ATTR, range: 8..9));
+                layer.add_attr(attr!(&QUERY_QQIC_ATTR, range: 9..10));
+                layer.add_attr(attr!(&QUERY_SOURCES_ATTR, range: 10..12));
+                let sources: usize = QUERY_SOURCES_ATTR_HEADER.try_get(&layer)?.try_into()?;
+                for i in 0..sources {
+                    let start = 12 + i * 4;
+                    if start + 4 > layer.data().len() {
+                        break;
+                    }
+                    layer.add_attr(attr!(&QUERY_SOURCE_ATTR, range: start..start + 4));
+                }
+            }
+        }
+
+        parent.add_child(layer);
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct IgmpDecoder {}
+
+impl Decoder for IgmpDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(IgmpWorker {})
+    }
+
+    fn metadata(&self) -> Metadata {
+        let mut fields = IGMP_CLASS.fields();
+        fields.extend(
+            [
+                &*MAX_RESP_ATTR,
+                &*GROUP_ATTR,
+                &*QUERY_SUPPRESS_ATTR,
+                &*QUERY_QRV_ATTR,
+                &*QUERY_QQIC_ATTR,
+                &*QUERY_SOURCES_ATTR,
+                &*QUERY_SOURCE_ATTR,
+                &*RECORDS_ATTR,
+                &*RECORD_ATTR,
+                &*RECORD_TYPE_ATTR,
+                &*RECORD_GROUP_ATTR,
+                &*RECORD_SOURCE_ATTR,
+            ]
+                .iter()
+                .map(|attr| Field::from(*attr)),
+        );
+        fields.extend((0..=0xff).filter_map(get_type).map(Field::from));
+        Metadata {
+            id: "app.genet.decoder.igmp".into(),
+            exec_type: ExecType::ParallelSync,
+            consumes: vec![String::from("@data:igmp")],
+            fields,
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(IGMP_CLASS, "igmp",
+    header: &TYPE_ATTR_HEADER,
+    header: attr!(&CHECKSUM_ATTR, range: 2..4)
+);
+
+def_attr!(TYPE_ATTR_HEADER,  &TYPE_ATTR, range: 0..1);
+def_attr!(RECORDS_ATTR_HEADER,  &RECORDS_ATTR, range: 6..8);
+def_attr!(QUERY_SOURCES_ATTR_HEADER,  &QUERY_SOURCES_ATTR, range: 10..12);
+
+def_attr_class!(TYPE_ATTR, "igmp.type",
+    typ: "@enum",
+    cast: cast::UInt8()
+);
+
+def_attr_class!(MAX_RESP_ATTR, "igmp.maxRespTime", cast: cast::UInt8());
+
+def_attr_class!(CHECKSUM_ATTR, "igmp.checksum", cast: cast::UInt16BE());
+
+def_attr_class!(GROUP_ATTR, "igmp.group",
+    typ: "@ipv4:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(QUERY_SUPPRESS_ATTR, "igmp.suppress",
+    cast: cast::UInt8().map(|v| v & 0b0000_1000 != 0)
+);
+
+def_attr_class!(QUERY_QRV_ATTR, "igmp.qrv",
+    cast: cast::UInt8().map(|v| v & 0b0000_0111)
+);
+
+def_attr_class!(QUERY_QQIC_ATTR, "igmp.qqic", cast: cast::UInt8());
+
+def_attr_class!(QUERY_SOURCES_ATTR, "igmp.numSources", cast: cast::UInt16BE());
+
+def_attr_class!(QUERY_SOURCE_ATTR, "igmp.source",
+    typ: "@ipv4:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(RECORDS_ATTR, "igmp.numRecords", cast: cast::UInt16BE());
+
+def_attr_class!(RECORD_ATTR, "igmp.record",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(RECORD_TYPE_ATTR, "igmp.record.type", cast: cast::UInt8());
+
+def_attr_class!(RECORD_GROUP_ATTR, "igmp.record.group",
+    typ: "@ipv4:addr",
+    cast: cast::ByteSlice()
+);
+
+def_attr_class!(RECORD_SOURCE_ATTR, "igmp.record.source",
+    typ: "@ipv4:addr",
+    cast: cast::ByteSlice()
+);
+
+fn get_type(val: u64) -> Option<&'static AttrClass> {
+    match val {
+        0x11 => Some(attr_class_lazy!("igmp.type.membershipQuery", typ: "@novalue", value: true)),
+        0x12 => Some(attr_class_lazy!("igmp.type.v1MembershipReport", typ: "@novalue", value: true)),
+        0x16 => Some(attr_class_lazy!("igmp.type.v2MembershipReport", typ: "@novalue", value: true)),
+        0x17 => Some(attr_class_lazy!("igmp.type.leaveGroup", typ: "@novalue", value: true)),
+        0x22 => Some(attr_class_lazy!("igmp.type.v3MembershipReport", typ: "@novalue", value: true)),
+        _ => None,
+    }
+}
+
+genet_decoders!(IgmpDecoder {});
+
+#[cfg(test)]
+mod tests {
+    use genet_sdk::{decoder::*, layer::attr_value, prelude::*};
+    use IgmpWorker;
+
+    def_layer_class!(IPV4_CLASS, "ipv4");
+
+    fn decode(data: &'static [u8]) -> &'static Layer {
+        let mut ctx = Context::default();
+        let mut ipv4 = Layer::new(&IPV4_CLASS, data);
+        ipv4.add_payload(Payload::new(data, "@data:igmp"));
+        let mut parent = Parent::from_mut_ref(&mut ipv4);
+        let status = IgmpWorker {}.decode(&mut ctx, &LayerStack::from_slice(&[]), &mut parent);
+        match status {
+            Ok(Status::Done) => {}
+            _ => panic!("not decoded"),
+        }
+        assert_eq!(parent.children().len(), 1);
+        unsafe { &*parent.children()[0] }
+    }
+
+    fn count(layer: &Layer, id: Token) -> usize {
+        layer.attrs().iter().filter(|attr| attr.id() == id).count()
+    }
+
+    #[test]
+    fn v2_report() {
+        let layer = decode(b"\x16\x00\x00\x00\xe0\x00\x00\xfb");
+        assert!(layer.attr(token!("igmp.type.v2MembershipReport")).is_some());
+        assert_eq!(
+            attr_value::<Vec<u8>>(layer, token!("igmp.group")).unwrap(),
+            vec![224, 0, 0, 251]
+        );
+    }
+
+    #[test]
+    fn v3_query() {
+        let layer = decode(
+            b"\x11\x64\x00\x00\xe0\x00\x00\xfb\x02\x7d\x00\x02\
+              \x0a\x00\x00\x01\x0a\x00\x00\x02",
+        );
+        assert!(layer.attr(token!("igmp.type.membershipQuery")).is_some());
+        assert_eq!(attr_value::<u64>(layer, token!("igmp.qqic")).unwrap(), 125);
+        assert_eq!(attr_value::<u64>(layer, token!("igmp.numSources")).unwrap(), 2);
+        assert_eq!(count(layer, token!("igmp.source")), 2);
+    }
+
+    #[test]
+    fn v3_report() {
+        let layer = decode(
+            b"\x22\x00\x00\x00\x00\x00\x00\x02\
+              \x04\x00\x00\x00\xe0\x00\x00\xfb\
+              \x01\x00\x00\x01\xe0\x00\x00\xfc\x0a\x00\x00\x01",
+        );
+        assert!(layer.attr(token!("igmp.type.v3MembershipReport")).is_some());
+        assert_eq!(count(layer, token!("igmp.record")), 2);
+        assert_eq!(count(layer, token!("igmp.record.source")), 1);
+        assert_eq!(
+            attr_value::<Vec<u8>>(layer, token!("igmp.record.source")).unwrap(),
+            vec![10, 0, 0, 1]
+        );
+    }
+
+    #[test]
+    fn v3_report_truncated() {
+        let layer = decode(
+            b"\x22\x00\x00\x00\x00\x00\x00\x02\
+              \x04\x00\x00\x00\xe0\x00\x00\xfb\
+              \x01\x00\x00\x04\xe0\x00\x00\xfc\x0a\x00\x00\x01",
+        );
+        assert_eq!(count(layer, token!("igmp.record")), 1);
+        assert_eq!(count(layer, token!("igmp.record.source")), 0);
+    }
+}
@@ -0,0 +1,124 @@
+extern crate genet_sdk;
+
+use genet_sdk::{decoder::*, layer::attr_value, prelude::*, reassembly::Reassembler};
+
+/// Seconds to keep incomplete datagrams by default.
+const DEFAULT_TIMEOUT: u64 = 30;
+
+const MAX_DATAGRAM_SIZE: usize = 65535;
+
+struct IPv4ReassemblyWorker {
+    reassembler: Reassembler<(Vec<u8>, Vec<u8>, u16, u8)>,
+}
+
+impl IPv4ReassemblyWorker {
+    fn new(timeout: u64) -> IPv4ReassemblyWorker {
+        IPv4ReassemblyWorker {
+            reassembler: Reassembler::new(timeout),
+        }
+    }
+}
+
+impl Worker for IPv4ReassemblyWorker {
+    fn decode(
+        &mut self,
+        ctx: &mut Context,
+        stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() != token!("ipv4") {
+            return Ok(Status::Skip);
+        }
+
+        let (data, typ) = if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:ipv4:fragment"))
+        {
+            (payload.data(), payload.typ())
+        } else {
+            return Ok(Status::Skip);
+        };
+
+        let key = {
+            let src: ByteSlice = attr_value(parent, token!("ipv4.src"))?;
+            let dst: ByteSlice = attr_value(parent, token!("ipv4.dst"))?;
+            let id: u16 = attr_value(parent, token!("ipv4.id"))?;
+            let proto: u8 = attr_value(parent, token!("ipv4.protocol"))?;
+            (src.to_vec(), dst.to_vec(), id, proto)
+        };
+        let more: u8 = attr_value(parent, token!("ipv4.flags.moreFragments"))?;
+        let offset: usize = attr_value(parent, token!("ipv4.fragmentOffset"))?;
+        let offset = offset * 8;
+        if offset + data.len() > MAX_DATAGRAM_SIZE {
+            return Ok(Status::Done);
+        }
+
+        let timestamp = stack
+            .bottom()
+            .and_then(|layer| attr_value(layer, token!("link.timestamp.sec")).ok())
+            .unwrap_or(0);
+
+        let frame = ctx.frame_index();
+        if let Some(datagram) = self
+            .reassembler
+            .put(key, timestamp, frame, offset, &data, more == 0)
+        {
+            parent.add_attr(attr!(&FRAGMENTS_ATTR, value: datagram.frames.len() as u64));
+            for frame in &datagram.frames {
+                parent.add_attr(attr!(&FRAGMENTS_FRAME_ATTR, value: u64::from(*frame)));
+            }
+            parent.add_attr(attr!(&FRAGMENTS_LENGTH_ATTR, value: datagram.payload.len() as u64));
+            if datagram.overlap {
+                parent.add_attr(attr!(&FRAGMENTS_OVERLAP_ATTR));
+            }
+            parent.add_payload(Payload::new(datagram.payload, typ));
+        }
+
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct IPv4ReassemblyDecoder {}
+
+impl Decoder for IPv4ReassemblyDecoder {
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        let timeout = ctx
+            .get_config("@genet/ipv4.reassemblyTimeout")
+            .parse()
+            .unwrap_or(DEFAULT_TIMEOUT);
+        Box::new(IPv4ReassemblyWorker::new(timeout))
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.ipv4-reassembly".into(),
+            exec_type: ExecType::SerialSync,
+            consumes: vec![String::from("@data:ipv4:fragment")],
+            fields: [
+                &*FRAGMENTS_ATTR,
+                &*FRAGMENTS_FRAME_ATTR,
+                &*FRAGMENTS_LENGTH_ATTR,
+                &*FRAGMENTS_OVERLAP_ATTR,
+            ]
+                .iter()
+                .map(|class| Field::from(*class))
+                .collect(),
+            ..Metadata::default()
+        }
+    }
+}
+
+def_attr_class!(FRAGMENTS_ATTR, "ipv4.fragments");
+
+def_attr_class!(FRAGMENTS_FRAME_ATTR, "ipv4.fragments.frame");
+
+def_attr_class!(FRAGMENTS_LENGTH_ATTR, "ipv4.fragments.length");
+
+def_attr_class!(FRAGMENTS_OVERLAP_ATTR, "ipv4.fragments.overlap",
+    typ: "@novalue",
+    value: true
+);
+
+genet_decoders!(IPv4ReassemblyDecoder {});
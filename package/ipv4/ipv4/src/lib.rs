@@ -1,6 +1,6 @@
 extern crate genet_sdk;
 
-use genet_sdk::{cast, decoder::*, prelude::*};
+use genet_sdk::{cast, decoder::*, error::Error, prelude::*};
 
 struct IPv4Worker {}
 
@@ -24,11 +24,60 @@ impl Worker for IPv4Worker {
         }
 
         let mut layer = Layer::new(&IPV4_CLASS, data);
+        let header_len: usize = HLEN_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        let header_len = header_len * 4;
+        if header_len < 20 {
+            return Err(Error::new("invalid header length").into());
+        }
+        let total_len: usize = LENGTH_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        let total_len = total_len.min(layer.data().len());
+
+        let header = layer.data().try_get(..header_len)?;
+        layer.add_attr(attr!(&CHECKSUM_VALID_ATTR, range: 10..12, value: checksum(&header) == 0));
+
+        let mut offset = 20;
+        while offset < header_len {
+            let typ = layer.data().try_get(offset)?;
+            if typ == 0 {
+                layer.add_attr(attr!(&OPTIONS_END_ATTR, range: offset..offset + 1));
+                break;
+            }
+            if typ == 1 {
+                layer.add_attr(attr!(&OPTIONS_NOP_ATTR, range: offset..offset + 1));
+                offset += 1;
+                continue;
+            }
+            let len = layer.data().try_get(offset + 1)? as usize;
+            if len < 2 || offset + len > header_len {
+                break;
+            }
+            let range = offset..offset + len;
+            match typ {
+                0x07 => layer.add_attr(attr!(&OPTIONS_RR_ATTR, range: range)),
+                0x44 => layer.add_attr(attr!(&OPTIONS_TS_ATTR, range: range)),
+                0x82 => layer.add_attr(attr!(&OPTIONS_SECURITY_ATTR, range: range)),
+                0x83 => layer.add_attr(attr!(&OPTIONS_LSRR_ATTR, range: range)),
+                0x89 => layer.add_attr(attr!(&OPTIONS_SSRR_ATTR, range: range)),
+                0x94 => layer.add_attr(attr!(&OPTIONS_RA_ATTR, range: range)),
+                _ => {}
+            }
+            offset += len;
+        }
+        if header_len > 20 {
+            layer.add_attr(attr!(&OPTIONS_ATTR, range: 20..header_len));
+        }
+
         let proto = PROTO_ATTR_HEADER.try_get(&layer)?.try_into()?;
         if let Some((typ, attr)) = get_proto(proto) {
             layer.add_attr(attr!(attr, range: 9..10));
-            let payload = layer.data().try_get(20..)?;
-            layer.add_payload(Payload::new(payload, typ));
+            let payload = layer.data().try_get(header_len..total_len)?;
+            let more: u8 = FLAGS_MF_ATTR_HEADER.try_get(&layer)?.try_into()?;
+            let offset: u16 = OFFSET_ATTR_HEADER.try_get(&layer)?.try_into()?;
+            if more != 0 || offset > 0 {
+                layer.add_payload(Payload::with_typ(payload, "@data:ipv4:fragment", typ));
+            } else {
+                layer.add_payload(Payload::new(payload, typ));
+            }
         }
 
         parent.add_child(layer);
@@ -45,8 +94,29 @@ impl Decoder for IPv4Decoder {
     }
 
     fn metadata(&self) -> Metadata {
+        let mut fields = IPV4_CLASS.fields();
+        fields.extend(
+            [
+                &*CHECKSUM_VALID_ATTR,
+                &*OPTIONS_ATTR,
+                &*OPTIONS_END_ATTR,
+                &*OPTIONS_NOP_ATTR,
+                &*OPTIONS_RR_ATTR,
+                &*OPTIONS_TS_ATTR,
+                &*OPTIONS_SECURITY_ATTR,
+                &*OPTIONS_LSRR_ATTR,
+                &*OPTIONS_SSRR_ATTR,
+                &*OPTIONS_RA_ATTR,
+            ]
+                .iter()
+                .map(|attr| Field::from(*attr)),
+        );
+        fields.extend((0..=0xff).filter_map(get_proto).map(|(_, attr)| Field::from(attr)));
         Metadata {
+            id: "app.genet.decoder.ipv4".into(),
             exec_type: ExecType::ParallelSync,
+            consumes: vec![String::from("@data:ipv4")],
+            fields,
             ..Metadata::default()
         }
     }
@@ -56,15 +126,15 @@ def_layer_class!(IPV4_CLASS, "ipv4",
     alias: "_.src" "ipv4.src",
     alias: "_.dst" "ipv4.dst",
     header: attr!(&VERSION_ATTR, bit_range: 0 0..4),
-    header: attr!(&HLEN_ATTR, bit_range: 0 4..8),
+    header: &HLEN_ATTR_HEADER,
     header: attr!(&TOS_ATTR, range: 1..2),
-    header: attr!(&LENGTH_ATTR, range: 2..4),
+    header: &LENGTH_ATTR_HEADER,
     header: attr!(&ID_ATTR, range: 4..6),
     header: attr!(&FLAGS_ATTR, bit_range: 6 0..1),
     header: attr!(&FLAGS_RV_ATTR, bit_range: 6 1..2),
     header: attr!(&FLAGS_DF_ATTR, bit_range: 6 2..3),
-    header: attr!(&FLAGS_MF_ATTR, bit_range: 6 3..4),
-    header: attr!(&OFFSET_ATTR, bit_range: 6 4..16),
+    header: &FLAGS_MF_ATTR_HEADER,
+    header: &OFFSET_ATTR_HEADER,
     header: attr!(&TTL_ATTR, range: 8..9),
     header: &PROTO_ATTR_HEADER,
     header: attr!(&CHECKSUM_ATTR, range: 10..12),
@@ -72,6 +142,14 @@ def_layer_class!(IPV4_CLASS, "ipv4",
     header: attr!(&DST_ATTR, range: 16..20)
 );
 
+def_attr!(HLEN_ATTR_HEADER, &HLEN_ATTR, bit_range: 0 4..8);
+
+def_attr!(LENGTH_ATTR_HEADER, &LENGTH_ATTR, range: 2..4);
+
+def_attr!(FLAGS_MF_ATTR_HEADER, &FLAGS_MF_ATTR, bit_range: 6 3..4);
+
+def_attr!(OFFSET_ATTR_HEADER, &OFFSET_ATTR, bit_range: 6 4..16);
+
 def_attr!(PROTO_ATTR_HEADER,  &PROTO_ATTR, range: 9..10);
 
 def_attr_class!(VERSION_ATTR, "ipv4.version",
@@ -118,6 +196,47 @@ def_attr_class!(PROTO_ATTR, "ipv4.protocol",
 
 def_attr_class!(CHECKSUM_ATTR, "ipv4.checksum", cast: cast::UInt16BE());
 
+def_attr_class!(CHECKSUM_VALID_ATTR, "ipv4.checksum.valid");
+
+def_attr_class!(OPTIONS_ATTR, "ipv4.options",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(OPTIONS_END_ATTR, "ipv4.options.end",
+    typ: "@novalue",
+    value: true
+);
+
+def_attr_class!(OPTIONS_NOP_ATTR, "ipv4.options.nop",
+    typ: "@novalue",
+    value: true
+);
+
+def_attr_class!(OPTIONS_RR_ATTR, "ipv4.options.recordRoute",
+    cast: cast::Ranged(cast::ByteSlice(), 3..)
+);
+
+def_attr_class!(OPTIONS_TS_ATTR, "ipv4.options.timestamp",
+    cast: cast::Ranged(cast::ByteSlice(), 4..)
+);
+
+def_attr_class!(OPTIONS_SECURITY_ATTR, "ipv4.options.security",
+    cast: cast::Ranged(cast::ByteSlice(), 2..)
+);
+
+def_attr_class!(OPTIONS_LSRR_ATTR, "ipv4.options.looseSourceRoute",
+    cast: cast::Ranged(cast::ByteSlice(), 3..)
+);
+
+def_attr_class!(OPTIONS_SSRR_ATTR, "ipv4.options.strictSourceRoute",
+    cast: cast::Ranged(cast::ByteSlice(), 3..)
+);
+
+def_attr_class!(OPTIONS_RA_ATTR, "ipv4.options.routerAlert",
+    cast: cast::Ranged(cast::UInt16BE(), 2..)
+);
+
 def_attr_class!(SRC_ATTR, "ipv4.src",
     typ: "@ipv4:addr",
     cast: cast::ByteSlice()
@@ -128,6 +247,18 @@ def_attr_class!(DST_ATTR, "ipv4.dst",
     cast: cast::ByteSlice()
 );
 
+/// Returns the one's complement sum of the header, which is zero if the checksum is correct.
+fn checksum(header: &[u8]) -> u16 {
+    let mut sum = header
+        .chunks(2)
+        .map(|c| u32::from(c[0]) << 8 | u32::from(*c.get(1).unwrap_or(&0)))
+        .sum::<u32>();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
 fn get_proto(val: u64) -> Option<(Token, &'static AttrClass)> {
     match val {
         0x01 => Some((
@@ -151,3 +282,110 @@ fn get_proto(val: u64) -> Option<(Token, &'static AttrClass)> {
 }
 
 genet_decoders!(IPv4Decoder {});
+
+#[cfg(test)]
+mod tests {
+    use genet_sdk::{decoder::*, layer::attr_value, prelude::*, variant::Variant};
+    use IPv4Worker;
+
+    def_layer_class!(ETH_CLASS, "eth");
+
+    fn decode(data: &'static [u8]) -> Result<&'static Layer> {
+        let mut ctx = Context::default();
+        let mut eth = Layer::new(&ETH_CLASS, data);
+        eth.add_payload(Payload::new(data, "@data:ipv4"));
+        let mut parent = Parent::from_mut_ref(&mut eth);
+        IPv4Worker {}.decode(&mut ctx, &LayerStack::from_slice(&[]), &mut parent)?;
+        assert_eq!(parent.children().len(), 1);
+        Ok(unsafe { &*parent.children()[0] })
+    }
+
+    fn checksum_valid(layer: &Layer) -> Variant {
+        let attr = layer.attr(token!("ipv4.checksum.valid")).unwrap();
+        attr.try_get(layer).unwrap()
+    }
+
+    #[test]
+    fn options() {
+        let layer = decode(
+            b"\x46\x00\x00\x1c\x12\x34\x40\x00\x40\x06\x7f\xa1\
+              \x0a\x00\x00\x01\x0a\x00\x00\x02\x94\x04\x00\x00abcd",
+        ).unwrap();
+        assert_eq!(attr_value::<u64>(layer, token!("ipv4.headerLength")).unwrap(), 6);
+        assert_eq!(checksum_valid(layer), Variant::Bool(true));
+        assert!(layer.attr(token!("ipv4.options")).is_some());
+        assert_eq!(
+            attr_value::<u64>(layer, token!("ipv4.options.routerAlert")).unwrap(),
+            0
+        );
+        assert!(layer.attr(token!("ipv4.protocol.tcp")).is_some());
+
+        let payload = &layer.payloads()[0];
+        assert_eq!(payload.id(), token!("@data:tcp"));
+        assert_eq!(&payload.data()[..], b"abcd");
+    }
+
+    #[test]
+    fn padding() {
+        // Ethernet pads short frames, and the padding is not part of the payload.
+        let layer = decode(
+            b"\x45\x00\x00\x18\x12\x34\x40\x00\x40\x11\x14\x9f\
+              \x0a\x00\x00\x01\x0a\x00\x00\x02abcd\x00\x00\x00\x00\x00\x00",
+        ).unwrap();
+        assert!(layer.attr(token!("ipv4.options")).is_none());
+        assert_eq!(checksum_valid(layer), Variant::Bool(true));
+        let payload = &layer.payloads()[0];
+        assert_eq!(payload.id(), token!("@data:udp"));
+        assert_eq!(&payload.data()[..], b"abcd");
+    }
+
+    #[test]
+    fn bad_checksum() {
+        let layer = decode(
+            b"\x45\x00\x00\x18\x12\x34\x40\x00\x40\x11\x14\x9e\
+              \x0a\x00\x00\x01\x0a\x00\x00\x02abcd",
+        ).unwrap();
+        assert_eq!(checksum_valid(layer), Variant::Bool(false));
+        assert_eq!(&layer.payloads()[0].data()[..], b"abcd");
+    }
+
+    #[test]
+    fn fragment() {
+        let layer = decode(
+            b"\x45\x00\x00\x18\x12\x34\x20\x00\x40\x06\x34\xaa\
+              \x0a\x00\x00\x01\x0a\x00\x00\x02abcd",
+        ).unwrap();
+        let payload = &layer.payloads()[0];
+        assert_eq!(payload.id(), token!("@data:ipv4:fragment"));
+        assert_eq!(payload.typ(), token!("@data:tcp"));
+    }
+
+    #[test]
+    fn invalid_length() {
+        // The header length is below 20 bytes.
+        assert!(
+            decode(
+                b"\x44\x00\x00\x18\x12\x34\x40\x00\x40\x11\x14\x9f\
+                  \x0a\x00\x00\x01\x0a\x00\x00\x02abcd",
+            ).is_err()
+        );
+
+        // The total length is beyond the data, so the payload ends at the data.
+        let layer = decode(
+            b"\x45\x00\x05\xdc\x12\x34\x40\x00\x40\x11\x14\x9f\
+              \x0a\x00\x00\x01\x0a\x00\x00\x02abcd",
+        ).unwrap();
+        assert_eq!(&layer.payloads()[0].data()[..], b"abcd");
+
+        // The total length is below the header length.
+        assert!(
+            decode(
+                b"\x45\x00\x00\x10\x12\x34\x40\x00\x40\x11\x14\x9f\
+                  \x0a\x00\x00\x01\x0a\x00\x00\x02abcd",
+            ).is_err()
+        );
+
+        // The header is truncated.
+        assert!(decode(b"\x46\x00\x00\x18\x12\x34\x40\x00\x40\x11\x14\x9f").is_err());
+    }
+}
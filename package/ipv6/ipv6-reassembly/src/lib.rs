@@ -0,0 +1,123 @@
+extern crate genet_sdk;
+
+use genet_sdk::{decoder::*, layer::attr_value, prelude::*, reassembly::Reassembler};
+
+/// Seconds to keep incomplete packets by default.
+const DEFAULT_TIMEOUT: u64 = 60;
+
+const MAX_PACKET_SIZE: usize = 65535;
+
+struct IPv6ReassemblyWorker {
+    reassembler: Reassembler<(Vec<u8>, Vec<u8>, u32)>,
+}
+
+impl IPv6ReassemblyWorker {
+    fn new(timeout: u64) -> IPv6ReassemblyWorker {
+        IPv6ReassemblyWorker {
+            reassembler: Reassembler::new(timeout),
+        }
+    }
+}
+
+impl Worker for IPv6ReassemblyWorker {
+    fn decode(
+        &mut self,
+        ctx: &mut Context,
+        stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() != token!("ipv6") {
+            return Ok(Status::Skip);
+        }
+
+        let (data, typ) = if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:ipv6:fragment"))
+        {
+            (payload.data(), payload.typ())
+        } else {
+            return Ok(Status::Skip);
+        };
+
+        let key = {
+            let src: ByteSlice = attr_value(parent, token!("ipv6.src"))?;
+            let dst: ByteSlice = attr_value(parent, token!("ipv6.dst"))?;
+            let id: u32 = attr_value(parent, token!("ipv6.fragment.id"))?;
+            (src.to_vec(), dst.to_vec(), id)
+        };
+        let more: u8 = attr_value(parent, token!("ipv6.fragment.moreFragments"))?;
+        let offset: usize = attr_value(parent, token!("ipv6.fragment.offset"))?;
+        let offset = offset * 8;
+        if offset + data.len() > MAX_PACKET_SIZE {
+            return Ok(Status::Done);
+        }
+
+        let timestamp = stack
+            .bottom()
+            .and_then(|layer| attr_value(layer, token!("link.timestamp.sec")).ok())
+            .unwrap_or(0);
+
+        let frame = ctx.frame_index();
+        if let Some(datagram) = self
+            .reassembler
+            .put(key, timestamp, frame, offset, &data, more == 0)
+        {
+            parent.add_attr(attr!(&FRAGMENTS_ATTR, value: datagram.frames.len() as u64));
+            for frame in &datagram.frames {
+                parent.add_attr(attr!(&FRAGMENTS_FRAME_ATTR, value: u64::from(*frame)));
+            }
+            parent.add_attr(attr!(&FRAGMENTS_LENGTH_ATTR, value: datagram.payload.len() as u64));
+            if datagram.overlap {
+                parent.add_attr(attr!(&FRAGMENTS_OVERLAP_ATTR));
+            }
+            parent.add_payload(Payload::new(datagram.payload, typ));
+        }
+
+        Ok(Status::Done)
+    }
+}
+
+#[derive(Clone)]
+struct IPv6ReassemblyDecoder {}
+
+impl Decoder for IPv6ReassemblyDecoder {
+    fn new_worker(&self, ctx: &Context) -> Box<Worker> {
+        let timeout = ctx
+            .get_config("@genet/ipv6.reassemblyTimeout")
+            .parse()
+            .unwrap_or(DEFAULT_TIMEOUT);
+        Box::new(IPv6ReassemblyWorker::new(timeout))
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.decoder.ipv6-reassembly".into(),
+            exec_type: ExecType::SerialSync,
+            consumes: vec![String::from("@data:ipv6:fragment")],
+            fields: [
+                &*FRAGMENTS_ATTR,
+                &*FRAGMENTS_FRAME_ATTR,
+                &*FRAGMENTS_LENGTH_ATTR,
+                &*FRAGMENTS_OVERLAP_ATTR,
+            ]
+                .iter()
+                .map(|class| Field::from(*class))
+                .collect(),
+            ..Metadata::default()
+        }
+    }
+}
+
+def_attr_class!(FRAGMENTS_ATTR, "ipv6.fragments");
+
+def_attr_class!(FRAGMENTS_FRAME_ATTR, "ipv6.fragments.frame");
+
+def_attr_class!(FRAGMENTS_LENGTH_ATTR, "ipv6.fragments.length");
+
+def_attr_class!(FRAGMENTS_OVERLAP_ATTR, "ipv6.fragments.overlap",
+    typ: "@novalue",
+    value: true
+);
+
+genet_decoders!(IPv6ReassemblyDecoder {});
@@ -1,6 +1,6 @@
 extern crate genet_sdk;
 
-use genet_sdk::{cast, decoder::*, prelude::*};
+use genet_sdk::{cast, decoder::*, error::Error, prelude::*};
 
 struct IPv6Worker {}
 
@@ -24,31 +24,88 @@ impl Worker for IPv6Worker {
         }
 
         let mut layer = Layer::new(&IPV6_CLASS, data);
-        let nheader = NHEADER_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        let payload_len: usize = LENGTH_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        let end = if payload_len == 0 {
+            layer.data().len()
+        } else {
+            (40 + payload_len).min(layer.data().len())
+        };
 
+        let mut range = NHEADER_ATTR_HEADER.range();
+        let mut offset = 40;
+        let mut fragment = false;
         loop {
-            match nheader {
-                // TODO:
-                // case 0 | 60 # Hop-by-Hop Options, Destination Options
-                // case 43  # Routing
-                // case 44  # Fragment
-                // case 51  # Authentication Header
-                // case 50  # Encapsulating Security Payload
-                // case 135 # Mobility
-                // No Next Header
-                59 => break,
+            let nheader = layer.data().try_get(range.start)?;
+            let len = match nheader {
+                // Hop-by-Hop Options, Routing, Destination Options
+                0 | 43 | 60 => (layer.data().try_get(offset + 1)? as usize + 1) * 8,
+                // Fragment
+                44 => 8,
+                // Authentication Header
+                51 => (layer.data().try_get(offset + 1)? as usize + 2) * 4,
                 _ => break,
+            };
+            if offset + len > end {
+                return Err(Error::new("truncated extension header").into());
+            }
+            let next = offset..offset + 1;
+            match nheader {
+                0 => {
+                    layer.add_attr(attr!(&HOP_ATTR, range: offset..offset + len));
+                    layer.add_attr(attr!(&HOP_NHEADER_ATTR, range: next.clone()));
+                    layer.add_attr(attr!(&HOP_OPTIONS_ATTR, range: offset + 2..offset + len));
+                }
+                43 => {
+                    layer.add_attr(attr!(&ROUTING_ATTR, range: offset..offset + len));
+                    layer.add_attr(attr!(&ROUTING_NHEADER_ATTR, range: next.clone()));
+                    layer.add_attr(attr!(&ROUTING_TYPE_ATTR, range: offset + 2..offset + 3));
+                    layer.add_attr(attr!(&ROUTING_LEFT_ATTR, range: offset + 3..offset + 4));
+                    layer.add_attr(attr!(&ROUTING_DATA_ATTR, range: offset + 4..offset + len));
+                }
+                44 => {
+                    layer.add_attr(attr!(&FRAGMENT_ATTR, range: offset..offset + len));
+                    layer.add_attr(attr!(&FRAGMENT_NHEADER_ATTR, range: next.clone()));
+                    let offset_attr = attr!(&FRAGMENT_OFFSET_ATTR, range: offset + 2..offset + 4);
+                    let more_attr = attr!(&FRAGMENT_MF_ATTR, range: offset + 3..offset + 4);
+                    let frag_offset: u16 = offset_attr.try_get(&layer)?.try_into()?;
+                    let more: u8 = more_attr.try_get(&layer)?.try_into()?;
+                    fragment = frag_offset > 0 || more != 0;
+                    layer.add_attr(offset_attr);
+                    layer.add_attr(more_attr);
+                    layer.add_attr(attr!(&FRAGMENT_ID_ATTR, range: offset + 4..offset + 8));
+                }
+                51 => {
+                    layer.add_attr(attr!(&AUTH_ATTR, range: offset..offset + len));
+                    layer.add_attr(attr!(&AUTH_NHEADER_ATTR, range: next.clone()));
+                    layer.add_attr(attr!(&AUTH_SPI_ATTR, range: offset + 4..offset + 8));
+                    layer.add_attr(attr!(&AUTH_SEQ_ATTR, range: offset + 8..offset + 12));
+                }
+                _ => {
+                    layer.add_attr(attr!(&DEST_ATTR, range: offset..offset + len));
+                    layer.add_attr(attr!(&DEST_NHEADER_ATTR, range: next.clone()));
+                    layer.add_attr(attr!(&DEST_OPTIONS_ATTR, range: offset + 2..offset + len));
+                }
+            }
+            range = next;
+            offset += len;
+
+            // The rest of the headers are in the fragmented part.
+            if fragment {
+                break;
             }
         }
 
-        let range = NHEADER_ATTR_HEADER.range();
         let proto_attr = attr!(&PROTOCOL_ATTR, range: range.clone());
         let proto = proto_attr.try_get(&layer)?.try_into()?;
         layer.add_attr(proto_attr);
         if let Some((typ, attr)) = get_proto(proto) {
             layer.add_attr(attr!(attr, range: range.clone()));
-            let payload = layer.data().try_get(40..)?;
-            layer.add_payload(Payload::new(payload, typ));
+            let payload = layer.data().try_get(offset..end)?;
+            if fragment {
+                layer.add_payload(Payload::with_typ(payload, "@data:ipv6:fragment", typ));
+            } else {
+                layer.add_payload(Payload::new(payload, typ));
+            }
         }
 
         parent.add_child(layer);
@@ -65,8 +122,40 @@ impl Decoder for IPv6Decoder {
     }
 
     fn metadata(&self) -> Metadata {
+        let mut fields = IPV6_CLASS.fields();
+        fields.extend(
+            [
+                &*HOP_ATTR,
+                &*HOP_NHEADER_ATTR,
+                &*HOP_OPTIONS_ATTR,
+                &*ROUTING_ATTR,
+                &*ROUTING_NHEADER_ATTR,
+                &*ROUTING_TYPE_ATTR,
+                &*ROUTING_LEFT_ATTR,
+                &*ROUTING_DATA_ATTR,
+                &*FRAGMENT_ATTR,
+                &*FRAGMENT_NHEADER_ATTR,
+                &*FRAGMENT_OFFSET_ATTR,
+                &*FRAGMENT_MF_ATTR,
+                &*FRAGMENT_ID_ATTR,
+                &*AUTH_ATTR,
+                &*AUTH_NHEADER_ATTR,
+                &*AUTH_SPI_ATTR,
+                &*AUTH_SEQ_ATTR,
+                &*DEST_ATTR,
+                &*DEST_NHEADER_ATTR,
+                &*DEST_OPTIONS_ATTR,
+                &*PROTOCOL_ATTR,
+            ]
+                .iter()
+                .map(|attr| Field::from(*attr)),
+        );
+        fields.extend((0..=0xff).filter_map(get_proto).map(|(_, attr)| Field::from(attr)));
         Metadata {
+            id: "app.genet.decoder.ipv6".into(),
             exec_type: ExecType::ParallelSync,
+            consumes: vec![String::from("@data:ipv6")],
+            fields,
             ..Metadata::default()
         }
     }
@@ -78,13 +167,15 @@ def_layer_class!(IPV6_CLASS, "ipv6",
     header: attr!(&VERSION_ATTR, bit_range: 0 0..4),
     header: attr!(&TRAFFIC_ATTR, bit_range: 0 4..12),
     header: attr!(&FLOW_ATTR, bit_range: 1 4..24),
-    header: attr!(&LENGTH_ATTR, range: 4..6),
+    header: &LENGTH_ATTR_HEADER,
     header: &NHEADER_ATTR_HEADER,
     header: attr!(&HLIMIT_ATTR, range: 7..8),
     header: attr!(&SRC_ATTR, range: 8..24),
     header: attr!(&DST_ATTR, range: 24..40)
 );
 
+def_attr!(LENGTH_ATTR_HEADER, &LENGTH_ATTR, range: 4..6);
+
 def_attr!(NHEADER_ATTR_HEADER,  &NHEADER_ATTR, range: 6..7);
 
 def_attr_class!(VERSION_ATTR, "ipv6.version",
@@ -101,7 +192,7 @@ def_attr_class!(FLOW_ATTR, "ipv6.flowLabel",
             .map(|v| (((v[2] as u32) & 0xf) << 16) | ((v[1] as u32) << 8) | v[2] as u32)
 );
 
-def_attr_class!(LENGTH_ATTR, "ipv6.payloadLength", cast: cast::UInt8());
+def_attr_class!(LENGTH_ATTR, "ipv6.payloadLength", cast: cast::UInt16BE());
 
 def_attr_class!(NHEADER_ATTR, "ipv6.nextHeader", cast: cast::UInt8());
 
@@ -117,6 +208,69 @@ def_attr_class!(DST_ATTR, "ipv6.dst",
     cast: cast::ByteSlice()
 );
 
+def_attr_class!(HOP_ATTR, "ipv6.hopByHop",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(HOP_NHEADER_ATTR, "ipv6.hopByHop.nextHeader", cast: cast::UInt8());
+
+def_attr_class!(HOP_OPTIONS_ATTR, "ipv6.hopByHop.options", cast: cast::ByteSlice());
+
+def_attr_class!(ROUTING_ATTR, "ipv6.routing",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(ROUTING_NHEADER_ATTR, "ipv6.routing.nextHeader", cast: cast::UInt8());
+
+def_attr_class!(ROUTING_TYPE_ATTR, "ipv6.routing.type", cast: cast::UInt8());
+
+def_attr_class!(ROUTING_LEFT_ATTR, "ipv6.routing.segmentsLeft", cast: cast::UInt8());
+
+def_attr_class!(ROUTING_DATA_ATTR, "ipv6.routing.data", cast: cast::ByteSlice());
+
+def_attr_class!(FRAGMENT_ATTR, "ipv6.fragment",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(FRAGMENT_NHEADER_ATTR, "ipv6.fragment.nextHeader", cast: cast::UInt8());
+
+def_attr_class!(FRAGMENT_OFFSET_ATTR, "ipv6.fragment.offset",
+    cast: cast::UInt16BE().map(|v| v >> 3)
+);
+
+def_attr_class!(FRAGMENT_MF_ATTR, "ipv6.fragment.moreFragments",
+    cast: cast::UInt8().map(|v| v & 0b0000_0001 != 0)
+);
+
+def_attr_class!(FRAGMENT_ID_ATTR, "ipv6.fragment.id", cast: cast::UInt32BE());
+
+def_attr_class!(AUTH_ATTR, "ipv6.authentication",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(AUTH_NHEADER_ATTR, "ipv6.authentication.nextHeader", cast: cast::UInt8());
+
+def_attr_class!(AUTH_SPI_ATTR, "ipv6.authentication.spi", cast: cast::UInt32BE());
+
+def_attr_class!(AUTH_SEQ_ATTR, "ipv6.authentication.sequence", cast: cast::UInt32BE());
+
+def_attr_class!(DEST_ATTR, "ipv6.destinationOptions",
+    typ: "@nested",
+    value: true
+);
+
+def_attr_class!(DEST_NHEADER_ATTR, "ipv6.destinationOptions.nextHeader",
+    cast: cast::UInt8()
+);
+
+def_attr_class!(DEST_OPTIONS_ATTR, "ipv6.destinationOptions.options",
+    cast: cast::ByteSlice()
+);
+
 def_attr_class!(PROTOCOL_ATTR, "ipv6.protocol",
     typ: "@enum",
     cast: cast::UInt8()
@@ -137,7 +291,7 @@ fn get_proto(val: u64) -> Option<(Token, &'static AttrClass)> {
             attr_class_lazy!("ipv6.protocol.udp", typ: "@novalue", value: true),
         )),
         0x3a => Some((
-            token!("@data:icmp"),
+            token!("@data:icmpv6"),
             attr_class_lazy!("ipv6.protocol.icmp", typ: "@novalue", value: true),
         )),
         _ => None,
@@ -145,3 +299,90 @@ fn get_proto(val: u64) -> Option<(Token, &'static AttrClass)> {
 }
 
 genet_decoders!(IPv6Decoder {});
+
+#[cfg(test)]
+mod tests {
+    use genet_sdk::{decoder::*, layer::attr_value, prelude::*};
+    use IPv6Worker;
+
+    def_layer_class!(ETH_CLASS, "eth");
+
+    fn decode(data: &'static [u8]) -> Result<&'static Layer> {
+        let mut ctx = Context::default();
+        let mut eth = Layer::new(&ETH_CLASS, data);
+        eth.add_payload(Payload::new(data, "@data:ipv6"));
+        let mut parent = Parent::from_mut_ref(&mut eth);
+        IPv6Worker {}.decode(&mut ctx, &LayerStack::from_slice(&[]), &mut parent)?;
+        assert_eq!(parent.children().len(), 1);
+        Ok(unsafe { &*parent.children()[0] })
+    }
+
+    #[test]
+    fn extension_headers() {
+        let layer = decode(
+            b"\x60\x00\x00\x00\x00\x14\x00\x40\
+              \xfe\x80\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\
+              \xfe\x80\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x02\
+              \x2b\x00\x01\x04\x00\x00\x00\x00\
+              \x06\x00\x00\x01\x00\x00\x00\x00\
+              abcd",
+        ).unwrap();
+        assert!(layer.attr(token!("ipv6.hopByHop")).is_some());
+        assert_eq!(
+            attr_value::<u64>(layer, token!("ipv6.hopByHop.nextHeader")).unwrap(),
+            43
+        );
+        assert!(layer.attr(token!("ipv6.routing")).is_some());
+        assert_eq!(
+            attr_value::<u64>(layer, token!("ipv6.routing.segmentsLeft")).unwrap(),
+            1
+        );
+        assert_eq!(attr_value::<u64>(layer, token!("ipv6.protocol")).unwrap(), 6);
+        assert!(layer.attr(token!("ipv6.protocol.tcp")).is_some());
+
+        let payload = &layer.payloads()[0];
+        assert_eq!(payload.id(), token!("@data:tcp"));
+        assert_eq!(&payload.data()[..], b"abcd");
+    }
+
+    #[test]
+    fn fragment() {
+        let layer = decode(
+            b"\x60\x00\x00\x00\x00\x0c\x2c\x40\
+              \xfe\x80\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\
+              \xfe\x80\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x02\
+              \x06\x00\x00\x09\x00\x00\x00\x2a\
+              abcd",
+        ).unwrap();
+        assert_eq!(attr_value::<u64>(layer, token!("ipv6.fragment.offset")).unwrap(), 1);
+        assert_eq!(attr_value::<u64>(layer, token!("ipv6.fragment.id")).unwrap(), 42);
+
+        let payload = &layer.payloads()[0];
+        assert_eq!(payload.id(), token!("@data:ipv6:fragment"));
+        assert_eq!(payload.typ(), token!("@data:tcp"));
+        assert_eq!(&payload.data()[..], b"abcd");
+    }
+
+    #[test]
+    fn truncated_extension_header() {
+        // The Hop-by-Hop Options header claims 16 bytes but only 8 bytes follow.
+        assert!(
+            decode(
+                b"\x60\x00\x00\x00\x00\x08\x00\x40\
+                  \xfe\x80\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\
+                  \xfe\x80\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x02\
+                  \x06\x01\x01\x04\x00\x00\x00\x00",
+            ).is_err()
+        );
+
+        // The header ends before its length field.
+        assert!(
+            decode(
+                b"\x60\x00\x00\x00\x00\x00\x00\x40\
+                  \xfe\x80\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\
+                  \xfe\x80\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x02\
+                  \x06",
+            ).is_err()
+        );
+    }
+}
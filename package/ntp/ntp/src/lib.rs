@@ -8,37 +8,21 @@ impl Worker for NtpWorker {
     fn decode(
         &mut self,
         _ctx: &mut Context,
-        stack: &LayerStack,
+        _stack: &LayerStack,
         parent: &mut Parent,
     ) -> Result<Status> {
-        if parent.id() != token!("udp") {
-            return Ok(Status::Skip);
-        }
-
         let data;
 
-        if let Some(payload) = parent.payloads().iter().next() {
+        if let Some(payload) = parent
+            .payloads()
+            .iter()
+            .find(|p| p.id() == token!("@data:ntp"))
+        {
             data = payload.data();
         } else {
             return Ok(Status::Skip);
         }
 
-        let parent_src: u16 = stack
-            .attr(token!("udp.src"))
-            .unwrap()
-            .try_get(parent)?
-            .try_into()?;
-
-        let parent_dst: u16 = stack
-            .attr(token!("udp.dst"))
-            .unwrap()
-            .try_get(parent)?
-            .try_into()?;
-
-        if parent_src != 123 && parent_dst != 123 {
-            return Ok(Status::Skip);
-        }
-
         let mut layer = Layer::new(&NTP_CLASS, data);
         let leap_type = LEAP_ATTR_HEADER.try_get(&layer)?.try_into()?;
 
@@ -46,6 +30,14 @@ impl Worker for NtpWorker {
         if let Some(attr) = leap {
             layer.add_attr(attr!(attr, range: 0..1));
         }
+        if leap_type == 3 {
+            layer.add_annotation(Annotation::new(
+                Severity::Warning,
+                "protocol",
+                "ntp.unsynchronized",
+                0..1,
+            ));
+        }
 
         let mode_type = MODE_ATTR_HEADER.try_get(&layer)?.try_into()?;
 
@@ -75,8 +67,17 @@ impl Decoder for NtpDecoder {
     }
 
     fn metadata(&self) -> Metadata {
+        let mut fields = NTP_CLASS.fields();
+        fields.push(Field::from(&*ID_IP_ATTR));
+        fields.extend((0..4).filter_map(get_leap).map(Field::from));
+        fields.extend((0..8).filter_map(get_mode).map(Field::from));
+        fields.push(Field::new("protocol", ""));
+        fields.push(Field::new("ntp.unsynchronized", ""));
         Metadata {
+            id: "app.genet.decoder.ntp".into(),
             exec_type: ExecType::ParallelSync,
+            consumes: vec![String::from("@data:ntp")],
+            fields,
             ..Metadata::default()
         }
     }
@@ -13,10 +13,17 @@ class OutputView {
               filters: [{
                 name: 'Pcap File',
                 extensions: ['pcap'],
+              }, {
+                name: 'Pcapng File',
+                extensions: ['pcapng'],
               }],
             })
             if (typeof file !== 'undefined') {
-              vnode.attrs.callback('app.genet.writer.pcap-file', { file })
+              if (file.endsWith('.pcapng')) {
+                vnode.attrs.callback('app.genet.writer.pcapng-file', { file })
+              } else {
+                vnode.attrs.callback('app.genet.writer.pcap-file', { file })
+              }
             }
           },
         })
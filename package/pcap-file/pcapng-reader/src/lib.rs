@@ -0,0 +1,784 @@
+extern crate byteorder;
+extern crate genet_sdk;
+extern crate serde;
+extern crate serde_json;
+
+#[macro_use]
+extern crate serde_derive;
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use genet_sdk::{prelude::*, reader::*};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufReader, Error, ErrorKind, Read, Seek, SeekFrom},
+};
+
+const BLOCK_SHB: u32 = 0x0a0d_0d0a;
+const BLOCK_IDB: u32 = 0x0000_0001;
+const BLOCK_SPB: u32 = 0x0000_0003;
+const BLOCK_NRB: u32 = 0x0000_0004;
+const BLOCK_ISB: u32 = 0x0000_0005;
+const BLOCK_EPB: u32 = 0x0000_0006;
+
+const BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+
+const OPT_END: u16 = 0;
+const OPT_COMMENT: u16 = 1;
+const OPT_IF_NAME: u16 = 2;
+const OPT_IF_DESCRIPTION: u16 = 3;
+const OPT_IF_TSRESOL: u16 = 9;
+const OPT_IF_TSOFFSET: u16 = 14;
+const OPT_EPB_FLAGS: u16 = 2;
+const OPT_EPB_DROPCOUNT: u16 = 4;
+const OPT_ISB_IFDROP: u16 = 5;
+const OPT_ISB_OSDROP: u16 = 7;
+
+#[derive(Deserialize)]
+struct Arg {
+    file: String,
+}
+
+#[derive(Clone)]
+struct PcapNgFileReader {}
+
+impl Reader for PcapNgFileReader {
+    fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
+        let arg: Arg = serde_json::from_str(arg)?;
+        let file = File::open(&arg.file)?;
+        Ok(Box::new(PcapNgFileWorker::new(BufReader::new(file))?))
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.reader.pcapng-file".into(),
+            filters: vec![FileType::new("Pcapng File", &["pcapng"])],
+            fields: [
+                &*TYPE_CLASS,
+                &*INTERFACE_CLASS,
+                &*SNAPLEN_CLASS,
+                &*IF_NAME_CLASS,
+                &*IF_DESCRIPTION_CLASS,
+                &*IF_COMMENT_CLASS,
+                &*LENGTH_CLASS,
+                &*TS_CLASS,
+                &*TS_SEC_CLASS,
+                &*TS_USEC_CLASS,
+                &*TS_NSEC_CLASS,
+                &*COMMENT_CLASS,
+                &*FLAGS_CLASS,
+                &*DROP_COUNT_CLASS,
+                &*IF_DROP_COUNT_CLASS,
+                &*OS_DROP_COUNT_CLASS,
+            ]
+                .iter()
+                .map(|class| Field::from(*class))
+                .collect(),
+            ..Metadata::default()
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Interface {
+    link_class: Fixed<LayerClass>,
+    snaplen: u32,
+    ts_units: u64,
+    ts_offset: i64,
+
+    /// Statistics blocks of the interface by their offsets.
+    statistics: BTreeMap<u64, Statistics>,
+}
+
+/// Drop counters of an interface statistics block.
+#[derive(Clone, Default)]
+struct Statistics {
+    if_drop: Option<u64>,
+    os_drop: Option<u64>,
+}
+
+#[derive(Default)]
+struct PacketOptions {
+    comment: Option<String>,
+    flags: Option<u32>,
+    drop_count: Option<u64>,
+}
+
+/// Byte order and interfaces of a section, used for random access.
+struct Section {
+    le: bool,
+    interfaces: Vec<Interface>,
+}
+
+struct PcapNgFileWorker<R> {
+    le: bool,
+    reader: R,
+    interfaces: Vec<Interface>,
+    sections: BTreeMap<u64, Section>,
+    offset: u64,
+    block: u64,
+    scanned: u64,
+    positions: Vec<u64>,
+    error: Option<Error>,
+}
+
+impl<R: Read + Seek> PcapNgFileWorker<R> {
+    fn new(reader: R) -> io::Result<Self> {
+        let mut worker = PcapNgFileWorker {
+            le: true,
+            reader,
+            interfaces: Vec::new(),
+            sections: BTreeMap::new(),
+            offset: 0,
+            block: 0,
+            scanned: 0,
+            positions: Vec::new(),
+            error: None,
+        };
+
+        match worker.read_block()? {
+            Some((BLOCK_SHB, _)) => Ok(worker),
+            _ => Err(Error::new(ErrorKind::InvalidData, "wrong magic number")),
+        }
+    }
+
+    fn u16(&self, data: &[u8]) -> u16 {
+        if self.le {
+            LittleEndian::read_u16(data)
+        } else {
+            BigEndian::read_u16(data)
+        }
+    }
+
+    fn u32(&self, data: &[u8]) -> u32 {
+        if self.le {
+            LittleEndian::read_u32(data)
+        } else {
+            BigEndian::read_u32(data)
+        }
+    }
+
+    fn u64(&self, data: &[u8]) -> u64 {
+        if self.le {
+            LittleEndian::read_u64(data)
+        } else {
+            BigEndian::read_u64(data)
+        }
+    }
+
+    fn options<'a>(&self, mut data: &'a [u8]) -> Vec<(u16, &'a [u8])> {
+        let mut options = Vec::new();
+        while data.len() >= 4 {
+            let code = self.u16(&data[0..2]);
+            let len = self.u16(&data[2..4]) as usize;
+            if code == OPT_END || data.len() < 4 + len {
+                break;
+            }
+            options.push((code, &data[4..4 + len]));
+            data = &data[(4 + pad(len)).min(data.len())..];
+        }
+        options
+    }
+
+    /// Reads the next block and returns its type and body.
+    ///
+    /// Returns None at the end of the file.
+    fn read_block(&mut self) -> io::Result<Option<(u32, Vec<u8>)>> {
+        self.block = self.offset;
+        let mut header = [0u8; 8];
+        if let Err(err) = self.reader.read_exact(&mut header[0..4]) {
+            return if err.kind() == ErrorKind::UnexpectedEof {
+                Ok(None)
+            } else {
+                Err(err)
+            };
+        }
+        self.reader.read_exact(&mut header[4..8])?;
+
+        let mut body_offset = 0;
+        let mut body = Vec::new();
+        if LittleEndian::read_u32(&header[0..4]) == BLOCK_SHB {
+            let mut magic = [0u8; 4];
+            self.reader.read_exact(&mut magic)?;
+            self.le = match LittleEndian::read_u32(&magic) {
+                BYTE_ORDER_MAGIC => true,
+                0x4d3c_2b1a => false,
+                _ => return Err(Error::new(ErrorKind::InvalidData, "wrong byte-order magic")),
+            };
+            self.interfaces.clear();
+            self.sections.insert(
+                self.block,
+                Section {
+                    le: self.le,
+                    interfaces: Vec::new(),
+                },
+            );
+            body.extend_from_slice(&magic);
+            body_offset = 4;
+        }
+
+        let typ = self.u32(&header[0..4]);
+        let len = self.u32(&header[4..8]) as usize;
+        if len < 12 + body_offset || len % 4 != 0 {
+            return Err(Error::new(ErrorKind::InvalidData, "wrong block length"));
+        }
+
+        body.resize(len - 12, 0);
+        self.reader.read_exact(&mut body[body_offset..])?;
+
+        let mut trailer = [0u8; 4];
+        self.reader.read_exact(&mut trailer)?;
+        if self.u32(&trailer) as usize != len {
+            return Err(Error::new(ErrorKind::InvalidData, "block length mismatch"));
+        }
+
+        self.offset += len as u64;
+        self.scanned = self.scanned.max(self.offset);
+        Ok(Some((typ, body)))
+    }
+
+    fn read_interface(&mut self, body: &[u8]) -> io::Result<()> {
+        if body.len() < 8 {
+            return Err(Error::new(ErrorKind::InvalidData, "truncated interface block"));
+        }
+        let link = self.u16(&body[0..2]);
+        let snaplen = self.u32(&body[4..8]);
+
+        let mut builder = LayerClass::builder(format!("[link-{}]", link))
+            .header(attr!(&TYPE_CLASS, value: i64::from(link)))
+            .header(attr!(&INTERFACE_CLASS, value: self.interfaces.len() as u64))
+            .header(attr!(&SNAPLEN_CLASS, value: u64::from(snaplen)));
+        let mut ts_units = 1_000_000;
+        let mut ts_offset = 0;
+        for (code, value) in self.options(&body[8..]) {
+            match code {
+                OPT_IF_NAME => {
+                    builder = builder.header(attr!(&IF_NAME_CLASS, value: string(value)));
+                }
+                OPT_IF_DESCRIPTION => {
+                    builder = builder.header(attr!(&IF_DESCRIPTION_CLASS, value: string(value)));
+                }
+                OPT_COMMENT => {
+                    builder = builder.header(attr!(&IF_COMMENT_CLASS, value: string(value)));
+                }
+                OPT_IF_TSRESOL if !value.is_empty() => {
+                    let exp = u32::from(value[0] & 0x7f);
+                    let units = if value[0] & 0x80 == 0 {
+                        10u64.checked_pow(exp)
+                    } else {
+                        2u64.checked_pow(exp)
+                    };
+                    ts_units = units
+                        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "wrong if_tsresol"))?;
+                }
+                OPT_IF_TSOFFSET if value.len() >= 8 => {
+                    ts_offset = self.u64(value) as i64;
+                }
+                _ => {}
+            }
+        }
+
+        let interface = Interface {
+            link_class: Fixed::new(builder.build()),
+            snaplen,
+            ts_units,
+            ts_offset,
+            statistics: BTreeMap::new(),
+        };
+        if let Some(section) = self.sections.values_mut().next_back() {
+            section.interfaces.push(interface.clone());
+        }
+        self.interfaces.push(interface);
+        Ok(())
+    }
+
+    fn read_statistics(&mut self, body: &[u8]) -> io::Result<()> {
+        if body.len() < 12 {
+            return Err(Error::new(ErrorKind::InvalidData, "truncated statistics block"));
+        }
+        let id = self.u32(&body[0..4]) as usize;
+        self.interface(id)?;
+
+        let mut statistics = Statistics::default();
+        for (code, value) in self.options(&body[12..]) {
+            match code {
+                OPT_ISB_IFDROP if value.len() >= 8 => statistics.if_drop = Some(self.u64(value)),
+                OPT_ISB_OSDROP if value.len() >= 8 => statistics.os_drop = Some(self.u64(value)),
+                _ => {}
+            }
+        }
+
+        let block = self.block;
+        if let Some(section) = self.sections.values_mut().next_back() {
+            if let Some(interface) = section.interfaces.get_mut(id) {
+                interface.statistics.insert(block, statistics.clone());
+            }
+        }
+        self.interfaces[id].statistics.insert(block, statistics);
+        Ok(())
+    }
+
+    fn read_packet_options(&self, data: &[u8]) -> PacketOptions {
+        let mut opts = PacketOptions::default();
+        for (code, value) in self.options(data) {
+            match code {
+                OPT_COMMENT => opts.comment = Some(String::from_utf8_lossy(value).into_owned()),
+                OPT_EPB_FLAGS if value.len() >= 4 => opts.flags = Some(self.u32(value)),
+                OPT_EPB_DROPCOUNT if value.len() >= 8 => opts.drop_count = Some(self.u64(value)),
+                _ => {}
+            }
+        }
+        opts
+    }
+
+    fn interface(&self, id: usize) -> io::Result<&Interface> {
+        self.interfaces
+            .get(id)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "unknown interface id"))
+    }
+
+    /// Adds the drop counters of the last statistics block before the packet.
+    fn add_statistics(&self, interface: &Interface, layer: &mut Layer) {
+        if let Some((_, statistics)) = interface.statistics.range(..self.block).next_back() {
+            if let Some(if_drop) = statistics.if_drop {
+                layer.add_attr(attr!(&IF_DROP_COUNT_CLASS, value: if_drop));
+            }
+            if let Some(os_drop) = statistics.os_drop {
+                layer.add_attr(attr!(&OS_DROP_COUNT_CLASS, value: os_drop));
+            }
+        }
+    }
+
+    fn read_enhanced_packet(&self, mut body: Vec<u8>) -> io::Result<Layer> {
+        if body.len() < 20 {
+            return Err(Error::new(ErrorKind::InvalidData, "truncated packet block"));
+        }
+        let interface = self.interface(self.u32(&body[0..4]) as usize)?;
+        let ts = (u64::from(self.u32(&body[4..8])) << 32) | u64::from(self.u32(&body[8..12]));
+        let incl_len = self.u32(&body[12..16]) as usize;
+        let orig_len = self.u32(&body[16..20]);
+        if body.len() < 20 + incl_len {
+            return Err(Error::new(ErrorKind::InvalidData, "truncated packet data"));
+        }
+        let options = self.read_packet_options(&body[(20 + pad(incl_len)).min(body.len())..]);
+
+        body.truncate(20 + incl_len);
+        let data = body.split_off(20);
+
+        let mut layer = Layer::new(interface.link_class.clone(), ByteSlice::from(data));
+        layer.add_attr(attr!(&LENGTH_CLASS, value: u64::from(orig_len)));
+
+        let sec = (ts / interface.ts_units) as i64 + interface.ts_offset;
+        let frac = ts % interface.ts_units;
+        let nsec = (u128::from(frac) * 1_000_000_000 / u128::from(interface.ts_units)) as u64;
+        layer.add_attr(attr!(
+            &TS_CLASS,
+            value: sec as f64 + frac as f64 / interface.ts_units as f64
+        ));
+        layer.add_attr(attr!(&TS_SEC_CLASS, value: sec as u64));
+        layer.add_attr(attr!(&TS_USEC_CLASS, value: nsec / 1000));
+        layer.add_attr(attr!(&TS_NSEC_CLASS, value: nsec));
+
+        if let Some(comment) = options.comment {
+            layer.add_attr(attr!(&COMMENT_CLASS, value: comment.into_boxed_str()));
+        }
+        if let Some(flags) = options.flags {
+            layer.add_attr(attr!(&FLAGS_CLASS, value: flags));
+        }
+        if let Some(drop_count) = options.drop_count {
+            layer.add_attr(attr!(&DROP_COUNT_CLASS, value: drop_count));
+        }
+        self.add_statistics(interface, &mut layer);
+        Ok(layer)
+    }
+
+    fn read_simple_packet(&self, mut body: Vec<u8>) -> io::Result<Layer> {
+        if body.len() < 4 {
+            return Err(Error::new(ErrorKind::InvalidData, "truncated packet block"));
+        }
+        let interface = self.interface(0)?;
+        let orig_len = self.u32(&body[0..4]);
+        let mut incl_len = body.len() - 4;
+        if interface.snaplen > 0 {
+            incl_len = incl_len.min(interface.snaplen as usize);
+        }
+        incl_len = incl_len.min(orig_len as usize);
+
+        body.truncate(4 + incl_len);
+        let data = body.split_off(4);
+
+        let mut layer = Layer::new(interface.link_class.clone(), ByteSlice::from(data));
+        layer.add_attr(attr!(&LENGTH_CLASS, value: u64::from(orig_len)));
+        self.add_statistics(interface, &mut layer);
+        Ok(layer)
+    }
+
+    fn seek(&mut self, offset: u64) -> io::Result<()> {
+        self.reader.seek(SeekFrom::Start(offset))?;
+        self.offset = offset;
+        Ok(())
+    }
+
+    /// Reads blocks up to the offset, keeping track of sections and interfaces.
+    fn scan(&mut self, offset: u64) -> io::Result<()> {
+        let scanned = self.scanned;
+        self.seek(scanned)?;
+        if let Some(section) = self.sections.values().next_back() {
+            self.le = section.le;
+            self.interfaces = section.interfaces.clone();
+        }
+        while self.offset < offset {
+            let mut header = [0u8; 8];
+            self.reader.read_exact(&mut header)?;
+            let typ = self.u32(&header[0..4]);
+            let len = u64::from(self.u32(&header[4..8]));
+            if len < 12 || len % 4 != 0 {
+                return Err(Error::new(ErrorKind::InvalidData, "wrong block length"));
+            }
+            if typ == BLOCK_SHB || typ == BLOCK_IDB || typ == BLOCK_ISB {
+                let block = self.offset;
+                self.seek(block)?;
+                match self.read_block()? {
+                    Some((BLOCK_IDB, body)) => self.read_interface(&body)?,
+                    Some((BLOCK_ISB, body)) => self.read_statistics(&body)?,
+                    _ => {}
+                }
+            } else {
+                let next = self.offset + len;
+                self.seek(next)?;
+                self.scanned = self.scanned.max(next);
+            }
+        }
+        Ok(())
+    }
+
+    fn read_packet_at(&mut self, position: u64) -> io::Result<Layer> {
+        if position >= self.scanned {
+            self.scan(position)?;
+        } else if let Some((_, section)) = self.sections.range(..=position).next_back() {
+            self.le = section.le;
+            self.interfaces = section.interfaces.clone();
+            self.seek(position)?;
+        }
+        match self.read_block()? {
+            Some((BLOCK_EPB, body)) => self.read_enhanced_packet(body),
+            Some((BLOCK_SPB, body)) => self.read_simple_packet(body),
+            _ => Err(Error::new(ErrorKind::InvalidData, "no packet block")),
+        }
+    }
+
+    fn read_one(&mut self) -> io::Result<Option<Layer>> {
+        loop {
+            let (typ, body) = if let Some(block) = self.read_block()? {
+                block
+            } else {
+                return Ok(None);
+            };
+            match typ {
+                BLOCK_IDB => self.read_interface(&body)?,
+                BLOCK_EPB => return self.read_enhanced_packet(body).map(Some),
+                BLOCK_SPB => return self.read_simple_packet(body).map(Some),
+                BLOCK_ISB => self.read_statistics(&body)?,
+                // Section headers are handled in read_block, and name resolution
+                // is not exposed to decoders.
+                BLOCK_SHB | BLOCK_NRB => {}
+                _ => {}
+            }
+        }
+    }
+}
+
+fn pad(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+fn string(data: &[u8]) -> Box<str> {
+    let data = match data.iter().position(|&b| b == 0) {
+        Some(end) => &data[..end],
+        None => data,
+    };
+    String::from_utf8_lossy(data).into_owned().into_boxed_str()
+}
+
+const BLOCK_SIZE: usize = 65535;
+
+impl<R: Read + Seek + Send> Worker for PcapNgFileWorker<R> {
+    fn read(&mut self) -> Result<Vec<Layer>> {
+        self.positions.clear();
+        if let Some(err) = self.error.take() {
+            return Err(err.into());
+        }
+        let mut layers = Vec::with_capacity(BLOCK_SIZE);
+        while layers.len() < BLOCK_SIZE {
+            match self.read_one() {
+                Ok(Some(layer)) => {
+                    layers.push(layer);
+                    self.positions.push(self.block);
+                }
+                Ok(None) => {
+                    if layers.is_empty() {
+                        return Err(Error::new(ErrorKind::UnexpectedEof, "end of file").into());
+                    }
+                    break;
+                }
+                Err(err) => {
+                    if layers.is_empty() {
+                        return Err(err.into());
+                    }
+                    // Return the frames read so far and report the error on the next call.
+                    self.error = Some(err);
+                    break;
+                }
+            }
+        }
+        Ok(layers)
+    }
+
+    fn positions(&mut self) -> Vec<u64> {
+        self.positions.clone()
+    }
+
+    fn read_at(&mut self, position: u64) -> Result<Layer> {
+        Ok(self.read_packet_at(position)?)
+    }
+}
+
+def_attr_class!(TYPE_CLASS, "link.type");
+def_attr_class!(INTERFACE_CLASS, "link.interface");
+def_attr_class!(SNAPLEN_CLASS, "link.snaplen");
+def_attr_class!(IF_NAME_CLASS, "link.interface.name");
+def_attr_class!(IF_DESCRIPTION_CLASS, "link.interface.description");
+def_attr_class!(IF_COMMENT_CLASS, "link.interface.comment");
+def_attr_class!(LENGTH_CLASS, "link.length");
+def_attr_class!(TS_CLASS, "link.timestamp",
+    typ: "@datetime:unix"
+);
+def_attr_class!(TS_SEC_CLASS, "link.timestamp.sec");
+def_attr_class!(TS_USEC_CLASS, "link.timestamp.usec");
+def_attr_class!(TS_NSEC_CLASS, "link.timestamp.nsec");
+def_attr_class!(COMMENT_CLASS, "link.comment");
+def_attr_class!(FLAGS_CLASS, "link.flags",
+    typ: "@flags"
+);
+def_attr_class!(DROP_COUNT_CLASS, "link.dropCount");
+def_attr_class!(IF_DROP_COUNT_CLASS, "link.interface.dropCount");
+def_attr_class!(OS_DROP_COUNT_CLASS, "link.interface.osDropCount");
+
+genet_readers!(PcapNgFileReader {});
+
+#[cfg(test)]
+mod tests {
+    use byteorder::{BigEndian, ByteOrder, LittleEndian};
+    use genet_sdk::{prelude::*, reader::Worker, variant::Variant};
+    use std::io::Cursor;
+    use PcapNgFileWorker;
+
+    fn block(le: bool, typ: u32, body: &[u8]) -> Vec<u8> {
+        let len = 12 + ::pad(body.len()) as u32;
+        let mut data = vec![0; len as usize];
+        if le {
+            LittleEndian::write_u32(&mut data[0..4], typ);
+            LittleEndian::write_u32(&mut data[4..8], len);
+            LittleEndian::write_u32(&mut data[len as usize - 4..], len);
+        } else {
+            BigEndian::write_u32(&mut data[0..4], typ);
+            BigEndian::write_u32(&mut data[4..8], len);
+            BigEndian::write_u32(&mut data[len as usize - 4..], len);
+        }
+        data[8..8 + body.len()].copy_from_slice(body);
+        data
+    }
+
+    fn option(le: bool, code: u16, value: &[u8]) -> Vec<u8> {
+        let mut data = vec![0; 4 + ::pad(value.len())];
+        if le {
+            LittleEndian::write_u16(&mut data[0..2], code);
+            LittleEndian::write_u16(&mut data[2..4], value.len() as u16);
+        } else {
+            BigEndian::write_u16(&mut data[0..2], code);
+            BigEndian::write_u16(&mut data[2..4], value.len() as u16);
+        }
+        data[4..4 + value.len()].copy_from_slice(value);
+        data
+    }
+
+    fn shb(le: bool) -> Vec<u8> {
+        let mut body = vec![0x4d, 0x3c, 0x2b, 0x1a, 1, 0, 0, 0];
+        body.extend_from_slice(&[0xff; 8]);
+        if !le {
+            body[0..4].reverse();
+            body[4..8].copy_from_slice(&[0, 1, 0, 0]);
+        }
+        block(le, ::BLOCK_SHB, &body)
+    }
+
+    fn idb(le: bool, link: u16, tsresol: u8, name: &str) -> Vec<u8> {
+        let mut body = vec![0; 8];
+        if le {
+            LittleEndian::write_u16(&mut body[0..2], link);
+            LittleEndian::write_u32(&mut body[4..8], 65535);
+        } else {
+            BigEndian::write_u16(&mut body[0..2], link);
+            BigEndian::write_u32(&mut body[4..8], 65535);
+        }
+        body.extend(option(le, ::OPT_IF_NAME, name.as_bytes()));
+        body.extend(option(le, ::OPT_IF_TSRESOL, &[tsresol]));
+        body.extend(option(le, ::OPT_END, &[]));
+        block(le, ::BLOCK_IDB, &body)
+    }
+
+    fn epb(le: bool, interface: u32, ts: u64, data: &[u8]) -> Vec<u8> {
+        let mut body = vec![0; 20];
+        let fields = [
+            interface,
+            (ts >> 32) as u32,
+            ts as u32,
+            data.len() as u32,
+            data.len() as u32,
+        ];
+        for (i, value) in fields.iter().enumerate() {
+            if le {
+                LittleEndian::write_u32(&mut body[i * 4..i * 4 + 4], *value);
+            } else {
+                BigEndian::write_u32(&mut body[i * 4..i * 4 + 4], *value);
+            }
+        }
+        body.extend_from_slice(data);
+        block(le, ::BLOCK_EPB, &body)
+    }
+
+    fn isb(le: bool, interface: u32, if_drop: u64, os_drop: u64) -> Vec<u8> {
+        let mut body = vec![0; 12];
+        let mut drops = [0; 16];
+        if le {
+            LittleEndian::write_u32(&mut body[0..4], interface);
+            LittleEndian::write_u64(&mut drops[0..8], if_drop);
+            LittleEndian::write_u64(&mut drops[8..16], os_drop);
+        } else {
+            BigEndian::write_u32(&mut body[0..4], interface);
+            BigEndian::write_u64(&mut drops[0..8], if_drop);
+            BigEndian::write_u64(&mut drops[8..16], os_drop);
+        }
+        body.extend(option(le, ::OPT_ISB_IFDROP, &drops[0..8]));
+        body.extend(option(le, ::OPT_ISB_OSDROP, &drops[8..16]));
+        body.extend(option(le, ::OPT_END, &[]));
+        block(le, ::BLOCK_ISB, &body)
+    }
+
+    fn worker(data: Vec<u8>) -> PcapNgFileWorker<Cursor<Vec<u8>>> {
+        PcapNgFileWorker::new(Cursor::new(data)).unwrap()
+    }
+
+    fn get(layer: &Layer, id: &str) -> Variant {
+        layer.attr(Token::from(id)).unwrap().try_get(layer).unwrap()
+    }
+
+    #[test]
+    fn sections() {
+        let mut data = Vec::new();
+        data.extend(shb(true));
+        data.extend(idb(true, 1, 9, "eth0"));
+        data.extend(idb(true, 101, 6, "tun0"));
+        data.extend(epb(true, 1, 1_500_000_250, b"abcd"));
+        data.extend(epb(true, 0, 1_500_000_000_250_000_000, b"efgh"));
+        data.extend(shb(false));
+        data.extend(idb(false, 105, 0x83, "wlan0"));
+        data.extend(epb(false, 0, 12, b"ijkl"));
+
+        let mut worker = worker(data);
+        let layers = worker.read().unwrap();
+        assert_eq!(layers.len(), 3);
+        let positions = worker.positions();
+        assert_eq!(positions.len(), 3);
+
+        assert_eq!(layers[0].id(), Token::from("[link-101]"));
+        assert_eq!(get(&layers[0], "link.interface"), Variant::UInt64(1));
+        assert_eq!(get(&layers[0], "link.snaplen"), Variant::UInt64(65535));
+        assert_eq!(
+            get(&layers[0], "link.interface.name"),
+            Variant::String("tun0".into())
+        );
+        assert_eq!(get(&layers[0], "link.timestamp.sec"), Variant::UInt64(1500));
+        assert_eq!(get(&layers[0], "link.timestamp.nsec"), Variant::UInt64(250_000));
+
+        assert_eq!(layers[1].id(), Token::from("[link-1]"));
+        assert_eq!(
+            get(&layers[1], "link.timestamp.sec"),
+            Variant::UInt64(1_500_000_000)
+        );
+        assert_eq!(get(&layers[1], "link.timestamp.nsec"), Variant::UInt64(250_000_000));
+        assert_eq!(layers[1].data(), ByteSlice::from(&b"efgh"[..]));
+
+        assert_eq!(layers[2].id(), Token::from("[link-105]"));
+        assert_eq!(get(&layers[2], "link.interface"), Variant::UInt64(0));
+        assert_eq!(get(&layers[2], "link.timestamp.sec"), Variant::UInt64(1));
+        assert_eq!(get(&layers[2], "link.timestamp.nsec"), Variant::UInt64(500_000_000));
+        assert_eq!(layers[2].data(), ByteSlice::from(&b"ijkl"[..]));
+
+        let layer = worker.read_at(positions[2]).unwrap();
+        assert_eq!(layer.id(), Token::from("[link-105]"));
+        let layer = worker.read_at(positions[0]).unwrap();
+        assert_eq!(layer.id(), Token::from("[link-101]"));
+    }
+
+    #[test]
+    fn statistics() {
+        for le in &[true, false] {
+            let le = *le;
+            let mut data = Vec::new();
+            data.extend(shb(le));
+            data.extend(idb(le, 1, 6, "eth0"));
+            data.extend(idb(le, 1, 6, "eth1"));
+            data.extend(epb(le, 0, 0, b"abcd"));
+            data.extend(isb(le, 0, 3, 2));
+            data.extend(epb(le, 0, 0, b"efgh"));
+            data.extend(epb(le, 1, 0, b"ijkl"));
+
+            let mut reader = worker(data.clone());
+            let layers = reader.read().unwrap();
+            assert_eq!(layers.len(), 3);
+            assert!(layers[0].attr(Token::from("link.interface.dropCount")).is_none());
+            assert_eq!(get(&layers[1], "link.interface.dropCount"), Variant::UInt64(3));
+            assert_eq!(get(&layers[1], "link.interface.osDropCount"), Variant::UInt64(2));
+            assert!(layers[2].attr(Token::from("link.interface.dropCount")).is_none());
+
+            let positions = reader.positions();
+            let layer = reader.read_at(positions[0]).unwrap();
+            assert!(layer.attr(Token::from("link.interface.dropCount")).is_none());
+
+            let mut reader = worker(data);
+            let layer = reader.read_at(positions[1]).unwrap();
+            assert_eq!(get(&layer, "link.interface.dropCount"), Variant::UInt64(3));
+            assert_eq!(get(&layer, "link.interface.osDropCount"), Variant::UInt64(2));
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        let mut data = Vec::new();
+        data.extend(shb(true));
+        data.extend(idb(true, 1, 6, "eth0"));
+        data.extend(epb(true, 0, 0, b"abcd"));
+        let mut last = epb(true, 0, 0, b"efgh");
+        last.truncate(last.len() - 6);
+        data.extend(last);
+
+        let mut worker = worker(data);
+        assert_eq!(worker.read().unwrap().len(), 1);
+        let err = worker.read().unwrap_err();
+        assert_ne!(err.to_string(), "end of file");
+    }
+
+    #[test]
+    fn zero_block_length() {
+        let mut data = Vec::new();
+        data.extend(shb(true));
+        data.extend(idb(true, 1, 6, "eth0"));
+        let position = data.len() as u64;
+        data.extend(vec![6, 0, 0, 0, 0, 0, 0, 0]);
+        data.extend(epb(true, 0, 0, b"abcd"));
+
+        let mut worker = worker(data);
+        assert!(worker.read_at(position + 28).is_err());
+    }
+}
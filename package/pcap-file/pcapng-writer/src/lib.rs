@@ -0,0 +1,333 @@
+extern crate byteorder;
+extern crate genet_sdk;
+extern crate serde;
+extern crate serde_json;
+
+#[macro_use]
+extern crate serde_derive;
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use genet_sdk::{prelude::*, writer::*};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+const BLOCK_SHB: u32 = 0x0a0d_0d0a;
+const BLOCK_IDB: u32 = 0x0000_0001;
+const BLOCK_EPB: u32 = 0x0000_0006;
+
+const BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+
+const OPT_END: u16 = 0;
+const OPT_COMMENT: u16 = 1;
+const OPT_IF_NAME: u16 = 2;
+const OPT_IF_DESCRIPTION: u16 = 3;
+const OPT_IF_TSRESOL: u16 = 9;
+const OPT_EPB_FLAGS: u16 = 2;
+const OPT_EPB_DROPCOUNT: u16 = 4;
+
+#[derive(Deserialize)]
+struct Arg {
+    file: String,
+}
+
+#[derive(Clone)]
+struct PcapNgFileWriter {}
+
+impl Writer for PcapNgFileWriter {
+    fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
+        let arg: Arg = serde_json::from_str(arg)?;
+        let file = File::create(&arg.file)?;
+        Ok(Box::new(PcapNgFileWorker::new(BufWriter::new(file))?))
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.writer.pcapng-file".into(),
+            filters: vec![FileType::new("Pcapng File", &["pcapng"])],
+            ..Metadata::default()
+        }
+    }
+}
+
+/// Interface of a frame, identified by `link.interface` and `link.type`.
+#[derive(Default)]
+struct Interface {
+    id: Option<u64>,
+    link: u16,
+    snaplen: u32,
+    name: Option<String>,
+    description: Option<String>,
+    comment: Option<String>,
+}
+
+struct PcapNgFileWorker<W> {
+    writer: W,
+    interfaces: HashMap<(Option<u64>, u16), u32>,
+}
+
+impl<W: Write> PcapNgFileWorker<W> {
+    fn new(writer: W) -> Result<Self> {
+        let mut worker = PcapNgFileWorker {
+            writer,
+            interfaces: HashMap::new(),
+        };
+        worker.write_section_header()?;
+        Ok(worker)
+    }
+
+    fn write_block(&mut self, typ: u32, body: &[u8]) -> Result<()> {
+        let len = body.len() as u32 + 12;
+        self.writer.write_u32::<LittleEndian>(typ)?;
+        self.writer.write_u32::<LittleEndian>(len)?;
+        self.writer.write_all(body)?;
+        self.writer.write_u32::<LittleEndian>(len)?;
+        Ok(())
+    }
+
+    fn write_section_header(&mut self) -> Result<()> {
+        let mut body = Vec::new();
+        body.write_u32::<LittleEndian>(BYTE_ORDER_MAGIC)?;
+        body.write_u16::<LittleEndian>(1)?;
+        body.write_u16::<LittleEndian>(0)?;
+        body.write_i64::<LittleEndian>(-1)?;
+        self.write_block(BLOCK_SHB, &body)
+    }
+
+    /// Returns the interface id for the interface,
+    /// writing a new interface description block if necessary.
+    fn interface(&mut self, interface: &Interface) -> Result<u32> {
+        let key = (interface.id, interface.link);
+        if let Some(id) = self.interfaces.get(&key) {
+            return Ok(*id);
+        }
+
+        let mut body = Vec::new();
+        body.write_u16::<LittleEndian>(interface.link)?;
+        body.write_u16::<LittleEndian>(0)?;
+        body.write_u32::<LittleEndian>(interface.snaplen)?;
+        if let Some(name) = &interface.name {
+            write_option(&mut body, OPT_IF_NAME, name.as_bytes())?;
+        }
+        if let Some(description) = &interface.description {
+            write_option(&mut body, OPT_IF_DESCRIPTION, description.as_bytes())?;
+        }
+        if let Some(comment) = &interface.comment {
+            write_option(&mut body, OPT_COMMENT, comment.as_bytes())?;
+        }
+        // Timestamps are always written in nanoseconds.
+        write_option(&mut body, OPT_IF_TSRESOL, &[9])?;
+        write_option(&mut body, OPT_END, &[])?;
+        self.write_block(BLOCK_IDB, &body)?;
+
+        let id = self.interfaces.len() as u32;
+        self.interfaces.insert(key, id);
+        Ok(id)
+    }
+
+    fn write_layer(&mut self, layer: &Layer) -> Result<()> {
+        let data = layer.data();
+        let mut orig_len = data.len() as u32;
+        let mut interface = Interface::default();
+        let mut ts_sec = 0u64;
+        let mut ts_nsec = 0u64;
+        let mut comment: Option<String> = None;
+        let mut flags: Option<u32> = None;
+        let mut drop_count: Option<u64> = None;
+
+        if let Some(attr) = layer.attr(token!("link.length")) {
+            orig_len = attr.try_get(layer)?.try_into()?;
+        }
+        if let Some(attr) = layer.attr(token!("link.type")) {
+            interface.link = attr.try_get(layer)?.try_into()?;
+        }
+        if let Some(attr) = layer.attr(token!("link.snaplen")) {
+            interface.snaplen = attr.try_get(layer)?.try_into()?;
+        }
+        if let Some(attr) = layer.attr(token!("link.interface")) {
+            interface.id = Some(attr.try_get(layer)?.try_into()?);
+        }
+        if let Some(attr) = layer.attr(token!("link.interface.name")) {
+            interface.name = Some(attr.try_get(layer)?.try_into()?);
+        }
+        if let Some(attr) = layer.attr(token!("link.interface.description")) {
+            interface.description = Some(attr.try_get(layer)?.try_into()?);
+        }
+        if let Some(attr) = layer.attr(token!("link.interface.comment")) {
+            interface.comment = Some(attr.try_get(layer)?.try_into()?);
+        }
+        if let Some(attr) = layer.attr(token!("link.timestamp.sec")) {
+            ts_sec = attr.try_get(layer)?.try_into()?;
+        }
+        if let Some(attr) = layer.attr(token!("link.timestamp.nsec")) {
+            ts_nsec = attr.try_get(layer)?.try_into()?;
+        } else if let Some(attr) = layer.attr(token!("link.timestamp.usec")) {
+            let ts_usec: u64 = attr.try_get(layer)?.try_into()?;
+            ts_nsec = ts_usec * 1000;
+        }
+        if let Some(attr) = layer.attr(token!("link.comment")) {
+            comment = Some(attr.try_get(layer)?.try_into()?);
+        }
+        if let Some(attr) = layer.attr(token!("link.flags")) {
+            flags = Some(attr.try_get(layer)?.try_into()?);
+        }
+        if let Some(attr) = layer.attr(token!("link.dropCount")) {
+            drop_count = Some(attr.try_get(layer)?.try_into()?);
+        }
+
+        let interface = self.interface(&interface)?;
+        let ts = ts_sec.wrapping_mul(1_000_000_000).wrapping_add(ts_nsec);
+
+        let mut body = Vec::with_capacity(data.len() + 32);
+        body.write_u32::<LittleEndian>(interface)?;
+        body.write_u32::<LittleEndian>((ts >> 32) as u32)?;
+        body.write_u32::<LittleEndian>(ts as u32)?;
+        body.write_u32::<LittleEndian>(data.len() as u32)?;
+        body.write_u32::<LittleEndian>(orig_len)?;
+        body.write_all(&data)?;
+        body.resize(pad(body.len()), 0);
+
+        if let Some(comment) = &comment {
+            write_option(&mut body, OPT_COMMENT, comment.as_bytes())?;
+        }
+        if let Some(flags) = flags {
+            let mut value = Vec::new();
+            value.write_u32::<LittleEndian>(flags)?;
+            write_option(&mut body, OPT_EPB_FLAGS, &value)?;
+        }
+        if let Some(drop_count) = drop_count {
+            let mut value = Vec::new();
+            value.write_u64::<LittleEndian>(drop_count)?;
+            write_option(&mut body, OPT_EPB_DROPCOUNT, &value)?;
+        }
+        if comment.is_some() || flags.is_some() || drop_count.is_some() {
+            write_option(&mut body, OPT_END, &[])?;
+        }
+
+        self.write_block(BLOCK_EPB, &body)
+    }
+}
+
+fn write_option(body: &mut Vec<u8>, code: u16, value: &[u8]) -> Result<()> {
+    body.write_u16::<LittleEndian>(code)?;
+    body.write_u16::<LittleEndian>(value.len() as u16)?;
+    body.write_all(value)?;
+    body.resize(pad(body.len()), 0);
+    Ok(())
+}
+
+fn pad(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+impl<W: Write + Send> Worker for PcapNgFileWorker<W> {
+    fn write(&mut self, _index: u32, stack: &LayerStack) -> Result<()> {
+        if let Some(layer) = stack.bottom() {
+            self.write_layer(layer)?;
+        }
+        Ok(())
+    }
+}
+
+genet_writers!(PcapNgFileWriter {});
+
+#[cfg(test)]
+mod tests {
+    use byteorder::{ByteOrder, LittleEndian};
+    use genet_sdk::prelude::*;
+    use PcapNgFileWorker;
+
+    def_attr_class!(TYPE_CLASS, "link.type");
+    def_attr_class!(SNAPLEN_CLASS, "link.snaplen");
+    def_attr_class!(INTERFACE_CLASS, "link.interface");
+    def_attr_class!(IF_NAME_CLASS, "link.interface.name");
+    def_attr_class!(IF_DESCRIPTION_CLASS, "link.interface.description");
+    def_attr_class!(TS_SEC_CLASS, "link.timestamp.sec");
+
+    fn layer(class: &Fixed<LayerClass>, ts_sec: u64) -> Layer {
+        let mut layer = Layer::new(class.clone(), ByteSlice::from(&b"abcd"[..]));
+        layer.add_attr(attr!(&TS_SEC_CLASS, value: ts_sec));
+        layer
+    }
+
+    /// Splits the written data into blocks of (type, body).
+    fn blocks(mut data: &[u8]) -> Vec<(u32, &[u8])> {
+        let mut blocks = Vec::new();
+        while !data.is_empty() {
+            let typ = LittleEndian::read_u32(&data[0..4]);
+            let len = LittleEndian::read_u32(&data[4..8]) as usize;
+            assert_eq!(LittleEndian::read_u32(&data[len - 4..len]) as usize, len);
+            blocks.push((typ, &data[8..len - 4]));
+            data = &data[len..];
+        }
+        blocks
+    }
+
+    fn options(mut data: &[u8]) -> Vec<(u16, &[u8])> {
+        let mut options = Vec::new();
+        while !data.is_empty() {
+            let code = LittleEndian::read_u16(&data[0..2]);
+            let len = LittleEndian::read_u16(&data[2..4]) as usize;
+            options.push((code, &data[4..4 + len]));
+            data = &data[4 + ::pad(len)..];
+        }
+        options
+    }
+
+    #[test]
+    fn interfaces() {
+        let interface = |id: u64, name: &str| {
+            Fixed::new(
+                LayerClass::builder("[link-1]")
+                    .header(attr!(&TYPE_CLASS, value: 1i64))
+                    .header(attr!(&SNAPLEN_CLASS, value: 1500u64))
+                    .header(attr!(&INTERFACE_CLASS, value: id))
+                    .header(attr!(&IF_NAME_CLASS, value: Box::<str>::from(name)))
+                    .header(attr!(
+                        &IF_DESCRIPTION_CLASS,
+                        value: Box::<str>::from("description")
+                    )).build(),
+            )
+        };
+        let eth0 = interface(0, "eth0");
+        let eth1 = interface(1, "eth1");
+
+        let mut worker = PcapNgFileWorker::new(Vec::new()).unwrap();
+        worker.write_layer(&layer(&eth0, 1)).unwrap();
+        worker.write_layer(&layer(&eth1, 2)).unwrap();
+        worker.write_layer(&layer(&eth0, 3)).unwrap();
+
+        let blocks = blocks(&worker.writer);
+        let types: Vec<u32> = blocks.iter().map(|(typ, _)| *typ).collect();
+        assert_eq!(
+            types,
+            vec![
+                ::BLOCK_SHB,
+                ::BLOCK_IDB,
+                ::BLOCK_EPB,
+                ::BLOCK_IDB,
+                ::BLOCK_EPB,
+                ::BLOCK_EPB,
+            ]
+        );
+
+        for (i, name) in [(1, "eth0"), (3, "eth1")].iter() {
+            let body = blocks[*i].1;
+            assert_eq!(LittleEndian::read_u16(&body[0..2]), 1);
+            assert_eq!(LittleEndian::read_u32(&body[4..8]), 1500);
+            let options = options(&body[8..]);
+            assert!(options.contains(&(::OPT_IF_NAME, name.as_bytes())));
+            assert!(options.contains(&(::OPT_IF_DESCRIPTION, &b"description"[..])));
+            assert!(options.contains(&(::OPT_IF_TSRESOL, &[9][..])));
+        }
+
+        let interfaces: Vec<u32> = [2, 4, 5]
+            .iter()
+            .map(|i| LittleEndian::read_u32(&blocks[*i].1[0..4]))
+            .collect();
+        assert_eq!(interfaces, vec![0, 1, 0]);
+        assert_eq!(LittleEndian::read_u32(&blocks[5].1[8..12]), 3_000_000_000);
+    }
+}
@@ -3,4 +3,8 @@ module.exports = (sess, arg) => {
     sess.createReader('app.genet.reader.pcap-file', arg)
     return true
   }
+  if (arg.file.endsWith('.pcapng')) {
+    sess.createReader('app.genet.reader.pcapng-file', arg)
+    return true
+  }
 }
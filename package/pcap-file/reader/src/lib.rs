@@ -10,7 +10,7 @@ use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
 use genet_sdk::{prelude::*, reader::*};
 use std::{
     fs::File,
-    io::{self, BufReader, Error, ErrorKind, Read},
+    io::{self, BufReader, Error, ErrorKind, Read, Seek, SeekFrom},
 };
 
 #[derive(Deserialize)]
@@ -25,8 +25,52 @@ impl Reader for PcapFileReader {
     fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
         let arg: Arg = serde_json::from_str(arg)?;
         let file = File::open(&arg.file)?;
-        let mut reader = BufReader::new(file);
+        Ok(Box::new(PcapFileWorker::new(BufReader::new(file))?))
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            id: "app.genet.reader.pcap-file".into(),
+            filters: vec![FileType::new("Pcap File", &["pcap"])],
+            fields: [
+                &*TYPE_CLASS,
+                &*SNAPLEN_CLASS,
+                &*THISZONE_CLASS,
+                &*SIGFIGS_CLASS,
+                &*BIG_ENDIAN_CLASS,
+                &*VERSION_MAJOR_CLASS,
+                &*VERSION_MINOR_CLASS,
+                &*LENGTH_CLASS,
+                &*TRUNCATED_CLASS,
+                &*TS_CLASS,
+                &*TS_SEC_CLASS,
+                &*TS_USEC_CLASS,
+                &*TS_NSEC_CLASS,
+                &*TS_RESOLUTION_CLASS,
+            ]
+                .iter()
+                .map(|class| Field::from(*class))
+                .collect(),
+            ..Metadata::default()
+        }
+    }
+}
+
+/// A worker reading a classic pcap file.
+///
+/// It is public so that the writer can test round trips against it.
+pub struct PcapFileWorker<R> {
+    le: bool,
+    nsec: bool,
+    thiszone: i32,
+    reader: R,
+    link_class: Fixed<LayerClass>,
+    offset: u64,
+    positions: Vec<u64>,
+}
 
+impl<R: Read + Seek> PcapFileWorker<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
         let magic_number = reader.read_u32::<BigEndian>()?;
 
         let (le, nsec) = match magic_number {
@@ -37,7 +81,7 @@ impl Reader for PcapFileReader {
             _ => Err(Error::new(ErrorKind::InvalidData, "wrong magic number")),
         }?;
 
-        let (_ver_major, _var_minor, _thiszone, _sigfigs, _snaplen, network) = if le {
+        let (ver_major, ver_minor, thiszone, sigfigs, snaplen, network) = if le {
             (
                 reader.read_u16::<LittleEndian>()?,
                 reader.read_u16::<LittleEndian>()?,
@@ -57,38 +101,32 @@ impl Reader for PcapFileReader {
             )
         };
 
+        let resolution: u64 = if nsec { 1_000_000_000 } else { 1_000_000 };
         let link_class = Fixed::new(layer_class!(
             format!("[link-{}]", network),
-            header: attr!(&TYPE_CLASS, value: i64::from(network))
+            header: attr!(&TYPE_CLASS, value: i64::from(network)),
+            header: attr!(&SNAPLEN_CLASS, value: u64::from(snaplen)),
+            header: attr!(&THISZONE_CLASS, value: i64::from(thiszone)),
+            header: attr!(&SIGFIGS_CLASS, value: u64::from(sigfigs)),
+            header: attr!(&BIG_ENDIAN_CLASS, value: !le),
+            header: attr!(&VERSION_MAJOR_CLASS, value: u64::from(ver_major)),
+            header: attr!(&VERSION_MINOR_CLASS, value: u64::from(ver_minor)),
+            header: attr!(&TS_RESOLUTION_CLASS, value: resolution)
         ));
 
-        Ok(Box::new(PcapFileWorker {
+        Ok(PcapFileWorker {
             le,
             nsec,
+            thiszone,
             reader,
             link_class,
-        }))
+            offset: 24,
+            positions: Vec::new(),
+        })
     }
 
-    fn metadata(&self) -> Metadata {
-        Metadata {
-            id: "app.genet.reader.pcap-file".into(),
-            filters: vec![FileType::new("Pcap File", &["pcap"])],
-            ..Metadata::default()
-        }
-    }
-}
-
-struct PcapFileWorker {
-    le: bool,
-    nsec: bool,
-    reader: BufReader<File>,
-    link_class: Fixed<LayerClass>,
-}
-
-impl PcapFileWorker {
     fn read_one(&mut self) -> io::Result<Layer> {
-        let (ts_sec, mut ts_usec, inc_len, orig_len) = if self.le {
+        let (ts_sec, ts_frac, inc_len, orig_len) = if self.le {
             (
                 self.reader.read_u32::<LittleEndian>()?,
                 self.reader.read_u32::<LittleEndian>()?,
@@ -104,26 +142,34 @@ impl PcapFileWorker {
             )
         };
 
-        if !self.nsec {
-            ts_usec *= 1000;
-        }
+        let ts_sec = i64::from(ts_sec) + i64::from(self.thiszone);
+        let ts_nsec = if self.nsec {
+            u64::from(ts_frac)
+        } else {
+            u64::from(ts_frac) * 1000
+        };
 
         let mut data = Vec::<u8>::with_capacity(inc_len as usize);
         unsafe {
             data.set_len(inc_len as usize);
         }
         self.reader.read_exact(&mut data)?;
+        self.offset += 16 + u64::from(inc_len);
 
         let payload = ByteSlice::from(data);
         let mut layer = Layer::new(self.link_class.clone(), payload);
 
         layer.add_attr(attr!(&LENGTH_CLASS, value: u64::from(orig_len)));
+        if inc_len < orig_len {
+            layer.add_attr(attr!(&TRUNCATED_CLASS, value: true));
+        }
         layer.add_attr(attr!(
             &TS_CLASS,
-            value: f64::from(ts_sec) + f64::from(ts_usec) / 1_000_000f64
+            value: ts_sec as f64 + ts_nsec as f64 / 1_000_000_000f64
         ));
-        layer.add_attr(attr!(&TS_SEC_CLASS, value: u64::from(ts_sec)));
-        layer.add_attr(attr!(&TS_USEC_CLASS, value: u64::from(ts_usec)));
+        layer.add_attr(attr!(&TS_SEC_CLASS, value: ts_sec as u64));
+        layer.add_attr(attr!(&TS_USEC_CLASS, value: ts_nsec / 1000));
+        layer.add_attr(attr!(&TS_NSEC_CLASS, value: ts_nsec));
 
         Ok(layer)
     }
@@ -131,12 +177,17 @@ impl PcapFileWorker {
 
 const BLOCK_SIZE: usize = 65535;
 
-impl Worker for PcapFileWorker {
+impl<R: Read + Seek + Send> Worker for PcapFileWorker<R> {
     fn read(&mut self) -> Result<Vec<Layer>> {
         let mut layers = Vec::with_capacity(BLOCK_SIZE);
+        self.positions.clear();
         for _ in 0..BLOCK_SIZE {
+            let offset = self.offset;
             match self.read_one() {
-                Ok(layer) => layers.push(layer),
+                Ok(layer) => {
+                    layers.push(layer);
+                    self.positions.push(offset);
+                }
                 Err(err) => {
                     if layers.is_empty() {
                         return Err(err.into());
@@ -146,14 +197,120 @@ impl Worker for PcapFileWorker {
         }
         Ok(layers)
     }
+
+    fn positions(&mut self) -> Vec<u64> {
+        self.positions.clone()
+    }
+
+    fn read_at(&mut self, position: u64) -> Result<Layer> {
+        self.reader.seek(SeekFrom::Start(position))?;
+        self.offset = position;
+        Ok(self.read_one()?)
+    }
 }
 
 def_attr_class!(TYPE_CLASS, "link.type");
+def_attr_class!(SNAPLEN_CLASS, "link.snaplen");
+def_attr_class!(THISZONE_CLASS, "link.thiszone");
+def_attr_class!(SIGFIGS_CLASS, "link.sigfigs");
+def_attr_class!(BIG_ENDIAN_CLASS, "link.bigEndian");
+def_attr_class!(VERSION_MAJOR_CLASS, "link.version.major");
+def_attr_class!(VERSION_MINOR_CLASS, "link.version.minor");
 def_attr_class!(LENGTH_CLASS, "link.length");
+def_attr_class!(TRUNCATED_CLASS, "link.truncated");
 def_attr_class!(TS_CLASS, "link.timestamp",
     typ: "@datetime:unix"
 );
 def_attr_class!(TS_SEC_CLASS, "link.timestamp.sec");
 def_attr_class!(TS_USEC_CLASS, "link.timestamp.usec");
+def_attr_class!(TS_NSEC_CLASS, "link.timestamp.nsec");
+def_attr_class!(TS_RESOLUTION_CLASS, "link.timestamp.resolution");
 
 genet_readers!(PcapFileReader {});
+
+#[cfg(test)]
+mod tests {
+    use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
+    use genet_sdk::{prelude::*, reader::Worker, variant::Variant};
+    use std::io::Cursor;
+    use PcapFileWorker;
+
+    /// Builds a pcap file with records of (ts_sec, ts_frac, orig_len, data).
+    fn file(le: bool, nsec: bool, thiszone: i32, records: &[(u32, u32, u32, &[u8])]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let put = |data: &mut Vec<u8>, value: u32| {
+            if le {
+                data.write_u32::<LittleEndian>(value).unwrap();
+            } else {
+                data.write_u32::<BigEndian>(value).unwrap();
+            }
+        };
+        put(&mut data, if nsec { 0xa1b2_3c4d } else { 0xa1b2_c3d4 });
+        put(&mut data, if le { 0x0004_0002 } else { 0x0002_0004 });
+        for value in &[thiszone as u32, 0, 65535, 1] {
+            put(&mut data, *value);
+        }
+        for (ts_sec, ts_frac, orig_len, payload) in records {
+            for value in &[*ts_sec, *ts_frac, payload.len() as u32, *orig_len] {
+                put(&mut data, *value);
+            }
+            data.extend_from_slice(payload);
+        }
+        data
+    }
+
+    fn get(layer: &Layer, id: &str) -> Variant {
+        layer.attr(Token::from(id)).unwrap().try_get(layer).unwrap()
+    }
+
+    #[test]
+    fn read() {
+        let data = file(
+            true,
+            false,
+            3600,
+            &[(1000, 250, 4, b"abcd"), (1001, 999_999, 8, b"efgh")],
+        );
+        let mut worker = PcapFileWorker::new(Cursor::new(data)).unwrap();
+        let layers = worker.read().unwrap();
+        assert_eq!(layers.len(), 2);
+
+        assert_eq!(layers[0].id(), Token::from("[link-1]"));
+        assert_eq!(get(&layers[0], "link.version.major"), Variant::UInt64(2));
+        assert_eq!(get(&layers[0], "link.version.minor"), Variant::UInt64(4));
+        assert_eq!(get(&layers[0], "link.bigEndian"), Variant::Bool(false));
+        assert_eq!(get(&layers[0], "link.timestamp.sec"), Variant::UInt64(4600));
+        assert_eq!(get(&layers[0], "link.timestamp.usec"), Variant::UInt64(250));
+        assert_eq!(get(&layers[0], "link.timestamp.nsec"), Variant::UInt64(250_000));
+        assert!(layers[0].attr(Token::from("link.truncated")).is_none());
+
+        assert_eq!(get(&layers[1], "link.length"), Variant::UInt64(8));
+        assert_eq!(get(&layers[1], "link.truncated"), Variant::Bool(true));
+        assert_eq!(layers[1].data(), ByteSlice::from(&b"efgh"[..]));
+
+        let positions = worker.positions();
+        assert_eq!(positions, vec![24, 44]);
+        let layer = worker.read_at(positions[1]).unwrap();
+        assert_eq!(get(&layer, "link.timestamp.nsec"), Variant::UInt64(999_999_000));
+    }
+
+    #[test]
+    fn nsec_big_endian() {
+        let data = file(false, true, -60, &[(1000, 123_456_789, 4, b"abcd")]);
+        let mut worker = PcapFileWorker::new(Cursor::new(data)).unwrap();
+        let layers = worker.read().unwrap();
+        assert_eq!(get(&layers[0], "link.bigEndian"), Variant::Bool(true));
+        assert_eq!(get(&layers[0], "link.thiszone"), Variant::Int64(-60));
+        assert_eq!(get(&layers[0], "link.timestamp.sec"), Variant::UInt64(940));
+        assert_eq!(get(&layers[0], "link.timestamp.nsec"), Variant::UInt64(123_456_789));
+        assert_eq!(
+            get(&layers[0], "link.timestamp.resolution"),
+            Variant::UInt64(1_000_000_000)
+        );
+    }
+
+    #[test]
+    fn wrong_magic() {
+        assert!(PcapFileWorker::new(Cursor::new(vec![0; 24])).is_err());
+    }
+}
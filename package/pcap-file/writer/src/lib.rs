@@ -6,7 +6,10 @@ extern crate serde_json;
 #[macro_use]
 extern crate serde_derive;
 
-use byteorder::{LittleEndian, WriteBytesExt};
+#[cfg(test)]
+extern crate reader;
+
+use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
 use genet_sdk::{prelude::*, writer::*};
 
 use std::{
@@ -26,12 +29,8 @@ impl Writer for PcapFileWriter {
     fn new_worker(&self, _ctx: &Context, arg: &str) -> Result<Box<Worker>> {
         let arg: Arg = serde_json::from_str(arg)?;
         let file = File::create(&arg.file)?;
-        let mut writer = BufWriter::new(file);
-        writer.write_all(&[0x4d, 0x3c, 0xb2, 0xa1])?;
-        Ok(Box::new(PcapFileWorker {
-            writer,
-            header: false,
-        }))
+        let writer = BufWriter::new(file);
+        Ok(Box::new(PcapFileWorker::new(writer)))
     }
 
     fn metadata(&self) -> Metadata {
@@ -43,62 +42,239 @@ impl Writer for PcapFileWriter {
     }
 }
 
-struct PcapFileWorker {
-    writer: BufWriter<File>,
-    header: bool,
+/// File header fields taken from the first frame.
+///
+/// A frame read from a classic pcap file carries the original header values,
+/// so writing it back produces the same file.
+#[derive(Clone, Copy)]
+struct Header {
+    big_endian: bool,
+    nsec: bool,
+    version_major: u16,
+    version_minor: u16,
+    thiszone: i32,
+    sigfigs: u32,
+    snaplen: u32,
+    network: u32,
+}
+
+impl Header {
+    fn new(layer: &Layer) -> Result<Header> {
+        let mut header = Header {
+            big_endian: false,
+            nsec: false,
+            version_major: 2,
+            version_minor: 4,
+            thiszone: 0,
+            sigfigs: 0,
+            snaplen: 0,
+            network: 0,
+        };
+        if let Some(attr) = layer.attr(token!("link.type")) {
+            header.network = attr.try_get(layer)?.try_into()?;
+        }
+        if let Some(attr) = layer.attr(token!("link.bigEndian")) {
+            let big_endian: u64 = attr.try_get(layer)?.try_into()?;
+            header.big_endian = big_endian != 0;
+        }
+        if let Some(attr) = layer.attr(token!("link.timestamp.resolution")) {
+            let resolution: u64 = attr.try_get(layer)?.try_into()?;
+            header.nsec = resolution > 1_000_000;
+        }
+        if let Some(attr) = layer.attr(token!("link.version.major")) {
+            let version: u64 = attr.try_get(layer)?.try_into()?;
+            header.version_major = version as u16;
+        }
+        if let Some(attr) = layer.attr(token!("link.version.minor")) {
+            let version: u64 = attr.try_get(layer)?.try_into()?;
+            header.version_minor = version as u16;
+        }
+        if let Some(attr) = layer.attr(token!("link.thiszone")) {
+            header.thiszone = attr.try_get(layer)?.try_into()?;
+        }
+        if let Some(attr) = layer.attr(token!("link.sigfigs")) {
+            header.sigfigs = attr.try_get(layer)?.try_into()?;
+        }
+        if let Some(attr) = layer.attr(token!("link.snaplen")) {
+            header.snaplen = attr.try_get(layer)?.try_into()?;
+        }
+        Ok(header)
+    }
+}
+
+struct PcapFileWorker<W> {
+    writer: W,
+    header: Option<Header>,
 }
 
-impl PcapFileWorker {
-    fn write_header(&mut self, snaplen: u32, network: u32) -> Result<()> {
-        if !self.header {
-            self.header = true;
-            let var_major = 2u16;
-            let var_minor = 4u16;
-            let thiszone = 0i32;
-            let sigfigs = 0u32;
-            self.writer.write_u16::<LittleEndian>(var_major)?;
-            self.writer.write_u16::<LittleEndian>(var_minor)?;
-            self.writer.write_i32::<LittleEndian>(thiszone)?;
-            self.writer.write_u32::<LittleEndian>(sigfigs)?;
-            self.writer.write_u32::<LittleEndian>(snaplen)?;
-            self.writer.write_u32::<LittleEndian>(network)?;
+impl<W: Write> PcapFileWorker<W> {
+    fn new(writer: W) -> Self {
+        PcapFileWorker {
+            writer,
+            header: None,
+        }
+    }
+
+    fn write_u16(&mut self, big_endian: bool, value: u16) -> Result<()> {
+        if big_endian {
+            self.writer.write_u16::<BigEndian>(value)?;
+        } else {
+            self.writer.write_u16::<LittleEndian>(value)?;
+        }
+        Ok(())
+    }
+
+    fn write_u32(&mut self, big_endian: bool, value: u32) -> Result<()> {
+        if big_endian {
+            self.writer.write_u32::<BigEndian>(value)?;
+        } else {
+            self.writer.write_u32::<LittleEndian>(value)?;
         }
         Ok(())
     }
+
+    fn write_header(&mut self, layer: &Layer) -> Result<Header> {
+        if let Some(header) = self.header {
+            Ok(header)
+        } else {
+            let header = Header::new(layer)?;
+            let magic = if header.nsec {
+                0xa1b2_3c4d
+            } else {
+                0xa1b2_c3d4
+            };
+            let be = header.big_endian;
+            self.write_u32(be, magic)?;
+            self.write_u16(be, header.version_major)?;
+            self.write_u16(be, header.version_minor)?;
+            self.write_u32(be, header.thiszone as u32)?;
+            self.write_u32(be, header.sigfigs)?;
+            self.write_u32(be, header.snaplen)?;
+            self.write_u32(be, header.network)?;
+            self.header = Some(header);
+            Ok(header)
+        }
+    }
 }
 
-impl Worker for PcapFileWorker {
+impl<W: Write + Send> Worker for PcapFileWorker<W> {
     fn write(&mut self, _index: u32, stack: &LayerStack) -> Result<()> {
         if let Some(layer) = stack.bottom() {
             let incl_len = layer.data().len();
             let mut orig_len = 0;
-            let mut ts_sec = 0;
-            let mut ts_usec = 0;
-            let mut link = 0;
+            let mut ts_sec = 0u64;
+            let mut ts_nsec = 0u64;
 
             if let Some(attr) = layer.attr(token!("link.length")) {
                 orig_len = attr.try_get(layer)?.try_into()?;
             }
-            if let Some(attr) = layer.attr(token!("link.type")) {
-                link = attr.try_get(layer)?.try_into()?;
-            }
             if let Some(attr) = layer.attr(token!("link.timestamp.sec")) {
                 ts_sec = attr.try_get(layer)?.try_into()?;
             }
-            if let Some(attr) = layer.attr(token!("link.timestamp.usec")) {
-                ts_usec = attr.try_get(layer)?.try_into()?;
+            if let Some(attr) = layer.attr(token!("link.timestamp.nsec")) {
+                ts_nsec = attr.try_get(layer)?.try_into()?;
+            } else if let Some(attr) = layer.attr(token!("link.timestamp.usec")) {
+                let ts_usec: u64 = attr.try_get(layer)?.try_into()?;
+                ts_nsec = ts_usec * 1000;
             }
 
-            self.write_header(0, link as u32)?;
-
-            self.writer.write_u32::<LittleEndian>(ts_sec as u32)?;
-            self.writer.write_u32::<LittleEndian>(ts_usec as u32)?;
-            self.writer.write_u32::<LittleEndian>(incl_len as u32)?;
-            self.writer.write_u32::<LittleEndian>(orig_len as u32)?;
+            let header = self.write_header(layer)?;
+            let be = header.big_endian;
+            let ts_sec = (ts_sec as i64 - i64::from(header.thiszone)) as u32;
+            let ts_frac = if header.nsec {
+                ts_nsec
+            } else {
+                ts_nsec / 1000
+            };
+            self.write_u32(be, ts_sec)?;
+            self.write_u32(be, ts_frac as u32)?;
+            self.write_u32(be, incl_len as u32)?;
+            self.write_u32(be, orig_len as u32)?;
             self.writer.write_all(&layer.data())?;
         }
         Ok(())
     }
+
+    fn end(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
 }
 
 genet_writers!(PcapFileWriter {});
+
+#[cfg(test)]
+mod tests {
+    use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
+    use genet_sdk::{prelude::*, reader::Worker as ReaderWorker, writer::Worker};
+    use reader;
+    use std::io::Cursor;
+    use PcapFileWorker;
+
+    /// Builds a pcap file with records of (ts_sec, ts_frac, orig_len, data).
+    fn file(
+        le: bool,
+        nsec: bool,
+        version_minor: u16,
+        thiszone: i32,
+        records: &[(u32, u32, u32, &[u8])],
+    ) -> Vec<u8> {
+        let mut data = Vec::new();
+        let put = |data: &mut Vec<u8>, value: u32| {
+            if le {
+                data.write_u32::<LittleEndian>(value).unwrap();
+            } else {
+                data.write_u32::<BigEndian>(value).unwrap();
+            }
+        };
+        put(&mut data, if nsec { 0xa1b2_3c4d } else { 0xa1b2_c3d4 });
+        let version = u32::from(version_minor);
+        put(&mut data, if le { version << 16 | 2 } else { 2 << 16 | version });
+        for value in &[thiszone as u32, 0, 65535, 1] {
+            put(&mut data, *value);
+        }
+        for (ts_sec, ts_frac, orig_len, payload) in records {
+            for value in &[*ts_sec, *ts_frac, payload.len() as u32, *orig_len] {
+                put(&mut data, *value);
+            }
+            data.extend_from_slice(payload);
+        }
+        data
+    }
+
+    /// Reads the file and writes the frames back.
+    fn round_trip(data: &[u8]) -> Vec<u8> {
+        let mut reader = reader::PcapFileWorker::new(Cursor::new(data.to_vec())).unwrap();
+        let mut writer = PcapFileWorker::new(Vec::new());
+        for (index, layer) in reader.read().unwrap().iter().enumerate() {
+            writer
+                .write(index as u32, &LayerStack::from_slice(&[layer]))
+                .unwrap();
+        }
+        writer.end().unwrap();
+        writer.writer
+    }
+
+    #[test]
+    fn round_trip_files() {
+        let records: &[(u32, u32, u32, &[u8])] = &[
+            (1_500_000_000, 999_999, 4, b"abcd"),
+            (1_500_000_001, 0, 1500, b"truncated"),
+            (1_500_000_002, 123_456, 9, b"truncated"),
+        ];
+        for le in &[true, false] {
+            for nsec in &[false, true] {
+                for thiszone in &[-32400, 3600] {
+                    let data = file(*le, *nsec, 4, *thiszone, records);
+                    assert_eq!(round_trip(&data), data);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip_version() {
+        let data = file(true, false, 3, 0, &[(0, 0, 4, b"abcd")]);
+        assert_eq!(round_trip(&data), data);
+    }
+}
@@ -51,6 +51,16 @@ impl Reader for PcapReader {
     fn metadata(&self) -> Metadata {
         Metadata {
             id: "app.genet.reader.pcap".into(),
+            fields: [
+                &*TYPE_CLASS,
+                &*LENGTH_CLASS,
+                &*TS_CLASS,
+                &*TS_SEC_CLASS,
+                &*TS_USEC_CLASS,
+            ]
+                .iter()
+                .map(|class| Field::from(*class))
+                .collect(),
             ..Metadata::default()
         }
     }
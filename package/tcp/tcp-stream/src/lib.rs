@@ -1,62 +1,75 @@
 extern crate genet_sdk;
 
-use genet_sdk::{cast, decoder::*, prelude::*};
+use genet_sdk::{decoder::*, error::Error, prelude::*};
 use std::collections::{BTreeMap, HashMap};
 
 #[derive(Debug)]
 struct Stream {
     pub id: u64,
-    pub seq: i64,
-    pub len: usize,
+    base: Option<u32>,
     offset: usize,
-    slices: BTreeMap<usize, ByteSlice>,
+    slices: BTreeMap<usize, Vec<u8>>,
 }
 
 impl Stream {
     fn new(id: u64) -> Stream {
         return Stream {
             id: id,
-            seq: -1,
-            len: 0,
+            base: None,
             offset: 0,
             slices: BTreeMap::new(),
         };
     }
 
-    fn put(&mut self, start: usize, data: ByteSlice) {
-        if data.len() > 0 {
-            self.slices.insert(start, data);
-            let mut end = self.offset;
-            for (start, slice) in self.slices.iter_mut() {
-                if *start < end {}
-                end = *start + slice.len();
-            }
+    /// Buffers the data and returns false if all of it has already been received.
+    fn put(&mut self, start: usize, data: &[u8]) -> bool {
+        let end = start + data.len();
+        if end <= self.offset {
+            return false;
+        }
+        let (start, data) = if start < self.offset {
+            (self.offset, &data[self.offset - start..])
+        } else {
+            (start, data)
+        };
+        if self
+            .slices
+            .range(..=start)
+            .any(|(pos, slice)| pos + slice.len() >= end)
+        {
+            return false;
         }
+        let slice = self.slices.entry(start).or_default();
+        if slice.len() < data.len() {
+            *slice = data.to_vec();
+        }
+        true
     }
 
-    fn fetch(&mut self) -> impl Iterator<Item = ByteSlice> {
+    fn fetch(&mut self) -> impl Iterator<Item = Vec<u8>> {
         let mut slices = Vec::new();
-        loop {
-            let pos;
-            if let Some((key, value)) = self.slices.iter().next() {
-                pos = *key;
-                if *key == self.offset {
-                    self.offset += value.len();
-                    slices.push(value.clone());
-                } else {
-                    break;
-                }
-            } else {
+        while let Some(pos) = self.slices.keys().next().cloned() {
+            if pos > self.offset {
                 break;
             }
-            self.slices.remove(&pos);
+            let mut data = self.slices.remove(&pos).unwrap();
+            let end = pos + data.len();
+            // Skip the data already delivered by an overlapping segment.
+            if end > self.offset {
+                slices.push(data.split_off(self.offset - pos));
+                self.offset = end;
+            }
         }
         slices.into_iter()
     }
 }
 
+/// Segments further than this from the start of the stream are treated as
+/// retransmissions of data before the initial sequence number.
+const MAX_OFFSET: u32 = 1 << 31;
+
 struct TcpStreamWorker {
-    map: HashMap<(ByteSlice, ByteSlice, u32, u32), Stream>,
+    map: HashMap<(Vec<u8>, Vec<u8>, u32, u32), Stream>,
 }
 
 impl TcpStreamWorker {
@@ -78,20 +91,24 @@ impl Worker for TcpStreamWorker {
             let slice: ByteSlice = parent
                 .payloads()
                 .iter()
-                .find(|p| p.id() == token!("@data:tcp"))
+                .next()
                 .unwrap()
                 .data();
 
             let stream_id = {
-                let parent_src: ByteSlice = stack
+                let ip = stack
+                    .layer(token!("ipv4"))
+                    .or_else(|| stack.layer(token!("ipv6")))
+                    .ok_or_else(|| Error::new("missing network layer"))?;
+                let parent_src: ByteSlice = ip
                     .attr(token!("_.src"))
                     .unwrap()
-                    .try_get(parent)?
+                    .try_get(ip)?
                     .try_into()?;
-                let parent_dst: ByteSlice = stack
+                let parent_dst: ByteSlice = ip
                     .attr(token!("_.dst"))
                     .unwrap()
-                    .try_get(parent)?
+                    .try_get(ip)?
                     .try_into()?;
                 let src: u32 = parent
                     .attr(token!("tcp.src"))
@@ -103,7 +120,7 @@ impl Worker for TcpStreamWorker {
                     .unwrap()
                     .try_get(parent)?
                     .try_into()?;
-                (parent_src, parent_dst, src, dst)
+                (parent_src.to_vec(), parent_dst.to_vec(), src, dst)
             };
 
             let id = self.map.len();
@@ -117,11 +134,6 @@ impl Worker for TcpStreamWorker {
                 .unwrap()
                 .try_get(parent)?
                 .try_into()?;
-            let window: u16 = parent
-                .attr(token!("tcp.window"))
-                .unwrap()
-                .try_get(parent)?
-                .try_into()?;
             let flags: u8 = parent
                 .attr(token!("tcp.flags"))
                 .unwrap()
@@ -130,28 +142,22 @@ impl Worker for TcpStreamWorker {
 
             let syn = (flags & (0x1 << 1)) != 0;
             if syn {
-                if stream.seq < 0 {
-                    let offset = stream.len;
-                    stream.seq = seq as i64;
-                    stream.len += slice.len();
-                    stream.put(offset, slice);
+                if stream.base.is_none() {
+                    stream.base = Some(seq.wrapping_add(1));
                 }
-            } else if stream.seq >= 0 {
-                if slice.len() > 0 {
-                    if seq >= stream.seq as u32 {
-                        let offset = stream.len + (seq - stream.seq as u32) as usize;
-                        stream.seq = seq as i64;
-                        stream.len += slice.len();
-                        stream.put(offset, slice);
-                    } else if stream.seq - seq as i64 > window as i64 {
-                        let offset = stream.len
-                            + ((std::u32::MAX as u32 - stream.seq as u32) + seq) as usize;
-                        stream.seq = seq as i64;
-                        stream.len += slice.len();
-                        stream.put(offset, slice);
-                    }
-                } else if (stream.seq + 1) % std::u32::MAX as i64 == seq as i64 {
-                    stream.seq = seq as i64;
+            } else if let Some(base) = stream.base {
+                // Offsets are relative to the initial sequence number and wrap around.
+                let offset = seq.wrapping_sub(base);
+                // Segments adding no new data are retransmissions.
+                if !slice.is_empty()
+                    && (offset >= MAX_OFFSET || !stream.put(offset as usize, &slice))
+                {
+                    parent.add_annotation(Annotation::new(
+                        Severity::Note,
+                        "sequence",
+                        "tcp.retransmission",
+                        4..8,
+                    ));
                 }
             }
 
@@ -160,7 +166,7 @@ impl Worker for TcpStreamWorker {
                 parent.add_payload(Payload::new(payload, "@stream:tcp"));
             }
 
-            parent.add_attr(attr!(&STREAM_ATTR));
+            parent.add_attr(attr!(&STREAM_ATTR, value: stream.id));
             Ok(Status::Done)
         } else {
             Ok(Status::Skip)
@@ -178,15 +184,195 @@ impl Decoder for TcpStreamDecoder {
 
     fn metadata(&self) -> Metadata {
         Metadata {
+            id: "app.genet.decoder.tcp-stream".into(),
             exec_type: ExecType::SerialSync,
+            consumes: vec![String::from("tcp")],
+            fields: vec![
+                Field::from(&*STREAM_ATTR),
+                Field::new("sequence", ""),
+                Field::new("tcp.retransmission", ""),
+            ],
             ..Metadata::default()
         }
     }
 }
 
-def_attr_class!(STREAM_ATTR, "tcp.stream",
-    typ: "@novalue",
-    cast: cast::Ranged(cast::UInt8(), 0..0).map(|v| v)
-);
+def_attr_class!(STREAM_ATTR, "tcp.stream");
 
 genet_decoders!(TcpStreamDecoder {});
+
+#[cfg(test)]
+mod tests {
+    use genet_sdk::{cast, decoder::*, layer::attr_value, prelude::*};
+    use TcpStreamWorker;
+
+    def_layer_class!(IPV4_CLASS, "ipv4",
+        alias: "_.src" "ipv4.src",
+        alias: "_.dst" "ipv4.dst",
+        header: attr!(&IPV4_SRC_ATTR, range: 12..16),
+        header: attr!(&IPV4_DST_ATTR, range: 16..20)
+    );
+
+    def_attr_class!(IPV4_SRC_ATTR, "ipv4.src", cast: cast::ByteSlice());
+
+    def_attr_class!(IPV4_DST_ATTR, "ipv4.dst", cast: cast::ByteSlice());
+
+    def_layer_class!(TCP_CLASS, "tcp",
+        header: attr!(&SRC_ATTR, range: 0..2),
+        header: attr!(&DST_ATTR, range: 2..4),
+        header: attr!(&SEQ_ATTR, range: 4..8),
+        header: attr!(&FLAGS_ATTR, range: 13..14)
+    );
+
+    def_attr_class!(SRC_ATTR, "tcp.src", cast: cast::UInt16BE());
+
+    def_attr_class!(DST_ATTR, "tcp.dst", cast: cast::UInt16BE());
+
+    def_attr_class!(SEQ_ATTR, "tcp.seq", cast: cast::UInt32BE());
+
+    def_attr_class!(FLAGS_ATTR, "tcp.flags", cast: cast::UInt8());
+
+    const SYN: u8 = 0b0000_0010;
+    const ACK: u8 = 0b0001_0000;
+
+    /// Decodes a segment from 10.0.0.1:1234 to the port and returns the TCP layer.
+    fn decode(
+        worker: &mut TcpStreamWorker,
+        port: u16,
+        seq: u32,
+        flags: u8,
+        data: &[u8],
+    ) -> Layer {
+        let mut ip = vec![0; 20];
+        ip[12..16].copy_from_slice(&[10, 0, 0, 1]);
+        ip[16..20].copy_from_slice(&[10, 0, 0, 2]);
+        let ip = Layer::new(&IPV4_CLASS, ip);
+
+        let mut header = vec![0x04, 0xd2, (port >> 8) as u8, port as u8];
+        header.extend_from_slice(&[
+            (seq >> 24) as u8,
+            (seq >> 16) as u8,
+            (seq >> 8) as u8,
+            seq as u8,
+        ]);
+        header.extend_from_slice(&[0, 0, 0, 0, 0x50, flags, 0xff, 0xff, 0, 0, 0, 0]);
+        let mut tcp = Layer::new(&TCP_CLASS, header);
+        tcp.add_payload(Payload::new(data.to_vec(), ""));
+
+        let mut ctx = Context::default();
+        worker
+            .decode(
+                &mut ctx,
+                &LayerStack::from_slice(&[&ip]),
+                &mut Parent::from_mut_ref(&mut tcp),
+            )
+            .unwrap();
+        tcp
+    }
+
+    /// Decodes a segment and returns the stream index and the reassembled payloads.
+    fn segment(
+        worker: &mut TcpStreamWorker,
+        port: u16,
+        seq: u32,
+        flags: u8,
+        data: &[u8],
+    ) -> (u64, Vec<Vec<u8>>) {
+        let tcp = decode(worker, port, seq, flags, data);
+        let payloads = tcp
+            .payloads()
+            .iter()
+            .filter(|p| p.id() == token!("@stream:tcp"))
+            .map(|p| p.data().to_vec())
+            .collect();
+        (attr_value(&tcp, token!("tcp.stream")).unwrap(), payloads)
+    }
+
+    fn fetch(worker: &mut TcpStreamWorker, seq: u32, flags: u8, data: &[u8]) -> Vec<Vec<u8>> {
+        segment(worker, 80, seq, flags, data).1
+    }
+
+    #[test]
+    fn in_order() {
+        let mut w = TcpStreamWorker::new();
+        assert!(fetch(&mut w, 1000, SYN, b"").is_empty());
+        assert_eq!(fetch(&mut w, 1001, ACK, b"abc"), vec![b"abc".to_vec()]);
+        assert_eq!(fetch(&mut w, 1004, ACK, b"def"), vec![b"def".to_vec()]);
+    }
+
+    #[test]
+    fn out_of_order() {
+        let mut w = TcpStreamWorker::new();
+        fetch(&mut w, 1000, SYN, b"");
+        assert!(fetch(&mut w, 1004, ACK, b"def").is_empty());
+        assert_eq!(
+            fetch(&mut w, 1001, ACK, b"abc"),
+            vec![b"abc".to_vec(), b"def".to_vec()]
+        );
+    }
+
+    #[test]
+    fn retransmission() {
+        let mut w = TcpStreamWorker::new();
+        fetch(&mut w, 1000, SYN, b"");
+        assert_eq!(fetch(&mut w, 1001, ACK, b"abcd"), vec![b"abcd".to_vec()]);
+        assert!(fetch(&mut w, 1001, ACK, b"abcd").is_empty());
+
+        // Only the new part of an overlapping segment is delivered.
+        assert_eq!(fetch(&mut w, 1003, ACK, b"cdef"), vec![b"ef".to_vec()]);
+
+        // Segments before the initial sequence number are ignored.
+        assert!(fetch(&mut w, 990, ACK, b"xx").is_empty());
+    }
+
+    #[test]
+    fn retransmission_annotation() {
+        let retransmission = |w: &mut TcpStreamWorker, seq: u32, data: &[u8]| {
+            decode(w, 80, seq, ACK, data)
+                .annotations()
+                .iter()
+                .any(|a| a.message() == token!("tcp.retransmission"))
+        };
+        let mut w = TcpStreamWorker::new();
+        fetch(&mut w, 1000, SYN, b"");
+        assert!(!retransmission(&mut w, 1001, b"abcd"));
+        assert!(retransmission(&mut w, 1001, b"abcd"));
+        assert!(retransmission(&mut w, 1003, b"cd"));
+        assert!(!retransmission(&mut w, 1003, b"cdef"));
+        assert!(retransmission(&mut w, 990, b"xx"));
+
+        // Buffered out-of-order segments count as received.
+        assert!(!retransmission(&mut w, 1011, b"kl"));
+        assert!(retransmission(&mut w, 1011, b"kl"));
+        assert!(!retransmission(&mut w, 1007, b"ghij"));
+
+        // Segments without payload such as pure ACKs are not retransmissions.
+        assert!(!retransmission(&mut w, 1005, b""));
+    }
+
+    #[test]
+    fn sequence_wraparound() {
+        let mut w = TcpStreamWorker::new();
+        fetch(&mut w, 0xffff_fffd, SYN, b"");
+        assert_eq!(fetch(&mut w, 0xffff_fffe, ACK, b"ab"), vec![b"ab".to_vec()]);
+        assert_eq!(fetch(&mut w, 0, ACK, b"cd"), vec![b"cd".to_vec()]);
+    }
+
+    #[test]
+    fn missing_syn() {
+        // Streams are reassembled only after their initial sequence number is known.
+        let mut w = TcpStreamWorker::new();
+        assert!(fetch(&mut w, 1001, ACK, b"abc").is_empty());
+        fetch(&mut w, 2000, SYN, b"");
+        assert_eq!(fetch(&mut w, 2001, ACK, b"def"), vec![b"def".to_vec()]);
+    }
+
+    #[test]
+    fn streams() {
+        let mut w = TcpStreamWorker::new();
+        assert_eq!(segment(&mut w, 80, 1000, SYN, b"").0, 0);
+        assert_eq!(segment(&mut w, 443, 5000, SYN, b"").0, 1);
+        assert_eq!(segment(&mut w, 443, 5001, ACK, b"abc"), (1, vec![b"abc".to_vec()]));
+        assert_eq!(segment(&mut w, 80, 1001, ACK, b"def"), (0, vec![b"def".to_vec()]));
+    }
+}
@@ -1,13 +1,37 @@
 extern crate genet_sdk;
 
 use genet_sdk::{cast, decoder::*, prelude::*};
+use std::collections::HashMap;
 
-struct TcpWorker {}
+struct TcpWorker {
+    ports: HashMap<u16, Option<Token>>,
+}
+
+impl TcpWorker {
+    fn new() -> TcpWorker {
+        TcpWorker {
+            ports: HashMap::new(),
+        }
+    }
+
+    /// Returns the payload type for the port.
+    ///
+    /// Stream-based protocols are decoded on top of tcp-stream,
+    /// so there are no defaults and only profile configs such as `tcp.port.8080=foo` apply.
+    fn port_type(&mut self, ctx: &Context, port: u16) -> Option<Token> {
+        *self.ports.entry(port).or_insert_with(|| {
+            match ctx.get_config(&format!("tcp.port.{}", port)) {
+                "" => None,
+                typ => Some(Token::from(format!("@data:{}", typ))),
+            }
+        })
+    }
+}
 
 impl Worker for TcpWorker {
     fn decode(
         &mut self,
-        _ctx: &mut Context,
+        ctx: &mut Context,
         _stack: &LayerStack,
         parent: &mut Parent,
     ) -> Result<Status> {
@@ -63,8 +87,25 @@ impl Worker for TcpWorker {
         }
         layer.add_attr(attr!(&OPTIONS_ATTR, range: 20..offset));
 
+        let flags: u8 = layer.data().try_get(13)?;
+        let window: u16 = WINDOW_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        if window == 0 && flags & 0b0000_0111 == 0 {
+            layer.add_annotation(Annotation::new(
+                Severity::Warning,
+                "sequence",
+                "tcp.zeroWindow",
+                14..16,
+            ));
+        }
+
+        let src = SRC_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        let dst = DST_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        let typ = self
+            .port_type(ctx, dst)
+            .or_else(|| self.port_type(ctx, src))
+            .unwrap_or_else(|| token!(""));
         let payload = layer.data().try_get(data_offset..)?;
-        layer.add_payload(Payload::new(payload, "@data:tcp"));
+        layer.add_payload(Payload::new(payload, typ));
 
         parent.add_child(layer);
         Ok(Status::Done)
@@ -76,12 +117,33 @@ struct TcpDecoder {}
 
 impl Decoder for TcpDecoder {
     fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
-        Box::new(TcpWorker {})
+        Box::new(TcpWorker::new())
     }
 
     fn metadata(&self) -> Metadata {
+        let mut fields = TCP_CLASS.fields();
+        fields.extend(
+            [
+                &*OPTIONS_ATTR,
+                &*OPTIONS_NOP_ATTR,
+                &*OPTIONS_MSS_ATTR,
+                &*OPTIONS_SCALE_ATTR,
+                &*OPTIONS_SACKP_ATTR,
+                &*OPTIONS_SACK_ATTR,
+                &*OPTIONS_TS_ATTR,
+                &*OPTIONS_TS_MY_ATTR,
+                &*OPTIONS_TS_ECHO_ATTR,
+            ]
+                .iter()
+                .map(|attr| Field::from(*attr)),
+        );
+        fields.push(Field::new("sequence", ""));
+        fields.push(Field::new("tcp.zeroWindow", ""));
         Metadata {
+            id: "app.genet.decoder.tcp".into(),
             exec_type: ExecType::ParallelSync,
+            consumes: vec![String::from("@data:tcp")],
+            fields,
             ..Metadata::default()
         }
     }
@@ -90,8 +152,8 @@ impl Decoder for TcpDecoder {
 def_layer_class!(
     TCP_CLASS,
     "tcp",
-    header: attr!(&SRC_ATTR, range: 0..2),
-    header: attr!(&DST_ATTR, range: 2..4),
+    header: &SRC_ATTR_HEADER,
+    header: &DST_ATTR_HEADER,
     header: attr!(&SEQ_ATTR, range: 4..8),
     header: attr!(&ACK_ATTR, range: 8..12),
     header: &OFFSET_ATTR_HEADER,
@@ -105,13 +167,19 @@ def_layer_class!(
     header: attr!(&FLAGS_RST_ATTR, bit_range: 13 5..6),
     header: attr!(&FLAGS_SYN_ATTR, bit_range: 13 6..7),
     header: attr!(&FLAGS_FIN_ATTR, bit_range: 13 7..8),
-    header: attr!(&WINDOW_ATTR, range: 14..16),
+    header: &WINDOW_ATTR_HEADER,
     header: attr!(&CHECKSUM_ATTR, range: 16..18),
     header: attr!(&URGENT_ATTR, range: 18..20)
 );
 
+def_attr!(SRC_ATTR_HEADER, &SRC_ATTR, range: 0..2);
+
+def_attr!(DST_ATTR_HEADER, &DST_ATTR, range: 2..4);
+
 def_attr!(OFFSET_ATTR_HEADER,  &OFFSET_ATTR, range: 12..13);
 
+def_attr!(WINDOW_ATTR_HEADER, &WINDOW_ATTR, range: 14..16);
+
 def_attr_class!(SRC_ATTR, "tcp.src",
     typ: "@tcp:port",
     cast: cast::UInt16BE()
@@ -220,3 +288,45 @@ def_attr_class!(
 );
 
 genet_decoders!(TcpDecoder {});
+
+#[cfg(test)]
+mod tests {
+    use genet_sdk::{decoder::*, prelude::*};
+    use TcpWorker;
+
+    def_layer_class!(IPV4_CLASS, "ipv4");
+
+    /// Decodes a segment between the ports and returns the payload type.
+    fn payload_type(worker: &mut TcpWorker, ctx: &mut Context, src: u16, dst: u16) -> Token {
+        let mut data = vec![(src >> 8) as u8, src as u8, (dst >> 8) as u8, dst as u8];
+        data.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 0, 0x50, 0x18, 0xff, 0xff, 0, 0, 0, 0]);
+        data.extend_from_slice(b"abcd");
+        let mut ipv4 = Layer::new(&IPV4_CLASS, data.clone());
+        ipv4.add_payload(Payload::new(data, "@data:tcp"));
+        let mut parent = Parent::from_mut_ref(&mut ipv4);
+        worker
+            .decode(ctx, &LayerStack::from_slice(&[]), &mut parent)
+            .unwrap();
+        let tcp = unsafe { &*parent.children()[0] };
+        tcp.payloads()[0].id()
+    }
+
+    #[test]
+    fn ports() {
+        let config = [("tcp.port.8080", "foo"), ("tcp.port.8443", "bar")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let mut ctx = Context::new(config);
+        let mut w = TcpWorker::new();
+
+        // Stream-based protocols are left to tcp-stream unless a port is mapped.
+        assert_eq!(payload_type(&mut w, &mut ctx, 50000, 80), token!(""));
+        assert_eq!(payload_type(&mut w, &mut ctx, 50000, 8080), token!("@data:foo"));
+        assert_eq!(payload_type(&mut w, &mut ctx, 8080, 50000), token!("@data:foo"));
+
+        // The destination port wins over the source port.
+        assert_eq!(payload_type(&mut w, &mut ctx, 8080, 8443), token!("@data:bar"));
+        assert_eq!(payload_type(&mut w, &mut ctx, 8443, 8080), token!("@data:foo"));
+    }
+}
@@ -0,0 +1,815 @@
+extern crate genet_sdk;
+extern crate md5;
+
+use genet_sdk::{cast, decoder::*, layer::attr_value, prelude::*, variant::Variant};
+use std::{collections::HashMap, ops::Range};
+
+/// Maximum length of a TLSCiphertext fragment.
+const MAX_RECORD_LENGTH: usize = (1 << 14) + 2048;
+
+/// Handshake messages longer than this are not reassembled.
+const MAX_HANDSHAKE_LENGTH: usize = 1 << 20;
+
+#[derive(Default)]
+struct Stream {
+    buf: Vec<u8>,
+    handshake: Vec<u8>,
+    encrypted: bool,
+    ignored: bool,
+}
+
+impl Stream {
+    /// Returns the next complete record in the buffer.
+    fn next(&mut self) -> Option<Vec<u8>> {
+        if self.ignored || self.buf.len() < 5 {
+            return None;
+        }
+        let typ = self.buf[0];
+        let major = self.buf[1];
+        let len = (self.buf[3] as usize) << 8 | self.buf[4] as usize;
+        if !(20..=24).contains(&typ) || major != 3 || len > MAX_RECORD_LENGTH {
+            self.ignored = true;
+            self.buf.clear();
+            return None;
+        }
+        if self.buf.len() < 5 + len {
+            return None;
+        }
+        Some(self.buf.drain(..5 + len).collect())
+    }
+}
+
+struct TlsWorker {
+    streams: HashMap<u64, Stream>,
+}
+
+impl TlsWorker {
+    fn new() -> TlsWorker {
+        TlsWorker {
+            streams: HashMap::new(),
+        }
+    }
+}
+
+impl Worker for TlsWorker {
+    fn decode(
+        &mut self,
+        _ctx: &mut Context,
+        _stack: &LayerStack,
+        parent: &mut Parent,
+    ) -> Result<Status> {
+        if parent.id() != token!("tcp") {
+            return Ok(Status::Skip);
+        }
+
+        let slices: Vec<ByteSlice> = parent
+            .payloads()
+            .iter()
+            .filter(|p| p.id() == token!("@stream:tcp"))
+            .map(|p| p.data())
+            .collect();
+
+        if slices.is_empty() {
+            return Ok(Status::Skip);
+        }
+
+        let id: u64 = attr_value(parent, token!("tcp.stream"))?;
+        let stream = self.streams.entry(id).or_default();
+        if stream.ignored {
+            return Ok(Status::Done);
+        }
+        for slice in slices {
+            stream.buf.extend_from_slice(&slice);
+        }
+
+        while let Some(record) = stream.next() {
+            let mut layer = Layer::new(&TLS_CLASS, record);
+            let typ = TYPE_ATTR_HEADER.try_get(&layer)?.try_into()?;
+            if let Some(attr) = get_type(typ) {
+                layer.add_attr(attr!(attr, range: 0..1));
+            }
+
+            match typ {
+                // ChangeCipherSpec
+                20 => stream.encrypted = true,
+                // Alert
+                21 if !stream.encrypted => {
+                    layer.add_attr(attr!(&ALERT_LEVEL_ATTR, range: 5..6));
+                    layer.add_attr(attr!(&ALERT_DESCRIPTION_ATTR, range: 6..7));
+                }
+                // Handshake
+                22 if !stream.encrypted => {
+                    let data = layer.data();
+                    decode_handshake(&mut layer, &mut stream.handshake, &data[5..]);
+                }
+                _ => {}
+            }
+
+            parent.add_child(layer);
+        }
+
+        Ok(Status::Done)
+    }
+}
+
+/// Appends a handshake fragment and decodes the completed handshake messages.
+///
+/// Messages lying entirely in the fragment get byte ranges in the record,
+/// while the ones spanning several records only have values.
+fn decode_handshake(layer: &mut Layer, buf: &mut Vec<u8>, fragment: &[u8]) {
+    let mut prev = buf.len();
+    let mut offset = 5;
+    buf.extend_from_slice(fragment);
+    while buf.len() >= 4 {
+        let len = (buf[1] as usize) << 16 | (buf[2] as usize) << 8 | buf[3] as usize;
+        if len > MAX_HANDSHAKE_LENGTH {
+            buf.clear();
+            return;
+        }
+        if buf.len() < 4 + len {
+            return;
+        }
+        let message: Vec<u8> = buf.drain(..4 + len).collect();
+        let base = if prev == 0 { Some(offset) } else { None };
+        let mut attrs = Attrs {
+            layer,
+            data: &message,
+            base,
+        };
+        // Truncated messages keep the attributes decoded so far.
+        let _ = attrs.handshake();
+
+        let n = message.len();
+        if prev >= n {
+            prev -= n;
+        } else {
+            offset += n - prev;
+            prev = 0;
+        }
+    }
+}
+
+/// Adds attributes decoded from a handshake message to a layer.
+struct Attrs<'a> {
+    layer: &'a mut Layer,
+    data: &'a [u8],
+    base: Option<usize>,
+}
+
+impl<'a> Attrs<'a> {
+    fn add<T: Into<Variant>>(&mut self, class: &'static AttrClass, range: Range<usize>, value: T) {
+        let attr = if let Some(base) = self.base {
+            attr!(class, range: base + range.start..base + range.end, value: value)
+        } else {
+            attr!(class, value: value)
+        };
+        self.layer.add_attr(attr);
+    }
+
+    fn uint(&mut self, class: &'static AttrClass, range: Range<usize>) -> Option<u64> {
+        let value = uint(self.data.get(range.clone())?);
+        self.add(class, range, value);
+        Some(value)
+    }
+
+    fn bytes(&mut self, class: &'static AttrClass, range: Range<usize>) -> Option<()> {
+        let value: Box<[u8]> = Box::from(self.data.get(range.clone())?);
+        self.add(class, range, value);
+        Some(())
+    }
+
+    fn text(&mut self, class: &'static AttrClass, range: Range<usize>) -> Option<()> {
+        let value = String::from_utf8_lossy(self.data.get(range.clone())?).into_owned();
+        self.add(class, range, value.into_boxed_str());
+        Some(())
+    }
+
+    fn handshake(&mut self) -> Option<()> {
+        let len = self.data.len();
+        self.add(&HANDSHAKE_ATTR, 0..len, true);
+        let typ = self.uint(&HANDSHAKE_TYPE_ATTR, 0..1)?;
+        if let Some(attr) = get_handshake_type(typ) {
+            self.add(attr, 0..1, true);
+        }
+        self.uint(&HANDSHAKE_LENGTH_ATTR, 1..4)?;
+        match typ {
+            1 => self.client_hello(),
+            2 => self.server_hello(),
+            11 => self.certificate(),
+            _ => Some(()),
+        }
+    }
+
+    fn client_hello(&mut self) -> Option<()> {
+        let version = self.uint(&VERSION_ATTR, 4..6)?;
+        self.bytes(&RANDOM_ATTR, 6..38)?;
+        let mut pos = self.session_id(38)?;
+
+        let suites = self.vector(pos, 2)?;
+        let mut ciphers = Vec::new();
+        for offset in suites.clone().step_by(2) {
+            let suite = self.uint(&CIPHER_SUITE_ATTR, offset..offset + 2)?;
+            if !is_grease(suite) {
+                ciphers.push(suite);
+            }
+        }
+        pos = suites.end;
+
+        let methods = self.vector(pos, 1)?;
+        for offset in methods.clone() {
+            self.uint(&COMPRESSION_METHOD_ATTR, offset..offset + 1)?;
+        }
+        pos = methods.end;
+
+        let mut extensions = Vec::new();
+        let mut groups = Vec::new();
+        let mut formats = Vec::new();
+        self.extensions(pos, |attrs, typ, range| {
+            if !is_grease(typ) {
+                extensions.push(typ);
+            }
+            match typ {
+                0 => attrs.server_name(range),
+                10 => {
+                    let list = attrs.vector(range.start, 2)?;
+                    for offset in list.step_by(2) {
+                        let group = attrs.uint_at(offset, 2)?;
+                        if !is_grease(group) {
+                            groups.push(group);
+                        }
+                    }
+                    Some(())
+                }
+                11 => {
+                    let list = attrs.vector(range.start, 1)?;
+                    for offset in list {
+                        formats.push(attrs.uint_at(offset, 1)?);
+                    }
+                    Some(())
+                }
+                16 => attrs.alpn(range),
+                43 => {
+                    let list = attrs.vector(range.start, 1)?;
+                    for offset in list.step_by(2) {
+                        attrs.uint(&SUPPORTED_VERSION_ATTR, offset..offset + 2)?;
+                    }
+                    Some(())
+                }
+                _ => Some(()),
+            }
+        })?;
+
+        let ja3 = format!(
+            "{},{},{},{},{}",
+            version,
+            join(&ciphers),
+            join(&extensions),
+            join(&groups),
+            join(&formats)
+        );
+        self.fingerprint(&JA3_ATTR, &JA3_STRING_ATTR, ja3);
+        Some(())
+    }
+
+    fn server_hello(&mut self) -> Option<()> {
+        let version = self.uint(&VERSION_ATTR, 4..6)?;
+        self.bytes(&RANDOM_ATTR, 6..38)?;
+        let pos = self.session_id(38)?;
+        let cipher = self.uint(&CIPHER_SUITE_ATTR, pos..pos + 2)?;
+        self.uint(&COMPRESSION_METHOD_ATTR, pos + 2..pos + 3)?;
+
+        let mut extensions = Vec::new();
+        self.extensions(pos + 3, |attrs, typ, range| {
+            extensions.push(typ);
+            match typ {
+                16 => attrs.alpn(range),
+                43 => attrs
+                    .uint(&SUPPORTED_VERSION_ATTR, range.start..range.start + 2)
+                    .map(|_| ()),
+                _ => Some(()),
+            }
+        })?;
+
+        let ja3s = format!("{},{},{}", version, cipher, join(&extensions));
+        self.fingerprint(&JA3S_ATTR, &JA3S_STRING_ATTR, ja3s);
+        Some(())
+    }
+
+    fn certificate(&mut self) -> Option<()> {
+        let list = self.vector(4, 3)?;
+        let mut pos = list.start;
+        while pos < list.end {
+            let range = self.vector(pos, 3)?;
+            let cert = Certificate::parse(self.data.get(range.clone())?);
+            self.add(&CERTIFICATE_ATTR, range.clone(), true);
+            if let Some(cert) = cert {
+                let fields = [
+                    (&*CERTIFICATE_SUBJECT_ATTR, cert.subject),
+                    (&*CERTIFICATE_ISSUER_ATTR, cert.issuer),
+                    (&*CERTIFICATE_NOT_BEFORE_ATTR, cert.not_before),
+                    (&*CERTIFICATE_NOT_AFTER_ATTR, cert.not_after),
+                ];
+                for (class, value) in fields.iter() {
+                    self.add(class, range.clone(), value.clone().into_boxed_str());
+                }
+            }
+            pos = range.end;
+        }
+        Some(())
+    }
+
+    fn session_id(&mut self, pos: usize) -> Option<usize> {
+        let range = self.vector(pos, 1)?;
+        self.bytes(&SESSION_ID_ATTR, range.clone())?;
+        Some(range.end)
+    }
+
+    /// Calls `f` with the type and data range of each extension from `pos`.
+    fn extensions<F>(&mut self, pos: usize, mut f: F) -> Option<()>
+    where
+        F: FnMut(&mut Self, u64, Range<usize>) -> Option<()>,
+    {
+        if pos >= self.data.len() {
+            return Some(());
+        }
+        let list = self.vector(pos, 2)?;
+        let mut pos = list.start;
+        while pos < list.end {
+            let data = self.vector(pos + 2, 2)?;
+            self.add(&EXTENSION_ATTR, pos..data.end, true);
+            let typ = self.uint(&EXTENSION_TYPE_ATTR, pos..pos + 2)?;
+            self.uint(&EXTENSION_LENGTH_ATTR, pos + 2..pos + 4)?;
+            pos = data.end;
+            f(self, typ, data)?;
+        }
+        Some(())
+    }
+
+    fn server_name(&mut self, range: Range<usize>) -> Option<()> {
+        let list = self.vector(range.start, 2)?;
+        let mut pos = list.start;
+        while pos < list.end {
+            let name = self.vector(pos + 1, 2)?;
+            // host_name
+            if self.uint_at(pos, 1)? == 0 {
+                self.text(&SNI_ATTR, name.clone())?;
+            }
+            pos = name.end;
+        }
+        Some(())
+    }
+
+    fn alpn(&mut self, range: Range<usize>) -> Option<()> {
+        let list = self.vector(range.start, 2)?;
+        let mut pos = list.start;
+        while pos < list.end {
+            let protocol = self.vector(pos, 1)?;
+            self.text(&ALPN_ATTR, protocol.clone())?;
+            pos = protocol.end;
+        }
+        Some(())
+    }
+
+    fn fingerprint(&mut self, hash: &'static AttrClass, string: &'static AttrClass, value: String) {
+        let digest = format!("{:x}", md5::compute(&value));
+        self.layer.add_attr(attr!(hash, value: digest.into_boxed_str()));
+        self.layer.add_attr(attr!(string, value: value.into_boxed_str()));
+    }
+
+    /// Returns the range of a vector with a length field of `size` bytes at `pos`.
+    fn vector(&self, pos: usize, size: usize) -> Option<Range<usize>> {
+        let start = pos + size;
+        Some(start..start + self.uint_at(pos, size)? as usize)
+    }
+
+    fn uint_at(&self, pos: usize, len: usize) -> Option<u64> {
+        self.data.get(pos..pos + len).map(uint)
+    }
+}
+
+fn uint(data: &[u8]) -> u64 {
+    data.iter().fold(0, |acc, b| acc << 8 | u64::from(*b))
+}
+
+/// Returns true if the value is a GREASE value reserved by RFC 8701.
+fn is_grease(value: u64) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+fn join(values: &[u64]) -> String {
+    values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Fields of an X.509 certificate.
+struct Certificate {
+    subject: String,
+    issuer: String,
+    not_before: String,
+    not_after: String,
+}
+
+impl Certificate {
+    fn parse(data: &[u8]) -> Option<Certificate> {
+        let (_, cert, _) = der(data)?;
+        let (_, tbs, _) = der(cert)?;
+
+        // version is optional
+        let (tag, _, mut rest) = der(tbs)?;
+        if tag == 0xa0 {
+            rest = der(rest)?.2;
+        }
+        // signature
+        rest = der(rest)?.2;
+        let (_, issuer, rest) = der(rest)?;
+        let (_, validity, rest) = der(rest)?;
+        let (_, subject, _) = der(rest)?;
+        let (before_tag, before, validity) = der(validity)?;
+        let (after_tag, after, _) = der(validity)?;
+
+        Some(Certificate {
+            subject: name(subject)?,
+            issuer: name(issuer)?,
+            not_before: time(before_tag, before)?,
+            not_after: time(after_tag, after)?,
+        })
+    }
+}
+
+/// Splits a DER encoded value into its tag, contents and the following data.
+fn der(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *data.first()?;
+    let first = *data.get(1)? as usize;
+    let (len, start) = if first < 0x80 {
+        (first, 2)
+    } else {
+        let n = first & 0x7f;
+        if n == 0 || n > 4 {
+            return None;
+        }
+        (uint(data.get(2..2 + n)?) as usize, 2 + n)
+    };
+    let contents = data.get(start..start + len)?;
+    Some((tag, contents, &data[start + len..]))
+}
+
+/// Formats a distinguished name such as "CN=example.com, O=Example".
+fn name(data: &[u8]) -> Option<String> {
+    let mut attrs = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let (_, set, next) = der(rest)?;
+        let (_, atv, _) = der(set)?;
+        let (_, oid, value) = der(atv)?;
+        let (_, value, _) = der(value)?;
+        let key = match oid {
+            [0x55, 0x04, 0x03] => "CN".to_string(),
+            [0x55, 0x04, 0x06] => "C".to_string(),
+            [0x55, 0x04, 0x07] => "L".to_string(),
+            [0x55, 0x04, 0x08] => "ST".to_string(),
+            [0x55, 0x04, 0x0a] => "O".to_string(),
+            [0x55, 0x04, 0x0b] => "OU".to_string(),
+            _ => object_id(oid),
+        };
+        attrs.push(format!("{}={}", key, String::from_utf8_lossy(value)));
+        rest = next;
+    }
+    Some(attrs.join(", "))
+}
+
+fn object_id(data: &[u8]) -> String {
+    let mut ids = Vec::new();
+    let mut value = 0u64;
+    for b in data {
+        value = value << 7 | u64::from(b & 0x7f);
+        if b & 0x80 == 0 {
+            if ids.is_empty() {
+                let first = (value / 40).min(2);
+                ids.push(first);
+                ids.push(value - first * 40);
+            } else {
+                ids.push(value);
+            }
+            value = 0;
+        }
+    }
+    join(&ids).replace('-', ".")
+}
+
+/// Formats UTCTime and GeneralizedTime as "YYYY-MM-DDTHH:MM:SSZ".
+fn time(tag: u8, data: &[u8]) -> Option<String> {
+    let text = std::str::from_utf8(data).ok()?;
+    let (year, rest) = match tag {
+        0x17 => {
+            let year: u32 = text.get(0..2)?.parse().ok()?;
+            (if year < 50 { 2000 + year } else { 1900 + year }, text.get(2..)?)
+        }
+        0x18 => (text.get(0..4)?.parse().ok()?, text.get(4..)?),
+        _ => return None,
+    };
+    Some(format!(
+        "{:04}-{}-{}T{}:{}:{}Z",
+        year,
+        rest.get(0..2)?,
+        rest.get(2..4)?,
+        rest.get(4..6)?,
+        rest.get(6..8)?,
+        rest.get(8..10)?
+    ))
+}
+
+#[derive(Clone)]
+struct TlsDecoder {}
+
+impl Decoder for TlsDecoder {
+    fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
+        Box::new(TlsWorker::new())
+    }
+
+    fn metadata(&self) -> Metadata {
+        let mut fields = TLS_CLASS.fields();
+        fields.extend(
+            [
+                &*ALERT_LEVEL_ATTR,
+                &*ALERT_DESCRIPTION_ATTR,
+                &*HANDSHAKE_ATTR,
+                &*HANDSHAKE_TYPE_ATTR,
+                &*HANDSHAKE_LENGTH_ATTR,
+                &*VERSION_ATTR,
+                &*RANDOM_ATTR,
+                &*SESSION_ID_ATTR,
+                &*CIPHER_SUITE_ATTR,
+                &*COMPRESSION_METHOD_ATTR,
+                &*EXTENSION_ATTR,
+                &*EXTENSION_TYPE_ATTR,
+                &*EXTENSION_LENGTH_ATTR,
+                &*SNI_ATTR,
+                &*ALPN_ATTR,
+                &*SUPPORTED_VERSION_ATTR,
+                &*JA3_ATTR,
+                &*JA3_STRING_ATTR,
+                &*JA3S_ATTR,
+                &*JA3S_STRING_ATTR,
+                &*CERTIFICATE_ATTR,
+                &*CERTIFICATE_SUBJECT_ATTR,
+                &*CERTIFICATE_ISSUER_ATTR,
+                &*CERTIFICATE_NOT_BEFORE_ATTR,
+                &*CERTIFICATE_NOT_AFTER_ATTR,
+            ]
+                .iter()
+                .map(|attr| Field::from(*attr)),
+        );
+        fields.extend((0..=0xff).filter_map(get_type).map(Field::from));
+        fields.extend((0..=0xff).filter_map(get_handshake_type).map(Field::from));
+        Metadata {
+            id: "app.genet.decoder.tls".into(),
+            exec_type: ExecType::SerialSync,
+            consumes: vec![String::from("@stream:tcp")],
+            fields,
+            ..Metadata::default()
+        }
+    }
+}
+
+def_layer_class!(TLS_CLASS, "tls",
+    header: &TYPE_ATTR_HEADER,
+    header: attr!(&RECORD_VERSION_ATTR, range: 1..3),
+    header: attr!(&LENGTH_ATTR, range: 3..5)
+);
+
+def_attr!(TYPE_ATTR_HEADER, &TYPE_ATTR, range: 0..1);
+
+def_attr_class!(TYPE_ATTR, "tls.type",
+    typ: "@enum",
+    cast: cast::UInt8()
+);
+
+def_attr_class!(RECORD_VERSION_ATTR, "tls.version", cast: cast::UInt16BE());
+
+def_attr_class!(LENGTH_ATTR, "tls.length", cast: cast::UInt16BE());
+
+def_attr_class!(ALERT_LEVEL_ATTR, "tls.alert.level", cast: cast::UInt8());
+
+def_attr_class!(ALERT_DESCRIPTION_ATTR, "tls.alert.description", cast: cast::UInt8());
+
+def_attr_class!(HANDSHAKE_ATTR, "tls.handshake", typ: "@nested");
+
+def_attr_class!(HANDSHAKE_TYPE_ATTR, "tls.handshake.type", typ: "@enum");
+
+def_attr_class!(HANDSHAKE_LENGTH_ATTR, "tls.handshake.length");
+
+def_attr_class!(VERSION_ATTR, "tls.handshake.version");
+
+def_attr_class!(RANDOM_ATTR, "tls.handshake.random");
+
+def_attr_class!(SESSION_ID_ATTR, "tls.handshake.sessionId");
+
+def_attr_class!(CIPHER_SUITE_ATTR, "tls.handshake.cipherSuite");
+
+def_attr_class!(COMPRESSION_METHOD_ATTR, "tls.handshake.compressionMethod");
+
+def_attr_class!(EXTENSION_ATTR, "tls.handshake.extension", typ: "@nested");
+
+def_attr_class!(EXTENSION_TYPE_ATTR, "tls.handshake.extension.type");
+
+def_attr_class!(EXTENSION_LENGTH_ATTR, "tls.handshake.extension.length");
+
+def_attr_class!(SNI_ATTR, "tls.handshake.sni");
+
+def_attr_class!(ALPN_ATTR, "tls.handshake.alpn");
+
+def_attr_class!(SUPPORTED_VERSION_ATTR, "tls.handshake.supportedVersion");
+
+def_attr_class!(JA3_ATTR, "tls.handshake.ja3");
+
+def_attr_class!(JA3_STRING_ATTR, "tls.handshake.ja3.string");
+
+def_attr_class!(JA3S_ATTR, "tls.handshake.ja3s");
+
+def_attr_class!(JA3S_STRING_ATTR, "tls.handshake.ja3s.string");
+
+def_attr_class!(CERTIFICATE_ATTR, "tls.handshake.certificate", typ: "@nested");
+
+def_attr_class!(CERTIFICATE_SUBJECT_ATTR, "tls.handshake.certificate.subject");
+
+def_attr_class!(CERTIFICATE_ISSUER_ATTR, "tls.handshake.certificate.issuer");
+
+def_attr_class!(CERTIFICATE_NOT_BEFORE_ATTR, "tls.handshake.certificate.notBefore");
+
+def_attr_class!(CERTIFICATE_NOT_AFTER_ATTR, "tls.handshake.certificate.notAfter");
+
+fn get_type(val: u64) -> Option<&'static AttrClass> {
+    match val {
+        20 => Some(attr_class_lazy!("tls.type.changeCipherSpec", typ: "@novalue", value: true)),
+        21 => Some(attr_class_lazy!("tls.type.alert", typ: "@novalue", value: true)),
+        22 => Some(attr_class_lazy!("tls.type.handshake", typ: "@novalue", value: true)),
+        23 => Some(attr_class_lazy!("tls.type.applicationData", typ: "@novalue", value: true)),
+        24 => Some(attr_class_lazy!("tls.type.heartbeat", typ: "@novalue", value: true)),
+        _ => None,
+    }
+}
+
+fn get_handshake_type(val: u64) -> Option<&'static AttrClass> {
+    match val {
+        0 => Some(attr_class_lazy!("tls.handshake.type.helloRequest", typ: "@novalue")),
+        1 => Some(attr_class_lazy!("tls.handshake.type.clientHello", typ: "@novalue")),
+        2 => Some(attr_class_lazy!("tls.handshake.type.serverHello", typ: "@novalue")),
+        4 => Some(attr_class_lazy!("tls.handshake.type.newSessionTicket", typ: "@novalue")),
+        8 => Some(attr_class_lazy!("tls.handshake.type.encryptedExtensions", typ: "@novalue")),
+        11 => Some(attr_class_lazy!("tls.handshake.type.certificate", typ: "@novalue")),
+        12 => Some(attr_class_lazy!("tls.handshake.type.serverKeyExchange", typ: "@novalue")),
+        13 => Some(attr_class_lazy!("tls.handshake.type.certificateRequest", typ: "@novalue")),
+        14 => Some(attr_class_lazy!("tls.handshake.type.serverHelloDone", typ: "@novalue")),
+        15 => Some(attr_class_lazy!("tls.handshake.type.certificateVerify", typ: "@novalue")),
+        16 => Some(attr_class_lazy!("tls.handshake.type.clientKeyExchange", typ: "@novalue")),
+        20 => Some(attr_class_lazy!("tls.handshake.type.finished", typ: "@novalue")),
+        _ => None,
+    }
+}
+
+genet_decoders!(TlsDecoder {});
+
+#[cfg(test)]
+mod tests {
+    use genet_sdk::{decoder::*, layer::attr_value, prelude::*};
+    use TlsWorker;
+
+    def_layer_class!(TCP_CLASS, "tcp");
+
+    def_attr_class!(STREAM_ATTR, "tcp.stream");
+
+    /// Decodes stream data and returns the TLS records.
+    fn segment(worker: &mut TlsWorker, data: &[u8]) -> Vec<&'static Layer> {
+        let mut tcp = Layer::new(&TCP_CLASS, ByteSlice::new());
+        tcp.add_attr(attr!(&STREAM_ATTR, value: 0u64));
+        tcp.add_payload(Payload::new(data.to_vec(), "@stream:tcp"));
+
+        let mut ctx = Context::default();
+        let mut parent = Parent::from_mut_ref(&mut tcp);
+        worker
+            .decode(&mut ctx, &LayerStack::from_slice(&[]), &mut parent)
+            .unwrap();
+        parent
+            .children()
+            .iter()
+            .map(|layer| unsafe { &**layer })
+            .collect()
+    }
+
+    fn u16s(values: &[u16]) -> Vec<u8> {
+        values.iter().flat_map(|v| vec![(v >> 8) as u8, *v as u8]).collect()
+    }
+
+    fn vector(size: usize, data: &[u8]) -> Vec<u8> {
+        let len = data.len();
+        let mut v: Vec<u8> = (0..size).rev().map(|i| (len >> (i * 8)) as u8).collect();
+        v.extend_from_slice(data);
+        v
+    }
+
+    fn extension(typ: u16, data: &[u8]) -> Vec<u8> {
+        let mut v = u16s(&[typ]);
+        v.extend(vector(2, data));
+        v
+    }
+
+    /// Returns the ClientHello of the JA3 example with GREASE values,
+    /// which are excluded from the fingerprint.
+    fn client_hello() -> Vec<u8> {
+        let mut body = u16s(&[0x0301]);
+        body.extend_from_slice(&[0; 32]);
+        body.extend(vector(1, &[]));
+        body.extend(vector(
+            2,
+            &u16s(&[
+                0x0a0a, 47, 53, 5, 10, 49161, 49162, 49171, 49172, 50, 56, 19, 4,
+            ]),
+        ));
+        body.extend(vector(1, &[0]));
+
+        let mut extensions = extension(0x1a1a, &[]);
+        let name = vector(2, b"example.com");
+        let mut list = vec![0];
+        list.extend(name);
+        extensions.extend(extension(0, &vector(2, &list)));
+        extensions.extend(extension(10, &vector(2, &u16s(&[0x2a2a, 23, 24, 25]))));
+        extensions.extend(extension(11, &vector(1, &[0])));
+        body.extend(vector(2, &extensions));
+
+        let mut message = vec![1];
+        message.extend(vector(3, &body));
+        message
+    }
+
+    fn record(typ: u8, fragment: &[u8]) -> Vec<u8> {
+        let mut v = vec![typ, 3, 1];
+        v.extend(vector(2, fragment));
+        v
+    }
+
+    fn text(layer: &Layer, id: Token) -> String {
+        attr_value(layer, id).unwrap()
+    }
+
+    #[test]
+    fn ja3() {
+        let mut w = TlsWorker::new();
+        let layers = segment(&mut w, &record(22, &client_hello()));
+        assert_eq!(layers.len(), 1);
+        assert_eq!(
+            text(layers[0], token!("tls.handshake.ja3.string")),
+            "769,47-53-5-10-49161-49162-49171-49172-50-56-19-4,0-10-11,23-24-25,0"
+        );
+        assert_eq!(
+            text(layers[0], token!("tls.handshake.ja3")),
+            "ada70206e40642a3e4461f35503241d5"
+        );
+        assert_eq!(text(layers[0], token!("tls.handshake.sni")), "example.com");
+    }
+
+    #[test]
+    fn split_handshake() {
+        // The ClientHello is fragmented into two records,
+        // and the second record is split into two segments.
+        let hello = client_hello();
+        let first = record(22, &hello[..30]);
+        let second = record(22, &hello[30..]);
+
+        let mut w = TlsWorker::new();
+        let layers = segment(&mut w, &first);
+        assert_eq!(layers.len(), 1);
+        assert!(layers[0].attr(token!("tls.handshake.ja3")).is_none());
+
+        assert!(segment(&mut w, &second[..10]).is_empty());
+        let layers = segment(&mut w, &second[10..]);
+        assert_eq!(layers.len(), 1);
+        assert_eq!(
+            text(layers[0], token!("tls.handshake.ja3")),
+            "ada70206e40642a3e4461f35503241d5"
+        );
+
+        // Messages spanning records have values without ranges.
+        let sni = layers[0].attr(token!("tls.handshake.sni")).unwrap();
+        assert_eq!(sni.range(), 0..0);
+    }
+
+    #[test]
+    fn encrypted() {
+        let mut w = TlsWorker::new();
+        let mut data = record(20, &[1]);
+        data.extend(record(22, &[0x01, 0x00, 0x00, 0x01, 0xff]));
+        let layers = segment(&mut w, &data);
+        assert_eq!(layers.len(), 2);
+        assert!(layers[1].attr(token!("tls.handshake")).is_none());
+    }
+
+    #[test]
+    fn not_tls() {
+        let mut w = TlsWorker::new();
+        assert!(segment(&mut w, b"GET / HTTP/1.1\r\n\r\n").is_empty());
+        assert!(w.streams[&0].ignored);
+    }
+}
@@ -1,13 +1,36 @@
 extern crate genet_sdk;
 
 use genet_sdk::{cast, decoder::*, prelude::*};
+use std::collections::HashMap;
 
-struct UdpWorker {}
+struct UdpWorker {
+    ports: HashMap<u16, Option<Token>>,
+}
+
+impl UdpWorker {
+    fn new() -> UdpWorker {
+        UdpWorker {
+            ports: HashMap::new(),
+        }
+    }
+
+    /// Returns the payload type for the port.
+    ///
+    /// A profile config such as `udp.port.5353=dns` overrides the well-known defaults.
+    fn port_type(&mut self, ctx: &Context, port: u16) -> Option<Token> {
+        *self.ports.entry(port).or_insert_with(|| {
+            match ctx.get_config(&format!("udp.port.{}", port)) {
+                "" => get_port_type(port).map(Token::from),
+                typ => Some(Token::from(format!("@data:{}", typ))),
+            }
+        })
+    }
+}
 
 impl Worker for UdpWorker {
     fn decode(
         &mut self,
-        _ctx: &mut Context,
+        ctx: &mut Context,
         _stack: &LayerStack,
         parent: &mut Parent,
     ) -> Result<Status> {
@@ -24,8 +47,14 @@ impl Worker for UdpWorker {
         }
 
         let mut layer = Layer::new(&UDP_CLASS, data);
+        let src = SRC_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        let dst = DST_ATTR_HEADER.try_get(&layer)?.try_into()?;
+        let typ = self
+            .port_type(ctx, dst)
+            .or_else(|| self.port_type(ctx, src))
+            .unwrap_or_else(|| token!(""));
         let payload = data.try_get(8..)?;
-        layer.add_payload(Payload::new(payload, ""));
+        layer.add_payload(Payload::new(payload, typ));
 
         parent.add_child(layer);
         Ok(Status::Done)
@@ -37,12 +66,15 @@ struct UdpDecoder {}
 
 impl Decoder for UdpDecoder {
     fn new_worker(&self, _ctx: &Context) -> Box<Worker> {
-        Box::new(UdpWorker {})
+        Box::new(UdpWorker::new())
     }
 
     fn metadata(&self) -> Metadata {
         Metadata {
+            id: "app.genet.decoder.udp".into(),
             exec_type: ExecType::ParallelSync,
+            consumes: vec![String::from("@data:udp")],
+            fields: UDP_CLASS.fields(),
             ..Metadata::default()
         }
     }
@@ -51,12 +83,16 @@ impl Decoder for UdpDecoder {
 def_layer_class!(UDP_CLASS, "udp",
     alias: "_.src" "udp.src",
     alias: "_.dst" "udp.dst",
-    header: attr!(&SRC_ATTR, range: 0..2),
-    header: attr!(&DST_ATTR, range: 2..4),
+    header: &SRC_ATTR_HEADER,
+    header: &DST_ATTR_HEADER,
     header: attr!(&LEN_ATTR, range: 4..6),
     header: attr!(&CHECKSUM_ATTR, range: 6..8)
 );
 
+def_attr!(SRC_ATTR_HEADER, &SRC_ATTR, range: 0..2);
+
+def_attr!(DST_ATTR_HEADER, &DST_ATTR, range: 2..4);
+
 def_attr_class!(SRC_ATTR, "udp.src",
     typ: "@udp:port",
     cast: cast::UInt16BE()
@@ -71,4 +107,62 @@ def_attr_class!(LEN_ATTR, "udp.length", cast: cast::UInt16BE());
 
 def_attr_class!(CHECKSUM_ATTR, "udp.checksum", cast: cast::UInt16BE());
 
+fn get_port_type(port: u16) -> Option<&'static str> {
+    match port {
+        53 | 5353 => Some("@data:dns"),
+        123 => Some("@data:ntp"),
+        _ => None,
+    }
+}
+
 genet_decoders!(UdpDecoder {});
+
+#[cfg(test)]
+mod tests {
+    use genet_sdk::{decoder::*, prelude::*};
+    use UdpWorker;
+
+    def_layer_class!(IPV4_CLASS, "ipv4");
+
+    /// Decodes a datagram between the ports and returns the payload type.
+    fn payload_type(worker: &mut UdpWorker, ctx: &mut Context, src: u16, dst: u16) -> Token {
+        let mut data = vec![(src >> 8) as u8, src as u8, (dst >> 8) as u8, dst as u8];
+        data.extend_from_slice(b"\x00\x0c\x00\x00abcd");
+        let mut ipv4 = Layer::new(&IPV4_CLASS, data.clone());
+        ipv4.add_payload(Payload::new(data, "@data:udp"));
+        let mut parent = Parent::from_mut_ref(&mut ipv4);
+        worker
+            .decode(ctx, &LayerStack::from_slice(&[]), &mut parent)
+            .unwrap();
+        let udp = unsafe { &*parent.children()[0] };
+        udp.payloads()[0].id()
+    }
+
+    #[test]
+    fn ports() {
+        let mut ctx = Context::default();
+        let mut w = UdpWorker::new();
+        assert_eq!(payload_type(&mut w, &mut ctx, 50000, 53), token!("@data:dns"));
+        assert_eq!(payload_type(&mut w, &mut ctx, 53, 50000), token!("@data:dns"));
+        assert_eq!(payload_type(&mut w, &mut ctx, 50000, 123), token!("@data:ntp"));
+        assert_eq!(payload_type(&mut w, &mut ctx, 50000, 50001), token!(""));
+
+        // The destination port wins over the source port.
+        assert_eq!(payload_type(&mut w, &mut ctx, 53, 123), token!("@data:ntp"));
+        assert_eq!(payload_type(&mut w, &mut ctx, 123, 53), token!("@data:dns"));
+    }
+
+    #[test]
+    fn port_config() {
+        let config = [("udp.port.5353", "foo"), ("udp.port.53", "bar")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let mut ctx = Context::new(config);
+        let mut w = UdpWorker::new();
+        assert_eq!(payload_type(&mut w, &mut ctx, 50000, 5353), token!("@data:foo"));
+        assert_eq!(payload_type(&mut w, &mut ctx, 50000, 53), token!("@data:bar"));
+        assert_eq!(payload_type(&mut w, &mut ctx, 50000, 123), token!("@data:ntp"));
+        assert_eq!(payload_type(&mut w, &mut ctx, 5353, 123), token!("@data:ntp"));
+    }
+}
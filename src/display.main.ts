@@ -5,6 +5,7 @@ export default function main() {
   const components = [
     'core:style',
     'core:token',
+    'core:filter:macro',
     'core:panel',
     'core:library',
     'core:file:reader',
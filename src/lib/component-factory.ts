@@ -1,5 +1,6 @@
 import { FileComponent } from './component/file'
 import { LibraryComponent } from './component/library'
+import { MacroComponent } from './component/macro'
 import { PanelComponent } from './component/panel'
 import { RendererComponent } from './component/renderer'
 import { StyleComponent } from './component/style'
@@ -9,6 +10,8 @@ export default class ComponentFactory {
     switch (comp.type) {
       case 'core:token':
         return new TokenComponent.Loader(comp, dir)
+      case 'core:filter:macro':
+        return new MacroComponent.Loader(comp, dir)
       case 'core:library':
         return new LibraryComponent.Loader(comp, dir)
       case 'core:file:reader':
@@ -0,0 +1,30 @@
+import BaseLoader from './base'
+import { Disposable } from '../disposable'
+import genet from '@genet/api'
+import path from 'path'
+import fs from 'fs-extra'
+
+export namespace MacroComponent {
+  export interface Config {
+    main: string
+  }
+
+  export class Loader implements BaseLoader {
+    private disposable: Disposable
+    private macroFile: string
+
+    constructor(comp: Config, dir: string) {
+      this.macroFile = path.resolve(dir, comp.main)
+    }
+    async load() {
+      this.disposable = genet.session.registerMacros(await fs.readJson(this.macroFile))
+      return true
+    }
+    async unload() {
+      if (this.disposable) {
+        this.disposable.dispose()
+      }
+      return true
+    }
+  }
+}
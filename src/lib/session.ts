@@ -9,6 +9,7 @@ import titleCase from 'title-case'
 export default class Session extends EventEmitter {
   private _config: any
   private _tokens: Map<string, any>
+  private _macros: Map<string, string>
   private _libs: Set<string>
   private _fileReaders: Set<any>
   private _layerRenderers: Map<string, any>
@@ -18,6 +19,7 @@ export default class Session extends EventEmitter {
     super()
     this._config = config
     this._tokens = new Map()
+    this._macros = new Map()
     this._libs = new Set()
     this._fileReaders = new Set()
     this._layerRenderers = new Map()
@@ -43,6 +45,17 @@ export default class Session extends EventEmitter {
     })
   }
 
+  registerMacros(macros) {
+    for (const [name, expr] of Object.entries(macros)) {
+      this._macros.set(name, expr as string)
+    }
+    return new Disposable(() => {
+      for (const name of Object.keys(macros)) {
+        this._macros.delete(name)
+      }
+    })
+  }
+
   registerFileReader(reader) {
     this._fileReaders.add(reader)
     return new Disposable(() => {
@@ -106,6 +119,13 @@ export default class Session extends EventEmitter {
         this.emit('error', new Error(`Filed to load ${file}: ${err.message}`))
       }
     }
+    for (const [name, expr] of this._macros) {
+      try {
+        profile.defineMacro(name, expr)
+      } catch (err) {
+        this.emit('error', new Error(`Failed to define @${name}: ${err.message}`))
+      }
+    }
     return new native.Session(profile, {})
   }
 }